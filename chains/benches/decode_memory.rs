@@ -0,0 +1,137 @@
+//! Peak heap memory used to decode 100 framed core arbitrating setups received concurrently,
+//! first by reading each complete frame into a buffer before decoding it, then by decoding each
+//! message while reading its stream with `SwapMessage::read_framed`.
+//!
+//! Run with `cargo bench --bench decode_memory`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::Message;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+
+use farcaster_chains::bitcoin::Bitcoin;
+use farcaster_chains::pairs::btcxmr::BtcXmr;
+use farcaster_core::protocol_message::{frame, read_frame, CoreArbitratingSetup, SwapMessage};
+
+use strict_encoding::strict_deserialize;
+
+/// Number of setups decoded at the same time.
+const SETUPS: usize = 100;
+
+/// Number of inputs and outputs of each transaction of the setup.
+const TX_SIZE: u32 = 20;
+
+/// Allocator keeping track of the heap memory in use and of its peak.
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn partial_transaction(seed: u32) -> PartiallySignedTransaction {
+    let tx = Transaction {
+        version: 2,
+        lock_time: 0,
+        input: (0..TX_SIZE)
+            .map(|vout| TxIn {
+                previous_output: OutPoint {
+                    vout: seed + vout,
+                    ..OutPoint::null()
+                },
+                script_sig: Script::new(),
+                sequence: 0xffff_ffff,
+                witness: vec![],
+            })
+            .collect(),
+        output: (0..TX_SIZE)
+            .map(|i| TxOut {
+                value: 10_000 + u64::from(i),
+                script_pubkey: Script::from(vec![0x00; 34]),
+            })
+            .collect(),
+    };
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+    for (i, input) in psbt.inputs.iter_mut().enumerate() {
+        input.witness_utxo = Some(TxOut {
+            value: 20_000 + i as u64,
+            script_pubkey: Script::from(vec![0x00; 34]),
+        });
+    }
+    psbt
+}
+
+/// Returns the heap memory allocated at the peak while running `decode`, on top of the memory
+/// already in use, and the decoded value.
+fn peak_while<T>(decode: impl FnOnce() -> T) -> (usize, T) {
+    let base = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(base, Ordering::SeqCst);
+    let value = decode();
+    (PEAK.load(Ordering::SeqCst) - base, value)
+}
+
+fn main() {
+    let key = SecretKey::from_slice(&[0x01; 32]).unwrap();
+    let cancel_sig = Bitcoin::secp().sign(&Message::from_slice(&[0x42; 32]).unwrap(), &key);
+    let streams: Vec<Vec<u8>> = (0..SETUPS as u32)
+        .map(|i| {
+            let setup = CoreArbitratingSetup::<BtcXmr> {
+                lock: partial_transaction(3 * i * TX_SIZE),
+                cancel: partial_transaction((3 * i + 1) * TX_SIZE),
+                refund: partial_transaction((3 * i + 2) * TX_SIZE),
+                cancel_sig,
+            };
+            frame(&SwapMessage::CoreArbitratingSetup(setup)).unwrap()
+        })
+        .collect();
+    println!("{} setups of {} bytes per frame", SETUPS, streams[0].len());
+
+    // Every connection holds its complete frame before the messages are decoded
+    let (buffered, from_buffers) = peak_while(|| {
+        let payloads: Vec<Vec<u8>> = streams
+            .iter()
+            .map(|stream| read_frame(&mut &stream[..]).unwrap())
+            .collect();
+        payloads
+            .iter()
+            .map(|payload| strict_deserialize::<SwapMessage<BtcXmr>>(payload).unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    // Every message is decoded while its stream is read
+    let (streamed, from_streams) = peak_while(|| {
+        streams
+            .iter()
+            .map(|stream| SwapMessage::<BtcXmr>::read_framed(&mut &stream[..]).unwrap())
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(from_buffers, from_streams);
+
+    println!("{:<24} {:>8} KiB peak", "buffered frames", buffered / 1024);
+    println!(
+        "{:<24} {:>8} KiB peak",
+        "streamed decoding",
+        streamed / 1024
+    );
+}
@@ -0,0 +1,47 @@
+//! Signing throughput with a secp256k1 context created for each signature, as the Bitcoin
+//! implementation used to do, and with the context shared through `Bitcoin::secp`.
+//!
+//! Run with `cargo bench --bench signing`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::{Message, Secp256k1, Signature};
+
+use farcaster_chains::bitcoin::Bitcoin;
+
+/// Number of signatures per measure.
+const SIGNATURES: u32 = 2_000;
+
+fn throughput(label: &str, mut sign: impl FnMut(&Message, &SecretKey) -> Signature) {
+    let key = SecretKey::from_slice(&[0x01; 32]).unwrap();
+    let messages: Vec<Message> = (0..SIGNATURES)
+        .map(|i| {
+            let mut digest = [0x42; 32];
+            digest[..4].copy_from_slice(&i.to_le_bytes());
+            Message::from_slice(&digest).unwrap()
+        })
+        .collect();
+
+    let start = Instant::now();
+    for msg in messages.iter() {
+        black_box(sign(msg, &key));
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "{:<24} {:>10.0} signatures/s",
+        label,
+        f64::from(SIGNATURES) / elapsed
+    );
+}
+
+fn main() {
+    // warm the shared context up, it is allocated on first use
+    Bitcoin::secp();
+
+    throughput("context per signature", |msg, key| {
+        Secp256k1::new().sign(msg, key)
+    });
+    throughput("shared context", |msg, key| Bitcoin::secp().sign(msg, key));
+}
@@ -0,0 +1,93 @@
+//! Output descriptors for the arbitrating outputs created during a swap, used to watch the swap
+//! outputs with external tools, e.g. by importing them in a watch-only wallet before funding.
+//!
+//! The swap scripts are not expressible with miniscript, descriptors are thus exported with the
+//! `raw()` script expression as defined in [BIP
+//! 385](https://github.com/bitcoin/bips/blob/master/bip-0385.mediawiki), followed by their
+//! checksum as defined in [BIP
+//! 380](https://github.com/bitcoin/bips/blob/master/bip-0380.mediawiki).
+
+use bitcoin::blockdata::script::Script;
+
+use farcaster_core::script::{DataLock, DataPunishableLock};
+
+use crate::bitcoin::transaction::cancel::punish_lock_script;
+use crate::bitcoin::transaction::lock::swaplock_script;
+use crate::bitcoin::Bitcoin;
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn poly_mod(mut c: u64, val: u64) -> u64 {
+    let c0 = c >> 35;
+    c = ((c & 0x7ffffffff) << 5) ^ val;
+    if c0 & 1 != 0 {
+        c ^= 0xf5dee51989;
+    }
+    if c0 & 2 != 0 {
+        c ^= 0xa9fdca3312;
+    }
+    if c0 & 4 != 0 {
+        c ^= 0x1bab10e32d;
+    }
+    if c0 & 8 != 0 {
+        c ^= 0x3706b1677a;
+    }
+    if c0 & 16 != 0 {
+        c ^= 0x644d626ffd;
+    }
+    c
+}
+
+/// Computes the eight characters checksum of a descriptor, returns `None` if the descriptor
+/// contains characters not allowed in descriptors.
+pub fn checksum(desc: &str) -> Option<String> {
+    let mut c = 1u64;
+    let mut cls = 0u64;
+    let mut clscount = 0;
+
+    for ch in desc.chars() {
+        let pos = INPUT_CHARSET.find(ch)? as u64;
+        c = poly_mod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        clscount += 1;
+        if clscount == 3 {
+            c = poly_mod(c, cls);
+            cls = 0;
+            clscount = 0;
+        }
+    }
+    if clscount > 0 {
+        c = poly_mod(c, cls);
+    }
+    for _ in 0..8 {
+        c = poly_mod(c, 0);
+    }
+    c ^= 1;
+
+    Some(
+        (0..8)
+            .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+            .collect(),
+    )
+}
+
+/// Returns the `raw()` descriptor with its checksum for the given script pubkey.
+pub fn raw_descriptor(script_pubkey: &Script) -> String {
+    let desc = format!("raw({})", hex::encode(script_pubkey.as_bytes()));
+    // hex characters are always valid descriptor characters
+    let checksum = checksum(&desc).expect("Valid descriptor characters");
+    format!("{}#{}", desc, checksum)
+}
+
+/// Returns the descriptor of the `lock (b)` output created with the given lock.
+pub fn lock_descriptor(lock: &DataLock<Bitcoin>) -> String {
+    raw_descriptor(&swaplock_script(lock).to_v0_p2wsh())
+}
+
+/// Returns the descriptor of the `cancel (d)` output created with the given punishable lock.
+pub fn punish_lock_descriptor(punish_lock: &DataPunishableLock<Bitcoin>) -> String {
+    raw_descriptor(&punish_lock_script(punish_lock).to_v0_p2wsh())
+}
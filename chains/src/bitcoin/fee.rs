@@ -1,3 +1,4 @@
+use bitcoin::blockdata::script::Script;
 use bitcoin::blockdata::transaction::TxOut;
 use bitcoin::util::amount;
 use bitcoin::util::psbt::PartiallySignedTransaction;
@@ -5,10 +6,12 @@ use strict_encoding::{StrictDecode, StrictEncode};
 
 use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy, FeeStrategyError};
 use farcaster_core::consensus::{self, Decodable, Encodable};
+use farcaster_core::transaction::TxId;
 
-use crate::bitcoin::transaction;
+use crate::bitcoin::transaction::{self, Buy, Cancel, Lock, Refund, Tx};
 use crate::bitcoin::{Amount, Bitcoin};
 
+use std::fmt;
 use std::io;
 use std::str::FromStr;
 
@@ -27,8 +30,24 @@ impl SatPerVByte {
     pub fn as_native_unit(&self) -> Amount {
         self.0
     }
+
+    /// Returns the fee rate at the given fraction between `start` and `end`, the fraction must be
+    /// in `[0, 1]`. Returns `None` if `end` is lower than `start` or on overflow.
+    pub fn interpolate(start: &Self, end: &Self, fraction: f64) -> Option<Self> {
+        let delta = end.as_sat().checked_sub(start.as_sat())?;
+        let step = (delta as f64 * fraction).round() as u64;
+        Some(Self::from_sat(start.as_sat().checked_add(step)?))
+    }
+
+    /// Returns the fee rate halfway between `a` and `b`, rounded down. The average is computed
+    /// without overflowing for any pair of rates.
+    pub fn midpoint(a: &Self, b: &Self) -> Self {
+        let (a, b) = (a.as_sat(), b.as_sat());
+        Self::from_sat(a / 2 + b / 2 + (a % 2 + b % 2) / 2)
+    }
 }
 
+/// Encoded as its inner [`Amount`], i.e. 8 bytes in little-endian byte order.
 impl Encodable for SatPerVByte {
     fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
         self.0.consensus_encode(writer)
@@ -53,6 +72,165 @@ impl FromStr for SatPerVByte {
     }
 }
 
+impl fmt::Display for SatPerVByte {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_sat())
+    }
+}
+
+/// Minimal value, in satoshi, a change output must keep after absorbing the fee. This is the dust
+/// limit of a P2PKH output, the highest among standard outputs, and applies to unknown scripts.
+pub const DUST_LIMIT: u64 = 546;
+
+/// Returns the dust limit, in satoshi, of an output paying to the given script, as computed by
+/// Bitcoin Core with the default dust relay fee of 3 sat/vByte. Outputs below this value are not
+/// relayed.
+pub fn dust_limit(script_pubkey: &Script) -> u64 {
+    match script_pubkey {
+        script if script.is_v0_p2wpkh() => 294,
+        script if script.is_v0_p2wsh() => 330,
+        script if script.is_p2sh() => 540,
+        _ => DUST_LIMIT,
+    }
+}
+
+/// Returns the sum of the inputs' amounts, fails if the previous output is missing on an input.
+fn input_sum(tx: &PartiallySignedTransaction) -> Result<Amount, FeeStrategyError> {
+    let inputs: Result<Vec<TxOut>, FeeStrategyError> = tx
+        .inputs
+        .iter()
+        .map(|psbt_in| {
+            psbt_in
+                .witness_utxo
+                .clone()
+                .ok_or(FeeStrategyError::MissingInputsMetadata)
+        })
+        .collect();
+    Ok(Amount::from_sat(
+        inputs?.iter().map(|txout| txout.value).sum(),
+    ))
+}
+
+/// Returns the fee rate selected by the politic within the strategy, `None` if the rate cannot
+/// be computed.
+fn fee_rate(strategy: &FeeStrategy<SatPerVByte>, politic: FeePolitic) -> Option<SatPerVByte> {
+    match strategy {
+        // The fee is paid by a child transaction
+        FeeStrategy::None => Some(SatPerVByte::from_sat(0)),
+        FeeStrategy::Fixed(sat_per_vbyte) => Some(sat_per_vbyte.clone()),
+        FeeStrategy::Range(range) => match politic {
+            FeePolitic::Aggressive => Some(range.start.clone()),
+            FeePolitic::Conservative => Some(range.end.clone()),
+            FeePolitic::Normal => Some(SatPerVByte::midpoint(&range.start, &range.end)),
+            FeePolitic::Interpolated(_) => {
+                SatPerVByte::interpolate(&range.start, &range.end, politic.fraction())
+            }
+        },
+    }
+}
+
+/// Computes the fee amount to pay for the given size at the rate selected by the politic within
+/// the strategy.
+fn fee_for_size(
+    size: u64,
+    strategy: &FeeStrategy<SatPerVByte>,
+    politic: FeePolitic,
+) -> Result<Amount, FeeStrategyError> {
+    fee_rate(strategy, politic)
+        .and_then(|rate| rate.as_native_unit().checked_mul(size))
+        .ok_or_else(|| FeeStrategyError::AmountOfFeeTooHigh {
+            // highest rate whose product with the size still fits in the amount
+            max_sat_per_vbyte: u64::MAX.checked_div(size).unwrap_or(u64::MAX),
+        })
+}
+
+/// Signatures counted on the inputs not finalized yet when estimating the size paying the fee,
+/// the 2-of-2 multisig paths of the swap scripts.
+const FEE_SIGNATURES: usize = 2;
+
+/// Returns the virtual size in vbytes the fee of the transaction is computed on, the size of the
+/// transaction once finalized as estimated by [`Tx::estimated_vsize`]. The witnesses not
+/// finalized yet are estimated with two signatures, the single signature path of `punish (f)` is
+/// slightly overpaid.
+pub fn fee_vsize(tx: &PartiallySignedTransaction) -> u64 {
+    transaction::estimated_vsize(tx, FEE_SIGNATURES)
+}
+
+/// Computes the fee amount to pay on the transaction for the given strategy and politic.
+fn fee_amount(
+    tx: &PartiallySignedTransaction,
+    strategy: &FeeStrategy<SatPerVByte>,
+    politic: FeePolitic,
+) -> Result<Amount, FeeStrategyError> {
+    fee_for_size(fee_vsize(tx), strategy, politic)
+}
+
+/// Calculates and sets the fees on the given transaction, deducting them from the output at
+/// `change_index`, and returns the fees set.
+///
+/// When `change_index` is `Some`, that output absorbs the fee and receives everything that is not
+/// paid to the other outputs, whose values are left untouched. When `change_index` is `None` the
+/// transaction must have exactly one output besides its fee-bump anchors, see
+/// [`transaction::is_anchor_output`], and it absorbs the fee as with [`Fee::set_fee`]. In both
+/// cases the output absorbing the fee must stay at or above the [`dust_limit`] of its script. An
+/// explicit index always takes precedence, no change output is ever guessed from the transaction.
+pub fn set_fee_with_change(
+    tx: &mut PartiallySignedTransaction,
+    strategy: &FeeStrategy<SatPerVByte>,
+    politic: FeePolitic,
+    change_index: Option<usize>,
+) -> Result<Amount, FeeStrategyError> {
+    // Get the available amount on the transaction
+    let input_sum = input_sum(tx)?;
+
+    let fee_amount = fee_amount(tx, strategy, politic)?;
+
+    // Fee-bump anchors keep their value, they are never used as change
+    let candidates: Vec<usize> = (0..tx.global.unsigned_tx.output.len())
+        .filter(|&i| !transaction::is_anchor_output(tx, i))
+        .collect();
+
+    let outputs = &mut tx.global.unsigned_tx.output;
+    let index = match change_index {
+        Some(index) if index >= outputs.len() => {
+            return Err(FeeStrategyError::new(transaction::Error::MissingOutput(
+                index,
+            )));
+        }
+        Some(index) => index,
+        None if candidates.len() != 1 => {
+            return Err(FeeStrategyError::new(
+                transaction::Error::MultiUTXOUnsuported,
+            ));
+        }
+        None => candidates[0],
+    };
+
+    // Amount already committed to the non-change outputs
+    let paid = outputs
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .try_fold(0u64, |acc, (_, txout)| acc.checked_add(txout.value))
+        .ok_or_else(|| FeeStrategyError::NotEnoughAssets)?;
+
+    // Apply the fee on the change output
+    let change = input_sum
+        .checked_sub(Amount::from_sat(paid))
+        .and_then(|available| available.checked_sub(fee_amount))
+        .ok_or_else(|| FeeStrategyError::NotEnoughAssets)?
+        .as_sat();
+    if change < dust_limit(&outputs[index].script_pubkey) {
+        return Err(FeeStrategyError::new(transaction::Error::ChangeBelowDust(
+            change,
+        )));
+    }
+    outputs[index].value = change;
+
+    // Return the fee amount set in native blockchain asset unit
+    Ok(fee_amount)
+}
+
 impl Fee for Bitcoin {
     type FeeUnit = SatPerVByte;
 
@@ -62,55 +240,130 @@ impl Fee for Bitcoin {
         strategy: &FeeStrategy<SatPerVByte>,
         politic: FeePolitic,
     ) -> Result<Amount, FeeStrategyError> {
-        // Get the available amount on the transaction
-        let inputs: Result<Vec<TxOut>, FeeStrategyError> = tx
-            .inputs
+        set_fee_with_change(tx, strategy, politic, None)
+    }
+
+    /// Validates that the fees for the given transaction are set accordingly to the strategy,
+    /// fails if the fees paid are lower or higher than the amounts allowed by the strategy
+    fn validate_fee(
+        tx: &PartiallySignedTransaction,
+        strategy: &FeeStrategy<SatPerVByte>,
+    ) -> Result<bool, FeeStrategyError> {
+        let output_sum = tx
+            .global
+            .unsigned_tx
+            .output
             .iter()
-            .map(|psbt_in| {
-                psbt_in
-                    .witness_utxo
-                    .clone()
-                    .ok_or(FeeStrategyError::MissingInputsMetadata)
-            })
-            .collect();
-        let input_sum = Amount::from_sat(inputs?.iter().map(|txout| txout.value).sum());
-
-        // FIXME This does not account for witnesses
-        // currently the fees are wrong
-        // Get the transaction weight
-        let weight = tx.global.unsigned_tx.get_weight() as u64;
-
-        // Compute the fee amount to set in total
-        let fee_amount = match strategy {
-            FeeStrategy::Fixed(sat_per_vbyte) => sat_per_vbyte.as_native_unit().checked_mul(weight),
-            FeeStrategy::Range(range) => match politic {
-                FeePolitic::Aggressive => range.start.as_native_unit().checked_mul(weight),
-                FeePolitic::Conservative => range.end.as_native_unit().checked_mul(weight),
-            },
-        }
-        .ok_or_else(|| FeeStrategyError::AmountOfFeeTooHigh)?;
+            .try_fold(0u64, |acc, txout| acc.checked_add(txout.value))
+            .ok_or_else(|| FeeStrategyError::NotEnoughAssets)?;
+        let fee = input_sum(tx)?
+            .checked_sub(Amount::from_sat(output_sum))
+            .ok_or_else(|| FeeStrategyError::NotEnoughAssets)?;
 
-        if tx.global.unsigned_tx.output.len() != 1 {
-            return Err(FeeStrategyError::new(
-                transaction::Error::MultiUTXOUnsuported,
-            ));
+        // Bounds are computed on the same size as in `set_fee`
+        let vsize = fee_vsize(tx);
+        let (min, max) = match strategy {
+            FeeStrategy::Fixed(sat_per_vbyte) => (sat_per_vbyte, sat_per_vbyte),
+            FeeStrategy::Range(range) => (&range.start, &range.end),
+            // Any fee is accepted, including zero, as it is bumped externally
+            FeeStrategy::None => return Ok(true),
+        };
+        match min.as_native_unit().checked_mul(vsize) {
+            Some(min) if fee >= min => (),
+            _ => return Err(FeeStrategyError::AmountOfFeeTooLow),
         }
+        match max.as_native_unit().checked_mul(vsize) {
+            Some(max_fee) if fee > max_fee => Err(FeeStrategyError::AmountOfFeeTooHigh {
+                max_sat_per_vbyte: max.as_native_unit().as_sat(),
+            }),
+            _ => Ok(true),
+        }
+    }
+}
+
+/// Estimated virtual size and fee of a transaction of the swap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxCost {
+    /// The estimated transaction
+    pub tx: TxId,
+    /// Estimated virtual size in vbytes, witness included
+    pub vsize: u64,
+    /// Fee paid for the virtual size at the estimated fee rate
+    pub fee: Amount,
+}
 
-        // Apply the fee on the first output
-        tx.global.unsigned_tx.output[0].value = input_sum
-            .checked_sub(fee_amount)
-            .ok_or_else(|| FeeStrategyError::NotEnoughAssets)?
-            .as_sat();
+/// Estimated costs of the transactions of a path of the swap, in the order they are broadcast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathCost {
+    /// The per-transaction breakdown of the path
+    pub transactions: Vec<TxCost>,
+    /// Total virtual size in vbytes of the transactions
+    pub vsize: u64,
+    /// Total fee of the transactions
+    pub fee: Amount,
+}
 
-        // Return the fee amount set in native blockchain asset unit
-        Ok(fee_amount)
+impl PathCost {
+    fn new(transactions: Vec<TxCost>) -> Result<Self, FeeStrategyError> {
+        let vsize = transactions.iter().map(|cost| cost.vsize).sum();
+        let fee = transactions
+            .iter()
+            .try_fold(Amount::from_sat(0), |acc, cost| acc.checked_add(cost.fee))
+            .ok_or(FeeStrategyError::AmountOfFeeTooHigh {
+                max_sat_per_vbyte: u64::MAX.checked_div(vsize).unwrap_or(u64::MAX),
+            })?;
+        Ok(Self {
+            transactions,
+            vsize,
+            fee,
+        })
     }
+}
 
-    /// Validates that the fees for the given transaction are set accordingly to the strategy
-    fn validate_fee(
-        _tx: &PartiallySignedTransaction,
-        _strategy: &FeeStrategy<SatPerVByte>,
-    ) -> Result<bool, FeeStrategyError> {
-        todo!()
+/// Estimated on-chain footprint of a swap on the arbitrating blockchain, used to compare the
+/// worst-case costs of a swap before committing funds. The happy path is made of the funding, the
+/// `lock (b)` and the `buy (c)` transactions, the failure path of the funding, the `lock (b)`, the
+/// `cancel (d)` and the `refund (e)` transactions. The fees paid on the accordant blockchain are
+/// not part of the estimate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapCostEstimate {
+    /// Costs of the happy path
+    pub success: PathCost,
+    /// Costs of the failure path
+    pub failure: PathCost,
+}
+
+impl SwapCostEstimate {
+    /// Estimates the costs of both paths from the funding transaction and the transaction
+    /// templates, at the fee rate selected by the politic within the strategy. The sizes of the
+    /// templates are estimated with [`Tx::estimated_vsize`], the funding transaction created by
+    /// the external wallet is measured as given and must carry its witness to be accounted for.
+    pub fn new(
+        strategy: &FeeStrategy<SatPerVByte>,
+        politic: FeePolitic,
+        funding: &bitcoin::Transaction,
+        lock: &Tx<Lock>,
+        buy: &Tx<Buy>,
+        cancel: &Tx<Cancel>,
+        refund: &Tx<Refund>,
+    ) -> Result<Self, FeeStrategyError> {
+        let cost = |tx: TxId, vsize: u64| -> Result<TxCost, FeeStrategyError> {
+            Ok(TxCost {
+                tx,
+                vsize,
+                fee: fee_for_size(vsize, strategy, politic)?,
+            })
+        };
+        let funding = cost(TxId::Funding, ((funding.get_weight() + 3) / 4) as u64)?;
+        let lock = cost(TxId::Lock, lock.estimated_vsize())?;
+        Ok(Self {
+            success: PathCost::new(vec![funding, lock, cost(TxId::Buy, buy.estimated_vsize())?])?,
+            failure: PathCost::new(vec![
+                funding,
+                lock,
+                cost(TxId::Cancel, cancel.estimated_vsize())?,
+                cost(TxId::Refund, refund.estimated_vsize())?,
+            ])?,
+        })
     }
 }
@@ -1,7 +1,10 @@
 //! Defines and implements all the traits for Bitcoin
 
-use bitcoin::secp256k1::Secp256k1;
-use bitcoin::secp256k1::Signature;
+use bitcoin::blockdata::script::Script;
+use bitcoin::hashes::{sha256, sha256d, Hash, HashEngine};
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::{All, Secp256k1};
+use bitcoin::secp256k1::{Message, Signature};
 use bitcoin::util::amount;
 use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey};
 use bitcoin::util::key::{PrivateKey, PublicKey};
@@ -11,7 +14,7 @@ use strict_encoding::{StrictDecode, StrictEncode};
 
 use farcaster_core::blockchain::{self, Asset, Onchain, Timelock, Transactions};
 use farcaster_core::consensus::{self, Decodable, Encodable};
-use farcaster_core::crypto::{self, ArbitratingKey, FromSeed, Keys, Signatures};
+use farcaster_core::crypto::{self, ArbitratingKey, CommitmentEngine, FromSeed, Keys, Signatures};
 use farcaster_core::role::{Arb, Arbitrating};
 
 use transaction::{Buy, Cancel, Funding, Lock, Punish, Refund, Tx};
@@ -20,12 +23,28 @@ use std::fmt::Debug;
 use std::io;
 use std::str::FromStr;
 
+pub mod descriptor;
 pub mod fee;
+#[cfg(feature = "rpc")]
+pub mod rpc;
 pub mod transaction;
+pub mod watcher;
 
 #[derive(Clone, Debug, Copy, Eq, PartialEq)]
 pub struct Bitcoin;
 
+lazy_static::lazy_static! {
+    static ref SECP: Secp256k1<All> = Secp256k1::new();
+}
+
+impl Bitcoin {
+    /// Returns the secp256k1 context shared by all the signing and verification operations, the
+    /// context is allocated and randomized once on first use.
+    pub fn secp() -> &'static Secp256k1<All> {
+        &SECP
+    }
+}
+
 impl FromStr for Bitcoin {
     type Err = consensus::Error;
 
@@ -58,6 +77,22 @@ impl Asset for Bitcoin {
     }
 }
 
+/// Commitment engine using double SHA-256 over the tagged value, can be used in swap contexts as
+/// an alternative to the accordant blockchain hash function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sha256dEngine;
+
+impl CommitmentEngine for Sha256dEngine {
+    type Commitment = sha256d::Hash;
+
+    fn commit(tag: &[u8], value: &[u8]) -> sha256d::Hash {
+        let mut engine = sha256d::Hash::engine();
+        engine.input(tag);
+        engine.input(value);
+        sha256d::Hash::from_engine(engine)
+    }
+}
+
 impl FromStr for Amount {
     type Err = consensus::Error;
 
@@ -86,17 +121,31 @@ impl Amount {
         Some(Self(self.0.checked_mul(other)?))
     }
 
+    pub fn checked_add(&self, other: Self) -> Option<Self> {
+        Some(Self(self.0.checked_add(other.0)?))
+    }
+
     pub fn checked_sub(&self, other: Self) -> Option<Self> {
         Some(Self(self.0.checked_sub(other.0)?))
     }
 }
 
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> u64 {
+        amount.as_sat()
+    }
+}
+
+/// Amounts are encoded as the number of satoshis in an unsigned 64-bit integer in little-endian
+/// byte order, as in Bitcoin transaction outputs. The layout is part of the wire format and must
+/// not change.
 impl Encodable for Amount {
     fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
         bitcoin::consensus::encode::Encodable::consensus_encode(&self.as_sat(), writer)
     }
 }
 
+/// Decodes the 8 bytes little-endian number of satoshis, fails on shorter buffers.
 impl Decodable for Amount {
     fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
         let sats: u64 = bitcoin::consensus::encode::Decodable::consensus_decode(d)
@@ -113,11 +162,18 @@ impl blockchain::Address for Bitcoin {
 impl Timelock for Bitcoin {
     /// Defines the type of timelock used for the arbitrating transactions
     type Timelock = CSVTimelock;
+
+    fn available_at(timelock: &CSVTimelock, height: u32) -> u32 {
+        match timelock.is_time_based() {
+            true => height.saturating_add(timelock.value() as u32 * SEQUENCE_LOCKTIME_GRANULARITY),
+            false => height.saturating_add(timelock.value() as u32),
+        }
+    }
 }
 
 impl Arbitrating for Bitcoin {}
 
-#[derive(Debug, Clone, StrictDecode, StrictEncode)]
+#[derive(Debug, Clone, PartialEq, Eq, StrictDecode, StrictEncode)]
 pub struct Address(pub bitcoin::Address);
 
 impl From<bitcoin::Address> for Address {
@@ -142,9 +198,29 @@ impl Decodable for Address {
     fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
         let bytes: String = bitcoin::consensus::encode::Decodable::consensus_decode(d)
             .map_err(|_| consensus::Error::ParseFailed("Bitcoin address parsing failed"))?;
-        let add: bitcoin::Address = FromStr::from_str(&bytes)
+        FromStr::from_str(&bytes)
+    }
+}
+
+/// Parses an address usable with the ECDSA backend. Segwit version 1 and later outputs, e.g.
+/// Taproot `bc1p…` addresses, require Schnorr signatures and are rejected. Bech32m encoded
+/// addresses are not supported by the underlying library and fail to parse.
+impl FromStr for Address {
+    type Err = consensus::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let address = bitcoin::Address::from_str(s)
             .map_err(|_| consensus::Error::ParseFailed("Bitcoin address parsing failed"))?;
-        Ok(Address(add))
+        match &address.payload {
+            bitcoin::util::address::Payload::WitnessProgram { version, .. }
+                if version.to_u8() != 0 =>
+            {
+                Err(consensus::Error::ParseFailed(
+                    "Segwit version 1+ addresses are not supported by the ECDSA backend",
+                ))
+            }
+            _ => Ok(Address(address)),
+        }
     }
 }
 
@@ -159,6 +235,19 @@ impl FromStr for CSVTimelock {
     }
 }
 
+/// If set in a sequence the relative timelock is disabled, as defined in BIP 68.
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+
+/// If set in a relative timelock the value is interpreted in units of 512 seconds instead of
+/// blocks, as defined in [BIP 68](https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki).
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+
+/// Mask applied on a relative timelock to extract its value, as defined in BIP 68.
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000ffff;
+
+/// Number of seconds in one unit of a time-based relative timelock, as defined in BIP 68.
+pub const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 512;
+
 #[derive(PartialEq, Eq, PartialOrd, Clone, Debug, StrictDecode, StrictEncode, Copy)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct CSVTimelock(u32);
@@ -168,9 +257,55 @@ impl CSVTimelock {
         Self(timelock)
     }
 
+    /// Creates a relative timelock from its raw value, fails if the disable flag or any reserved
+    /// bit is set, only the type flag and the value bits are allowed.
+    pub fn from_u32(timelock: u32) -> Result<Self, transaction::Error> {
+        let timelock = Self(timelock);
+        timelock.as_sequence()?;
+        Ok(timelock)
+    }
+
     pub fn as_u32(&self) -> u32 {
         self.0
     }
+
+    /// Adds two relative timelocks of the same kind, returns `None` if the kinds differ or if the
+    /// resulting value does not fit in the timelock value bits.
+    pub fn checked_add(&self, other: Self) -> Option<Self> {
+        if self.is_time_based() != other.is_time_based() {
+            return None;
+        }
+        let value = self.value().checked_add(other.value())?;
+        Some(Self((self.0 & SEQUENCE_LOCKTIME_TYPE_FLAG) | value as u32))
+    }
+
+    /// Returns true if the timelock is expressed in units of 512 seconds instead of blocks.
+    pub fn is_time_based(&self) -> bool {
+        self.0 & SEQUENCE_LOCKTIME_TYPE_FLAG != 0
+    }
+
+    /// Returns the number of blocks, or units of 512 seconds, enforced by the timelock.
+    pub fn value(&self) -> u16 {
+        (self.0 & SEQUENCE_LOCKTIME_MASK) as u16
+    }
+
+    /// Returns the input sequence enforcing the relative timelock. The sequence is the same value
+    /// as the one pushed for `OP_CSV` in the scripts, fails if the disable flag or any bit other
+    /// than the type flag and the value is set as the timelock would not be enforced as expected.
+    pub fn as_sequence(&self) -> Result<u32, transaction::Error> {
+        let sequence = self.0 & (SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK);
+        match sequence == self.0 {
+            true => Ok(sequence),
+            false => Err(transaction::Error::InvalidRelativeTimelock(self.0)),
+        }
+    }
+
+    /// Returns the value to push for `OP_CSV` in the scripts, the same value as the sequence
+    /// returned by [`CSVTimelock::as_sequence`]. Fails if the value does not fit in the 16 value
+    /// bits or if a flag other than the type flag is set, as the script could never be spent.
+    pub fn to_csv_value(&self) -> Result<i64, transaction::Error> {
+        self.as_sequence().map(i64::from)
+    }
 }
 
 impl Encodable for CSVTimelock {
@@ -194,6 +329,30 @@ impl Onchain for Bitcoin {
 
     /// Defines the finalized transaction format for the arbitrating blockchain
     type Transaction = bitcoin::blockdata::transaction::Transaction;
+
+    /// Defines the identifier of a finalized transaction
+    type TransactionId = bitcoin::Txid;
+
+    /// Clears the partial signatures and the finalized script signatures and witnesses, the
+    /// witness UTXOs and witness scripts are kept.
+    fn strip_signatures(psbt: &mut PartiallySignedTransaction) {
+        for input in psbt.inputs.iter_mut() {
+            input.partial_sigs.clear();
+            input.final_script_sig = None;
+            input.final_script_witness = None;
+        }
+        for txin in psbt.global.unsigned_tx.input.iter_mut() {
+            txin.script_sig = Script::default();
+            txin.witness.clear();
+        }
+    }
+
+    fn sighash_type(psbt: &PartiallySignedTransaction) -> Option<u8> {
+        psbt.inputs
+            .get(0)
+            .and_then(|input| input.sighash_type)
+            .map(|sighash_type| sighash_type.as_u32() as u8)
+    }
 }
 
 impl Transactions for Bitcoin {
@@ -207,7 +366,11 @@ impl Transactions for Bitcoin {
     type Punish = Tx<Punish>;
 }
 
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+/// ECDSA adaptor signature, or pre-signature, of a message encrypted with the adaptor `point`
+/// `Y`. The `sig` holds `(r, s')` where `r` is the x coordinate of the encrypted nonce `R = k * Y`
+/// and `s' = k^-1 * (m + r * x)`. Decrypting it with the adaptor secret `y` gives the signature
+/// `(r, s' * y^-1)`, see [`Signatures::adapt`].
+#[derive(Clone, Debug, PartialEq, Eq, StrictDecode, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct ECDSAAdaptorSig {
     pub sig: Signature,
@@ -215,20 +378,248 @@ pub struct ECDSAAdaptorSig {
     pub dleq: PDLEQ,
 }
 
+impl ECDSAAdaptorSig {
+    /// Creates the adaptor signature of the message with the private key, encrypted with the
+    /// adaptor point. The nonce is derived deterministically from the private key, the adaptor
+    /// point, and the message.
+    pub fn sign(
+        privkey: &PrivateKey,
+        adaptor: &PublicKey,
+        msg: &Message,
+    ) -> Result<Self, crypto::Error> {
+        let secp = Bitcoin::secp();
+        let m = scalar_from_bytes(&msg[..]).map_err(crypto::Error::new)?;
+        let nonce = hash_to_scalar(
+            ADAPTOR_NONCE_TAG,
+            &[&privkey.key[..], &adaptor.key.serialize(), &msg[..]],
+        );
+
+        let public_nonce = bitcoin::secp256k1::PublicKey::from_secret_key(secp, &nonce);
+        let mut encrypted_nonce = adaptor.key;
+        encrypted_nonce
+            .mul_assign(secp, &nonce[..])
+            .map_err(crypto::Error::new)?;
+
+        // s' = k^-1 * (m + r * x)
+        let r = scalar_from_bytes(&encrypted_nonce.serialize()[1..]).map_err(crypto::Error::new)?;
+        let mut pre_s = r;
+        pre_s
+            .mul_assign(&privkey.key[..])
+            .map_err(crypto::Error::new)?;
+        pre_s.add_assign(&m[..]).map_err(crypto::Error::new)?;
+        pre_s
+            .mul_assign(&invert_scalar(&nonce).map_err(crypto::Error::new)?[..])
+            .map_err(crypto::Error::new)?;
+        let sig =
+            Signature::from_compact(&[&r[..], &pre_s[..]].concat()).map_err(crypto::Error::new)?;
+
+        let dleq = PDLEQ::prove(&nonce, adaptor, public_nonce, encrypted_nonce)?;
+        Ok(Self {
+            sig,
+            point: *adaptor,
+            dleq,
+        })
+    }
+
+    /// Verifies the adaptor signature of the message under the public key: the proof links the
+    /// public and encrypted nonces, `r` is the x coordinate of the encrypted nonce, and `s' * R' =
+    /// m * G + r * X`. Fails with [`crypto::Error::InvalidProof`] if the nonces are not linked and
+    /// with [`crypto::Error::InvalidAdaptorSignature`] otherwise.
+    pub fn verify(&self, pubkey: &PublicKey, msg: &Message) -> Result<(), crypto::Error> {
+        let secp = Bitcoin::secp();
+        self.dleq.verify(&self.point)?;
+
+        let compact = self.sig.serialize_compact();
+        let r = scalar_from_bytes(&self.dleq.encrypted_nonce.key.serialize()[1..])
+            .map_err(crypto::Error::new)?;
+        if compact[..32] != r[..] {
+            return Err(crypto::Error::InvalidAdaptorSignature);
+        }
+        let m = scalar_from_bytes(&msg[..]).map_err(crypto::Error::new)?;
+
+        let mut rx = pubkey.key;
+        rx.mul_assign(secp, &r[..]).map_err(crypto::Error::new)?;
+        let expected = bitcoin::secp256k1::PublicKey::from_secret_key(secp, &m)
+            .combine(&rx)
+            .map_err(crypto::Error::new)?;
+        let mut found = self.dleq.public_nonce.key;
+        found
+            .mul_assign(secp, &compact[32..])
+            .map_err(crypto::Error::new)?;
+
+        match found == expected {
+            true => Ok(()),
+            false => Err(crypto::Error::InvalidAdaptorSignature),
+        }
+    }
+}
+
+/// Tag of the hash deriving the nonce `k` of an adaptor signature.
+const ADAPTOR_NONCE_TAG: &[u8] = b"farcaster/adaptor/nonce";
+/// Tag of the hash deriving the nonce of the proof of an adaptor signature.
+const DLEQ_NONCE_TAG: &[u8] = b"farcaster/dleq/nonce";
+/// Tag of the hash deriving the challenge of the proof of an adaptor signature.
+const DLEQ_CHALLENGE_TAG: &[u8] = b"farcaster/dleq/challenge";
+
 /// Produces a zero-knowledge proof of knowledge of the same relation k between two pairs of
-/// elements in the same group, i.e. `(G, R')` and `(T, R)`.
-#[derive(Clone, Debug)]
-pub struct PDLEQ;
+/// elements in the same group, i.e. `(G, R')` and `(Y, R)`: the public nonce `R' = k * G` and the
+/// encrypted nonce `R = k * Y` of an adaptor signature. The Chaum-Pedersen proof is made
+/// non-interactive with a tagged hash of the transcript.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PDLEQ {
+    /// The public nonce `R' = k * G`
+    pub public_nonce: PublicKey,
+    /// The encrypted nonce `R = k * Y`
+    pub encrypted_nonce: PublicKey,
+    /// The challenge `c` of the proof
+    pub challenge: SecretKey,
+    /// The response `z = a + c * k` of the proof, `a` being the proof nonce
+    pub response: SecretKey,
+}
+
+impl PDLEQ {
+    fn prove(
+        nonce: &SecretKey,
+        adaptor: &PublicKey,
+        public_nonce: bitcoin::secp256k1::PublicKey,
+        encrypted_nonce: bitcoin::secp256k1::PublicKey,
+    ) -> Result<Self, crypto::Error> {
+        let secp = Bitcoin::secp();
+        let proof_nonce = hash_to_scalar(
+            DLEQ_NONCE_TAG,
+            &[
+                &nonce[..],
+                &public_nonce.serialize(),
+                &encrypted_nonce.serialize(),
+            ],
+        );
+        let first = bitcoin::secp256k1::PublicKey::from_secret_key(secp, &proof_nonce);
+        let mut second = adaptor.key;
+        second
+            .mul_assign(secp, &proof_nonce[..])
+            .map_err(crypto::Error::new)?;
+
+        let challenge = dleq_challenge(adaptor, &public_nonce, &encrypted_nonce, &first, &second);
+        let mut response = challenge;
+        response
+            .mul_assign(&nonce[..])
+            .map_err(crypto::Error::new)?;
+        response
+            .add_assign(&proof_nonce[..])
+            .map_err(crypto::Error::new)?;
+
+        Ok(Self {
+            public_nonce: PublicKey {
+                compressed: true,
+                key: public_nonce,
+            },
+            encrypted_nonce: PublicKey {
+                compressed: true,
+                key: encrypted_nonce,
+            },
+            challenge,
+            response,
+        })
+    }
+
+    /// Verifies the proof against the adaptor point `Y`, the commitments of the prover are
+    /// recomputed as `z * G - c * R'` and `z * Y - c * R` and must hash to the challenge. Fails
+    /// with [`crypto::Error::InvalidProof`] otherwise.
+    pub fn verify(&self, adaptor: &PublicKey) -> Result<(), crypto::Error> {
+        let secp = Bitcoin::secp();
+        let mut minus_challenge = self.challenge;
+        minus_challenge.negate_assign();
+
+        let mut public_term = self.public_nonce.key;
+        public_term
+            .mul_assign(secp, &minus_challenge[..])
+            .map_err(crypto::Error::new)?;
+        let first = bitcoin::secp256k1::PublicKey::from_secret_key(secp, &self.response)
+            .combine(&public_term)
+            .map_err(|_| crypto::Error::InvalidProof)?;
+
+        let mut encrypted_term = self.encrypted_nonce.key;
+        encrypted_term
+            .mul_assign(secp, &minus_challenge[..])
+            .map_err(crypto::Error::new)?;
+        let mut second = adaptor.key;
+        second
+            .mul_assign(secp, &self.response[..])
+            .map_err(crypto::Error::new)?;
+        let second = second
+            .combine(&encrypted_term)
+            .map_err(|_| crypto::Error::InvalidProof)?;
+
+        let challenge = dleq_challenge(
+            adaptor,
+            &self.public_nonce.key,
+            &self.encrypted_nonce.key,
+            &first,
+            &second,
+        );
+        match challenge == self.challenge {
+            true => Ok(()),
+            false => Err(crypto::Error::InvalidProof),
+        }
+    }
+}
+
+/// Hashes the transcript of the proof into its challenge.
+fn dleq_challenge(
+    adaptor: &PublicKey,
+    public_nonce: &bitcoin::secp256k1::PublicKey,
+    encrypted_nonce: &bitcoin::secp256k1::PublicKey,
+    first: &bitcoin::secp256k1::PublicKey,
+    second: &bitcoin::secp256k1::PublicKey,
+) -> SecretKey {
+    hash_to_scalar(
+        DLEQ_CHALLENGE_TAG,
+        &[
+            &adaptor.key.serialize(),
+            &public_nonce.serialize(),
+            &encrypted_nonce.serialize(),
+            &first.serialize(),
+            &second.serialize(),
+        ],
+    )
+}
 
 impl StrictEncode for PDLEQ {
-    fn strict_encode<E: std::io::Write>(&self, mut _e: E) -> Result<usize, strict_encoding::Error> {
-        Ok(0)
+    fn strict_encode<E: std::io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        e.write_all(&self.public_nonce.key.serialize())?;
+        e.write_all(&self.encrypted_nonce.key.serialize())?;
+        e.write_all(&self.challenge[..])?;
+        e.write_all(&self.response[..])?;
+        Ok(2 * 33 + 2 * 32)
     }
 }
 
 impl StrictDecode for PDLEQ {
-    fn strict_decode<D: std::io::Read>(mut _d: D) -> Result<Self, strict_encoding::Error> {
-        Ok(Self)
+    fn strict_decode<D: std::io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let invalid =
+            |_| strict_encoding::Error::DataIntegrityError("Invalid DLEQ proof".to_string());
+        let mut point = [0u8; 33];
+        d.read_exact(&mut point)?;
+        let public_nonce = bitcoin::secp256k1::PublicKey::from_slice(&point).map_err(invalid)?;
+        d.read_exact(&mut point)?;
+        let encrypted_nonce = bitcoin::secp256k1::PublicKey::from_slice(&point).map_err(invalid)?;
+        let mut scalar = [0u8; 32];
+        d.read_exact(&mut scalar)?;
+        let challenge = SecretKey::from_slice(&scalar).map_err(invalid)?;
+        d.read_exact(&mut scalar)?;
+        let response = SecretKey::from_slice(&scalar).map_err(invalid)?;
+        Ok(Self {
+            public_nonce: PublicKey {
+                compressed: true,
+                key: public_nonce,
+            },
+            encrypted_nonce: PublicKey {
+                compressed: true,
+                key: encrypted_nonce,
+            },
+            challenge,
+            response,
+        })
     }
 }
 
@@ -242,53 +633,226 @@ impl Keys for Bitcoin {
     fn as_bytes(pubkey: &PublicKey) -> Vec<u8> {
         pubkey.to_bytes()
     }
+
+    fn from_cross_group_secret(secret: &[u8; 32]) -> Result<PublicKey, crypto::Error> {
+        crypto::check_cross_group_secret(secret)?;
+        // secp256k1 secret keys are big-endian encoded
+        let mut bytes = *secret;
+        bytes.reverse();
+        let key = SecretKey::from_slice(&bytes).map_err(crypto::Error::new)?;
+        Ok(PublicKey {
+            compressed: true,
+            key: bitcoin::secp256k1::PublicKey::from_secret_key(Bitcoin::secp(), &key),
+        })
+    }
 }
 
 impl Signatures for Bitcoin {
     type Signature = Signature;
     type AdaptorSignature = ECDSAAdaptorSig;
 
-    fn adapt(
-        _key: &PrivateKey,
-        _sig: ECDSAAdaptorSig,
-    ) -> Result<Signature, farcaster_core::crypto::Error> {
-        todo!()
+    /// Decrypts the adaptor signature with the adaptor secret `y`, the adapted signature is `s =
+    /// s' * y^-1` normalized to low S. Fails with [`crypto::Error::InvalidKey`] if the key is not
+    /// the secret of the adaptor point.
+    fn adapt(key: &PrivateKey, adaptor_sig: ECDSAAdaptorSig) -> Result<Signature, crypto::Error> {
+        if key.public_key(Bitcoin::secp()).key != adaptor_sig.point.key {
+            return Err(crypto::Error::InvalidKey);
+        }
+        let compact = adaptor_sig.sig.serialize_compact();
+        let pre_s = SecretKey::from_slice(&compact[32..]).map_err(crypto::Error::new)?;
+
+        let mut s = invert_scalar(&key.key).map_err(crypto::Error::new)?;
+        s.mul_assign(&pre_s[..]).map_err(crypto::Error::new)?;
+        let mut sig = Signature::from_compact(&[&compact[..32], &s[..]].concat())
+            .map_err(crypto::Error::new)?;
+        sig.normalize_s();
+        Ok(sig)
+    }
+
+    fn recover_secret(
+        adaptor_sig: &ECDSAAdaptorSig,
+        final_sig: &Signature,
+    ) -> Result<PrivateKey, crypto::Error> {
+        let secp = Bitcoin::secp();
+        let pre_s = SecretKey::from_slice(&adaptor_sig.sig.serialize_compact()[32..])
+            .map_err(crypto::Error::new)?;
+        let s = SecretKey::from_slice(&final_sig.serialize_compact()[32..])
+            .map_err(crypto::Error::new)?;
+
+        // The adapted signature is `s = s' * y^-1`, possibly negated when normalized to low S
+        let mut secret = invert_scalar(&s).map_err(crypto::Error::new)?;
+        secret.mul_assign(&pre_s[..]).map_err(crypto::Error::new)?;
+        for _ in 0..2 {
+            let candidate = PrivateKey {
+                compressed: true,
+                network: Network::Bitcoin,
+                key: secret,
+            };
+            if candidate.public_key(secp).key == adaptor_sig.point.key {
+                return Ok(candidate);
+            }
+            secret.negate_assign();
+        }
+
+        Err(crypto::Error::InvalidAdaptorSignature)
     }
 
-    fn recover_key(_sig: Signature, _adapted_sig: ECDSAAdaptorSig) -> PrivateKey {
-        todo!()
+    /// Only low-S signatures are relayed by the network, a high-S signature is the malleated form
+    /// of its low-S counterpart.
+    fn validate_signature(sig: &Signature) -> Result<(), consensus::Error> {
+        let mut normalized = *sig;
+        normalized.normalize_s();
+        match normalized == *sig {
+            true => Ok(()),
+            false => Err(consensus::Error::InvalidSignatureEncoding),
+        }
+    }
+
+    /// The pre-signature `s'` is not a valid signature and is not normalized, its `s'` value is
+    /// either high or low. Only the adapted signature must be low-S, see [`Signatures::adapt`].
+    fn validate_adaptor_signature(_adaptor_sig: &ECDSAAdaptorSig) -> Result<(), consensus::Error> {
+        Ok(())
     }
 }
 
+/// Parses a DER encoded signature received from a peer, fails with
+/// [`InvalidSignatureEncoding`](consensus::Error::InvalidSignatureEncoding) if the encoding is not
+/// canonical DER or if the signature is high-S.
+pub fn signature_from_der(der: &[u8]) -> Result<Signature, consensus::Error> {
+    let sig = Signature::from_der(der).map_err(|_| consensus::Error::InvalidSignatureEncoding)?;
+    if &sig.serialize_der()[..] != der {
+        return Err(consensus::Error::InvalidSignatureEncoding);
+    }
+    Bitcoin::validate_signature(&sig)?;
+    Ok(sig)
+}
+
+/// Order of the secp256k1 group minus two, inverting a scalar is raising it to this power.
+const CURVE_ORDER_MINUS_TWO: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x3f,
+];
+
+/// Order of the secp256k1 group.
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+/// Interprets 32 big-endian bytes as a scalar reduced modulo the group order, fails if the
+/// reduced scalar is zero. The bytes are less than twice the order, a single subtraction reduces
+/// them.
+fn scalar_from_bytes(bytes: &[u8]) -> Result<SecretKey, bitcoin::secp256k1::Error> {
+    let mut reduced = [0u8; 32];
+    if bytes.len() != 32 {
+        return Err(bitcoin::secp256k1::Error::InvalidSecretKey);
+    }
+    reduced.copy_from_slice(bytes);
+    if reduced >= CURVE_ORDER {
+        let mut borrow = 0;
+        for (byte, order) in reduced.iter_mut().zip(CURVE_ORDER.iter()).rev() {
+            let diff = *byte as i16 - *order as i16 - borrow;
+            borrow = (diff < 0) as i16;
+            *byte = (diff + (borrow << 8)) as u8;
+        }
+    }
+    SecretKey::from_slice(&reduced)
+}
+
+/// Hashes the tagged data into a scalar, a counter is appended and incremented until the hash is
+/// a valid non-zero scalar.
+fn hash_to_scalar(tag: &[u8], data: &[&[u8]]) -> SecretKey {
+    let tag = sha256::Hash::hash(tag);
+    let mut counter = 0u32;
+    loop {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&tag[..]);
+        engine.input(&tag[..]);
+        for chunk in data {
+            engine.input(chunk);
+        }
+        engine.input(&counter.to_be_bytes());
+        if let Ok(scalar) = SecretKey::from_slice(&sha256::Hash::from_engine(engine)[..]) {
+            return scalar;
+        }
+        counter += 1;
+    }
+}
+
+/// Computes the modular inverse of a non-zero scalar with Fermat's little theorem.
+fn invert_scalar(scalar: &SecretKey) -> Result<SecretKey, bitcoin::secp256k1::Error> {
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let mut result = SecretKey::from_slice(&one)?;
+    for byte in CURVE_ORDER_MINUS_TWO.iter() {
+        for bit in (0..8).rev() {
+            let square = result;
+            result.mul_assign(&square[..])?;
+            if (byte >> bit) & 1 == 1 {
+                result.mul_assign(&scalar[..])?;
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the signatures found in the witnesses of the transaction inputs, witness signatures
+/// are DER encoded and followed by their sighash type.
+pub fn witness_signatures(tx: &bitcoin::Transaction) -> Vec<Signature> {
+    tx.input
+        .iter()
+        .flat_map(|input| input.witness.iter())
+        .filter_map(|element| {
+            let (_, der) = element.split_last()?;
+            Signature::from_der(der).ok()
+        })
+        .collect()
+}
+
+/// Recovers the adaptor secret from a transaction spending with the adapted signature, i.e. the
+/// `buy (c)` transaction once seen on-chain, and the adaptor signature it was adapted from.
+///
+/// Each signature in the witness of the transaction inputs is paired with the adaptor signature
+/// with [`recover_secret`](Signatures::recover_secret). Fails with
+/// [`SecretNotRecoverable`](transaction::Error::SecretNotRecoverable) if no signature matches.
+pub fn recover_adaptor_secret_from_tx(
+    tx: &bitcoin::Transaction,
+    adaptor_sig: &ECDSAAdaptorSig,
+) -> Result<PrivateKey, transaction::Error> {
+    witness_signatures(tx)
+        .iter()
+        .find_map(|sig| Bitcoin::recover_secret(adaptor_sig, sig).ok())
+        .ok_or(transaction::Error::SecretNotRecoverable)
+}
+
 impl FromSeed<Arb> for Bitcoin {
     type Seed = [u8; 32];
 
     fn get_privkey(seed: &[u8; 32], key_type: ArbitratingKey) -> Result<PrivateKey, crypto::Error> {
-        let secp = Secp256k1::new();
+        let secp = Self::secp();
         let master_key = ExtendedPrivKey::new_master(Network::Bitcoin, seed.as_ref())
             .map_err(|e| crypto::Error::new(e))?;
         let key = match key_type {
             ArbitratingKey::Fund => {
-                master_key.derive_priv(&secp, &DerivationPath::from_str("m/0/1/1").unwrap())
+                master_key.derive_priv(secp, &DerivationPath::from_str("m/0/1/1").unwrap())
             }
             ArbitratingKey::Buy => {
-                master_key.derive_priv(&secp, &DerivationPath::from_str("m/0/1/2").unwrap())
+                master_key.derive_priv(secp, &DerivationPath::from_str("m/0/1/2").unwrap())
             }
             ArbitratingKey::Cancel => {
-                master_key.derive_priv(&secp, &DerivationPath::from_str("m/0/1/3").unwrap())
+                master_key.derive_priv(secp, &DerivationPath::from_str("m/0/1/3").unwrap())
             }
             ArbitratingKey::Refund => {
-                master_key.derive_priv(&secp, &DerivationPath::from_str("m/0/1/4").unwrap())
+                master_key.derive_priv(secp, &DerivationPath::from_str("m/0/1/4").unwrap())
             }
             ArbitratingKey::Punish => {
-                master_key.derive_priv(&secp, &DerivationPath::from_str("m/0/1/5").unwrap())
+                master_key.derive_priv(secp, &DerivationPath::from_str("m/0/1/5").unwrap())
             }
         };
         Ok(key.map_err(|e| crypto::Error::new(e))?.private_key)
     }
 
     fn get_pubkey(seed: &[u8; 32], key_type: ArbitratingKey) -> Result<PublicKey, crypto::Error> {
-        let secp = Secp256k1::new();
-        Ok(Self::get_privkey(&seed, key_type)?.public_key(&secp))
+        Ok(Self::get_privkey(&seed, key_type)?.public_key(Self::secp()))
     }
 }
@@ -0,0 +1,185 @@
+//! Chain watcher backed by the RPC interface of a Bitcoin Core node
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::task::{self, Context, Waker};
+use std::thread;
+use std::time::Duration;
+
+use bitcoin::blockdata::transaction::{OutPoint, Transaction, TxOut};
+use bitcoin::hash_types::Txid;
+use bitcoincore_rpc::jsonrpc;
+use bitcoincore_rpc::{Client, RpcApi};
+
+use farcaster_core::transaction::{ChainWatcher, Error as FError, WatchFuture};
+
+use crate::bitcoin::{Address, Amount, Bitcoin};
+
+/// RPC error code returned by Bitcoin Core when a transaction is not found.
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+/// Watches the chain by polling a Bitcoin Core node at a fixed interval.
+///
+/// Looking up arbitrary transactions requires the node to run with `-txindex` and outputs are
+/// looked up in the node wallet, thus the watched addresses must be imported beforehand.
+#[derive(Debug, Clone)]
+pub struct BitcoinCoreWatcher {
+    client: Arc<Client>,
+    poll_interval: Duration,
+}
+
+impl BitcoinCoreWatcher {
+    /// Create a new watcher querying the node with the client every `poll_interval`.
+    pub fn new(client: Client, poll_interval: Duration) -> Self {
+        Self {
+            client: Arc::new(client),
+            poll_interval,
+        }
+    }
+}
+
+/// Check run by a [`Polling`] future until it returns a value.
+type Check<T> = Box<dyn FnMut() -> Result<Option<T>, FError> + Send>;
+
+/// State shared between a [`Polling`] future and its background thread.
+struct Shared<T> {
+    result: Option<Result<T, FError>>,
+    waker: Option<Waker>,
+}
+
+/// Future resolved when `check` returns a value. On the first poll a single background thread
+/// is started, it calls `check` every interval and wakes the task up once the value is
+/// available, so the future never blocks the executor and is not tied to a specific runtime.
+/// The thread stops after the next interval once the future is dropped.
+struct Polling<T> {
+    check: Option<(Check<T>, Duration)>,
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Polling<T>
+where
+    T: Send + 'static,
+{
+    fn new<F>(check: F, interval: Duration) -> Self
+    where
+        F: FnMut() -> Result<Option<T>, FError> + Send + 'static,
+    {
+        Self {
+            check: Some((Box::new(check), interval)),
+            shared: Arc::new(Mutex::new(Shared {
+                result: None,
+                waker: None,
+            })),
+        }
+    }
+
+    fn spawn(mut check: Check<T>, interval: Duration, shared: Weak<Mutex<Shared<T>>>) {
+        thread::spawn(move || {
+            let result = loop {
+                match check() {
+                    Ok(None) => thread::sleep(interval),
+                    result => break result.transpose(),
+                }
+                // The future has been dropped, nobody awaits the value anymore
+                if shared.strong_count() == 0 {
+                    return;
+                }
+            };
+            if let Some(shared) = shared.upgrade() {
+                let mut shared = shared.lock().unwrap_or_else(PoisonError::into_inner);
+                shared.result = result;
+                if let Some(waker) = shared.waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+    }
+}
+
+impl<T> Future for Polling<T>
+where
+    T: Send + 'static,
+{
+    type Output = Result<T, FError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> task::Poll<Self::Output> {
+        if let Some((check, interval)) = self.check.take() {
+            Self::spawn(check, interval, Arc::downgrade(&self.shared));
+        }
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        match shared.result.take() {
+            Some(result) => task::Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                task::Poll::Pending
+            }
+        }
+    }
+}
+
+fn is_not_found(error: &bitcoincore_rpc::Error) -> bool {
+    match error {
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::error::Error::Rpc(e)) => {
+            e.code == RPC_INVALID_ADDRESS_OR_KEY
+        }
+        _ => false,
+    }
+}
+
+impl ChainWatcher<Bitcoin> for BitcoinCoreWatcher {
+    type Txid = Txid;
+
+    type Output = (OutPoint, TxOut);
+
+    fn wait_for_confirmation(
+        &self,
+        txid: Txid,
+        confirmations: u32,
+    ) -> WatchFuture<'_, Transaction> {
+        let client = self.client.clone();
+        Box::pin(Polling::new(
+            move || match client.get_raw_transaction_info(&txid, None) {
+                Ok(info) if info.confirmations.unwrap_or(0) >= confirmations => {
+                    info.transaction().map(Some).map_err(FError::new)
+                }
+                Ok(_) => Ok(None),
+                Err(e) if is_not_found(&e) => Ok(None),
+                Err(e) => Err(FError::new(e)),
+            },
+            self.poll_interval,
+        ))
+    }
+
+    fn wait_for_output(
+        &self,
+        address: Address,
+        amount: Amount,
+    ) -> WatchFuture<'_, (OutPoint, TxOut)> {
+        let client = self.client.clone();
+        let script_pubkey = address.0.script_pubkey();
+        Box::pin(Polling::new(
+            move || {
+                let unspents = client
+                    .list_unspent(Some(0), None, Some(&[&address.0]), None, None)
+                    .map_err(FError::new)?;
+                Ok(unspents
+                    .into_iter()
+                    .find(|utxo| {
+                        utxo.script_pub_key == script_pubkey
+                            && utxo.amount.as_sat() == amount.as_sat()
+                    })
+                    .map(|utxo| {
+                        (
+                            OutPoint::new(utxo.txid, utxo.vout),
+                            TxOut {
+                                value: utxo.amount.as_sat(),
+                                script_pubkey: utxo.script_pub_key,
+                            },
+                        )
+                    }))
+            },
+            self.poll_interval,
+        ))
+    }
+}
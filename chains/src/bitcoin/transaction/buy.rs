@@ -1,114 +1,177 @@
-use bitcoin::blockdata::script::Instruction;
+use std::marker::PhantomData;
+
+use bitcoin::blockdata::transaction::{SigHashType, TxOut};
 use bitcoin::secp256k1::Signature;
 use bitcoin::util::key::{PrivateKey, PublicKey};
 use bitcoin::util::psbt::PartiallySignedTransaction;
 
-use farcaster_core::script;
-use farcaster_core::transaction::{AdaptorSignable, Buyable, Error as FError, Lockable, Signable};
+use farcaster_core::script::{self, ScriptPath};
+use farcaster_core::transaction::{
+    AdaptorSignable, Buyable, Error as FError, Lockable, Signable, TxId,
+};
 
-use crate::bitcoin::transaction::{Error, MetadataOutput, SubTransaction, Tx};
-use crate::bitcoin::{Address, Bitcoin, ECDSAAdaptorSig};
+use crate::bitcoin::transaction::lock::{finalize_swaplock, parse_swaplock, swaplock_script_with};
+use crate::bitcoin::transaction::{
+    push_anchor_output, verify_outputs_with_anchor, verify_script_spend, witness_script_keys,
+    Error, KeyOrdering, MetadataOutput, SubTransaction, Tx,
+};
+use crate::bitcoin::{Address, Bitcoin, ECDSAAdaptorSig, SEQUENCE_LOCKTIME_DISABLE_FLAG};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Buy;
 
 impl SubTransaction for Buy {
     fn finalize(psbt: &mut PartiallySignedTransaction) -> Result<(), FError> {
         let script = psbt.inputs[0]
             .witness_script
-            .clone()
+            .as_ref()
             .ok_or(FError::MissingWitness)?;
+        let lock = parse_swaplock(script)?;
+        finalize_swaplock(psbt, &lock, ScriptPath::Success)
+    }
 
-        let mut keys = script.instructions().skip(2).take(2);
-
-        psbt.inputs[0].final_script_witness = Some(vec![
-            vec![], // 0 for multisig
-            psbt.inputs[0]
-                .partial_sigs
-                .get(
-                    &PublicKey::from_slice(
-                        keys.next()
-                            .ok_or(FError::MissingPublicKey)?
-                            .map(|i| match i {
-                                Instruction::PushBytes(b) => Ok(b),
-                                _ => Err(FError::MissingPublicKey),
-                            })
-                            .map_err(Error::from)??,
-                    )
-                    .map_err(|_| FError::MissingPublicKey)?,
-                )
-                .ok_or(FError::MissingSignature)?
-                .clone(),
-            psbt.inputs[0]
-                .partial_sigs
-                .get(
-                    &PublicKey::from_slice(
-                        keys.next()
-                            .ok_or(FError::MissingPublicKey)?
-                            .map(|i| match i {
-                                Instruction::PushBytes(b) => Ok(b),
-                                _ => Err(FError::MissingPublicKey),
-                            })
-                            .map_err(Error::from)??,
-                    )
-                    .map_err(|_| FError::MissingPublicKey)?,
-                )
-                .ok_or(FError::MissingSignature)?
-                .clone(),
-            vec![1],             // OP_TRUE
-            script.into_bytes(), // swaplock script
-        ]);
+    fn required_keys(psbt: &PartiallySignedTransaction) -> Vec<PublicKey> {
+        // Both keys of the swaplock consensus path
+        witness_script_keys(psbt, &[2, 3])
+    }
+}
 
-        Ok(())
+impl Tx<Buy> {
+    /// Returns a human readable snapshot of the `buy (c)` transaction, named after
+    /// [`Buyable::TX_ID`].
+    pub fn describe(&self) -> String {
+        self.describe_as(<Self as Buyable<Bitcoin, MetadataOutput>>::TX_ID)
+    }
+
+    /// Initialize the buy transaction with a fee-bump anchor output at index 1, spendable by
+    /// either participant with its success key, see [`super::anchor_script`]. The anchor holds
+    /// the dust limit of its script and the fee is later deducted from the destination output
+    /// only. As for the refund, the input is still signed with `SIGHASH_ALL`.
+    pub fn initialize_with_anchor(
+        prev: &impl Lockable<Bitcoin, MetadataOutput>,
+        lock: script::DataLock<Bitcoin>,
+        destination_target: Address,
+    ) -> Result<Self, FError> {
+        Self::initialize_buy(prev, lock, destination_target, true)
+    }
+
+    fn initialize_buy(
+        prev: &impl Lockable<Bitcoin, MetadataOutput>,
+        lock: script::DataLock<Bitcoin>,
+        destination_target: Address,
+        with_anchor: bool,
+    ) -> Result<Self, FError> {
+        let output_metadata = prev.get_consumable_output()?;
+        // Follow the key ordering selected when creating the lock
+        let ordering = KeyOrdering::recorded(prev.partial())?;
+
+        if output_metadata.script_pubkey != Some(swaplock_script_with(&lock, ordering)) {
+            return Err(FError::WrongTemplate);
+        }
+
+        let mut output = vec![TxOut {
+            value: output_metadata.tx_out.value,
+            script_pubkey: destination_target.0.script_pubkey(),
+        }];
+        let anchor = if with_anchor {
+            Some(push_anchor_output(&mut output, &lock.success, ordering)?)
+        } else {
+            None
+        };
+
+        let unsigned_tx = bitcoin::blockdata::transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            // activate disable flag on CSV
+            input: vec![output_metadata.as_txin(SEQUENCE_LOCKTIME_DISABLE_FLAG)],
+            output,
+        };
+
+        let mut psbt =
+            PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).map_err(Error::from)?;
+
+        // Set the input witness data and sighash type
+        output_metadata.apply_to_psbt_input(&mut psbt.inputs[0], SigHashType::All);
+        if let Some(script) = anchor {
+            psbt.outputs[1].witness_script = Some(script);
+        }
+        ordering.record(&mut psbt);
+
+        let buy = Tx {
+            psbt,
+            _t: PhantomData,
+        };
+        // Defense in depth, the input must spend exactly the lock output
+        buy.check_links(prev)?;
+        Ok(buy)
     }
 }
 
 impl Buyable<Bitcoin, MetadataOutput> for Tx<Buy> {
+    const TX_ID: TxId = TxId::Buy;
+
     fn initialize(
-        _prev: &impl Lockable<Bitcoin, MetadataOutput>,
-        _lock: script::DataLock<Bitcoin>,
-        _destination_target: Address,
+        prev: &impl Lockable<Bitcoin, MetadataOutput>,
+        lock: script::DataLock<Bitcoin>,
+        destination_target: Address,
     ) -> Result<Self, FError> {
-        todo!()
+        Self::initialize_buy(prev, lock, destination_target, false)
     }
 
     fn verify_template(
         &self,
-        _lock: script::DataLock<Bitcoin>,
-        _destination_target: Address,
+        lock: script::DataLock<Bitcoin>,
+        destination_target: Address,
     ) -> Result<(), FError> {
-        todo!()
+        let ordering = KeyOrdering::recorded(&self.psbt)?;
+        // The input spends the swaplock through its consensus path
+        verify_script_spend(
+            &self.psbt,
+            SEQUENCE_LOCKTIME_DISABLE_FLAG,
+            &swaplock_script_with(&lock, ordering),
+        )?;
+
+        // The destination output, optionally followed by the fee-bump anchor
+        verify_outputs_with_anchor(
+            &self.psbt,
+            &destination_target.0.script_pubkey(),
+            &lock.success,
+            ordering,
+        )?;
+
+        Ok(())
     }
 }
 
 impl Signable<Bitcoin> for Tx<Buy> {
-    fn generate_witness(&self, _privkey: &PrivateKey) -> Result<Signature, FError> {
-        {
-            // TODO validate the transaction before signing
-        }
-        todo!()
+    fn generate_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
+        self.sign_first_input(privkey)
+    }
+
+    fn witness_sighash(&self) -> Result<Vec<u8>, FError> {
+        Ok(self.input_sighash()?[..].to_vec())
     }
 
-    fn verify_witness(&self, _pubkey: &PublicKey, _sig: Signature) -> Result<(), FError> {
-        todo!()
+    fn verify_witness(&self, pubkey: &PublicKey, sig: Signature) -> Result<(), FError> {
+        self.verify_first_input(pubkey, &sig)
     }
 }
 
 impl AdaptorSignable<Bitcoin> for Tx<Buy> {
     fn generate_adaptor_witness(
         &self,
-        _privkey: &PrivateKey,
-        _adaptor: &PublicKey,
+        privkey: &PrivateKey,
+        adaptor: &PublicKey,
     ) -> Result<ECDSAAdaptorSig, FError> {
-        todo!()
+        self.adaptor_sign_first_input(privkey, adaptor)
     }
 
     fn verify_adaptor_witness(
         &self,
-        _pubkey: &PublicKey,
-        _adaptor: &PublicKey,
-        _sig: ECDSAAdaptorSig,
+        pubkey: &PublicKey,
+        adaptor: &PublicKey,
+        sig: ECDSAAdaptorSig,
     ) -> Result<(), FError> {
-        todo!()
+        self.verify_first_input_adaptor(pubkey, adaptor, &sig)
     }
 }
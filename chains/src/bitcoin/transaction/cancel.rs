@@ -1,106 +1,171 @@
 use std::marker::PhantomData;
 
 use bitcoin::blockdata::opcodes;
-use bitcoin::blockdata::script::Builder;
-use bitcoin::blockdata::script::Instruction;
-use bitcoin::blockdata::transaction::{SigHashType, TxIn, TxOut};
-use bitcoin::secp256k1::{Secp256k1, Signature};
+use bitcoin::blockdata::script::{Builder, Script};
+use bitcoin::blockdata::transaction::{SigHashType, TxOut};
+use bitcoin::secp256k1::Signature;
 use bitcoin::util::key::{PrivateKey, PublicKey};
 use bitcoin::util::psbt::PartiallySignedTransaction;
 
-use farcaster_core::script;
-use farcaster_core::transaction::{Cancelable, Error as FError, Forkable, Lockable};
-
-use crate::bitcoin::transaction::{sign_input, Error, MetadataOutput, SubTransaction, Tx, TxInRef};
+use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy};
+use farcaster_core::script::{self, DoubleKeys, ScriptPath};
+use farcaster_core::transaction::{
+    Cancelable, Error as FError, Forkable, Lockable, Transaction, TxId,
+};
+
+use crate::bitcoin::fee::SatPerVByte;
+use crate::bitcoin::transaction::lock::{
+    finalize_swaplock, parse_swaplock, parse_swaplock_keys, swaplock_script_with,
+};
+use crate::bitcoin::transaction::{
+    multisig_witness, partial_sig, pushed_key, verify_multisig_witness, verify_script_spend,
+    witness_script_keys, Error, KeyOrdering, MetadataOutput, SubTransaction, Tx,
+};
 use crate::bitcoin::Bitcoin;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Cancel;
 
+/// Creates the punishable lock script of the `cancel (d)` output. The first branch is the
+/// consensus path used by `refund (e)`, the second branch is the timelocked path used by `punish
+/// (f)`. The keys are pushed with the role-based ordering, see [`punish_lock_script_with`].
+pub fn punish_lock_script(punish_lock: &script::DataPunishableLock<Bitcoin>) -> Script {
+    punish_lock_script_with(punish_lock, KeyOrdering::RoleBased)
+}
+
+/// Creates the punishable lock script of the `cancel (d)` output, the keys of the multisig branch
+/// are pushed following the given ordering. The timelock is pushed as is, see
+/// [`CSVTimelock::to_csv_value`](crate::bitcoin::CSVTimelock::to_csv_value) to validate it.
+pub fn punish_lock_script_with(
+    punish_lock: &script::DataPunishableLock<Bitcoin>,
+    ordering: KeyOrdering,
+) -> Script {
+    let success = ordering.order(&punish_lock.success);
+    Builder::new()
+        .push_opcode(opcodes::all::OP_IF)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_key(&success.0)
+        .push_key(&success.1)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::all::OP_ELSE)
+        .push_int(punish_lock.timelock.as_u32().into())
+        .push_opcode(opcodes::all::OP_CSV)
+        .push_opcode(opcodes::all::OP_DROP)
+        .push_key(&punish_lock.failure)
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .push_opcode(opcodes::all::OP_ENDIF)
+        .into_script()
+}
+
+/// Finalizes the input consuming the punishable lock output of the `cancel (d)` transaction
+/// through the given path. The success path is the consensus path used by `refund (e)` and
+/// requires both signatures, the failure path is the timelocked path used by `punish (f)` and
+/// requires only the signature of the failure key.
+pub fn finalize_punish_lock(
+    psbt: &mut PartiallySignedTransaction,
+    path: ScriptPath,
+) -> Result<(), FError> {
+    let script = psbt.inputs[0]
+        .witness_script
+        .clone()
+        .ok_or(FError::MissingWitness)?;
+
+    let witness = match path {
+        ScriptPath::Success => {
+            let keys = DoubleKeys::new(pushed_key(&script, 2)?, pushed_key(&script, 3)?);
+            multisig_witness(&psbt.inputs[0], &keys, &path, script)?
+        }
+        ScriptPath::Failure => {
+            let failure = pushed_key(&script, 10)?;
+            vec![
+                partial_sig(&psbt.inputs[0], &failure)?,
+                vec![],              // OP_FALSE
+                script.into_bytes(), // punishable lock script
+            ]
+        }
+    };
+
+    psbt.inputs[0].final_script_witness = Some(witness);
+    Ok(())
+}
+
+/// Returns the keys required to spend the punishable lock output of the `cancel (d)` transaction
+/// through the given path, see [`finalize_punish_lock`].
+pub fn punish_lock_keys(psbt: &PartiallySignedTransaction, path: ScriptPath) -> Vec<PublicKey> {
+    match path {
+        ScriptPath::Success => witness_script_keys(psbt, &[2, 3]),
+        ScriptPath::Failure => witness_script_keys(psbt, &[10]),
+    }
+}
+
 impl SubTransaction for Cancel {
     fn finalize(psbt: &mut PartiallySignedTransaction) -> Result<(), FError> {
         let script = psbt.inputs[0]
             .witness_script
-            .clone()
+            .as_ref()
             .ok_or(FError::MissingWitness)?;
+        let lock = parse_swaplock(script)?;
+        let keys = &lock.failure;
+        // Reject signatures from any other key than the two multisig keys
+        if psbt.inputs[0]
+            .partial_sigs
+            .keys()
+            .any(|key| *key != keys.alice && *key != keys.bob)
+        {
+            return Err(FError::UnexpectedSignature);
+        }
+
+        // Finalizing again only verifies the existing witness, a retry leaves it untouched
+        if let Some(witness) = &psbt.inputs[0].final_script_witness {
+            return verify_multisig_witness(psbt, keys, &ScriptPath::Failure, witness);
+        }
+
+        finalize_swaplock(psbt, &lock, ScriptPath::Failure)
+    }
 
-        let mut keys = script.instructions().skip(11).take(2);
-
-        psbt.inputs[0].final_script_witness = Some(vec![
-            vec![], // 0 for multisig
-            psbt.inputs[0]
-                .partial_sigs
-                .get(
-                    &PublicKey::from_slice(
-                        keys.next()
-                            .ok_or(FError::MissingPublicKey)?
-                            .map(|i| match i {
-                                Instruction::PushBytes(b) => Ok(b),
-                                _ => Err(FError::MissingPublicKey),
-                            })
-                            .map_err(Error::from)??,
-                    )
-                    .map_err(|_| FError::MissingPublicKey)?,
-                )
-                .ok_or(FError::MissingSignature)?
-                .clone(),
-            psbt.inputs[0]
-                .partial_sigs
-                .get(
-                    &PublicKey::from_slice(
-                        keys.next()
-                            .ok_or(FError::MissingPublicKey)?
-                            .map(|i| match i {
-                                Instruction::PushBytes(b) => Ok(b),
-                                _ => Err(FError::MissingPublicKey),
-                            })
-                            .map_err(Error::from)??,
-                    )
-                    .map_err(|_| FError::MissingPublicKey)?,
-                )
-                .ok_or(FError::MissingSignature)?
-                .clone(),
-            vec![],              // OP_FALSE
-            script.into_bytes(), // swaplock script
-        ]);
-
-        Ok(())
+    fn required_keys(psbt: &PartiallySignedTransaction) -> Vec<PublicKey> {
+        // Both keys of the swaplock failure path
+        psbt.inputs
+            .get(0)
+            .and_then(|input| input.witness_script.as_ref())
+            .and_then(|script| parse_swaplock_keys(script).ok())
+            .map_or(vec![], |(alice, bob)| vec![alice, bob])
     }
 }
 
-impl Cancelable<Bitcoin, MetadataOutput> for Tx<Cancel> {
-    fn initialize(
+impl Tx<Cancel> {
+    /// Returns a human readable snapshot of the `cancel (d)` transaction, named after
+    /// [`Cancelable::TX_ID`].
+    pub fn describe(&self) -> String {
+        self.describe_as(<Self as Cancelable<Bitcoin, MetadataOutput>>::TX_ID)
+    }
+
+    /// Creates the `cancel (d)` template without fee, the whole lock output is moved into the
+    /// punishable lock.
+    fn initialize_cancel(
         prev: &impl Lockable<Bitcoin, MetadataOutput>,
         lock: script::DataLock<Bitcoin>,
         punish_lock: script::DataPunishableLock<Bitcoin>,
     ) -> Result<Self, FError> {
-        let script = Builder::new()
-            .push_opcode(opcodes::all::OP_IF)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_key(&punish_lock.success.alice)
-            .push_key(&punish_lock.success.bob)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
-            .push_opcode(opcodes::all::OP_ELSE)
-            .push_int(punish_lock.timelock.as_u32().into())
-            .push_opcode(opcodes::all::OP_CSV)
-            .push_opcode(opcodes::all::OP_DROP)
-            .push_key(&punish_lock.failure)
-            .push_opcode(opcodes::all::OP_CHECKSIG)
-            .push_opcode(opcodes::all::OP_ENDIF)
-            .into_script();
+        // Follow the key ordering selected when creating the lock
+        let ordering = KeyOrdering::recorded(prev.partial())?;
+        // An out of range timelock would make the punish path unspendable
+        punish_lock.timelock.to_csv_value()?;
+        let script = punish_lock_script_with(&punish_lock, ordering);
 
         let output_metadata = prev.get_consumable_output()?;
 
+        // The sequence must match the timelock pushed for OP_CSV in the consumed swaplock
+        let sequence = lock.timelock.as_sequence()?;
+        if output_metadata.script_pubkey != Some(swaplock_script_with(&lock, ordering)) {
+            return Err(FError::WrongTemplate);
+        }
+
         let unsigned_tx = bitcoin::blockdata::transaction::Transaction {
             version: 2,
             lock_time: 0,
-            input: vec![TxIn {
-                previous_output: output_metadata.out_point,
-                script_sig: bitcoin::blockdata::script::Script::default(),
-                sequence: lock.timelock.as_u32(),
-                witness: vec![],
-            }],
+            input: vec![output_metadata.as_txin(sequence)],
             output: vec![TxOut {
                 value: output_metadata.tx_out.value,
                 script_pubkey: script.to_v0_p2wsh(),
@@ -111,65 +176,76 @@ impl Cancelable<Bitcoin, MetadataOutput> for Tx<Cancel> {
             PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).map_err(Error::from)?;
 
         // Set the input witness data and sighash type
-        psbt.inputs[0].witness_utxo = Some(output_metadata.tx_out);
-        psbt.inputs[0].witness_script = output_metadata.script_pubkey;
-        psbt.inputs[0].sighash_type = Some(SigHashType::All);
+        output_metadata.apply_to_psbt_input(&mut psbt.inputs[0], SigHashType::All);
 
         // Set the script witness of the output
         psbt.outputs[0].witness_script = Some(script);
+        ordering.record(&mut psbt);
 
-        // TODO move the logic inside core
-        //// Set the fees according to the given strategy
-        //Bitcoin::set_fees(&mut psbt, fee_strategy, fee_politic)?;
-
-        Ok(Tx {
+        let cancel = Tx {
             psbt,
             _t: PhantomData,
-        })
+        };
+        // Defense in depth, the input must spend exactly the lock output
+        cancel.check_links(prev)?;
+        Ok(cancel)
+    }
+}
+
+impl Cancelable<Bitcoin, MetadataOutput> for Tx<Cancel> {
+    const TX_ID: TxId = TxId::Cancel;
+
+    fn initialize(
+        prev: &impl Lockable<Bitcoin, MetadataOutput>,
+        lock: script::DataLock<Bitcoin>,
+        punish_lock: script::DataPunishableLock<Bitcoin>,
+        fee_strategy: &FeeStrategy<SatPerVByte>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, farcaster_core::Error> {
+        let mut cancel = Self::initialize_cancel(prev, lock, punish_lock)?;
+        Bitcoin::set_fee(&mut cancel.psbt, fee_strategy, fee_politic)?;
+        Ok(cancel)
     }
 
     fn verify_template(
         &self,
-        _lock: script::DataLock<Bitcoin>,
-        _punish_lock: script::DataPunishableLock<Bitcoin>,
+        lock: script::DataLock<Bitcoin>,
+        punish_lock: script::DataPunishableLock<Bitcoin>,
     ) -> Result<(), FError> {
-        todo!()
+        let ordering = KeyOrdering::recorded(&self.psbt)?;
+        // The input spends the swaplock through its timelocked failure path
+        verify_script_spend(
+            &self.psbt,
+            lock.timelock.as_sequence()?,
+            &swaplock_script_with(&lock, ordering),
+        )?;
+
+        // The single output locks the funds in the punishable lock
+        let script = punish_lock_script_with(&punish_lock, ordering);
+        (self.psbt.global.unsigned_tx.output.len() == 1)
+            .then(|| 0)
+            .ok_or_else(|| FError::WrongTemplate)?;
+        (self.psbt.global.unsigned_tx.output[0].script_pubkey == script.to_v0_p2wsh())
+            .then(|| 0)
+            .ok_or_else(|| FError::WrongTemplate)?;
+        (self.psbt.outputs[0].witness_script.as_ref() == Some(&script))
+            .then(|| 0)
+            .ok_or_else(|| FError::WrongTemplate)?;
+
+        Ok(())
     }
 }
 
 impl Forkable<Bitcoin> for Tx<Cancel> {
     fn generate_failure_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
-        let mut secp = Secp256k1::new();
-
-        let unsigned_tx = self.psbt.global.unsigned_tx.clone();
-        let txin = TxInRef::new(&unsigned_tx, 0);
-
-        let witness_utxo = self.psbt.inputs[0]
-            .witness_utxo
-            .clone()
-            .ok_or(FError::MissingWitness)?;
-
-        let script = self.psbt.inputs[0]
-            .witness_script
-            .clone()
-            .ok_or(FError::MissingWitness)?;
-
-        let value = witness_utxo.value;
-
-        let sighash_type = self.psbt.inputs[0]
-            .sighash_type
-            .ok_or(FError::new(Error::MissingSigHashType))?;
-
-        let sig = sign_input(&mut secp, txin, &script, value, sighash_type, &privkey.key)
-            .map_err(Error::from)?;
-        // TODO
-        //let pubkey = PublicKey::from_private_key(&secp, &privkey);
-        //self.add_cooperation(pubkey, sig)?;
+        self.sign_first_input(privkey)
+    }
 
-        Ok(sig)
+    fn failure_witness_sighash(&self) -> Result<Vec<u8>, FError> {
+        Ok(self.input_sighash()?[..].to_vec())
     }
 
-    fn verify_failure_witness(&self, _pubkey: &PublicKey, _sig: Signature) -> Result<(), FError> {
-        todo!()
+    fn verify_failure_witness(&self, pubkey: &PublicKey, sig: Signature) -> Result<(), FError> {
+        self.verify_first_input(pubkey, &sig)
     }
 }
@@ -0,0 +1,110 @@
+use std::marker::PhantomData;
+
+use bitcoin::blockdata::transaction::{SigHashType, TxOut};
+use bitcoin::secp256k1::Signature;
+use bitcoin::util::key::{PrivateKey, PublicKey};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+
+use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy};
+use farcaster_core::script;
+use farcaster_core::transaction::{Error as FError, Lockable, Signable, Transaction};
+
+use crate::bitcoin::fee::SatPerVByte;
+use crate::bitcoin::transaction::lock::swaplock_script_with;
+use crate::bitcoin::transaction::{Buy, Error, KeyOrdering, MetadataOutput, SubTransaction, Tx};
+use crate::bitcoin::{Address, Bitcoin};
+
+/// Spends the consensus path of the `lock (b)` transaction back to Bob when both participants
+/// agree to abort the swap before the `cancel (d)` transaction becomes valid.
+#[derive(Debug, Clone, Copy)]
+pub struct CooperativeClose;
+
+impl SubTransaction for CooperativeClose {
+    fn finalize(psbt: &mut PartiallySignedTransaction) -> Result<(), FError> {
+        // Same 2-of-2 witness on the swaplock consensus path as buy (c)
+        Buy::finalize(psbt)
+    }
+
+    fn required_keys(psbt: &PartiallySignedTransaction) -> Vec<PublicKey> {
+        Buy::required_keys(psbt)
+    }
+}
+
+impl Tx<CooperativeClose> {
+    /// Returns a human readable snapshot of the cooperative close transaction, it is not part of
+    /// the transaction graph and has no [`TxId`](farcaster_core::transaction::TxId).
+    pub fn describe(&self) -> String {
+        self.describe_as(CooperativeClose)
+    }
+
+    /// Creates a new cooperative close transaction based on the `lock (b)` transaction, sending
+    /// the funds to the target address minus the fee set according to the fee strategy and the
+    /// politic.
+    pub fn initialize(
+        prev: &impl Lockable<Bitcoin, MetadataOutput>,
+        lock: script::DataLock<Bitcoin>,
+        target: Address,
+        fee_strategy: &FeeStrategy<SatPerVByte>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, farcaster_core::Error> {
+        let mut close = Self::initialize_close(prev, lock, target)?;
+        Bitcoin::set_fee(&mut close.psbt, fee_strategy, fee_politic)?;
+        Ok(close)
+    }
+
+    /// Creates the cooperative close template without fee, the whole lock output is sent to the
+    /// target address.
+    fn initialize_close(
+        prev: &impl Lockable<Bitcoin, MetadataOutput>,
+        lock: script::DataLock<Bitcoin>,
+        target: Address,
+    ) -> Result<Self, FError> {
+        let output_metadata = prev.get_consumable_output()?;
+        let ordering = KeyOrdering::recorded(prev.partial())?;
+
+        let script_pubkey = swaplock_script_with(&lock, ordering).to_v0_p2wsh();
+        if output_metadata.tx_out.script_pubkey != script_pubkey {
+            return Err(FError::WrongTemplate);
+        }
+
+        let unsigned_tx = bitcoin::blockdata::transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            // activate disable flag on CSV
+            input: vec![output_metadata.as_txin((1 << 31) as u32)],
+            output: vec![TxOut {
+                value: output_metadata.tx_out.value,
+                script_pubkey: target.0.script_pubkey(),
+            }],
+        };
+
+        let mut psbt =
+            PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).map_err(Error::from)?;
+
+        // Set the input witness data and sighash type
+        output_metadata.apply_to_psbt_input(&mut psbt.inputs[0], SigHashType::All);
+        ordering.record(&mut psbt);
+
+        let close = Tx {
+            psbt,
+            _t: PhantomData,
+        };
+        // Defense in depth, the input must spend exactly the lock output
+        close.check_links(prev)?;
+        Ok(close)
+    }
+}
+
+impl Signable<Bitcoin> for Tx<CooperativeClose> {
+    fn generate_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
+        self.sign_first_input(privkey)
+    }
+
+    fn witness_sighash(&self) -> Result<Vec<u8>, FError> {
+        Ok(self.input_sighash()?[..].to_vec())
+    }
+
+    fn verify_witness(&self, pubkey: &PublicKey, sig: Signature) -> Result<(), FError> {
+        self.verify_first_input(pubkey, &sig)
+    }
+}
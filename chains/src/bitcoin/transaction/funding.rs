@@ -1,76 +1,256 @@
+use bitcoin::blockdata::script::Script;
 use bitcoin::blockdata::transaction::{OutPoint, Transaction};
 use bitcoin::network::constants::Network as BtcNetwork;
 use bitcoin::util::key::PublicKey;
 
 use farcaster_core::blockchain::Network;
-use farcaster_core::transaction::{Error as FError, Fundable, Linkable};
+use farcaster_core::transaction::{Error as FError, Fundable, Linkable, TxId};
+
+use std::convert::TryFrom;
 
 use crate::bitcoin::transaction::{Error, MetadataOutput};
 use crate::bitcoin::{Address, Bitcoin};
 
+/// The type of address used to receive the funds of the `funding (a)` transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressType {
+    /// Native segwit v0 pay-to-witness-public-key-hash address.
+    P2wpkh,
+    /// Pay-to-witness-public-key-hash address nested in a pay-to-script-hash, for wallets that
+    /// cannot pay to native segwit addresses.
+    P2shwpkh,
+}
+
+impl Default for AddressType {
+    fn default() -> Self {
+        AddressType::P2wpkh
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Funding {
     pubkey: Option<PublicKey>,
     network: Option<Network>,
+    address_type: AddressType,
+    vout: Option<usize>,
     seen_tx: Option<Transaction>,
+    /// Additional outputs aggregated with the seen transaction to fund the swap, with their index
+    /// and the type of address they pay
+    additional: Vec<(Transaction, usize, AddressType)>,
+}
+
+fn btc_network(network: Network) -> BtcNetwork {
+    match network {
+        Network::Mainnet => BtcNetwork::Bitcoin,
+        Network::Testnet => BtcNetwork::Testnet,
+        Network::Local => BtcNetwork::Regtest,
+    }
+}
+
+impl Funding {
+    /// Create a new funding receiving on an address of the given type, use
+    /// [`Fundable::initialize`] for a native segwit address.
+    pub fn initialize_with_type(
+        pubkey: PublicKey,
+        network: Network,
+        address_type: AddressType,
+    ) -> Result<Self, FError> {
+        Ok(Funding {
+            pubkey: Some(pubkey),
+            network: Some(network),
+            address_type,
+            vout: None,
+            seen_tx: None,
+            additional: vec![],
+        })
+    }
+
+    /// Return the type of address used by the funding.
+    pub fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    /// Return the address of the given type for the public key on the network.
+    fn address(
+        pubkey: &PublicKey,
+        network: Network,
+        address_type: AddressType,
+    ) -> Result<bitcoin::Address, Error> {
+        let network = btc_network(network);
+        Ok(match address_type {
+            AddressType::P2wpkh => bitcoin::Address::p2wpkh(pubkey, network)?,
+            AddressType::P2shwpkh => bitcoin::Address::p2shwpkh(pubkey, network)?,
+        })
+    }
+
+    /// Return the witness program revealed in the input script when spending a P2SH-wrapped
+    /// output, native segwit outputs have no redeem script.
+    fn redeem_script(
+        pubkey: &PublicKey,
+        address_type: AddressType,
+    ) -> Result<Option<Script>, Error> {
+        match address_type {
+            AddressType::P2wpkh => Ok(None),
+            // The network does not change the witness program
+            AddressType::P2shwpkh => Ok(Some(
+                bitcoin::Address::p2wpkh(pubkey, BtcNetwork::Bitcoin)?.script_pubkey(),
+            )),
+        }
+    }
+
+    /// Aggregate an output of another transaction paying the funding address, the swap is then
+    /// funded by all the aggregated outputs and the `lock (b)` transaction has one input per
+    /// output. Adding an output already aggregated has no effect. Fails with
+    /// [`Error::NoMatchingOutput`] if no output pays one of the funding addresses.
+    ///
+    /// A single funding output remains the default, set with [`Fundable::update`].
+    pub fn add_utxo(&mut self, tx: Transaction) -> Result<(), FError> {
+        let (vout, address_type) = self.find_output(&tx).ok_or(Error::NoMatchingOutput)?;
+        let txid = tx.txid();
+        let seen = |t: &Transaction, v: usize| t.txid() == txid && v == vout;
+        let known = self
+            .seen_tx
+            .iter()
+            .any(|t| self.vout.map_or(false, |v| seen(t, v)))
+            || self.additional.iter().any(|(t, v, _)| seen(t, *v));
+        if !known {
+            self.additional.push((tx, vout, address_type));
+        }
+        Ok(())
+    }
+
+    /// Return the consumable output at `vout` in the transaction, paying the address of the given
+    /// type.
+    fn metadata(
+        &self,
+        tx: &Transaction,
+        vout: usize,
+        address_type: AddressType,
+    ) -> Result<MetadataOutput, FError> {
+        let tx_out = tx.output.get(vout).ok_or(Error::MissingOutput(vout))?;
+
+        let pubkey = match self.pubkey {
+            Some(pubkey) => Ok(pubkey),
+            None => Err(FError::MissingPublicKey),
+        }?;
+
+        Ok(MetadataOutput {
+            out_point: OutPoint::new(tx.txid(), vout as u32),
+            tx_out: tx_out.clone(),
+            script_pubkey: Some(
+                match self.network {
+                    Some(network) => bitcoin::Address::p2pkh(&pubkey, btc_network(network)),
+                    None => Err(FError::MissingNetwork)?,
+                }
+                .script_pubkey(),
+            ),
+            redeem_script: Self::redeem_script(&pubkey, address_type)?,
+        })
+    }
+
+    /// Return the index and the address type of the first output paying one of the addresses of
+    /// the public key, if the public key and the network are known.
+    fn find_output(&self, tx: &Transaction) -> Option<(usize, AddressType)> {
+        let (pubkey, network) = (self.pubkey?, self.network?);
+        [AddressType::P2wpkh, AddressType::P2shwpkh]
+            .iter()
+            .copied()
+            .find_map(|address_type| {
+                let script = Self::address(&pubkey, network, address_type)
+                    .ok()?
+                    .script_pubkey();
+                let vout = tx
+                    .output
+                    .iter()
+                    .position(|txout| txout.script_pubkey == script)?;
+                Some((vout, address_type))
+            })
+    }
+
+    /// Infer the address type from the output script form when the public key is not known. Keep
+    /// the current type if nothing matches.
+    fn detect_address_type(&self, tx: &Transaction) -> AddressType {
+        tx.output
+            .iter()
+            .find_map(|txout| match &txout.script_pubkey {
+                script if script.is_p2sh() => Some(AddressType::P2shwpkh),
+                script if script.is_v0_p2wpkh() => Some(AddressType::P2wpkh),
+                _ => None,
+            })
+            .unwrap_or(self.address_type)
+    }
+}
+
+/// Creates the funding state from a transaction seen on-chain, the transaction is scanned for the
+/// output paying the funding address derived from the public key on the network, either native or
+/// P2SH-wrapped segwit. Fails with [`Error::NoMatchingOutput`] if no output pays the address.
+impl TryFrom<(Transaction, PublicKey, Network)> for Funding {
+    type Error = Error;
+
+    fn try_from((tx, pubkey, network): (Transaction, PublicKey, Network)) -> Result<Self, Error> {
+        let mut funding = Funding {
+            pubkey: Some(pubkey),
+            network: Some(network),
+            address_type: AddressType::default(),
+            vout: None,
+            seen_tx: None,
+            additional: vec![],
+        };
+        let (vout, address_type) = funding.find_output(&tx).ok_or(Error::NoMatchingOutput)?;
+        funding.vout = Some(vout);
+        funding.address_type = address_type;
+        funding.seen_tx = Some(tx);
+        Ok(funding)
+    }
 }
 
 impl Linkable<MetadataOutput> for Funding {
     fn get_consumable_output(&self) -> Result<MetadataOutput, FError> {
         match &self.seen_tx {
             Some(t) => {
-                // More than one UTXO is not supported
-                match t.output.len() {
-                    1 => (),
-                    2 =>
-                    // Check if coinbase transaction
-                    {
-                        if !t.is_coin_base() {
-                            return Err(FError::new(Error::MultiUTXOUnsuported));
-                        }
-                    }
-                    _ => return Err(FError::new(Error::MultiUTXOUnsuported)),
-                }
-
-                let pubkey = match self.pubkey {
-                    Some(pubkey) => Ok(pubkey),
-                    None => Err(FError::MissingPublicKey),
-                }?;
-
-                // vout is always 0 because output len is 1
-                Ok(MetadataOutput {
-                    out_point: OutPoint::new(t.txid(), 0),
-                    tx_out: t.output[0].clone(),
-                    script_pubkey: Some(
-                        match self.network {
-                            Some(Network::Mainnet) => {
-                                bitcoin::Address::p2pkh(&pubkey, BtcNetwork::Bitcoin)
+                let vout = match self.vout {
+                    // The output paying the funding address is known
+                    Some(vout) => vout,
+                    None => {
+                        // More than one UTXO is not supported
+                        match t.output.len() {
+                            1 => (),
+                            2 =>
+                            // Check if coinbase transaction
+                            {
+                                if !t.is_coin_base() {
+                                    return Err(FError::new(Error::MultiUTXOUnsuported));
+                                }
                             }
-                            Some(Network::Testnet) => {
-                                bitcoin::Address::p2pkh(&pubkey, BtcNetwork::Testnet)
-                            }
-                            Some(Network::Local) => {
-                                bitcoin::Address::p2pkh(&pubkey, BtcNetwork::Regtest)
-                            }
-                            None => Err(FError::MissingNetwork)?,
+                            _ => return Err(FError::new(Error::MultiUTXOUnsuported)),
                         }
-                        .script_pubkey(),
-                    ),
-                })
+                        // vout is always 0 because output len is 1
+                        0
+                    }
+                };
+                self.metadata(t, vout, self.address_type)
             }
             // The transaction has not been see yet, cannot infer the UTXO
             None => Err(FError::MissingOnchainTransaction),
         }
     }
+
+    /// Return the output of the seen transaction followed by the aggregated outputs added with
+    /// [`Funding::add_utxo`].
+    fn get_consumable_outputs(&self) -> Result<Vec<MetadataOutput>, FError> {
+        let mut outputs = vec![self.get_consumable_output()?];
+        for (tx, vout, address_type) in self.additional.iter() {
+            outputs.push(self.metadata(tx, *vout, *address_type)?);
+        }
+        Ok(outputs)
+    }
 }
 
 impl Fundable<Bitcoin, MetadataOutput> for Funding {
+    const TX_ID: TxId = TxId::Funding;
+
     fn initialize(pubkey: PublicKey, network: Network) -> Result<Self, FError> {
-        Ok(Funding {
-            pubkey: Some(pubkey),
-            network: Some(network),
-            seen_tx: None,
-        })
+        Self::initialize_with_type(pubkey, network, AddressType::default())
     }
 
     fn get_address(&self) -> Result<Address, FError> {
@@ -80,29 +260,51 @@ impl Fundable<Bitcoin, MetadataOutput> for Funding {
         }?;
 
         match self.network {
-            Some(Network::Mainnet) => Ok(Address(
-                bitcoin::Address::p2wpkh(&pubkey, BtcNetwork::Bitcoin).map_err(Error::from)?,
-            )),
-            Some(Network::Testnet) => Ok(Address(
-                bitcoin::Address::p2wpkh(&pubkey, BtcNetwork::Testnet).map_err(Error::from)?,
-            )),
-            Some(Network::Local) => Ok(Address(
-                bitcoin::Address::p2wpkh(&pubkey, BtcNetwork::Regtest).map_err(Error::from)?,
-            )),
+            Some(network) => Ok(Address(Self::address(&pubkey, network, self.address_type)?)),
             None => Err(FError::MissingNetwork),
         }
     }
 
     fn update(&mut self, tx: Transaction) -> Result<(), FError> {
+        match self.find_output(&tx) {
+            Some((vout, address_type)) => {
+                self.vout = Some(vout);
+                self.address_type = address_type;
+            }
+            None => {
+                self.vout = None;
+                self.address_type = self.detect_address_type(&tx);
+            }
+        }
         self.seen_tx = Some(tx);
         Ok(())
     }
 
+    fn invalidate(&mut self, txid: &bitcoin::Txid) -> bool {
+        // Aggregated outputs created by the transaction are dropped as well
+        let count = self.additional.len();
+        self.additional.retain(|(tx, _, _)| &tx.txid() != txid);
+        let dropped = self.additional.len() != count;
+        match &self.seen_tx {
+            Some(tx) if &tx.txid() == txid => {
+                self.seen_tx = None;
+                self.vout = None;
+                true
+            }
+            _ => dropped,
+        }
+    }
+
     fn raw(tx: Transaction) -> Result<Self, FError> {
-        Ok(Self {
+        let mut funding = Self {
             pubkey: None,
             network: None,
-            seen_tx: Some(tx),
-        })
+            address_type: AddressType::default(),
+            vout: None,
+            seen_tx: None,
+            additional: vec![],
+        };
+        funding.update(tx)?;
+        Ok(funding)
     }
 }
@@ -1,99 +1,259 @@
+use std::convert::TryFrom;
 use std::marker::PhantomData;
 
 use bitcoin::blockdata::opcodes;
-use bitcoin::blockdata::script::Builder;
-use bitcoin::blockdata::transaction::{SigHashType, TxIn, TxOut};
-use bitcoin::secp256k1::{Secp256k1, Signature};
+use bitcoin::blockdata::script::{read_scriptint, Builder, Instruction, Script};
+use bitcoin::blockdata::transaction::{SigHashType, TxOut};
+use bitcoin::secp256k1::Signature;
 use bitcoin::util::key::{PrivateKey, PublicKey};
-use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::util::psbt::{self, PartiallySignedTransaction};
 
-use farcaster_core::script;
-use farcaster_core::transaction::{Error as FError, Fundable, Lockable, Signable};
+use farcaster_core::blockchain::{FeePolitic, FeeStrategy};
+use farcaster_core::script::{self, DoubleKeys, ScriptPath};
+use farcaster_core::transaction::{Error as FError, Fundable, Lockable, Signable, TxId};
 
-use crate::bitcoin::transaction::{sign_input, Error, MetadataOutput, SubTransaction, Tx, TxInRef};
-use crate::bitcoin::{Amount, Bitcoin};
+use crate::bitcoin::fee::{set_fee_with_change, SatPerVByte};
+use crate::bitcoin::transaction::{
+    check_sighash_type, multisig_witness, Error, KeyOrdering, MetadataOutput, SubTransaction, Tx,
+};
+use crate::bitcoin::{Address, Amount, Bitcoin, CSVTimelock};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Lock;
 
+/// Creates the swaplock script of the `lock (b)` output. The first branch is the consensus path
+/// used by `buy (c)`, the second branch is the timelocked path used by `cancel (d)`. The keys are
+/// pushed with the role-based ordering, see [`swaplock_script_with`].
+pub fn swaplock_script(lock: &script::DataLock<Bitcoin>) -> Script {
+    swaplock_script_with(lock, KeyOrdering::RoleBased)
+}
+
+/// Creates the swaplock script of the `lock (b)` output, the keys of both multisig branches are
+/// pushed following the given ordering. The timelock is pushed as is, see
+/// [`CSVTimelock::to_csv_value`](crate::bitcoin::CSVTimelock::to_csv_value) to validate it.
+pub fn swaplock_script_with(lock: &script::DataLock<Bitcoin>, ordering: KeyOrdering) -> Script {
+    let success = ordering.order(&lock.success);
+    let failure = ordering.order(&lock.failure);
+    Builder::new()
+        .push_opcode(opcodes::all::OP_IF)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_key(&success.0)
+        .push_key(&success.1)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::all::OP_ELSE)
+        .push_int(lock.timelock.as_u32().into())
+        .push_opcode(opcodes::all::OP_CSV)
+        .push_opcode(opcodes::all::OP_DROP)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_key(&failure.0)
+        .push_key(&failure.1)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::all::OP_ENDIF)
+        .into_script()
+}
+
+/// Finalizes the input consuming the swaplock output of the `lock (b)` transaction through the
+/// given path. The success path is used by `buy (c)` and the failure path by `cancel (d)`, the
+/// signatures are resolved with the Alice and Bob keys of the lock, independently of their order
+/// in the partial signatures, and placed in the order of the [`KeyOrdering`] recorded in the
+/// partial transaction. Fails if the witness script does not match the lock.
+pub fn finalize_swaplock(
+    psbt: &mut PartiallySignedTransaction,
+    lock: &script::DataLock<Bitcoin>,
+    path: ScriptPath,
+) -> Result<(), FError> {
+    let script = psbt.inputs[0]
+        .witness_script
+        .clone()
+        .ok_or(FError::MissingWitness)?;
+
+    let ordering = KeyOrdering::recorded(psbt)?;
+    if script != swaplock_script_with(lock, ordering) {
+        return Err(FError::WrongTemplate);
+    }
+
+    let (first, second) = match path {
+        ScriptPath::Success => ordering.order(&lock.success),
+        ScriptPath::Failure => ordering.order(&lock.failure),
+    };
+    let keys = DoubleKeys::new(first, second);
+
+    let witness = multisig_witness(&psbt.inputs[0], &keys, &path, script)?;
+    psbt.inputs[0].final_script_witness = Some(witness);
+    Ok(())
+}
+
+/// Element expected at a given position when walking the swaplock script.
+enum Expected {
+    Op(opcodes::All),
+    Key,
+    Timelock,
+}
+
+impl Expected {
+    fn describe(&self) -> &'static str {
+        match self {
+            Expected::Op(op) if *op == opcodes::all::OP_IF => "OP_IF",
+            Expected::Op(op) if *op == opcodes::all::OP_ELSE => "OP_ELSE",
+            Expected::Op(op) if *op == opcodes::all::OP_ENDIF => "OP_ENDIF",
+            Expected::Op(op) if *op == opcodes::all::OP_PUSHNUM_2 => "OP_PUSHNUM_2",
+            Expected::Op(op) if *op == opcodes::all::OP_CHECKMULTISIG => "OP_CHECKMULTISIG",
+            Expected::Op(op) if *op == opcodes::all::OP_CSV => "OP_CSV",
+            Expected::Op(op) if *op == opcodes::all::OP_DROP => "OP_DROP",
+            Expected::Op(_) => "opcode",
+            Expected::Key => "public key",
+            Expected::Timelock => "relative timelock",
+        }
+    }
+
+    fn matches(&self, instruction: &Instruction) -> bool {
+        match (self, instruction) {
+            (Expected::Op(op), Instruction::Op(found)) => op == found,
+            (Expected::Key, Instruction::PushBytes(_)) => true,
+            // A script number is at most 5 bytes, small values are pushed with an opcode
+            (Expected::Timelock, Instruction::PushBytes(bytes)) => bytes.len() <= 5,
+            (Expected::Timelock, Instruction::Op(op)) => {
+                let op = op.into_u8();
+                let (min, max) = (
+                    opcodes::all::OP_PUSHNUM_1.into_u8(),
+                    opcodes::all::OP_PUSHNUM_16.into_u8(),
+                );
+                op == opcodes::all::OP_PUSHNUM_NEG1.into_u8() || (min..=max).contains(&op)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parses a swaplock script created with [`swaplock_script`] and returns the Alice and Bob keys
+/// of the timelocked branch used by `cancel (d)`, in the order they are pushed for scripts
+/// created with another [`KeyOrdering`]. See [`parse_swaplock`].
+pub fn parse_swaplock_keys(script: &Script) -> Result<(PublicKey, PublicKey), Error> {
+    let lock = parse_swaplock(script)?;
+    Ok((lock.failure.alice, lock.failure.bob))
+}
+
+/// Parses a swaplock script and returns the lock it implements. The keys of both branches are
+/// returned in the order they are pushed, the lock recreates the script with the [`KeyOrdering`]
+/// used to create it. The script is walked instruction by instruction against the expected
+/// template, the error identifies the instruction where parsing diverged.
+pub fn parse_swaplock(script: &Script) -> Result<script::DataLock<Bitcoin>, Error> {
+    use opcodes::all::{OP_CHECKMULTISIG, OP_CSV, OP_DROP, OP_ELSE, OP_ENDIF, OP_IF, OP_PUSHNUM_2};
+
+    let template = [
+        Expected::Op(OP_IF),
+        Expected::Op(OP_PUSHNUM_2),
+        Expected::Key,
+        Expected::Key,
+        Expected::Op(OP_PUSHNUM_2),
+        Expected::Op(OP_CHECKMULTISIG),
+        Expected::Op(OP_ELSE),
+        Expected::Timelock,
+        Expected::Op(OP_CSV),
+        Expected::Op(OP_DROP),
+        Expected::Op(OP_PUSHNUM_2),
+        Expected::Key,
+        Expected::Key,
+        Expected::Op(OP_PUSHNUM_2),
+        Expected::Op(OP_CHECKMULTISIG),
+        Expected::Op(OP_ENDIF),
+    ];
+
+    let mut instructions = script.instructions();
+    let mut keys = vec![];
+    let mut timelock = CSVTimelock::new(0);
+
+    for (index, expected) in template.iter().enumerate() {
+        let invalid = Error::InvalidSwaplockScript {
+            index,
+            expected: expected.describe(),
+        };
+        let instruction = match instructions.next() {
+            Some(instruction) => instruction?,
+            None => return Err(invalid),
+        };
+        if !expected.matches(&instruction) {
+            return Err(invalid);
+        }
+        match (expected, instruction) {
+            (Expected::Key, Instruction::PushBytes(bytes)) => {
+                keys.push(PublicKey::from_slice(bytes).map_err(|_| invalid)?);
+            }
+            (Expected::Timelock, instruction) => {
+                let value = match instruction {
+                    Instruction::PushBytes(bytes) => read_scriptint(bytes)?,
+                    // OP_PUSHNUM_NEG1 to OP_PUSHNUM_16 push -1 to 16
+                    Instruction::Op(op) => {
+                        op.into_u8() as i64 - opcodes::all::OP_PUSHNUM_1.into_u8() as i64 + 1
+                    }
+                };
+                let value = u32::try_from(value).map_err(|_| invalid)?;
+                timelock = CSVTimelock::new(value);
+            }
+            _ => (),
+        }
+    }
+
+    if instructions.next().is_some() {
+        return Err(Error::InvalidSwaplockScript {
+            index: template.len(),
+            expected: "end of script",
+        });
+    }
+
+    Ok(script::DataLock {
+        timelock,
+        success: DoubleKeys::new(keys[0], keys[1]),
+        failure: DoubleKeys::new(keys[2], keys[3]),
+    })
+}
+
+/// Returns the script code committed to by the signature of an input consuming a funding output
+/// controlled by the key.
+fn funding_script_code(pubkey: &PublicKey) -> Script {
+    bitcoin::Address::p2pkh(pubkey, bitcoin::Network::Bitcoin).script_pubkey()
+}
+
 impl SubTransaction for Lock {
     fn finalize(psbt: &mut PartiallySignedTransaction) -> Result<(), FError> {
-        let (pubkey, full_sig) = psbt.inputs[0]
-            .partial_sigs
-            .iter()
-            .next()
-            .ok_or(FError::MissingSignature)?;
-        psbt.inputs[0].final_script_witness = Some(vec![full_sig.clone(), pubkey.to_bytes()]);
+        // Each input consumes a funding output and is finalized with the signature of its key
+        for input in psbt.inputs.iter_mut() {
+            let script_code = input.witness_script.as_ref();
+            let (pubkey, full_sig) = input
+                .partial_sigs
+                .iter()
+                .find(|(pubkey, _)| script_code == Some(&funding_script_code(pubkey)))
+                .ok_or(FError::MissingSignature)?;
+            check_sighash_type(input, full_sig)?;
+            input.final_script_witness = Some(vec![full_sig.clone(), pubkey.to_bytes()]);
+            // A P2SH-wrapped funding output reveals its witness program in the input script
+            if let Some(redeem_script) = &input.redeem_script {
+                input.final_script_sig = Some(
+                    Builder::new()
+                        .push_slice(redeem_script.as_bytes())
+                        .into_script(),
+                );
+            }
+        }
         Ok(())
     }
+
+    fn has_change(psbt: &PartiallySignedTransaction) -> bool {
+        psbt.global.unsigned_tx.output.len() == 2
+    }
 }
 
 impl Lockable<Bitcoin, MetadataOutput> for Tx<Lock> {
+    const TX_ID: TxId = TxId::Lock;
+
     fn initialize(
         prev: &impl Fundable<Bitcoin, MetadataOutput>,
         lock: script::DataLock<Bitcoin>,
         target_amount: Amount,
     ) -> Result<Self, FError> {
-        let script = Builder::new()
-            .push_opcode(opcodes::all::OP_IF)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_key(&lock.success.alice)
-            .push_key(&lock.success.bob)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
-            .push_opcode(opcodes::all::OP_ELSE)
-            .push_int(lock.timelock.as_u32().into())
-            .push_opcode(opcodes::all::OP_CSV)
-            .push_opcode(opcodes::all::OP_DROP)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_key(&lock.failure.alice)
-            .push_key(&lock.failure.bob)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
-            .push_opcode(opcodes::all::OP_ENDIF)
-            .into_script();
-
-        let output_metadata = prev.get_consumable_output()?;
-
-        match output_metadata.tx_out.value < target_amount.as_sat() {
-            true => Err(FError::NotEnoughAssets)?,
-            false => (),
-        }
-
-        let unsigned_tx = bitcoin::blockdata::transaction::Transaction {
-            version: 2,
-            lock_time: 0,
-            input: vec![TxIn {
-                previous_output: output_metadata.out_point,
-                script_sig: bitcoin::blockdata::script::Script::default(),
-                sequence: (1 << 31) as u32, // activate disable flag on CSV
-                witness: vec![],
-            }],
-            output: vec![TxOut {
-                value: target_amount.as_sat(),
-                script_pubkey: script.to_v0_p2wsh(),
-            }],
-        };
-
-        let mut psbt =
-            PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).map_err(Error::from)?;
-
-        // Set the input witness data and sighash type
-        psbt.inputs[0].witness_utxo = Some(output_metadata.tx_out);
-        psbt.inputs[0].witness_script = output_metadata.script_pubkey;
-        psbt.inputs[0].sighash_type = Some(SigHashType::All);
-
-        // Set the script witness of the output
-        psbt.outputs[0].witness_script = Some(script);
-
-        // TODO move the logic inside core
-        //// Set the fees according to the given strategy
-        //Bitcoin::set_fees(&mut psbt, fee_strategy, fee_politic)?;
-
-        Ok(Tx {
-            psbt,
-            _t: PhantomData,
-        })
+        Self::initialize_with_ordering(prev, lock, target_amount, KeyOrdering::default())
     }
 
     fn verify_template(&self, lock: script::DataLock<Bitcoin>) -> Result<(), FError> {
@@ -103,37 +263,21 @@ impl Lockable<Bitcoin, MetadataOutput> for Tx<Lock> {
         (self.psbt.global.unsigned_tx.lock_time == 0)
             .then(|| 0)
             .ok_or_else(|| FError::WrongTemplate)?;
-        (self.psbt.global.unsigned_tx.input.len() == 1)
+        (!self.psbt.global.unsigned_tx.input.is_empty())
             .then(|| 0)
             .ok_or_else(|| FError::WrongTemplate)?;
-        (self.psbt.global.unsigned_tx.output.len() == 1)
+        // The swaplock output is optionally followed by a change output
+        (matches!(self.psbt.global.unsigned_tx.output.len(), 1 | 2))
             .then(|| 0)
             .ok_or_else(|| FError::WrongTemplate)?;
 
-        let txin = &self.psbt.global.unsigned_tx.input[0];
-        (txin.sequence == (1 << 31) as u32)
+        let txins = &self.psbt.global.unsigned_tx.input;
+        (txins.iter().all(|txin| txin.sequence == (1 << 31) as u32))
             .then(|| 0)
             .ok_or_else(|| FError::WrongTemplate)?;
 
         let txout = &self.psbt.global.unsigned_tx.output[0];
-        let script = Builder::new()
-            .push_opcode(opcodes::all::OP_IF)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_key(&lock.success.alice)
-            .push_key(&lock.success.bob)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
-            .push_opcode(opcodes::all::OP_ELSE)
-            .push_int(lock.timelock.as_u32().into())
-            .push_opcode(opcodes::all::OP_CSV)
-            .push_opcode(opcodes::all::OP_DROP)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_key(&lock.failure.alice)
-            .push_key(&lock.failure.bob)
-            .push_opcode(opcodes::all::OP_PUSHNUM_2)
-            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
-            .push_opcode(opcodes::all::OP_ENDIF)
-            .into_script();
+        let script = swaplock_script_with(&lock, KeyOrdering::recorded(&self.psbt)?);
         (txout.script_pubkey == script.to_v0_p2wsh())
             .then(|| 0)
             .ok_or_else(|| FError::WrongTemplate)?;
@@ -142,47 +286,174 @@ impl Lockable<Bitcoin, MetadataOutput> for Tx<Lock> {
     }
 }
 
-impl Signable<Bitcoin> for Tx<Lock> {
-    fn generate_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
-        {
-            // TODO validate the transaction before signing
+impl Tx<Lock> {
+    /// Returns a human readable snapshot of the `lock (b)` transaction, named after
+    /// [`Lockable::TX_ID`].
+    pub fn describe(&self) -> String {
+        self.describe_as(<Self as Lockable<Bitcoin, MetadataOutput>>::TX_ID)
+    }
+
+    /// Creates the `lock (b)` transaction as [`Lockable::initialize`] does, the keys of the
+    /// swaplock script are pushed following the given ordering. The ordering is recorded in the
+    /// partial transaction and followed by the transactions built on top of the lock. Everything
+    /// funded above the target amount is left as fee, see [`Tx::initialize_with_change`] to get
+    /// the surplus back.
+    pub fn initialize_with_ordering(
+        prev: &impl Fundable<Bitcoin, MetadataOutput>,
+        lock: script::DataLock<Bitcoin>,
+        target_amount: Amount,
+        ordering: KeyOrdering,
+    ) -> Result<Self, FError> {
+        // An out of range timelock would make the cancel path unspendable
+        lock.timelock.to_csv_value()?;
+        let script = swaplock_script_with(&lock, ordering);
+
+        // The swap can be funded by multiple outputs, each one is consumed by an input
+        let outputs_metadata = prev.get_consumable_outputs()?;
+
+        let funded = outputs_metadata
+            .iter()
+            .try_fold(0u64, |acc, output| acc.checked_add(output.tx_out.value))
+            .ok_or(FError::NotEnoughAssets)?;
+        if outputs_metadata.is_empty() || funded < target_amount.as_sat() {
+            return Err(FError::NotEnoughAssets);
         }
 
-        let mut secp = Secp256k1::new();
+        let unsigned_tx = bitcoin::blockdata::transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            // activate disable flag on CSV
+            input: outputs_metadata
+                .iter()
+                .map(|output| output.as_txin((1 << 31) as u32))
+                .collect(),
+            output: vec![TxOut {
+                value: target_amount.as_sat(),
+                script_pubkey: script.to_v0_p2wsh(),
+            }],
+        };
 
-        let unsigned_tx = self.psbt.global.unsigned_tx.clone();
-        let txin = TxInRef::new(&unsigned_tx, 0);
+        let mut psbt =
+            PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).map_err(Error::from)?;
 
-        let witness_utxo = self.psbt.inputs[0]
-            .witness_utxo
-            .clone()
-            .ok_or(FError::MissingWitness)?;
+        // Set the inputs witness data and sighash type
+        for (output, input) in outputs_metadata.into_iter().zip(psbt.inputs.iter_mut()) {
+            output.apply_to_psbt_input(input, SigHashType::All);
+        }
+
+        // Set the script witness of the output
+        psbt.outputs[0].witness_script = Some(script);
+        ordering.record(&mut psbt);
+
+        let tx = Tx {
+            psbt,
+            _t: PhantomData,
+        };
+        // Defense in depth, each input must spend exactly one of the funding outputs
+        let funding = prev.get_consumable_outputs()?;
+        let linked = funding.len() == tx.psbt.inputs.len()
+            && funding
+                .iter()
+                .zip(&tx.psbt.global.unsigned_tx.input)
+                .zip(&tx.psbt.inputs)
+                .all(|((output, txin), input)| {
+                    txin.previous_output == output.out_point
+                        && input.witness_utxo.as_ref().map(|txout| txout.value)
+                            == Some(output.tx_out.value)
+                });
+        if !linked {
+            return Err(FError::InvalidLink);
+        }
+        Ok(tx)
+    }
+
+    /// Creates the `lock (b)` transaction as [`Tx::initialize_with_ordering`] does and pays what
+    /// is left after the target amount and the fee to the change address, in a second output. The
+    /// fee is set according to the fee strategy and the politic. Fails if the change is below the
+    /// dust limit of its script.
+    pub fn initialize_with_change(
+        prev: &impl Fundable<Bitcoin, MetadataOutput>,
+        lock: script::DataLock<Bitcoin>,
+        target_amount: Amount,
+        ordering: KeyOrdering,
+        change: Address,
+        fee_strategy: &FeeStrategy<SatPerVByte>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, farcaster_core::Error> {
+        let mut tx = Self::initialize_with_ordering(prev, lock, target_amount, ordering)?;
+        tx.psbt.global.unsigned_tx.output.push(TxOut {
+            value: 0,
+            script_pubkey: change.0.script_pubkey(),
+        });
+        tx.psbt.outputs.push(psbt::Output::default());
+        set_fee_with_change(&mut tx.psbt, fee_strategy, fee_politic, Some(1))?;
+        Ok(tx)
+    }
 
-        let script = self.psbt.inputs[0]
-            .witness_script
-            .clone()
-            .ok_or(FError::MissingWitness)?;
-        let value = witness_utxo.value;
+    /// Signs the input at `index` with the private key controlling the funding output it
+    /// consumes. The other inputs are left to their owners, e.g. a co-funder of the swap.
+    pub fn generate_input_witness(
+        &self,
+        index: usize,
+        privkey: &PrivateKey,
+    ) -> Result<Signature, FError> {
+        self.sign_input_at(index, privkey)
+    }
 
-        let sighash_type = self.psbt.inputs[0]
-            .sighash_type
-            .ok_or(FError::new(Error::MissingSigHashType))?;
+    /// Signs every input consuming a funding output of the private key and returns the
+    /// signatures with their input index. Inputs controlled by other keys are skipped, the result
+    /// is empty if the key controls none of the inputs.
+    pub fn generate_owned_witnesses(
+        &self,
+        privkey: &PrivateKey,
+    ) -> Result<Vec<(usize, Signature)>, FError> {
+        let pubkey = PublicKey::from_private_key(Bitcoin::secp(), privkey);
+        let script_code = funding_script_code(&pubkey);
+        self.psbt
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| input.witness_script.as_ref() == Some(&script_code))
+            .map(|(index, _)| Ok((index, self.sign_input_at(index, privkey)?)))
+            .collect()
+    }
 
-        let sig = sign_input(&mut secp, txin, &script, value, sighash_type, &privkey.key)
-            .map_err(Error::from)?;
+    /// Verifies a signature on the input at `index` against the given public key.
+    pub fn verify_input_witness(
+        &self,
+        index: usize,
+        pubkey: &PublicKey,
+        sig: Signature,
+    ) -> Result<(), FError> {
+        self.verify_input_at(index, pubkey, &sig)
+    }
+
+    /// Adds the signature of the input at `index`, each input is finalized with its own
+    /// signature.
+    pub fn add_input_witness(
+        &mut self,
+        index: usize,
+        pubkey: PublicKey,
+        sig: Signature,
+    ) -> Result<(), FError> {
+        self.add_witness_at(index, pubkey, sig)
+    }
+}
 
-        // Finalize the witness
-        let mut full_sig = sig.serialize_der().to_vec();
-        full_sig.extend_from_slice(&[sighash_type.as_u32() as u8]);
+impl Signable<Bitcoin> for Tx<Lock> {
+    fn generate_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
+        {
+            // TODO validate the transaction before signing
+        }
 
-        // TODO
-        //let pubkey = PublicKey::from_private_key(&secp, &privkey);
-        //self.psbt.inputs[0].partial_sigs.insert(pubkey, full_sig);
+        self.sign_first_input(privkey)
+    }
 
-        Ok(sig)
+    fn witness_sighash(&self) -> Result<Vec<u8>, FError> {
+        Ok(self.input_sighash()?[..].to_vec())
     }
 
-    fn verify_witness(&self, _pubkey: &PublicKey, _sig: Signature) -> Result<(), FError> {
-        todo!()
+    fn verify_witness(&self, pubkey: &PublicKey, sig: Signature) -> Result<(), FError> {
+        self.verify_first_input(pubkey, &sig)
     }
 }
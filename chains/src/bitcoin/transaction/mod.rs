@@ -1,25 +1,33 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Write};
+use std::io;
 use std::marker::PhantomData;
 
-use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::opcodes;
+use bitcoin::blockdata::script::{Builder, Instruction, Script};
 use bitcoin::blockdata::transaction::{OutPoint, SigHashType, TxIn, TxOut};
+use bitcoin::consensus::encode::{Decodable, Encodable};
 use bitcoin::hashes::sha256d::Hash;
 use bitcoin::secp256k1::{Message, Secp256k1, Signature, Signing};
 use bitcoin::util::address;
 use bitcoin::util::bip143::SigHashCache;
-use bitcoin::util::key::PublicKey;
+use bitcoin::util::key::{PrivateKey, PublicKey};
 use bitcoin::util::psbt::{self, PartiallySignedTransaction};
 
+use strict_encoding::{StrictDecode, StrictEncode};
 use thiserror::Error;
 
+use farcaster_core::crypto::{self, Signatures};
+use farcaster_core::script::{DoubleKeys, ScriptPath};
 use farcaster_core::transaction::{
     Broadcastable, Error as FError, Finalizable, Linkable, Transaction, Witnessable,
 };
 
-use crate::bitcoin::{Amount, Bitcoin};
+use crate::bitcoin::fee::dust_limit;
+use crate::bitcoin::{Amount, Bitcoin, ECDSAAdaptorSig};
 
 pub mod buy;
 pub mod cancel;
+pub mod cooperative_close;
 pub mod funding;
 pub mod lock;
 pub mod punish;
@@ -27,6 +35,7 @@ pub mod refund;
 
 pub use buy::Buy;
 pub use cancel::Cancel;
+pub use cooperative_close::CooperativeClose;
 pub use funding::Funding;
 pub use lock::Lock;
 pub use punish::Punish;
@@ -40,6 +49,44 @@ pub enum Error {
     /// SigHash type is missing
     #[error("SigHash type is missing")]
     MissingSigHashType,
+    /// Relative timelock cannot be used as an input sequence
+    #[error("Relative timelock cannot be used as an input sequence: `{0:#010x}`")]
+    InvalidRelativeTimelock(u32),
+    /// The swaplock script does not follow the expected template
+    #[error("Invalid swaplock script, expected {expected} at instruction {index}")]
+    InvalidSwaplockScript {
+        /// Index of the instruction where parsing diverged
+        index: usize,
+        /// Description of the expected instruction
+        expected: &'static str,
+    },
+    /// The output index does not exist in the transaction
+    #[error("The output index `{0}` does not exist in the transaction")]
+    MissingOutput(usize),
+    /// The input index does not exist in the transaction
+    #[error("The input index `{0}` does not exist in the transaction")]
+    MissingInput(usize),
+    /// No output of the transaction pays the funding address
+    #[error("No output of the transaction pays the funding address")]
+    NoMatchingOutput,
+    /// The output absorbing the fee, the change output or the single output of the transaction,
+    /// would be below the dust limit of its script after paying the fee
+    #[error("The change output would be below the dust limit: `{0}` sat")]
+    ChangeBelowDust(u64),
+    /// The sighash type appended to a signature differs from the one set on the input
+    #[error("Signature sighash type `{found:#04x}` differs from input type `{expected:#04x}`")]
+    SigHashTypeMismatch {
+        /// Sighash type set on the input
+        expected: u8,
+        /// Sighash type appended to the signature
+        found: u8,
+    },
+    /// The key ordering recorded in the partial transaction is unknown
+    #[error("Unknown multisig key ordering recorded in the partial transaction: `{0:?}`")]
+    UnknownKeyOrdering(Vec<u8>),
+    /// The adaptor secret cannot be recovered from the transaction
+    #[error("The adaptor secret cannot be recovered from the transaction")]
+    SecretNotRecoverable,
     /// Partially signed transaction error
     #[error("Partially signed transaction error: `{0}`")]
     PSBT(#[from] psbt::Error),
@@ -65,10 +112,511 @@ pub struct MetadataOutput {
     pub out_point: OutPoint,
     pub tx_out: TxOut,
     pub script_pubkey: Option<Script>,
+    /// The redeem script to reveal in the input script when the output is P2SH-wrapped.
+    pub redeem_script: Option<Script>,
+}
+
+/// Encodes the optional script with a presence byte followed by its consensus serialization.
+fn encode_script<E: io::Write>(script: &Option<Script>, mut e: E) -> Result<usize, io::Error> {
+    match script {
+        Some(script) => Ok(1u8.consensus_encode(&mut e)? + script.consensus_encode(&mut e)?),
+        None => 0u8.consensus_encode(&mut e),
+    }
+}
+
+/// Decodes an optional script encoded with [`encode_script`].
+fn decode_script<D: io::Read>(mut d: D) -> Result<Option<Script>, strict_encoding::Error> {
+    let invalid =
+        |_| strict_encoding::Error::DataIntegrityError("Failed to decode the script".to_string());
+    match u8::consensus_decode(&mut d).map_err(invalid)? {
+        0x00 => Ok(None),
+        0x01 => Ok(Some(Script::consensus_decode(&mut d).map_err(invalid)?)),
+        _ => Err(strict_encoding::Error::DataIntegrityError(
+            "Invalid script presence flag".to_string(),
+        )),
+    }
+}
+
+/// Encoded as the consensus serialization of the outpoint, i.e. the txid and the vout, and of the
+/// output, i.e. the value and the script pubkey, followed by the optional witness script and the
+/// optional redeem script.
+impl StrictEncode for MetadataOutput {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        let mut len = self.out_point.consensus_encode(&mut e)?;
+        len += self.tx_out.consensus_encode(&mut e)?;
+        len += encode_script(&self.script_pubkey, &mut e)?;
+        Ok(len + encode_script(&self.redeem_script, &mut e)?)
+    }
+}
+
+impl StrictDecode for MetadataOutput {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let invalid = |_| {
+            strict_encoding::Error::DataIntegrityError(
+                "Failed to decode the output metadata".to_string(),
+            )
+        };
+        Ok(Self {
+            out_point: OutPoint::consensus_decode(&mut d).map_err(invalid)?,
+            tx_out: TxOut::consensus_decode(&mut d).map_err(invalid)?,
+            script_pubkey: decode_script(&mut d)?,
+            redeem_script: decode_script(&mut d)?,
+        })
+    }
+}
+
+impl MetadataOutput {
+    /// Creates an unsigned input consuming the output with the given sequence, the sequence
+    /// encodes the relative timelock enforced on the input if any.
+    pub fn as_txin(&self, sequence: u32) -> TxIn {
+        TxIn {
+            previous_output: self.out_point,
+            script_sig: Script::default(),
+            sequence,
+            witness: vec![],
+        }
+    }
+
+    /// Sets the witness UTXO, the witness script, the redeem script, and the signature hash type
+    /// on the partial input consuming the output.
+    pub fn apply_to_psbt_input(self, input: &mut psbt::Input, sighash_type: SigHashType) {
+        input.witness_utxo = Some(self.tx_out);
+        input.witness_script = self.script_pubkey;
+        input.redeem_script = self.redeem_script;
+        input.sighash_type = Some(sighash_type);
+    }
+}
+
+/// Proprietary key of the partial transaction global map recording the [`KeyOrdering`] of the
+/// multisig scripts, `0xfc` followed by the length prefixed `farcaster` identifier and subtype `0`.
+const KEY_ORDERING_TYPE: u8 = 0xfc;
+const KEY_ORDERING_KEY: &[u8] = b"\x09farcaster\x00";
+
+/// Order of the Alice and Bob keys pushed in the 2-of-2 multisig branches of the swaplock and
+/// punishable lock scripts. The ordering is selected when creating the `lock (b)` transaction and
+/// recorded in its partial transaction, the transactions built on top of it follow the recorded
+/// ordering so finalization and verification agree on the scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyOrdering {
+    /// Alice's key is pushed first, followed by Bob's key
+    RoleBased,
+    /// Keys are sorted by their serialization as defined by [`BIP-67`][bip-67], third-party
+    /// wallets recognize the multisig without knowing the swap roles
+    ///
+    /// [bip-67]: https://github.com/bitcoin/bips/blob/master/bip-0067.mediawiki
+    Lexicographic,
+}
+
+impl Default for KeyOrdering {
+    fn default() -> Self {
+        KeyOrdering::RoleBased
+    }
+}
+
+impl KeyOrdering {
+    /// Returns the keys in the order they are pushed in the multisig.
+    pub fn order(&self, keys: &DoubleKeys<Bitcoin>) -> (PublicKey, PublicKey) {
+        match self {
+            KeyOrdering::Lexicographic if keys.bob.to_bytes() < keys.alice.to_bytes() => {
+                (keys.bob, keys.alice)
+            }
+            _ => (keys.alice, keys.bob),
+        }
+    }
+
+    /// Returns the ordering recorded in the partial transaction, role-based if none is recorded
+    /// as for transactions created before orderings were introduced.
+    pub fn recorded(psbt: &PartiallySignedTransaction) -> Result<Self, FError> {
+        match psbt
+            .global
+            .unknown
+            .get(&Self::psbt_key())
+            .map(Vec::as_slice)
+        {
+            None | Some([0]) => Ok(KeyOrdering::RoleBased),
+            Some([1]) => Ok(KeyOrdering::Lexicographic),
+            Some(value) => Err(Error::UnknownKeyOrdering(value.to_vec()).into()),
+        }
+    }
+
+    /// Records the ordering in the partial transaction. The role-based ordering is the default
+    /// and is not recorded, partial transactions using it are left unchanged.
+    pub(crate) fn record(&self, psbt: &mut PartiallySignedTransaction) {
+        match self {
+            KeyOrdering::RoleBased => psbt.global.unknown.remove(&Self::psbt_key()),
+            KeyOrdering::Lexicographic => psbt.global.unknown.insert(Self::psbt_key(), vec![1]),
+        };
+    }
+
+    fn psbt_key() -> psbt::raw::Key {
+        psbt::raw::Key {
+            type_value: KEY_ORDERING_TYPE,
+            key: KEY_ORDERING_KEY.to_vec(),
+        }
+    }
+}
+
+/// Returns the public key pushed by the instruction at the given index in the script.
+pub(crate) fn pushed_key(script: &Script, index: usize) -> Result<PublicKey, FError> {
+    match script.instructions().nth(index) {
+        Some(Ok(Instruction::PushBytes(bytes))) => {
+            PublicKey::from_slice(bytes).map_err(|_| FError::MissingPublicKey)
+        }
+        Some(Err(e)) => Err(Error::from(e).into()),
+        _ => Err(FError::MissingPublicKey),
+    }
+}
+
+/// Returns the public keys pushed at the given instruction indexes of the witness script of the
+/// first input, indexes not pushing a valid key are skipped.
+pub(crate) fn witness_script_keys(
+    psbt: &PartiallySignedTransaction,
+    indexes: &[usize],
+) -> Vec<PublicKey> {
+    match psbt
+        .inputs
+        .get(0)
+        .and_then(|input| input.witness_script.as_ref())
+    {
+        Some(script) => indexes
+            .iter()
+            .filter_map(|index| pushed_key(script, *index).ok())
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Checks that the sighash type appended to the full signature is the one set on the input, a
+/// signature produced under another sighash type yields a witness rejected at broadcast.
+pub(crate) fn check_sighash_type(input: &psbt::Input, full_sig: &[u8]) -> Result<(), FError> {
+    let expected = input
+        .sighash_type
+        .ok_or(FError::new(Error::MissingSigHashType))?
+        .as_u32() as u8;
+    let found = *full_sig.last().ok_or(FError::MissingSignature)?;
+    if found != expected {
+        return Err(FError::new(Error::SigHashTypeMismatch { expected, found }));
+    }
+    Ok(())
+}
+
+/// Checks the common template of the transactions spending a script output: version 2, no lock
+/// time, a single input with the given sequence whose previous output is the P2WSH output of the
+/// witness script, and outputs not spending more than the input amount. Fails with
+/// [`FError::WrongTemplate`] otherwise.
+pub(crate) fn verify_script_spend(
+    psbt: &PartiallySignedTransaction,
+    sequence: u32,
+    witness_script: &Script,
+) -> Result<(), FError> {
+    let tx = &psbt.global.unsigned_tx;
+    (tx.version == 2)
+        .then(|| 0)
+        .ok_or_else(|| FError::WrongTemplate)?;
+    (tx.lock_time == 0)
+        .then(|| 0)
+        .ok_or_else(|| FError::WrongTemplate)?;
+    (tx.input.len() == 1 && tx.input[0].sequence == sequence)
+        .then(|| 0)
+        .ok_or_else(|| FError::WrongTemplate)?;
+
+    let input = psbt.inputs.get(0).ok_or(FError::WrongTemplate)?;
+    (input.witness_script.as_ref() == Some(witness_script))
+        .then(|| 0)
+        .ok_or_else(|| FError::WrongTemplate)?;
+    let prev_output = input.witness_utxo.as_ref().ok_or(FError::WrongTemplate)?;
+    (prev_output.script_pubkey == witness_script.to_v0_p2wsh())
+        .then(|| 0)
+        .ok_or_else(|| FError::WrongTemplate)?;
+
+    let spent = tx
+        .output
+        .iter()
+        .try_fold(0u64, |total, output| total.checked_add(output.value));
+    (spent.map_or(false, |spent| spent <= prev_output.value))
+        .then(|| 0)
+        .ok_or_else(|| FError::WrongTemplate)?;
+
+    Ok(())
+}
+
+/// Returns the full signature, with its sighash type, made by the public key on the input.
+pub(crate) fn partial_sig(input: &psbt::Input, pubkey: &PublicKey) -> Result<Vec<u8>, FError> {
+    let full_sig = input
+        .partial_sigs
+        .get(pubkey)
+        .ok_or(FError::MissingSignature)?;
+    check_sighash_type(input, full_sig)?;
+    Ok(full_sig.clone())
+}
+
+/// Creates the witness stack spending a two-of-two multisig branch of a script. Signatures are
+/// resolved by role, Alice's signature comes first as her key is pushed first in the multisig,
+/// followed by the branch selector and the witness script.
+pub(crate) fn multisig_witness(
+    input: &psbt::Input,
+    keys: &DoubleKeys<Bitcoin>,
+    path: &ScriptPath,
+    script: Script,
+) -> Result<Vec<Vec<u8>>, FError> {
+    let branch = match path {
+        ScriptPath::Success => vec![1], // OP_TRUE
+        ScriptPath::Failure => vec![],  // OP_FALSE
+    };
+    Ok(vec![
+        vec![], // 0 for multisig
+        partial_sig(input, &keys.alice)?,
+        partial_sig(input, &keys.bob)?,
+        branch,
+        script.into_bytes(),
+    ])
+}
+
+/// Verifies a witness stack created by [`multisig_witness`] on the first input of the partial
+/// transaction. The stack must select the branch of the path and reveal the witness script of the
+/// input, and both signatures must be valid for the keys under the sighash type of the input.
+pub(crate) fn verify_multisig_witness(
+    psbt: &PartiallySignedTransaction,
+    keys: &DoubleKeys<Bitcoin>,
+    path: &ScriptPath,
+    witness: &[Vec<u8>],
+) -> Result<(), FError> {
+    let input = &psbt.inputs[0];
+    let script = input
+        .witness_script
+        .as_ref()
+        .ok_or(FError::MissingWitness)?;
+    let branch: &[u8] = match path {
+        ScriptPath::Success => &[1],
+        ScriptPath::Failure => &[],
+    };
+    match witness {
+        [multisig, alice, bob, selector, revealed]
+            if multisig.is_empty()
+                && selector.as_slice() == branch
+                && revealed.as_slice() == script.as_bytes() =>
+        {
+            let value = input
+                .witness_utxo
+                .as_ref()
+                .ok_or(FError::MissingWitness)?
+                .value;
+            let sighash_type = input
+                .sighash_type
+                .ok_or(FError::new(Error::MissingSigHashType))?;
+            let txin = TxInRef::new(&psbt.global.unsigned_tx, 0);
+            let sighash = signature_hash(txin, script, value, sighash_type);
+            let msg = Message::from_slice(&sighash[..]).map_err(Error::from)?;
+            for (full_sig, pubkey) in [(alice, &keys.alice), (bob, &keys.bob)].iter() {
+                check_sighash_type(input, full_sig)?;
+                let sig =
+                    Signature::from_der(&full_sig[..full_sig.len() - 1]).map_err(Error::from)?;
+                Bitcoin::secp()
+                    .verify(&msg, &sig, &pubkey.key)
+                    .map_err(Error::from)?;
+            }
+            Ok(())
+        }
+        _ => Err(FError::WrongTemplate),
+    }
+}
+
+/// Creates the script of a fee-bump anchor output, a 1-of-2 multisig spendable by either
+/// participant to bump the fee of its transaction with a child-pays-for-parent transaction. The
+/// keys are pushed following the given ordering.
+pub fn anchor_script(keys: &DoubleKeys<Bitcoin>, ordering: KeyOrdering) -> Script {
+    let (first, second) = ordering.order(keys);
+    Builder::new()
+        .push_opcode(opcodes::all::OP_PUSHNUM_1)
+        .push_key(&first)
+        .push_key(&second)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .into_script()
+}
+
+/// Returns `true` if the output at `index` of the partial transaction is a fee-bump anchor, i.e.
+/// its witness script is an [`anchor_script`] matching the script pubkey of the output.
+pub fn is_anchor_output(psbt: &PartiallySignedTransaction, index: usize) -> bool {
+    let (output, script) = match (
+        psbt.global.unsigned_tx.output.get(index),
+        psbt.outputs
+            .get(index)
+            .and_then(|output| output.witness_script.as_ref()),
+    ) {
+        (Some(output), Some(script)) => (output, script),
+        _ => return false,
+    };
+    match (pushed_key(script, 1), pushed_key(script, 2)) {
+        (Ok(first), Ok(second)) => {
+            let keys = DoubleKeys::new(first, second);
+            *script == anchor_script(&keys, KeyOrdering::RoleBased)
+                && output.script_pubkey == script.to_v0_p2wsh()
+        }
+        _ => false,
+    }
+}
+
+/// Moves the dust limit of a fee-bump anchor out of the single output and appends the anchor,
+/// spendable by either key of `keys`. Returns the anchor script to record in the partial output.
+pub(crate) fn push_anchor_output(
+    output: &mut Vec<TxOut>,
+    keys: &DoubleKeys<Bitcoin>,
+    ordering: KeyOrdering,
+) -> Result<Script, FError> {
+    let script = anchor_script(keys, ordering);
+    let script_pubkey = script.to_v0_p2wsh();
+    let value = dust_limit(&script_pubkey);
+    let target = output
+        .get_mut(0)
+        .ok_or(FError::new(Error::MissingOutput(0)))?;
+    target.value = target
+        .value
+        .checked_sub(value)
+        .ok_or(FError::NotEnoughAssets)?;
+    output.push(TxOut {
+        value,
+        script_pubkey,
+    });
+    Ok(script)
+}
+
+/// Checks that the outputs are a single output paying `target`, optionally followed by the
+/// fee-bump anchor of `keys` holding its dust limit. Fails with [`FError::WrongTemplate`]
+/// otherwise.
+pub(crate) fn verify_outputs_with_anchor(
+    psbt: &PartiallySignedTransaction,
+    target: &Script,
+    keys: &DoubleKeys<Bitcoin>,
+    ordering: KeyOrdering,
+) -> Result<(), FError> {
+    let anchor = anchor_script(keys, ordering).to_v0_p2wsh();
+    let valid = match psbt.global.unsigned_tx.output.as_slice() {
+        [output] => output.script_pubkey == *target,
+        [output, bump] => {
+            output.script_pubkey == *target
+                && bump.script_pubkey == anchor
+                && bump.value == dust_limit(&anchor)
+                && is_anchor_output(psbt, 1)
+        }
+        _ => false,
+    };
+    valid.then(|| ()).ok_or(FError::WrongTemplate)
+}
+
+/// Returns the standard type of the script pubkey, used to describe transaction outputs.
+fn script_type(script_pubkey: &Script) -> &'static str {
+    if script_pubkey.is_p2pkh() {
+        "p2pkh"
+    } else if script_pubkey.is_p2sh() {
+        "p2sh"
+    } else if script_pubkey.is_v0_p2wpkh() {
+        "p2wpkh"
+    } else if script_pubkey.is_v0_p2wsh() {
+        "p2wsh"
+    } else if script_pubkey.is_op_return() {
+        "op_return"
+    } else {
+        "non-standard"
+    }
+}
+
+/// Maximum length in bytes of a DER encoded signature followed by its sighash type.
+const MAX_FULL_SIG_LEN: usize = 73;
+
+/// Returns the length in bytes of the compact size prefix of the given length.
+fn compact_size_len(len: usize) -> usize {
+    match len {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Returns the length in bytes of the serialized witness of the input, the witness is estimated
+/// from the witness script when not finalized. A P2WPKH input is spent with a signature and a
+/// public key, a P2WSH input with the `signatures`, the branch selector and the script, preceded
+/// by the dummy element consumed by `OP_CHECKMULTISIG` for multiple signatures.
+fn witness_len(input: &psbt::Input, signatures: usize) -> usize {
+    let items = match (&input.final_script_witness, &input.witness_script) {
+        (Some(witness), _) => witness.iter().map(Vec::len).collect(),
+        (None, Some(script)) if script.is_p2pkh() => vec![MAX_FULL_SIG_LEN, 33],
+        (None, Some(script)) => {
+            let mut items = match signatures {
+                0 | 1 => vec![],
+                _ => vec![0],
+            };
+            items.extend(vec![MAX_FULL_SIG_LEN; signatures]);
+            items.extend(&[1, script.len()]);
+            items
+        }
+        (None, None) => vec![],
+    };
+    compact_size_len(items.len())
+        + items
+            .iter()
+            .map(|len| compact_size_len(*len) + len)
+            .sum::<usize>()
+}
+
+/// Returns the estimated virtual size in vbytes of the partial transaction once finalized, the
+/// inputs not finalized yet are spent with `signatures` signatures, see [`witness_len`].
+pub(crate) fn estimated_vsize(psbt: &PartiallySignedTransaction, signatures: usize) -> u64 {
+    // Scripts revealed in the input scripts of P2SH-wrapped inputs
+    let script_sigs: usize = psbt
+        .inputs
+        .iter()
+        .map(
+            |input| match (&input.final_script_sig, &input.redeem_script) {
+                (Some(script_sig), _) => script_sig.len(),
+                (None, Some(redeem_script)) => 1 + redeem_script.len(),
+                (None, None) => 0,
+            },
+        )
+        .sum();
+    let witnesses: usize = psbt
+        .inputs
+        .iter()
+        .map(|input| witness_len(input, signatures))
+        .sum();
+    let has_witness = psbt
+        .inputs
+        .iter()
+        .any(|input| input.final_script_witness.is_some() || input.witness_script.is_some());
+    // The segwit marker and flag count as witness data
+    let witness_weight = match has_witness {
+        true => 2 + witnesses,
+        false => 0,
+    };
+    let weight = psbt.global.unsigned_tx.get_weight() + 4 * script_sigs + witness_weight;
+    ((weight + 3) / 4) as u64
 }
 
 pub trait SubTransaction: Debug {
     fn finalize(psbt: &mut PartiallySignedTransaction) -> Result<(), FError>;
+
+    /// Returns the keys whose signatures are required on the first input to finalize the
+    /// transaction, by default every key pushed in the witness script of the input.
+    fn required_keys(psbt: &PartiallySignedTransaction) -> Vec<PublicKey> {
+        match psbt
+            .inputs
+            .get(0)
+            .and_then(|input| input.witness_script.as_ref())
+        {
+            Some(script) => {
+                let len = script.instructions().count();
+                witness_script_keys(psbt, &(0..len).collect::<Vec<_>>())
+            }
+            None => vec![],
+        }
+    }
+
+    /// Returns true if the transaction pays a change output at index 1, after the output consumed
+    /// by the next transaction. No transaction has a change output by default.
+    fn has_change(_psbt: &PartiallySignedTransaction) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
@@ -77,6 +625,290 @@ pub struct Tx<T: SubTransaction> {
     _t: PhantomData<T>,
 }
 
+// Implemented manually to not require the marker type to implement the traits, only the partial
+// transaction is cloned and compared.
+impl<T> Clone for Tx<T>
+where
+    T: SubTransaction,
+{
+    fn clone(&self) -> Self {
+        Self {
+            psbt: self.psbt.clone(),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Tx<T>
+where
+    T: SubTransaction,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.psbt == other.psbt
+    }
+}
+
+impl<T> Eq for Tx<T> where T: SubTransaction {}
+
+impl<T> Tx<T>
+where
+    T: SubTransaction,
+{
+    /// Checks that the transaction has a single input spending the consumable output of the
+    /// previous transaction, both the outpoint and the amount of the input must match the output.
+    /// Fails with [`FError::InvalidLink`] otherwise.
+    pub fn check_links(&self, prev: &impl Linkable<MetadataOutput>) -> Result<(), FError> {
+        let output = prev.get_consumable_output()?;
+        if self.psbt.global.unsigned_tx.input.len() != 1 || !self.consumes(&output) {
+            return Err(FError::InvalidLink);
+        }
+        match &self.psbt.inputs[0].witness_utxo {
+            Some(txout) if txout.value == output.tx_out.value => Ok(()),
+            _ => Err(FError::InvalidLink),
+        }
+    }
+
+    /// Returns a human readable snapshot of the transaction named `name`: its inputs with their
+    /// outpoints and sequences, outputs with their values and script types, and the signatures
+    /// present and missing on the first input. The output is deterministic and only depends on
+    /// the partial transaction. Exposed as `describe` on each transaction type, named after the
+    /// `TX_ID` of the transaction trait it implements.
+    fn describe_as(&self, name: impl Debug) -> String {
+        let tx = &self.psbt.global.unsigned_tx;
+        let mut out = String::new();
+        // Writing into a string cannot fail
+        let _ = writeln!(out, "{:?} {}", name, tx.txid());
+        let _ = writeln!(out, "  version {}, lock time {}", tx.version, tx.lock_time);
+        for (index, (txin, input)) in tx.input.iter().zip(self.psbt.inputs.iter()).enumerate() {
+            let value = input.witness_utxo.as_ref().map_or_else(
+                || "unknown".to_string(),
+                |txout| format!("{} sat", txout.value),
+            );
+            let _ = writeln!(
+                out,
+                "  input {}: {} sequence {:#010x} value {}",
+                index, txin.previous_output, txin.sequence, value
+            );
+        }
+        for (index, txout) in tx.output.iter().enumerate() {
+            let kind = if is_anchor_output(&self.psbt, index) {
+                "anchor"
+            } else {
+                script_type(&txout.script_pubkey)
+            };
+            let _ = writeln!(out, "  output {}: {} sat {}", index, txout.value, kind);
+        }
+        let keys = |keys: &[PublicKey]| {
+            keys.iter()
+                .map(|key| key.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self.psbt.inputs.get(0) {
+            Some(input) if input.final_script_witness.is_some() => {
+                let _ = writeln!(out, "  signatures: finalized");
+            }
+            Some(input) => {
+                let present: Vec<PublicKey> = input.partial_sigs.keys().copied().collect();
+                let missing = self.missing_signatures();
+                let _ = writeln!(out, "  signatures present: [{}]", keys(&present));
+                let _ = writeln!(out, "  signatures missing: [{}]", keys(&missing));
+            }
+            None => (),
+        }
+        out
+    }
+
+    /// Returns `true` if the first input already carries a partial signature made by the public
+    /// key, the signature itself is not verified.
+    pub fn has_signature(&self, pubkey: &PublicKey) -> bool {
+        self.psbt
+            .inputs
+            .get(0)
+            .map_or(false, |input| input.partial_sigs.contains_key(pubkey))
+    }
+
+    /// Returns the keys whose signatures are still missing on the first input before the
+    /// transaction can be finalized, i.e. the keys required by the witness script without a
+    /// partial signature. An empty list means the transaction is ready to be finalized.
+    pub fn missing_signatures(&self) -> Vec<PublicKey> {
+        T::required_keys(&self.psbt)
+            .into_iter()
+            .filter(|pubkey| !self.has_signature(pubkey))
+            .collect()
+    }
+
+    /// Return the fee-bump anchor output of the transaction, if any.
+    pub fn get_anchor_output(&self) -> Option<MetadataOutput> {
+        let index = (0..self.psbt.outputs.len()).find(|&i| is_anchor_output(&self.psbt, i))?;
+        let tx_out = self.psbt.global.unsigned_tx.output[index].clone();
+        let script = self.psbt.outputs[index].witness_script.clone()?;
+        Some(MetadataOutput {
+            out_point: OutPoint::new(self.psbt.global.unsigned_tx.txid(), index as u32),
+            tx_out,
+            script_pubkey: Some(script),
+            redeem_script: None,
+        })
+    }
+
+    /// Returns the estimated virtual size of the transaction in vbytes once finalized. Finalized
+    /// witnesses are measured, others are estimated from the witness scripts with the
+    /// signatures required to finalize the transaction, see [`Tx::missing_signatures`], counted
+    /// at their maximum length.
+    pub fn estimated_vsize(&self) -> u64 {
+        estimated_vsize(&self.psbt, T::required_keys(&self.psbt).len())
+    }
+
+    /// Returns the witness script, the amount of the previous output, and the signature hash type
+    /// set on the partial input at `index`, i.e. the data committed to by its signatures.
+    fn signing_data(&self, index: usize) -> Result<(&Script, u64, SigHashType), FError> {
+        let input = self
+            .psbt
+            .inputs
+            .get(index)
+            .ok_or(FError::new(Error::MissingInput(index)))?;
+        let value = input
+            .witness_utxo
+            .as_ref()
+            .ok_or(FError::MissingWitness)?
+            .value;
+        let script = input
+            .witness_script
+            .as_ref()
+            .ok_or(FError::MissingWitness)?;
+        let sighash_type = input
+            .sighash_type
+            .ok_or(FError::new(Error::MissingSigHashType))?;
+        Ok((script, value, sighash_type))
+    }
+
+    /// Returns the [`BIP-143`][bip-143] sighash signed for the first input, external signers can
+    /// sign it instead of the private key being passed to the transaction.
+    ///
+    /// [bip-143]: https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
+    pub fn input_sighash(&self) -> Result<Hash, FError> {
+        self.input_sighash_at(0)
+    }
+
+    /// Returns the [`BIP-143`][bip-143] sighash signed for the input at `index`, fails with
+    /// [`Error::MissingInput`] if the transaction has no such input.
+    ///
+    /// [bip-143]: https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
+    pub fn input_sighash_at(&self, index: usize) -> Result<Hash, FError> {
+        let (script, value, sighash_type) = self.signing_data(index)?;
+        let txin = TxInRef::new(&self.psbt.global.unsigned_tx, index);
+        Ok(signature_hash(txin, script, value, sighash_type))
+    }
+
+    /// Signs the first input with the private key, the signature commits to the sighash returned
+    /// by [`Tx::input_sighash`].
+    pub(crate) fn sign_first_input(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
+        self.sign_input_at(0, privkey)
+    }
+
+    /// Signs the input at `index` with the private key, the signature commits to the sighash
+    /// returned by [`Tx::input_sighash_at`].
+    pub(crate) fn sign_input_at(
+        &self,
+        index: usize,
+        privkey: &PrivateKey,
+    ) -> Result<Signature, FError> {
+        let (script, value, sighash_type) = self.signing_data(index)?;
+        let txin = TxInRef::new(&self.psbt.global.unsigned_tx, index);
+        let sig = sign_input(
+            Bitcoin::secp(),
+            txin,
+            script,
+            value,
+            sighash_type,
+            &privkey.key,
+        )
+        .map_err(Error::from)?;
+        Ok(sig)
+    }
+
+    /// Verifies a counter-party signature on the first input against the sighash returned by
+    /// [`Tx::input_sighash`]. Fails with
+    /// [`InvalidSignatureEncoding`](farcaster_core::consensus::Error::InvalidSignatureEncoding) if
+    /// the signature is high-S ([`BIP-146`][bip-146]), as the transaction would not be relayed
+    /// once signed.
+    ///
+    /// [bip-146]: https://github.com/bitcoin/bips/blob/master/bip-0146.mediawiki
+    pub(crate) fn verify_first_input(
+        &self,
+        pubkey: &PublicKey,
+        sig: &Signature,
+    ) -> Result<(), FError> {
+        self.verify_input_at(0, pubkey, sig)
+    }
+
+    /// Verifies a counter-party signature on the input at `index` as [`Tx::verify_first_input`]
+    /// does for the first input.
+    pub(crate) fn verify_input_at(
+        &self,
+        index: usize,
+        pubkey: &PublicKey,
+        sig: &Signature,
+    ) -> Result<(), FError> {
+        Bitcoin::validate_signature(sig).map_err(FError::new)?;
+        let msg = Message::from_slice(&self.input_sighash_at(index)?[..]).map_err(Error::from)?;
+        Bitcoin::secp()
+            .verify(&msg, sig, &pubkey.key)
+            .map_err(Error::from)?;
+        Ok(())
+    }
+
+    /// Creates the adaptor signature of the first input with the private key, encrypted with the
+    /// adaptor point. The adapted signature commits to the sighash returned by
+    /// [`Tx::input_sighash`].
+    pub(crate) fn adaptor_sign_first_input(
+        &self,
+        privkey: &PrivateKey,
+        adaptor: &PublicKey,
+    ) -> Result<ECDSAAdaptorSig, FError> {
+        let msg = Message::from_slice(&self.input_sighash()?[..]).map_err(Error::from)?;
+        ECDSAAdaptorSig::sign(privkey, adaptor, &msg).map_err(FError::new)
+    }
+
+    /// Verifies a counter-party adaptor signature on the first input, the signature must be
+    /// encrypted with the adaptor point and adapt into a valid signature of the sighash returned by
+    /// [`Tx::input_sighash`].
+    pub(crate) fn verify_first_input_adaptor(
+        &self,
+        pubkey: &PublicKey,
+        adaptor: &PublicKey,
+        sig: &ECDSAAdaptorSig,
+    ) -> Result<(), FError> {
+        if sig.point != *adaptor {
+            return Err(FError::new(crypto::Error::InvalidAdaptorSignature));
+        }
+        let msg = Message::from_slice(&self.input_sighash()?[..]).map_err(Error::from)?;
+        sig.verify(pubkey, &msg).map_err(FError::new)
+    }
+
+    /// Adds the signature, with the sighash type set on the input, to the partial signatures of
+    /// the input at `index`.
+    pub(crate) fn add_witness_at(
+        &mut self,
+        index: usize,
+        pubkey: PublicKey,
+        sig: Signature,
+    ) -> Result<(), FError> {
+        let input = self
+            .psbt
+            .inputs
+            .get_mut(index)
+            .ok_or(FError::new(Error::MissingInput(index)))?;
+        let sighash_type = input
+            .sighash_type
+            .ok_or(FError::new(Error::MissingSigHashType))?;
+        let mut full_sig = sig.serialize_der().to_vec();
+        full_sig.extend_from_slice(&[sighash_type.as_u32() as u8]);
+        input.partial_sigs.insert(pubkey, full_sig);
+        Ok(())
+    }
+}
+
 impl<T> Transaction<Bitcoin, MetadataOutput> for Tx<T>
 where
     T: SubTransaction,
@@ -107,6 +939,7 @@ where
                 .clone(),
             tx_out: self.psbt.inputs[0].witness_utxo.clone().unwrap(), // FIXME
             script_pubkey: self.psbt.inputs[0].witness_script.clone(),
+            redeem_script: self.psbt.inputs[0].redeem_script.clone(),
         }
     }
 
@@ -140,6 +973,7 @@ where
     fn get_consumable_output(&self) -> Result<MetadataOutput, FError> {
         match self.psbt.global.unsigned_tx.output.len() {
             1 => (),
+            2 if T::has_change(&self.psbt) => (),
             2 => {
                 if !self.psbt.global.unsigned_tx.is_coin_base() {
                     return Err(FError::new(Error::MultiUTXOUnsuported));
@@ -152,8 +986,21 @@ where
             out_point: OutPoint::new(self.psbt.global.unsigned_tx.txid(), 0),
             tx_out: self.psbt.global.unsigned_tx.output[0].clone(),
             script_pubkey: self.psbt.outputs[0].witness_script.clone(),
+            redeem_script: self.psbt.outputs[0].redeem_script.clone(),
         })
     }
+
+    /// Compares the outpoint spent by the first input with the outpoint of the parent output.
+    fn consumes(&self, parent_output: &MetadataOutput) -> bool {
+        self.psbt
+            .global
+            .unsigned_tx
+            .input
+            .first()
+            .map_or(false, |txin| {
+                txin.previous_output == parent_output.out_point
+            })
+    }
 }
 
 impl<T> Witnessable<Bitcoin> for Tx<T>
@@ -161,13 +1008,7 @@ where
     T: SubTransaction,
 {
     fn add_witness(&mut self, pubkey: PublicKey, sig: Signature) -> Result<(), FError> {
-        let sighash_type = self.psbt.inputs[0]
-            .sighash_type
-            .ok_or(FError::new(Error::MissingSigHashType))?;
-        let mut full_sig = sig.serialize_der().to_vec();
-        full_sig.extend_from_slice(&[sighash_type.as_u32() as u8]);
-        self.psbt.inputs[0].partial_sigs.insert(pubkey, full_sig);
-        Ok(())
+        self.add_witness_at(0, pubkey, sig)
     }
 }
 
@@ -229,10 +1070,15 @@ pub fn signature_hash<'a>(
 /// Computes the [`BIP-143`][bip-143] compliant signature for the given input.
 /// [Read more...][signature-hash]
 ///
+/// The nonce is derived deterministically from the secret key and the sighash as specified in
+/// [`RFC 6979`][rfc-6979], signing the same input twice produces byte-identical signatures. The
+/// signature is normalized to its low S form.
+///
 /// [bip-143]: https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
 /// [signature-hash]: fn.signature_hash.html
+/// [rfc-6979]: https://datatracker.ietf.org/doc/html/rfc6979
 pub fn sign_input<'a, C>(
-    context: &mut Secp256k1<C>,
+    context: &Secp256k1<C>,
     txin: TxInRef<'a>,
     script: &Script,
     value: u64,
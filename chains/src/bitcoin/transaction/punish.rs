@@ -1,38 +1,102 @@
+use std::marker::PhantomData;
+
+use bitcoin::blockdata::transaction::{SigHashType, TxOut};
 use bitcoin::secp256k1::Signature;
 use bitcoin::util::key::{PrivateKey, PublicKey};
 use bitcoin::util::psbt::PartiallySignedTransaction;
 
-use farcaster_core::script;
-use farcaster_core::transaction::{Cancelable, Error, Forkable, Punishable};
+use farcaster_core::script::{self, ScriptPath};
+use farcaster_core::transaction::{
+    Cancelable, Error as FError, Forkable, Punishable, Transaction, TxId, Unilateral,
+};
 
-use crate::bitcoin::transaction::{MetadataOutput, SubTransaction, Tx};
+use crate::bitcoin::transaction::cancel::{
+    finalize_punish_lock, punish_lock_keys, punish_lock_script_with,
+};
+use crate::bitcoin::transaction::{Error, KeyOrdering, MetadataOutput, SubTransaction, Tx};
 use crate::bitcoin::{Address, Bitcoin};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Punish;
 
 impl SubTransaction for Punish {
-    fn finalize(_psbt: &mut PartiallySignedTransaction) -> Result<(), Error> {
-        todo!()
+    fn finalize(psbt: &mut PartiallySignedTransaction) -> Result<(), FError> {
+        finalize_punish_lock(psbt, ScriptPath::Failure)
+    }
+
+    fn required_keys(psbt: &PartiallySignedTransaction) -> Vec<PublicKey> {
+        punish_lock_keys(psbt, ScriptPath::Failure)
+    }
+}
+
+impl Tx<Punish> {
+    /// Returns a human readable snapshot of the `punish (f)` transaction, named after
+    /// [`Punishable::TX_ID`].
+    pub fn describe(&self) -> String {
+        self.describe_as(<Self as Punishable<Bitcoin, MetadataOutput>>::TX_ID)
     }
 }
 
 impl Punishable<Bitcoin, MetadataOutput> for Tx<Punish> {
+    const TX_ID: TxId = TxId::Punish;
+
     fn initialize(
-        _prev: &impl Cancelable<Bitcoin, MetadataOutput>,
-        _punish_lock: script::DataPunishableLock<Bitcoin>,
-        _destination_target: Address,
-    ) -> Result<Self, Error> {
-        todo!()
+        prev: &impl Cancelable<Bitcoin, MetadataOutput>,
+        punish_lock: script::DataPunishableLock<Bitcoin>,
+        destination_target: Address,
+    ) -> Result<Self, FError> {
+        let output_metadata = prev.get_consumable_output()?;
+        let ordering = KeyOrdering::recorded(prev.partial())?;
+
+        // The sequence must match the timelock pushed for OP_CSV in the punishable lock
+        let sequence = punish_lock.timelock.as_sequence()?;
+        if output_metadata.script_pubkey != Some(punish_lock_script_with(&punish_lock, ordering)) {
+            return Err(FError::WrongTemplate);
+        }
+
+        let unsigned_tx = bitcoin::blockdata::transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![output_metadata.as_txin(sequence)],
+            output: vec![TxOut {
+                value: output_metadata.tx_out.value,
+                script_pubkey: destination_target.0.script_pubkey(),
+            }],
+        };
+
+        let mut psbt =
+            PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).map_err(Error::from)?;
+
+        // Set the input witness data and sighash type
+        output_metadata.apply_to_psbt_input(&mut psbt.inputs[0], SigHashType::All);
+        ordering.record(&mut psbt);
+
+        // TODO move the logic inside core
+        //// Set the fees according to the given strategy
+        //Bitcoin::set_fees(&mut psbt, fee_strategy, fee_politic)?;
+
+        let punish = Tx {
+            psbt,
+            _t: PhantomData,
+        };
+        // The input must spend exactly the cancel output
+        punish.check_links(prev)?;
+        Ok(punish)
     }
 }
 
+impl Unilateral<Bitcoin> for Tx<Punish> {}
+
 impl Forkable<Bitcoin> for Tx<Punish> {
-    fn generate_failure_witness(&self, _privkey: &PrivateKey) -> Result<Signature, Error> {
-        todo!()
+    fn generate_failure_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
+        self.sign_first_input(privkey)
+    }
+
+    fn failure_witness_sighash(&self) -> Result<Vec<u8>, FError> {
+        Ok(self.input_sighash()?[..].to_vec())
     }
 
-    fn verify_failure_witness(&self, _pubkey: &PublicKey, _sig: Signature) -> Result<(), Error> {
-        todo!()
+    fn verify_failure_witness(&self, pubkey: &PublicKey, sig: Signature) -> Result<(), FError> {
+        self.verify_first_input(pubkey, &sig)
     }
 }
@@ -1,102 +1,187 @@
 use std::marker::PhantomData;
 
-use bitcoin::blockdata::transaction::{SigHashType, TxIn, TxOut};
+use bitcoin::blockdata::transaction::{SigHashType, TxOut};
 use bitcoin::secp256k1::Signature;
 use bitcoin::util::key::{PrivateKey, PublicKey};
 use bitcoin::util::psbt::PartiallySignedTransaction;
 
-use farcaster_core::script;
+use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy};
+use farcaster_core::script::{self, ScriptPath};
 use farcaster_core::transaction::{
-    AdaptorSignable, Cancelable, Error as FError, Refundable, Signable,
+    AdaptorSignable, Cancelable, Error as FError, Refundable, Signable, Transaction, TxId,
 };
 
-use crate::bitcoin::transaction::{Error, MetadataOutput, SubTransaction, Tx};
+use crate::bitcoin::fee::SatPerVByte;
+use crate::bitcoin::transaction::cancel::{
+    finalize_punish_lock, punish_lock_keys, punish_lock_script_with,
+};
+use crate::bitcoin::transaction::{
+    push_anchor_output, verify_outputs_with_anchor, verify_script_spend, Error, KeyOrdering,
+    MetadataOutput, SubTransaction, Tx,
+};
 use crate::bitcoin::{Address, Bitcoin, ECDSAAdaptorSig};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Refund;
 
 impl SubTransaction for Refund {
-    fn finalize(_psbt: &mut PartiallySignedTransaction) -> Result<(), FError> {
-        todo!()
+    fn finalize(psbt: &mut PartiallySignedTransaction) -> Result<(), FError> {
+        finalize_punish_lock(psbt, ScriptPath::Success)
+    }
+
+    fn required_keys(psbt: &PartiallySignedTransaction) -> Vec<PublicKey> {
+        punish_lock_keys(psbt, ScriptPath::Success)
     }
 }
 
-impl Refundable<Bitcoin, MetadataOutput> for Tx<Refund> {
-    fn initialize(
+impl Tx<Refund> {
+    /// Returns a human readable snapshot of the `refund (e)` transaction, named after
+    /// [`Refundable::TX_ID`].
+    pub fn describe(&self) -> String {
+        self.describe_as(<Self as Refundable<Bitcoin, MetadataOutput>>::TX_ID)
+    }
+
+    /// Initialize the refund transaction with a fee-bump anchor output at index 1, spendable by
+    /// either participant with its success key, see [`super::anchor_script`]. The anchor holds the
+    /// dust limit of its script and the fee, set according to the fee strategy and the politic, is
+    /// deducted from the refund output only.
+    ///
+    /// The input is still signed with `SIGHASH_ALL`: the refund adaptor signature must commit to
+    /// all outputs, and a child transaction spending the anchor bumps the fee without modifying
+    /// the refund transaction, so `SIGHASH_SINGLE|ANYONECANPAY` is not needed.
+    pub fn initialize_with_anchor(
         prev: &impl Cancelable<Bitcoin, MetadataOutput>,
         punish_lock: script::DataPunishableLock<Bitcoin>,
         refund_target: Address,
+        fee_strategy: &FeeStrategy<SatPerVByte>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, farcaster_core::Error> {
+        let mut refund = Self::initialize_refund(prev, punish_lock, refund_target, true)?;
+        Bitcoin::set_fee(&mut refund.psbt, fee_strategy, fee_politic)?;
+        Ok(refund)
+    }
+
+    /// Creates the `refund (e)` template without fee, the whole punishable lock output is moved
+    /// to the refund address, minus the anchor value if any.
+    fn initialize_refund(
+        prev: &impl Cancelable<Bitcoin, MetadataOutput>,
+        punish_lock: script::DataPunishableLock<Bitcoin>,
+        refund_target: Address,
+        with_anchor: bool,
     ) -> Result<Self, FError> {
         let output_metadata = prev.get_consumable_output()?;
+        let ordering = KeyOrdering::recorded(prev.partial())?;
+
+        let mut output = vec![TxOut {
+            value: output_metadata.tx_out.value,
+            script_pubkey: refund_target.0.script_pubkey(),
+        }];
+        let anchor = if with_anchor {
+            Some(push_anchor_output(
+                &mut output,
+                &punish_lock.success,
+                ordering,
+            )?)
+        } else {
+            None
+        };
 
         let unsigned_tx = bitcoin::blockdata::transaction::Transaction {
             version: 2,
             lock_time: 0,
-            input: vec![TxIn {
-                previous_output: output_metadata.out_point,
-                script_sig: bitcoin::blockdata::script::Script::default(),
-                sequence: punish_lock.timelock.as_u32(),
-                witness: vec![],
-            }],
-            output: vec![TxOut {
-                value: output_metadata.tx_out.value,
-                script_pubkey: refund_target.0.script_pubkey(),
-            }],
+            input: vec![output_metadata.as_txin(punish_lock.timelock.as_u32())],
+            output,
         };
 
         let mut psbt =
             PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).map_err(Error::from)?;
 
         // Set the input witness data and sighash type
-        psbt.inputs[0].witness_utxo = Some(output_metadata.tx_out);
-        psbt.inputs[0].witness_script = output_metadata.script_pubkey;
-        psbt.inputs[0].sighash_type = Some(SigHashType::All);
-
-        // TODO move the logic inside core
-        //// Set the fees according to the given strategy
-        //Bitcoin::set_fees(&mut psbt, fee_strategy, fee_politic)?;
+        output_metadata.apply_to_psbt_input(&mut psbt.inputs[0], SigHashType::All);
+        if let Some(script) = anchor {
+            psbt.outputs[1].witness_script = Some(script);
+        }
+        ordering.record(&mut psbt);
 
-        Ok(Tx {
+        let refund = Tx {
             psbt,
             _t: PhantomData,
-        })
+        };
+        // Defense in depth, the input must spend exactly the cancel output
+        refund.check_links(prev)?;
+        Ok(refund)
+    }
+}
+
+impl Refundable<Bitcoin, MetadataOutput> for Tx<Refund> {
+    const TX_ID: TxId = TxId::Refund;
+
+    fn initialize(
+        prev: &impl Cancelable<Bitcoin, MetadataOutput>,
+        punish_lock: script::DataPunishableLock<Bitcoin>,
+        refund_target: Address,
+        fee_strategy: &FeeStrategy<SatPerVByte>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, farcaster_core::Error> {
+        let mut refund = Self::initialize_refund(prev, punish_lock, refund_target, false)?;
+        Bitcoin::set_fee(&mut refund.psbt, fee_strategy, fee_politic)?;
+        Ok(refund)
     }
 
     fn verify_template(
         &self,
-        _punish_lock: script::DataPunishableLock<Bitcoin>,
-        _refund_target: Address,
+        punish_lock: script::DataPunishableLock<Bitcoin>,
+        refund_target: Address,
     ) -> Result<(), FError> {
-        todo!()
+        let ordering = KeyOrdering::recorded(&self.psbt)?;
+        // The input spends the punishable lock through its multisig success path
+        verify_script_spend(
+            &self.psbt,
+            punish_lock.timelock.as_u32(),
+            &punish_lock_script_with(&punish_lock, ordering),
+        )?;
+
+        // The refund output, optionally followed by the fee-bump anchor
+        verify_outputs_with_anchor(
+            &self.psbt,
+            &refund_target.0.script_pubkey(),
+            &punish_lock.success,
+            ordering,
+        )?;
+
+        Ok(())
     }
 }
 
 impl Signable<Bitcoin> for Tx<Refund> {
-    fn generate_witness(&self, _privkey: &PrivateKey) -> Result<Signature, FError> {
-        todo!()
+    fn generate_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
+        self.sign_first_input(privkey)
+    }
+
+    fn witness_sighash(&self) -> Result<Vec<u8>, FError> {
+        Ok(self.input_sighash()?[..].to_vec())
     }
 
-    fn verify_witness(&self, _pubkey: &PublicKey, _sig: Signature) -> Result<(), FError> {
-        todo!()
+    fn verify_witness(&self, pubkey: &PublicKey, sig: Signature) -> Result<(), FError> {
+        self.verify_first_input(pubkey, &sig)
     }
 }
 
 impl AdaptorSignable<Bitcoin> for Tx<Refund> {
     fn generate_adaptor_witness(
         &self,
-        _privkey: &PrivateKey,
-        _adaptor: &PublicKey,
+        privkey: &PrivateKey,
+        adaptor: &PublicKey,
     ) -> Result<ECDSAAdaptorSig, FError> {
-        todo!()
+        self.adaptor_sign_first_input(privkey, adaptor)
     }
 
     fn verify_adaptor_witness(
         &self,
-        _pubkey: &PublicKey,
-        _adaptor: &PublicKey,
-        _sig: ECDSAAdaptorSig,
+        pubkey: &PublicKey,
+        adaptor: &PublicKey,
+        sig: ECDSAAdaptorSig,
     ) -> Result<(), FError> {
-        todo!()
+        self.verify_first_input_adaptor(pubkey, adaptor, &sig)
     }
 }
@@ -0,0 +1,134 @@
+//! Watch-only reconstruction of the arbitrating scripts of a swap from public data, used by
+//! third-party monitors such as watchtowers.
+//!
+//! The scripts and addresses are derived from the offer and the two `reveal` protocol messages
+//! only, no secret is needed. A monitor can then detect a counterparty broadcasting the `cancel
+//! (d)` transaction and react before the punish timelock expires.
+
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::Transaction;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+
+use farcaster_core::negotiation::Offer;
+use farcaster_core::protocol_message::{RevealAliceParameters, RevealBobParameters};
+use farcaster_core::script::{DataLock, DataPunishableLock, DoubleKeys};
+use farcaster_core::swap::Swap;
+use farcaster_core::transaction::{
+    Cancelable, Error as FError, Lockable, Refundable, Transaction as _,
+};
+
+use crate::bitcoin::transaction::cancel::punish_lock_script_with;
+use crate::bitcoin::transaction::lock::swaplock_script_with;
+use crate::bitcoin::transaction::{Cancel, KeyOrdering, Lock, Refund, Tx};
+use crate::bitcoin::{Address, Bitcoin};
+
+/// The arbitrating contracts of a swap reconstructed from the revealed parameters.
+#[derive(Clone)]
+pub struct SwapWatcher {
+    /// The cancelable contract created by the `lock (b)` transaction
+    pub lock: DataLock<Bitcoin>,
+    /// The punishable contract created by the `cancel (d)` transaction
+    pub punish_lock: DataPunishableLock<Bitcoin>,
+    /// Alice's destination address, paid by the `buy (c)` and `punish (f)` transactions
+    pub alice_address: Address,
+    /// Bob's refund address, paid by the `refund (e)` transaction
+    pub bob_address: Address,
+    /// The order of the keys pushed in the multisig scripts
+    pub ordering: KeyOrdering,
+}
+
+impl SwapWatcher {
+    /// Reconstructs the swap contracts from the offer and the parameters revealed by Alice and
+    /// Bob.
+    pub fn from_reveals<Ctx>(
+        offer: &Offer<Ctx>,
+        alice: &RevealAliceParameters<Ctx>,
+        bob: &RevealBobParameters<Ctx>,
+    ) -> Self
+    where
+        Ctx: Swap<Ar = Bitcoin>,
+    {
+        Self {
+            lock: DataLock {
+                timelock: offer.cancel_timelock,
+                success: DoubleKeys::new(alice.buy, bob.buy),
+                failure: DoubleKeys::new(alice.cancel, bob.cancel),
+            },
+            punish_lock: DataPunishableLock {
+                timelock: offer.punish_timelock,
+                success: DoubleKeys::new(alice.refund, bob.refund),
+                failure: alice.punish,
+            },
+            alice_address: alice.address.clone(),
+            bob_address: bob.address.clone(),
+            ordering: KeyOrdering::default(),
+        }
+    }
+
+    /// Sets the order of the keys pushed in the multisig scripts, must be the ordering selected
+    /// when creating the `lock (b)` transaction.
+    pub fn with_ordering(mut self, ordering: KeyOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Returns the witness script of the `lock (b)` output.
+    pub fn lock_script(&self) -> Script {
+        swaplock_script_with(&self.lock, self.ordering)
+    }
+
+    /// Returns the script pubkey of the `lock (b)` output.
+    pub fn lock_script_pubkey(&self) -> Script {
+        self.lock_script().to_v0_p2wsh()
+    }
+
+    /// Returns the witness script of the `cancel (d)` output, spent by the `refund (e)` or the
+    /// `punish (f)` transaction.
+    pub fn punish_lock_script(&self) -> Script {
+        punish_lock_script_with(&self.punish_lock, self.ordering)
+    }
+
+    /// Returns the script pubkey of the `cancel (d)` output.
+    pub fn punish_lock_script_pubkey(&self) -> Script {
+        self.punish_lock_script().to_v0_p2wsh()
+    }
+
+    /// Returns true if the transaction creates the `cancel (d)` output, i.e. the swap has been
+    /// cancelled on-chain.
+    pub fn is_cancel(&self, tx: &Transaction) -> bool {
+        let script_pubkey = self.punish_lock_script_pubkey();
+        tx.output
+            .iter()
+            .any(|txout| txout.script_pubkey == script_pubkey)
+    }
+
+    /// Verifies that the `lock (b)` transaction follows the expected template and creates the
+    /// watched `lock (b)` output.
+    pub fn verify_lock(&self, lock: &Tx<Lock>) -> Result<(), FError> {
+        self.verify_ordering(lock.partial())?;
+        lock.verify_template(self.lock.clone())
+    }
+
+    /// Verifies that the `cancel (d)` transaction spends the watched `lock (b)` output with the
+    /// cancel timelock and creates the watched `cancel (d)` output.
+    pub fn verify_cancel(&self, cancel: &Tx<Cancel>) -> Result<(), FError> {
+        self.verify_ordering(cancel.partial())?;
+        cancel.verify_template(self.lock.clone(), self.punish_lock.clone())
+    }
+
+    /// Verifies that the `refund (e)` transaction spends the watched `cancel (d)` output to Bob's
+    /// refund address.
+    pub fn verify_refund(&self, refund: &Tx<Refund>) -> Result<(), FError> {
+        self.verify_ordering(refund.partial())?;
+        refund.verify_template(self.punish_lock.clone(), self.bob_address.clone())
+    }
+
+    // The templates are verified against the ordering recorded in the transaction, it must be
+    // the watched ordering for the transaction to use the watched scripts.
+    fn verify_ordering(&self, psbt: &PartiallySignedTransaction) -> Result<(), FError> {
+        (KeyOrdering::recorded(psbt)? == self.ordering)
+            .then(|| 0)
+            .ok_or_else(|| FError::WrongTemplate)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,957 @@
+//! Defines and implements the arbitrating traits for an Ethereum-style blockchain where the swap
+//! conditions are enforced by a contract instead of scripts. Each arbitrating transaction is a
+//! call to the swap contract, the partial transaction carries the ABI-encoded call data and the
+//! signatures authorizing the call, verified on-chain by the contract.
+//!
+//! This backend is a skeleton: only the `funding (a)`, `lock (b)`, and `cancel (d)` transactions
+//! are implemented, the adaptor signatures are not, and no swap contract is deployed yet.
+
+use bitcoin::secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::Message;
+use bitcoin::util::key::{PrivateKey, PublicKey};
+use monero::cryptonote::hash::Hash;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+use farcaster_core::blockchain::{
+    self, Asset, Fee, FeePolitic, FeeStrategy, FeeStrategyError, Network, Onchain, Timelock,
+};
+use farcaster_core::consensus::{self, Decodable, Encodable};
+use farcaster_core::crypto::{self, Keys, Signatures};
+use farcaster_core::protocol_message::SIGHASH_ALL;
+use farcaster_core::script::{DataLock, DataPunishableLock};
+use farcaster_core::transaction::{
+    Broadcastable, Cancelable, Error as FError, Finalizable, Forkable, Fundable, Linkable,
+    Lockable, Signable, Transaction, TxId, Witnessable,
+};
+
+use std::fmt::{self, Debug};
+use std::io;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use crate::bitcoin::Bitcoin;
+
+/// Placeholder address of the swap contract the arbitrating transactions call, no deployment
+/// exists yet.
+pub const SWAP_CONTRACT: Address = Address([0; 20]);
+
+/// Gas limit set on the `lock (b)` contract call.
+pub const LOCK_GAS_LIMIT: u64 = 120_000;
+
+/// Gas limit set on the `cancel (d)` contract call.
+pub const CANCEL_GAS_LIMIT: u64 = 90_000;
+
+/// Solidity signature of the swap contract function crediting a deposit to an address.
+pub const DEPOSIT_FUNCTION: &str = "deposit(address)";
+
+/// Solidity signature of the swap contract function locking a deposit, the trailing arguments are
+/// the `(v, r, s)` signature of the depositor.
+pub const LOCK_FUNCTION: &str =
+    "lock(bytes32,uint256,address,address,address,address,uint256,uint8,bytes32,bytes32)";
+
+/// Solidity signature of the swap contract function cancelling a lock, the trailing arguments are
+/// the `(v, r, s)` signatures of both participants on the failure path of the lock.
+pub const CANCEL_FUNCTION: &str =
+    "cancel(bytes32,address,address,address,uint256,uint8,bytes32,bytes32,uint8,bytes32,bytes32)";
+
+#[derive(Clone, Debug, Copy, Eq, PartialEq)]
+pub struct Ethereum;
+
+impl FromStr for Ethereum {
+    type Err = consensus::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Ethereum" => Ok(Self),
+            _ => Err(consensus::Error::UnknownType),
+        }
+    }
+}
+
+impl Asset for Ethereum {
+    /// Type for the traded asset unit
+    type AssetUnit = Amount;
+
+    /// Create a new Ethereum blockchain
+    fn new() -> Self {
+        Ethereum {}
+    }
+
+    fn from_u32(bytes: u32) -> Option<Self> {
+        match bytes {
+            0x8000003c => Some(Self::new()),
+            _ => None,
+        }
+    }
+
+    fn to_u32(&self) -> u32 {
+        0x8000003c
+    }
+}
+
+/// Returns the Keccak-256 hash of the data, as used for addresses, function selectors, and
+/// message digests.
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Hash::hash(data).to_fixed_bytes()
+}
+
+/// Ether amount in gwei, i.e. 10^9 wei.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, StrictDecode, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub fn from_gwei(gwei: u64) -> Self {
+        Self(gwei)
+    }
+
+    pub fn as_gwei(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> u64 {
+        amount.as_gwei()
+    }
+}
+
+/// Amounts are encoded as the number of gwei in an unsigned 64-bit integer in little-endian byte
+/// order.
+impl Encodable for Amount {
+    fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        self.0.consensus_encode(writer)
+    }
+}
+
+impl Decodable for Amount {
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Ok(Amount(Decodable::consensus_decode(d)?))
+    }
+}
+
+/// A 20 bytes account address, the last 20 bytes of the Keccak-256 hash of the uncompressed
+/// public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Address(pub [u8; 20]);
+
+impl Address {
+    /// Returns the address of the account controlled by the public key.
+    pub fn from_pubkey(pubkey: &PublicKey) -> Self {
+        let hash = keccak256(&pubkey.key.serialize_uncompressed()[1..]);
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&hash[12..]);
+        Self(address)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for Address {
+    type Err = consensus::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |_| consensus::Error::ParseFailed("Ethereum address parsing failed");
+        let bytes = hex::decode(s.trim_start_matches("0x")).map_err(invalid)?;
+        match bytes.len() {
+            20 => {
+                let mut address = [0u8; 20];
+                address.copy_from_slice(&bytes);
+                Ok(Self(address))
+            }
+            _ => Err(consensus::Error::ParseFailed(
+                "Ethereum address must be 20 bytes",
+            )),
+        }
+    }
+}
+
+/// Encoded as the raw 20 bytes of the address.
+impl Encodable for Address {
+    fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        writer.write_all(&self.0)?;
+        Ok(20)
+    }
+}
+
+impl Decodable for Address {
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        let mut address = [0u8; 20];
+        d.read_exact(&mut address)
+            .map_err(|_| consensus::Error::ParseFailed("Ethereum address parsing failed"))?;
+        Ok(Self(address))
+    }
+}
+
+impl StrictEncode for Address {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        Ok(self.consensus_encode(&mut e)?)
+    }
+}
+
+impl StrictDecode for Address {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Self::consensus_decode(&mut d).map_err(|_| {
+            strict_encoding::Error::DataIntegrityError("Failed to decode the address".to_string())
+        })
+    }
+}
+
+impl blockchain::Address for Ethereum {
+    /// Defines the address format for the arbitrating blockchain
+    type Address = Address;
+}
+
+/// Relative timelock enforced by the swap contract, in number of blocks after the block including
+/// the call creating the lock.
+#[derive(PartialEq, Eq, PartialOrd, Clone, Debug, StrictDecode, StrictEncode, Copy)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct BlockTimelock(u32);
+
+impl BlockTimelock {
+    pub fn new(timelock: u32) -> Self {
+        Self(timelock)
+    }
+
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Encodable for BlockTimelock {
+    fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        self.0.consensus_encode(writer)
+    }
+}
+
+impl Decodable for BlockTimelock {
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Ok(BlockTimelock(Decodable::consensus_decode(d)?))
+    }
+}
+
+impl Timelock for Ethereum {
+    /// Defines the type of timelock used for the arbitrating transactions
+    type Timelock = BlockTimelock;
+
+    fn available_at(timelock: &BlockTimelock, height: u32) -> u32 {
+        height.saturating_add(timelock.as_u32())
+    }
+}
+
+/// Recoverable ECDSA signature, as verified with `ecrecover` by the swap contract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signature(pub RecoverableSignature);
+
+impl Signature {
+    /// Returns the `(v, r, s)` components of the signature, `v` is the recovery identifier offset
+    /// by 27 as expected by `ecrecover`.
+    pub fn to_vrs(&self) -> (u8, [u8; 32], [u8; 32]) {
+        let (recovery_id, compact) = self.0.serialize_compact();
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&compact[..32]);
+        s.copy_from_slice(&compact[32..]);
+        (27 + recovery_id.to_i32() as u8, r, s)
+    }
+}
+
+/// Encoded on 65 bytes, the compact `r || s` signature followed by the recovery identifier.
+impl StrictEncode for Signature {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        let (recovery_id, compact) = self.0.serialize_compact();
+        e.write_all(&compact)?;
+        e.write_all(&[recovery_id.to_i32() as u8])?;
+        Ok(65)
+    }
+}
+
+impl StrictDecode for Signature {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let invalid = |_| {
+            strict_encoding::Error::DataIntegrityError("Invalid recoverable signature".to_string())
+        };
+        let mut bytes = [0u8; 65];
+        d.read_exact(&mut bytes)?;
+        let recovery_id = RecoveryId::from_i32(bytes[64] as i32).map_err(invalid)?;
+        let sig = RecoverableSignature::from_compact(&bytes[..64], recovery_id).map_err(invalid)?;
+        Ok(Self(sig))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, StrictDecode, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct AdaptorSignature {
+    pub sig: Signature,
+    pub point: PublicKey,
+}
+
+impl Keys for Ethereum {
+    /// Private key type for the blockchain
+    type PrivateKey = PrivateKey;
+
+    /// Public key type for the blockchain
+    type PublicKey = PublicKey;
+
+    fn as_bytes(pubkey: &PublicKey) -> Vec<u8> {
+        pubkey.to_bytes()
+    }
+
+    fn from_cross_group_secret(secret: &[u8; 32]) -> Result<PublicKey, crypto::Error> {
+        // Same curve as Bitcoin
+        Bitcoin::from_cross_group_secret(secret)
+    }
+}
+
+impl Signatures for Ethereum {
+    type Signature = Signature;
+    type AdaptorSignature = AdaptorSignature;
+
+    /// The swap contract verifies regular signatures only, no adaptor signature scheme is
+    /// defined for Ethereum.
+    fn adapt(_key: &PrivateKey, _sig: AdaptorSignature) -> Result<Signature, crypto::Error> {
+        Err(crypto::Error::UnsupportedOperation)
+    }
+
+    /// No adaptor signature scheme is defined for Ethereum, see [`Signatures::adapt`].
+    fn recover_secret(
+        _adaptor_sig: &AdaptorSignature,
+        _final_sig: &Signature,
+    ) -> Result<PrivateKey, crypto::Error> {
+        Err(crypto::Error::UnsupportedOperation)
+    }
+
+    /// The contract rejects high-S signatures, as `ecrecover` accepts both forms of a signature.
+    fn validate_signature(sig: &Signature) -> Result<(), consensus::Error> {
+        let sig = sig.0.to_standard();
+        let mut normalized = sig;
+        normalized.normalize_s();
+        match normalized == sig {
+            true => Ok(()),
+            false => Err(consensus::Error::InvalidSignatureEncoding),
+        }
+    }
+
+    /// The pre-signature is not normalized, only the adapted signature must be low-S.
+    fn validate_adaptor_signature(_adaptor_sig: &AdaptorSignature) -> Result<(), consensus::Error> {
+        Ok(())
+    }
+}
+
+/// Returns the 4 bytes function selector, the first bytes of the Keccak-256 hash of the Solidity
+/// function signature.
+pub fn selector(function: &str) -> [u8; 4] {
+    let mut selector = [0u8; 4];
+    selector.copy_from_slice(&keccak256(function.as_bytes())[..4]);
+    selector
+}
+
+/// ABI-encodes a static value on a 32 bytes word, left-padded with zeros.
+fn word(bytes: &[u8]) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[32 - bytes.len()..].copy_from_slice(bytes);
+    word
+}
+
+fn address_word(pubkey: &PublicKey) -> [u8; 32] {
+    word(&Address::from_pubkey(pubkey).0)
+}
+
+fn uint_word(value: u64) -> [u8; 32] {
+    word(&value.to_be_bytes())
+}
+
+/// Returns the call data crediting the value sent to the swap contract to the address.
+pub fn deposit_calldata(address: &Address) -> Vec<u8> {
+    [&selector(DEPOSIT_FUNCTION)[..], &word(&address.0)].concat()
+}
+
+/// Returns the call data locking `amount` of the deposit `id` under the conditions of the lock,
+/// without the trailing signature arguments.
+pub fn lock_calldata(id: &[u8; 32], amount: Amount, lock: &DataLock<Ethereum>) -> Vec<u8> {
+    [
+        &selector(LOCK_FUNCTION)[..],
+        id,
+        &uint_word(amount.as_gwei()),
+        &address_word(&lock.success.alice),
+        &address_word(&lock.success.bob),
+        &address_word(&lock.failure.alice),
+        &address_word(&lock.failure.bob),
+        &uint_word(lock.timelock.as_u32() as u64),
+    ]
+    .concat()
+}
+
+/// Returns the call data cancelling the lock `id` and creating the punishable lock, without the
+/// trailing signature arguments.
+pub fn cancel_calldata(id: &[u8; 32], punish_lock: &DataPunishableLock<Ethereum>) -> Vec<u8> {
+    [
+        &selector(CANCEL_FUNCTION)[..],
+        id,
+        &address_word(&punish_lock.success.alice),
+        &address_word(&punish_lock.success.bob),
+        &address_word(&punish_lock.failure),
+        &uint_word(punish_lock.timelock.as_u32() as u64),
+    ]
+    .concat()
+}
+
+/// Gas price in gwei per unit of gas.
+#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, StrictDecode, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct GasPrice(Amount);
+
+impl GasPrice {
+    pub fn from_gwei(gwei: u64) -> Self {
+        GasPrice(Amount::from_gwei(gwei))
+    }
+
+    pub fn as_gwei(&self) -> u64 {
+        self.0.as_gwei()
+    }
+}
+
+/// Encoded as its inner [`Amount`], i.e. 8 bytes in little-endian byte order.
+impl Encodable for GasPrice {
+    fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        self.0.consensus_encode(writer)
+    }
+}
+
+impl Decodable for GasPrice {
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Ok(GasPrice(Decodable::consensus_decode(d)?))
+    }
+}
+
+/// Funds held by the swap contract under an identifier, i.e. a deposit or a lock. This is the
+/// equivalent of a transaction output, consumed by the next contract call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallOutput {
+    /// Contract holding the funds
+    pub holder: Address,
+    /// Identifier of the funds in the contract
+    pub id: [u8; 32],
+    /// Amount held
+    pub amount: Amount,
+}
+
+/// Encoded as the holder address, the 32 bytes identifier, and the amount.
+impl StrictEncode for CallOutput {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        let len = self.holder.strict_encode(&mut e)?;
+        e.write_all(&self.id)?;
+        Ok(len + 32 + self.amount.strict_encode(&mut e)?)
+    }
+}
+
+impl StrictDecode for CallOutput {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let holder = Address::strict_decode(&mut d)?;
+        let mut id = [0u8; 32];
+        d.read_exact(&mut id)?;
+        Ok(Self {
+            holder,
+            id,
+            amount: Amount::strict_decode(&mut d)?,
+        })
+    }
+}
+
+/// Maximum length in bytes of the call data of a [`ContractCall`] accepted when decoding.
+pub const MAX_CALL_DATA_LEN: usize = 1024;
+
+/// Maximum number of signatures on a [`ContractCall`] accepted when decoding, the signatures of
+/// both participants.
+pub const MAX_CALL_SIGNATURES: usize = 2;
+
+/// A partial contract call exchanged between the participants, the equivalent of a partially
+/// signed transaction.
+#[derive(Debug, Clone, PartialEq, Eq, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct ContractCall {
+    /// Funds consumed by the call
+    pub input: CallOutput,
+    /// Contract called
+    pub to: Address,
+    /// Amount moved by the call in the contract
+    pub amount: Amount,
+    /// Maximum amount of gas consumed by the call
+    pub gas_limit: u64,
+    /// Price paid per unit of gas, set by the fee strategy
+    pub gas_price: GasPrice,
+    /// ABI-encoded call data without the trailing signature arguments
+    pub data: Vec<u8>,
+    /// Signatures authorizing the call, appended to the call data once finalized
+    pub signatures: Vec<Signature>,
+}
+
+/// Decodes the call with bounded call data and signatures, a peer cannot force a large
+/// allocation with a big length prefix.
+impl StrictDecode for ContractCall {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let input = CallOutput::strict_decode(&mut d)?;
+        let to = Address::strict_decode(&mut d)?;
+        let amount = Amount::strict_decode(&mut d)?;
+        let gas_limit = u64::strict_decode(&mut d)?;
+        let gas_price = GasPrice::strict_decode(&mut d)?;
+        let data = consensus::decode_bounded_vec(&mut d, MAX_CALL_DATA_LEN)
+            .map_err(|e| strict_encoding::Error::DataIntegrityError(e.to_string()))?;
+        let len = u16::strict_decode(&mut d)? as usize;
+        if len > MAX_CALL_SIGNATURES {
+            return Err(strict_encoding::Error::DataIntegrityError(
+                "Too many signatures on the contract call".to_string(),
+            ));
+        }
+        let signatures = (0..len)
+            .map(|_| Signature::strict_decode(&mut d))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            input,
+            to,
+            amount,
+            gas_limit,
+            gas_price,
+            data,
+            signatures,
+        })
+    }
+}
+
+impl ContractCall {
+    /// Returns the digest signed by the participants, the Keccak-256 hash of the called contract,
+    /// the consumed funds identifier, and the call data.
+    pub fn sighash(&self) -> [u8; 32] {
+        keccak256(&[&self.to.0[..], &self.input.id, &self.data].concat())
+    }
+
+    /// Returns the identifier of the funds created by the call in the contract.
+    pub fn output_id(&self) -> [u8; 32] {
+        keccak256(&self.data)
+    }
+
+    fn message(&self) -> Message {
+        Message::from_slice(&self.sighash()).expect("32 bytes digest")
+    }
+
+    fn sign(&self, privkey: &PrivateKey) -> Signature {
+        Signature(Bitcoin::secp().sign_recoverable(&self.message(), &privkey.key))
+    }
+
+    /// Verifies that the signature is valid for the call and recovers the public key.
+    fn verify(&self, pubkey: &PublicKey, sig: &Signature) -> Result<(), FError> {
+        Ethereum::validate_signature(sig).map_err(FError::new)?;
+        let signer = Bitcoin::secp()
+            .recover(&self.message(), &sig.0)
+            .map_err(FError::new)?;
+        match signer == pubkey.key {
+            true => Ok(()),
+            false => Err(FError::UnexpectedSignature),
+        }
+    }
+}
+
+/// A finalized contract call ready to be wrapped in a transaction signed by the account paying
+/// for the gas.
+#[derive(Debug, Clone, PartialEq, Eq, StrictDecode, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct SignedCall {
+    /// Contract called
+    pub to: Address,
+    /// Ether sent with the call, only used to deposit funds in the contract
+    pub value: Amount,
+    /// Maximum amount of gas consumed by the call
+    pub gas_limit: u64,
+    /// Price paid per unit of gas
+    pub gas_price: GasPrice,
+    /// Complete ABI-encoded call data, including the signature arguments
+    pub data: Vec<u8>,
+}
+
+impl SignedCall {
+    /// Returns the identifier of the call, the Keccak-256 hash of its strict encoding.
+    pub fn id(&self) -> [u8; 32] {
+        keccak256(&strict_encoding::strict_serialize(self).expect("In-memory encoding"))
+    }
+}
+
+impl Onchain for Ethereum {
+    /// Defines the transaction format used to transfer partial transaction between participant for
+    /// the arbitrating blockchain
+    type PartialTransaction = ContractCall;
+
+    /// Defines the finalized transaction format for the arbitrating blockchain
+    type Transaction = SignedCall;
+
+    /// Defines the identifier of a finalized transaction
+    type TransactionId = [u8; 32];
+
+    /// Clears the signatures collected on the call.
+    fn strip_signatures(call: &mut ContractCall) {
+        call.signatures.clear();
+    }
+
+    /// The signatures always commit to the whole call.
+    fn sighash_type(_call: &ContractCall) -> Option<u8> {
+        Some(SIGHASH_ALL)
+    }
+}
+
+impl Fee for Ethereum {
+    type FeeUnit = GasPrice;
+
+    /// The gas is paid by the account sending the call and is not deducted from the amount moved
+    /// in the contract, returns the maximum fee paid for the gas limit.
+    fn set_fee(
+        tx: &mut ContractCall,
+        strategy: &FeeStrategy<GasPrice>,
+        politic: FeePolitic,
+    ) -> Result<Amount, FeeStrategyError> {
+        let price = match strategy {
+            FeeStrategy::Fixed(price) => price.as_gwei(),
+            FeeStrategy::Range(range) => {
+                let (start, end) = (range.start.as_gwei(), range.end.as_gwei());
+                let delta = end.saturating_sub(start);
+                start + (delta as f64 * politic.fraction()).round() as u64
+            }
+            // The gas price is set by the account sending the call
+            FeeStrategy::None => 0,
+        };
+        let fee = price.checked_mul(tx.gas_limit).ok_or_else(|| {
+            FeeStrategyError::AmountOfFeeTooHigh {
+                max_sat_per_vbyte: u64::MAX.checked_div(tx.gas_limit).unwrap_or(u64::MAX),
+            }
+        })?;
+        tx.gas_price = GasPrice::from_gwei(price);
+        Ok(Amount::from_gwei(fee))
+    }
+
+    fn validate_fee(
+        tx: &ContractCall,
+        strategy: &FeeStrategy<GasPrice>,
+    ) -> Result<bool, FeeStrategyError> {
+        let (min, max) = match strategy {
+            FeeStrategy::Fixed(price) => (price, price),
+            FeeStrategy::Range(range) => (&range.start, &range.end),
+            FeeStrategy::None => return Ok(true),
+        };
+        if &tx.gas_price < min {
+            return Err(FeeStrategyError::AmountOfFeeTooLow);
+        }
+        match &tx.gas_price > max {
+            true => Err(FeeStrategyError::AmountOfFeeTooHigh {
+                max_sat_per_vbyte: max.as_gwei(),
+            }),
+            false => Ok(true),
+        }
+    }
+}
+
+/// Funds deposited in the swap contract on behalf of the funding address with a
+/// [`DEPOSIT_FUNCTION`] call, the `lock (b)` call then moves them under the lock conditions.
+#[derive(Debug, Clone)]
+pub struct Funding {
+    pubkey: Option<PublicKey>,
+    network: Option<Network>,
+    seen_tx: Option<SignedCall>,
+}
+
+impl Funding {
+    /// Return the network the funding is created for, if known.
+    pub fn network(&self) -> Option<Network> {
+        self.network
+    }
+}
+
+impl Linkable<CallOutput> for Funding {
+    fn get_consumable_output(&self) -> Result<CallOutput, FError> {
+        let tx = self
+            .seen_tx
+            .as_ref()
+            .ok_or(FError::MissingOnchainTransaction)?;
+        Ok(CallOutput {
+            holder: tx.to,
+            id: tx.id(),
+            amount: tx.value,
+        })
+    }
+}
+
+impl Fundable<Ethereum, CallOutput> for Funding {
+    const TX_ID: TxId = TxId::Funding;
+
+    fn initialize(pubkey: PublicKey, network: Network) -> Result<Self, FError> {
+        Ok(Funding {
+            pubkey: Some(pubkey),
+            network: Some(network),
+            seen_tx: None,
+        })
+    }
+
+    fn get_address(&self) -> Result<Address, FError> {
+        let pubkey = self.pubkey.as_ref().ok_or(FError::MissingPublicKey)?;
+        Ok(Address::from_pubkey(pubkey))
+    }
+
+    fn update(&mut self, tx: SignedCall) -> Result<(), FError> {
+        let address = self.get_address()?;
+        if tx.to != SWAP_CONTRACT || tx.data != deposit_calldata(&address) {
+            return Err(FError::WrongTemplate);
+        }
+        self.seen_tx = Some(tx);
+        Ok(())
+    }
+
+    fn invalidate(&mut self, txid: &[u8; 32]) -> bool {
+        match self.seen_tx.as_ref().map(SignedCall::id) {
+            Some(id) if &id == txid => {
+                self.seen_tx = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn raw(tx: SignedCall) -> Result<Self, FError> {
+        Ok(Funding {
+            pubkey: None,
+            network: None,
+            seen_tx: Some(tx),
+        })
+    }
+}
+
+/// Marker trait for the contract calls, defines the number of signatures required to finalize
+/// the call.
+pub trait SubCall: Debug {
+    const SIGNATURES: usize;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Lock;
+
+impl SubCall for Lock {
+    const SIGNATURES: usize = 1;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cancel;
+
+impl SubCall for Cancel {
+    const SIGNATURES: usize = 2;
+}
+
+#[derive(Debug)]
+pub struct Tx<T: SubCall> {
+    call: ContractCall,
+    _t: PhantomData<T>,
+}
+
+impl<T> Transaction<Ethereum, CallOutput> for Tx<T>
+where
+    T: SubCall,
+{
+    fn partial(&self) -> &ContractCall {
+        &self.call
+    }
+
+    fn partial_mut(&mut self) -> &mut ContractCall {
+        &mut self.call
+    }
+
+    fn to_partial(self) -> ContractCall {
+        self.call
+    }
+
+    fn from_partial(call: ContractCall) -> Self {
+        Self {
+            call,
+            _t: PhantomData,
+        }
+    }
+
+    fn based_on(&self) -> CallOutput {
+        self.call.input.clone()
+    }
+
+    fn output_amount(&self) -> Amount {
+        self.call.amount
+    }
+}
+
+impl<T> Linkable<CallOutput> for Tx<T>
+where
+    T: SubCall,
+{
+    fn get_consumable_output(&self) -> Result<CallOutput, FError> {
+        Ok(CallOutput {
+            holder: self.call.to,
+            id: self.call.output_id(),
+            amount: self.call.amount,
+        })
+    }
+
+    /// Compares the funds consumed by the call with the parent output, regardless of the amount.
+    fn consumes(&self, parent_output: &CallOutput) -> bool {
+        self.call.input.holder == parent_output.holder && self.call.input.id == parent_output.id
+    }
+}
+
+impl<T> Witnessable<Ethereum> for Tx<T>
+where
+    T: SubCall,
+{
+    fn add_witness(&mut self, pubkey: PublicKey, sig: Signature) -> Result<(), FError> {
+        self.call.verify(&pubkey, &sig)?;
+        if !self.call.signatures.contains(&sig) {
+            self.call.signatures.push(sig);
+        }
+        Ok(())
+    }
+}
+
+impl<T> Finalizable for Tx<T>
+where
+    T: SubCall,
+{
+    fn finalize(&mut self) -> Result<(), FError> {
+        match self.call.signatures.len() == T::SIGNATURES {
+            true => Ok(()),
+            false => Err(FError::MissingSignature),
+        }
+    }
+}
+
+impl<T> Broadcastable<Ethereum> for Tx<T>
+where
+    T: SubCall,
+{
+    /// Appends the `(v, r, s)` words of each signature to the call data.
+    fn extract(&self) -> SignedCall {
+        let mut data = self.call.data.clone();
+        for sig in self.call.signatures.iter() {
+            let (v, r, s) = sig.to_vrs();
+            data.extend_from_slice(&word(&[v]));
+            data.extend_from_slice(&r);
+            data.extend_from_slice(&s);
+        }
+        SignedCall {
+            to: self.call.to,
+            value: Amount::from_gwei(0),
+            gas_limit: self.call.gas_limit,
+            gas_price: self.call.gas_price.clone(),
+            data,
+        }
+    }
+}
+
+impl Signable<Ethereum> for Tx<Lock> {
+    fn generate_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
+        Ok(self.call.sign(privkey))
+    }
+
+    fn witness_sighash(&self) -> Result<Vec<u8>, FError> {
+        Ok(self.call.sighash().to_vec())
+    }
+
+    fn verify_witness(&self, pubkey: &PublicKey, sig: Signature) -> Result<(), FError> {
+        self.call.verify(pubkey, &sig)
+    }
+}
+
+impl Lockable<Ethereum, CallOutput> for Tx<Lock> {
+    const TX_ID: TxId = TxId::Lock;
+
+    /// Creates the `lock (b)` call moving `target_amount` of the deposit under the lock
+    /// conditions. The gas is paid outside of the deposit, thus the whole deposit can be locked.
+    fn initialize(
+        prev: &impl Fundable<Ethereum, CallOutput>,
+        lock: DataLock<Ethereum>,
+        target_amount: Amount,
+    ) -> Result<Self, FError> {
+        let input = prev.get_consumable_output()?;
+        if input.amount < target_amount {
+            return Err(FError::NotEnoughAssets);
+        }
+        let data = lock_calldata(&input.id, target_amount, &lock);
+        Ok(Self::from_partial(ContractCall {
+            input,
+            to: SWAP_CONTRACT,
+            amount: target_amount,
+            gas_limit: LOCK_GAS_LIMIT,
+            gas_price: GasPrice::from_gwei(0),
+            data,
+            signatures: vec![],
+        }))
+    }
+
+    fn verify_template(&self, lock: DataLock<Ethereum>) -> Result<(), FError> {
+        let data = lock_calldata(&self.call.input.id, self.call.amount, &lock);
+        match self.call.to == SWAP_CONTRACT && self.call.data == data {
+            true => Ok(()),
+            false => Err(FError::WrongTemplate),
+        }
+    }
+}
+
+impl Forkable<Ethereum> for Tx<Cancel> {
+    fn generate_failure_witness(&self, privkey: &PrivateKey) -> Result<Signature, FError> {
+        Ok(self.call.sign(privkey))
+    }
+
+    fn failure_witness_sighash(&self) -> Result<Vec<u8>, FError> {
+        Ok(self.call.sighash().to_vec())
+    }
+
+    fn verify_failure_witness(&self, pubkey: &PublicKey, sig: Signature) -> Result<(), FError> {
+        self.call.verify(pubkey, &sig)
+    }
+}
+
+impl Cancelable<Ethereum, CallOutput> for Tx<Cancel> {
+    const TX_ID: TxId = TxId::Cancel;
+
+    /// Creates the `cancel (d)` call, the signatures are verified by the contract against the
+    /// failure keys registered by the `lock (b)` call, in any order. The gas price is set
+    /// according to the fee strategy and the politic.
+    fn initialize(
+        prev: &impl Lockable<Ethereum, CallOutput>,
+        _lock: DataLock<Ethereum>,
+        punish_lock: DataPunishableLock<Ethereum>,
+        fee_strategy: &FeeStrategy<GasPrice>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, farcaster_core::Error> {
+        let input = prev.get_consumable_output()?;
+        let data = cancel_calldata(&input.id, &punish_lock);
+        let mut call = ContractCall {
+            amount: input.amount,
+            input,
+            to: SWAP_CONTRACT,
+            gas_limit: CANCEL_GAS_LIMIT,
+            gas_price: GasPrice::from_gwei(0),
+            data,
+            signatures: vec![],
+        };
+        Ethereum::set_fee(&mut call, fee_strategy, fee_politic)?;
+        Ok(Self::from_partial(call))
+    }
+
+    fn verify_template(
+        &self,
+        _lock: DataLock<Ethereum>,
+        punish_lock: DataPunishableLock<Ethereum>,
+    ) -> Result<(), FError> {
+        let data = cancel_calldata(&self.call.input.id, &punish_lock);
+        match self.call.to == SWAP_CONTRACT
+            && self.call.data == data
+            && self.call.amount == self.call.input.amount
+        {
+            true => Ok(()),
+            false => Err(FError::WrongTemplate),
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod bitcoin;
+#[cfg(feature = "ethereum")]
+pub mod ethereum;
 pub mod monero;
 pub mod pairs;
@@ -1,18 +1,32 @@
 //! Defines and implements all the traits for Monero
 
+use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use strict_encoding::{StrictDecode, StrictEncode};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
 use farcaster_core::blockchain::Asset;
 use farcaster_core::crypto::{
-    self, AccordantKey, FromSeed, Keys, SharedPrivateKey, SharedPrivateKeys,
+    self, AccordantKey, AmountCommitments, FromSeed, Keys, SharedPrivateKey, SharedPrivateKeys,
 };
 use farcaster_core::role::{Acc, Accordant};
 
 use monero::cryptonote::hash::Hash;
 use monero::util::key::{PrivateKey, PublicKey};
 
+use std::convert::TryInto;
 use std::fmt::{self, Debug, Display, Formatter};
 
 pub const SHARED_KEY_BITS: usize = 252;
 
+/// Generator used for the amounts in Pedersen commitments, `H = 8 * to_point(keccak(G))`.
+pub(crate) const H: [u8; 32] = [
+    0x8b, 0x65, 0x59, 0x70, 0x15, 0x37, 0x99, 0xaf, 0x2a, 0xea, 0xdc, 0x9f, 0xf1, 0xad, 0xd0, 0xea,
+    0x6c, 0x72, 0x51, 0xd5, 0x41, 0x54, 0xcf, 0xa9, 0x2c, 0x17, 0x3a, 0x0d, 0xd3, 0x9c, 0x1f, 0x94,
+];
+
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub struct Monero;
 
@@ -57,6 +71,117 @@ impl Asset for Monero {
 
 impl Accordant for Monero {}
 
+/// Commitment to the amount sent to a Monero output, as found in RingCT transactions. The output
+/// is identified by the transaction public key and its index in the transaction, the amount is
+/// encrypted with the shared secret derived from the view key.
+#[derive(Clone, Debug, PartialEq, Eq, StrictDecode, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct AmountCommitment {
+    pub tx_pubkey: PublicKey,
+    pub index: u64,
+    pub commitment: PublicKey,
+    pub encrypted_amount: u64,
+}
+
+impl AmountCommitment {
+    /// Creates the commitment to `amount` sent on output `index` to the owner of the public view
+    /// key, as computed by the sender with the transaction private key.
+    pub fn new(
+        tx_key: &PrivateKey,
+        view: &PublicKey,
+        index: u64,
+        amount: u64,
+    ) -> Result<Self, crypto::Error> {
+        let shared = shared_scalar(tx_key, view, index)?;
+        let commitment = PublicKey::from_slice(commit(&shared, amount)?.as_bytes())
+            .map_err(crypto::Error::new)?;
+        Ok(Self {
+            tx_pubkey: PublicKey::from_private_key(tx_key),
+            index,
+            commitment,
+            encrypted_amount: amount ^ amount_mask(&shared),
+        })
+    }
+
+    /// Opens the commitment with the private view key and returns the amount it hides, fails if
+    /// the decrypted amount does not match the commitment.
+    pub fn open(&self, view_key: &PrivateKey) -> Result<u64, crypto::Error> {
+        let shared = shared_scalar(view_key, &self.tx_pubkey, self.index)?;
+        let amount = self.encrypted_amount ^ amount_mask(&shared);
+        match commit(&shared, amount)?.as_bytes() == self.commitment.as_bytes() {
+            true => Ok(amount),
+            false => Err(crypto::Error::InvalidAmountCommitment),
+        }
+    }
+}
+
+impl AmountCommitments<Acc> for Monero {
+    type AmountCommitment = AmountCommitment;
+
+    fn verify_amount(
+        commitment: &AmountCommitment,
+        view_key: &PrivateViewKey,
+        expected: u64,
+    ) -> Result<(), crypto::Error> {
+        match commitment.open(&view_key.0)? == expected {
+            true => Ok(()),
+            false => Err(crypto::Error::InvalidAmountCommitment),
+        }
+    }
+}
+
+fn decompress(bytes: &[u8]) -> Result<EdwardsPoint, crypto::Error> {
+    CompressedEdwardsY::from_slice(bytes)
+        .decompress()
+        .ok_or(crypto::Error::InvalidAmountCommitment)
+}
+
+fn hash_to_scalar(bytes: &[u8]) -> Scalar {
+    Scalar::from_bytes_mod_order(Hash::hash(bytes).to_fixed_bytes())
+}
+
+/// Returns the scalar `Hs(8 * k * P || index)` shared by the sender and the receiver of the
+/// output, with `k` and `P` being either the transaction private key and the public view key or
+/// the private view key and the transaction public key.
+fn shared_scalar(
+    key: &PrivateKey,
+    pubkey: &PublicKey,
+    index: u64,
+) -> Result<Scalar, crypto::Error> {
+    let key_bytes: [u8; 32] = key
+        .as_bytes()
+        .try_into()
+        .map_err(|_| crypto::Error::InvalidAmountCommitment)?;
+    let derivation = (Scalar::from_bytes_mod_order(key_bytes) * decompress(pubkey.as_bytes())?)
+        .mul_by_cofactor()
+        .compress();
+    let mut bytes = derivation.as_bytes().to_vec();
+    // the output index is serialized as a varint
+    let mut index = index;
+    while index >= 0x80 {
+        bytes.push((index as u8 & 0x7f) | 0x80);
+        index >>= 7;
+    }
+    bytes.push(index as u8);
+    Ok(hash_to_scalar(&bytes))
+}
+
+/// Returns the mask xored with the amount, the first 8 bytes of `keccak("amount" || shared)`.
+fn amount_mask(shared: &Scalar) -> u64 {
+    let mut bytes = Vec::from(b"amount".as_ref());
+    bytes.extend_from_slice(shared.as_bytes());
+    let hash = Hash::hash(&bytes).to_fixed_bytes();
+    u64::from_le_bytes(hash[..8].try_into().expect("Slice of 8 bytes"))
+}
+
+/// Returns the commitment `mask * G + amount * H` with `mask = Hs("commitment_mask" || shared)`.
+fn commit(shared: &Scalar, amount: u64) -> Result<CompressedEdwardsY, crypto::Error> {
+    let mut bytes = Vec::from(b"commitment_mask".as_ref());
+    bytes.extend_from_slice(shared.as_bytes());
+    let mask = hash_to_scalar(&bytes);
+    Ok((&mask * &ED25519_BASEPOINT_TABLE + Scalar::from(amount) * decompress(&H)?).compress())
+}
+
 impl Keys for Monero {
     /// Private key type for the blockchain
     type PrivateKey = PrivateKey;
@@ -67,26 +192,80 @@ impl Keys for Monero {
     fn as_bytes(pubkey: &PublicKey) -> Vec<u8> {
         pubkey.as_bytes().into()
     }
+
+    /// Rejects the identity and the other points of small order, they would cancel out when
+    /// added to the counter-party key.
+    fn validate_pubkey(pubkey: &PublicKey) -> Result<(), crypto::Error> {
+        match decompress(pubkey.as_bytes()) {
+            Ok(point) if !point.is_small_order() => Ok(()),
+            _ => Err(crypto::Error::InvalidKey),
+        }
+    }
+
+    fn from_cross_group_secret(secret: &[u8; 32]) -> Result<PublicKey, crypto::Error> {
+        crypto::check_cross_group_secret(secret)?;
+        let key = PrivateKey::from_slice(secret).map_err(crypto::Error::new)?;
+        Ok(PublicKey::from_private_key(&key))
+    }
+}
+
+/// Monero private view key shared with the counter-party. With the `zeroize` feature enabled the
+/// scalar is overwritten with zeros when the key is zeroized, e.g. when dropped inside a
+/// [`Secret`](farcaster_core::crypto::Secret).
+/// Encoded as the inner private key.
+#[derive(Clone, Debug, PartialEq, Eq, StrictDecode, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct PrivateViewKey(pub PrivateKey);
+
+impl From<PrivateKey> for PrivateViewKey {
+    fn from(key: PrivateKey) -> Self {
+        Self(key)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for PrivateViewKey {
+    fn zeroize(&mut self) {
+        self.0.scalar.zeroize();
+    }
 }
 
 impl SharedPrivateKeys<Acc> for Monero {
-    type SharedPrivateKey = PrivateKey;
+    type SharedPrivateKey = PrivateViewKey;
 
     fn get_shared_privkey(
         seed: &[u8; 32],
         key_type: SharedPrivateKey,
-    ) -> Result<PrivateKey, crypto::Error> {
+    ) -> Result<PrivateViewKey, crypto::Error> {
         match key_type {
             SharedPrivateKey::View => {
                 let mut bytes = Vec::from(b"farcaster_priv_view".as_ref());
                 bytes.extend_from_slice(&seed.as_ref());
-                Ok(Hash::hash(&bytes).as_scalar())
+                Ok(PrivateViewKey(Hash::hash(&bytes).as_scalar()))
             }
         }
     }
 
-    fn as_bytes(privkey: &PrivateKey) -> Vec<u8> {
-        privkey.as_bytes().into()
+    fn as_bytes(privkey: &PrivateViewKey) -> Vec<u8> {
+        privkey.0.as_bytes().into()
+    }
+
+    fn validate_shared_privkey(privkey: &PrivateViewKey) -> Result<(), crypto::Error> {
+        let bytes: [u8; 32] = privkey
+            .0
+            .as_bytes()
+            .try_into()
+            .map_err(|_| crypto::Error::InvalidKey)?;
+        match Scalar::from_canonical_bytes(bytes) {
+            Some(scalar) if scalar != Scalar::zero() => Ok(()),
+            _ => Err(crypto::Error::InvalidKey),
+        }
+    }
+
+    /// The private view key must not be the private key of the spend point, revealing it would
+    /// reveal the spend key.
+    fn is_consistent_with(privkey: &PrivateViewKey, pubkey: &PublicKey) -> bool {
+        PublicKey::from_private_key(&privkey.0) != *pubkey
     }
 }
 
@@ -1,17 +1,29 @@
 use strict_encoding::{StrictDecode, StrictEncode};
 
-use farcaster_core::crypto::{self, Commitment, DleqProof};
+use std::io;
+
+use farcaster_core::crypto::{self, Commitment, CommitmentEngine, DleqProof};
 use farcaster_core::swap::Swap;
 
 use crate::bitcoin::Bitcoin;
-use crate::monero::{private_spend_from_seed, Monero};
+use crate::monero::{private_spend_from_seed, Monero, H, SHARED_KEY_BITS};
 
 use monero::cryptonote::hash::Hash;
 
-use bitcoin::secp256k1::key::SecretKey;
-use bitcoin::secp256k1::Secp256k1;
+use bitcoin::hashes::{sha256, Hash as _, HashEngine};
+use bitcoin::secp256k1::{self, key::SecretKey};
+
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+
+use rand_core::{CryptoRng, RngCore};
+
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroizing;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BtcXmr;
 
 impl Swap for BtcXmr {
@@ -28,19 +40,53 @@ impl Swap for BtcXmr {
 impl Commitment for BtcXmr {
     type Commitment = Hash;
 
-    fn commit_to<T: AsRef<[u8]>>(value: T) -> Hash {
-        Hash::hash(value.as_ref())
+    type Engine = KeccakEngine;
+}
+
+/// Default commitment engine for [`BtcXmr`], commitments are Keccak-256 hashes of the tagged
+/// value as used on Monero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeccakEngine;
+
+impl CommitmentEngine for KeccakEngine {
+    type Commitment = Hash;
+
+    fn commit(tag: &[u8], value: &[u8]) -> Hash {
+        if tag.is_empty() {
+            Hash::hash(value)
+        } else {
+            Hash::hash(&[tag, value].concat())
+        }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct RingProof;
+/// Transcript of the cross-group discrete logarithm equality proof between the accordant spend
+/// key and the arbitrating adaptor key. The proof is made of one two members ring signature per
+/// bit of the shared key, thus the commitments and challenges contain at most [`SHARED_KEY_BITS`]
+/// elements and the responses twice as many.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RingProof {
+    /// Per-bit commitments, serialized as compressed points on secp256k1 and ed25519
+    pub commitments: Vec<([u8; 33], [u8; 32])>,
+    /// Per-bit ring signature challenge of the first member, little-endian encoded
+    pub challenges: Vec<[u8; 32]>,
+    /// Per-bit ring signature response scalars on secp256k1, big-endian encoded, and ed25519,
+    /// little-endian encoded, for the first then the second member
+    pub responses: Vec<([u8; 32], [u8; 32])>,
+}
 
 impl DleqProof<Bitcoin, Monero> for RingProof {
     fn project_over(ac_seed: &[u8; 32]) -> Result<bitcoin::PrivateKey, crypto::Error> {
         let spend = private_spend_from_seed(&ac_seed)?;
-        let bytes = spend.to_bytes(); // FIXME warn this copy the priv key
-        let adaptor = SecretKey::from_slice(&bytes).map_err(|e| crypto::Error::new(e))?;
+        #[cfg(feature = "zeroize")]
+        let mut bytes = Zeroizing::new(spend.to_bytes()); // FIXME warn this copy the priv key
+        #[cfg(not(feature = "zeroize"))]
+        let mut bytes = spend.to_bytes(); // FIXME warn this copy the priv key
+
+        // Same scalar in both groups, as with `Keys::from_cross_group_secret`, the ed25519 secret
+        // is little-endian encoded while secp256k1 secret keys are big-endian encoded
+        bytes.reverse();
+        let adaptor = SecretKey::from_slice(&bytes[..]).map_err(|e| crypto::Error::new(e))?;
 
         Ok(bitcoin::PrivateKey {
             compressed: true,
@@ -49,39 +95,402 @@ impl DleqProof<Bitcoin, Monero> for RingProof {
         })
     }
 
-    fn generate(
+    fn generate_with_rng<R: RngCore + CryptoRng>(
         ac_seed: &[u8; 32],
+        rng: &mut R,
     ) -> Result<(monero::PublicKey, bitcoin::PublicKey, Self), crypto::Error> {
-        let secp = Secp256k1::new();
-
         let spend = private_spend_from_seed(&ac_seed)?;
         let adaptor = Self::project_over(&ac_seed)?;
+        let spend = monero::PublicKey::from_private_key(&spend);
+        let adaptor_pubkey = bitcoin::PublicKey::from_private_key(Bitcoin::secp(), &adaptor);
 
-        Ok((
-            monero::PublicKey::from_private_key(&spend),
-            bitcoin::PublicKey::from_private_key(&secp, &adaptor),
-            // TODO
-            Self,
-        ))
+        // The secret as a little-endian encoded scalar, lower than 2^SHARED_KEY_BITS
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&adaptor.key[..]);
+        secret.reverse();
+        let proof = prove(&secret, &adaptor_pubkey, &spend, rng)?;
+
+        Ok((spend, adaptor_pubkey, proof))
     }
 
     fn verify(
-        _spend: &monero::PublicKey,
-        _adaptor: &bitcoin::PublicKey,
-        _proof: Self,
+        spend: &monero::PublicKey,
+        adaptor: &bitcoin::PublicKey,
+        proof: Self,
     ) -> Result<(), crypto::Error> {
-        Ok(())
+        if proof.commitments.len() != SHARED_KEY_BITS
+            || proof.challenges.len() != SHARED_KEY_BITS
+            || proof.responses.len() != 2 * SHARED_KEY_BITS
+        {
+            return Err(crypto::Error::InvalidProof);
+        }
+        let spend_point = CompressedEdwardsY::from_slice(spend.as_bytes())
+            .decompress()
+            .ok_or(crypto::Error::InvalidProof)?;
+
+        let mut secp_sum: Option<secp256k1::PublicKey> = None;
+        let mut ed_sum = EdwardsPoint::identity();
+        let mut power = Scalar::one();
+        let rings = proof
+            .commitments
+            .iter()
+            .zip(proof.challenges.iter())
+            .zip(proof.responses.chunks(2));
+        for (bit, ((commitment, challenge), responses)) in rings.enumerate() {
+            let commitment = (
+                secp256k1::PublicKey::from_slice(&commitment.0)
+                    .map_err(|_| crypto::Error::InvalidProof)?,
+                CompressedEdwardsY(commitment.1)
+                    .decompress()
+                    .filter(|point| point.is_torsion_free())
+                    .ok_or(crypto::Error::InvalidProof)?,
+            );
+
+            // Accumulate the commitments weighted by their bit position
+            let term = secp_mul(&commitment.0, &secp_power_of_two(bit))?;
+            secp_sum = Some(match secp_sum {
+                Some(sum) => sum
+                    .combine(&term)
+                    .map_err(|_| crypto::Error::InvalidProof)?,
+                None => term,
+            });
+            ed_sum += power * commitment.1;
+            power += power;
+
+            // Walk the ring from the first member challenge, it must close on itself
+            let mut next = *challenge;
+            for (member, response) in ring_members(&commitment)?.iter().zip(responses) {
+                let secp_response =
+                    SecretKey::from_slice(&response.0).map_err(|_| crypto::Error::InvalidProof)?;
+                let ed_response =
+                    Scalar::from_canonical_bytes(response.1).ok_or(crypto::Error::InvalidProof)?;
+                let nonce = ring_nonce(member, &next, (&secp_response, &ed_response))
+                    .map_err(|_| crypto::Error::InvalidProof)?;
+                next = ring_challenge(adaptor, spend, bit, &commitment, &nonce);
+            }
+            if next != *challenge {
+                return Err(crypto::Error::InvalidProof);
+            }
+        }
+
+        match secp_sum == Some(adaptor.key) && ed_sum == spend_point {
+            true => Ok(()),
+            false => Err(crypto::Error::InvalidProof),
+        }
+    }
+}
+
+/// Tag prefixed to the hashed ring signature transcripts.
+const RING_PROOF_TAG: &[u8] = b"farcaster_ring_proof";
+
+/// Second generator on secp256k1 with an unknown discrete logarithm, the BIP-341 NUMS point
+/// `H = lift_x(sha256(G))` with `G` uncompressed.
+const SECP_H: [u8; 33] = [
+    0x02, 0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a,
+    0x5e, 0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a,
+    0xc0,
+];
+
+/// Big-endian encoding of the secp256k1 scalar one.
+const SECP_ONE: [u8; 32] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+];
+
+/// A point on each curve, the secp256k1 and ed25519 halves of a ring member or nonce.
+type CrossPoint = (secp256k1::PublicKey, EdwardsPoint);
+
+fn secp_generator() -> Result<secp256k1::PublicKey, crypto::Error> {
+    let one = SecretKey::from_slice(&SECP_ONE).map_err(crypto::Error::new)?;
+    Ok(secp256k1::PublicKey::from_secret_key(Bitcoin::secp(), &one))
+}
+
+fn secp_h() -> Result<secp256k1::PublicKey, crypto::Error> {
+    secp256k1::PublicKey::from_slice(&SECP_H).map_err(crypto::Error::new)
+}
+
+fn ed25519_h() -> Result<EdwardsPoint, crypto::Error> {
+    CompressedEdwardsY(H)
+        .decompress()
+        .ok_or(crypto::Error::InvalidKey)
+}
+
+/// Returns `2^bit` as a big-endian encoded secp256k1 scalar.
+fn secp_power_of_two(bit: usize) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[31 - bit / 8] = 1 << (bit % 8);
+    bytes
+}
+
+/// Draws a uniformly random secp256k1 scalar, retrying on the negligible out of range values.
+fn random_secp_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> SecretKey {
+    loop {
+        let mut bytes = [0u8; 32];
+        rng.fill_bytes(&mut bytes);
+        if let Ok(scalar) = SecretKey::from_slice(&bytes) {
+            return scalar;
+        }
     }
 }
 
+/// Returns `point * scalar` with the scalar big-endian encoded.
+fn secp_mul(
+    point: &secp256k1::PublicKey,
+    scalar: &[u8],
+) -> Result<secp256k1::PublicKey, crypto::Error> {
+    let mut point = *point;
+    point
+        .mul_assign(Bitcoin::secp(), scalar)
+        .map_err(crypto::Error::new)?;
+    Ok(point)
+}
+
+/// Returns `a - b`.
+fn secp_sub(
+    a: &secp256k1::PublicKey,
+    b: &secp256k1::PublicKey,
+) -> Result<secp256k1::PublicKey, crypto::Error> {
+    let mut b = *b;
+    b.negate_assign(Bitcoin::secp());
+    a.combine(&b).map_err(crypto::Error::new)
+}
+
+/// Returns the ring signature nonce `z * H - e * P` of the ring member `P` in both groups.
+fn ring_nonce(
+    member: &CrossPoint,
+    challenge: &[u8; 32],
+    response: (&SecretKey, &Scalar),
+) -> Result<CrossPoint, crypto::Error> {
+    let mut secp_challenge = *challenge;
+    secp_challenge.reverse();
+    let ed_challenge = Scalar::from_bytes_mod_order(*challenge);
+    Ok((
+        secp_sub(
+            &secp_mul(&secp_h()?, &response.0[..])?,
+            &secp_mul(&member.0, &secp_challenge)?,
+        )?,
+        response.1 * ed25519_h()? - ed_challenge * member.1,
+    ))
+}
+
+/// Returns the two ring members of a bit commitment: the commitment itself, known to the prover
+/// if the bit is zero, and the commitment minus the generators, known if the bit is one.
+fn ring_members(commitment: &CrossPoint) -> Result<[CrossPoint; 2], crypto::Error> {
+    Ok([
+        *commitment,
+        (
+            secp_sub(&commitment.0, &secp_generator()?)?,
+            commitment.1 - ED25519_BASEPOINT_POINT,
+        ),
+    ])
+}
+
+/// Hashes the transcript of the ring signature of `bit` into the challenge of the next ring
+/// member. The challenge is little-endian encoded and lower than `2^252`, thus the same scalar in
+/// both groups.
+fn ring_challenge(
+    adaptor: &bitcoin::PublicKey,
+    spend: &monero::PublicKey,
+    bit: usize,
+    commitment: &CrossPoint,
+    nonce: &CrossPoint,
+) -> [u8; 32] {
+    let mut engine = sha256::Hash::engine();
+    engine.input(RING_PROOF_TAG);
+    engine.input(&adaptor.key.serialize());
+    engine.input(spend.as_bytes());
+    engine.input(&(bit as u16).to_le_bytes());
+    engine.input(&commitment.0.serialize());
+    engine.input(commitment.1.compress().as_bytes());
+    engine.input(&nonce.0.serialize());
+    engine.input(nonce.1.compress().as_bytes());
+    let mut challenge = sha256::Hash::from_engine(engine).into_inner();
+    challenge[31] &= 0b0000_1111;
+    challenge
+}
+
+/// Proves that `adaptor` and `spend` share the little-endian encoded `secret`. Each bit of the
+/// secret is committed to in both groups, `b * G + r * H`, with blinding factors summing to zero
+/// once weighted by the bit positions, so the weighted sum of the commitments is the public key.
+/// A two members ring signature per bit proves the same bit is committed to in both groups. The
+/// blinding factors and the nonces are drawn from `rng`.
+fn prove<R: RngCore + CryptoRng>(
+    secret: &[u8; 32],
+    adaptor: &bitcoin::PublicKey,
+    spend: &monero::PublicKey,
+    rng: &mut R,
+) -> Result<RingProof, crypto::Error> {
+    let (secp_h, ed_h) = (secp_h()?, ed25519_h()?);
+
+    // Random blinding factors, the first one balances the weighted sum to zero
+    let mut secp_blindings: Vec<SecretKey> = (0..SHARED_KEY_BITS)
+        .map(|_| random_secp_scalar(rng))
+        .collect();
+    let mut ed_blindings: Vec<Scalar> = (0..SHARED_KEY_BITS).map(|_| Scalar::random(rng)).collect();
+    let mut secp_sum: Option<SecretKey> = None;
+    let mut ed_sum = Scalar::zero();
+    let mut power = Scalar::one();
+    let blindings = secp_blindings.iter().zip(ed_blindings.iter());
+    for (bit, (secp_blinding, ed_blinding)) in blindings.enumerate().skip(1) {
+        let mut term = *secp_blinding;
+        term.mul_assign(&secp_power_of_two(bit))
+            .map_err(crypto::Error::new)?;
+        secp_sum = Some(match secp_sum {
+            Some(mut sum) => {
+                sum.add_assign(&term[..]).map_err(crypto::Error::new)?;
+                sum
+            }
+            None => term,
+        });
+        power += power;
+        ed_sum += power * ed_blinding;
+    }
+    if let Some(mut sum) = secp_sum {
+        sum.negate_assign();
+        secp_blindings[0] = sum;
+    }
+    ed_blindings[0] = -ed_sum;
+
+    let mut proof = RingProof::default();
+    let blindings = secp_blindings.iter().zip(ed_blindings.iter());
+    for (bit, (r, s)) in blindings.enumerate() {
+        let set = (secret[bit / 8] >> (bit % 8)) & 1 == 1;
+        let mut commitment = (secp_mul(&secp_h, &r[..])?, s * ed_h);
+        if set {
+            commitment.0 = commitment
+                .0
+                .combine(&secp_generator()?)
+                .map_err(crypto::Error::new)?;
+            commitment.1 += ED25519_BASEPOINT_POINT;
+        }
+        let members = ring_members(&commitment)?;
+        let (real, forged) = if set { (1, 0) } else { (0, 1) };
+
+        // The nonce of the known member gives the challenge of the other member
+        let (secp_nonce, ed_nonce) = (random_secp_scalar(rng), Scalar::random(rng));
+        let nonce = (secp_mul(&secp_h, &secp_nonce[..])?, ed_nonce * ed_h);
+        let mut challenges = [[0u8; 32]; 2];
+        challenges[forged] = ring_challenge(adaptor, spend, bit, &commitment, &nonce);
+
+        // Random responses for the other member close the ring on the known member
+        let forged_response = (random_secp_scalar(rng), Scalar::random(rng));
+        let forged_nonce = ring_nonce(
+            &members[forged],
+            &challenges[forged],
+            (&forged_response.0, &forged_response.1),
+        )?;
+        challenges[real] = ring_challenge(adaptor, spend, bit, &commitment, &forged_nonce);
+
+        // z = k + e * r in both groups
+        let mut secp_challenge = challenges[real];
+        secp_challenge.reverse();
+        let mut secp_response = *r;
+        secp_response
+            .mul_assign(&secp_challenge)
+            .map_err(crypto::Error::new)?;
+        secp_response
+            .add_assign(&secp_nonce[..])
+            .map_err(crypto::Error::new)?;
+        let ed_response = ed_nonce + Scalar::from_bytes_mod_order(challenges[real]) * s;
+
+        let mut responses = [([0u8; 32], [0u8; 32]); 2];
+        responses[real] = (to_secp_bytes(&secp_response), ed_response.to_bytes());
+        responses[forged] = (
+            to_secp_bytes(&forged_response.0),
+            forged_response.1.to_bytes(),
+        );
+
+        proof
+            .commitments
+            .push((commitment.0.serialize(), commitment.1.compress().to_bytes()));
+        proof.challenges.push(challenges[0]);
+        proof.responses.extend_from_slice(&responses);
+    }
+    Ok(proof)
+}
+
+fn to_secp_bytes(scalar: &SecretKey) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&scalar[..]);
+    bytes
+}
+
+fn encode_len<E: io::Write>(
+    len: usize,
+    max: usize,
+    e: &mut E,
+) -> Result<usize, strict_encoding::Error> {
+    if len > max {
+        return Err(strict_encoding::Error::DataIntegrityError(
+            "Too many elements in the ring proof".to_string(),
+        ));
+    }
+    (len as u16).strict_encode(e)
+}
+
+fn decode_len<D: io::Read>(d: &mut D, max: usize) -> Result<usize, strict_encoding::Error> {
+    let len = u16::strict_decode(d)? as usize;
+    if len > max {
+        return Err(strict_encoding::Error::DataIntegrityError(
+            "Too many elements in the ring proof".to_string(),
+        ));
+    }
+    Ok(len)
+}
+
 impl StrictEncode for RingProof {
-    fn strict_encode<E: std::io::Write>(&self, mut _e: E) -> Result<usize, strict_encoding::Error> {
-        Ok(0)
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        let mut len = encode_len(self.commitments.len(), SHARED_KEY_BITS, &mut e)?;
+        for (secp, ed) in self.commitments.iter() {
+            e.write_all(secp)?;
+            e.write_all(ed)?;
+            len += secp.len() + ed.len();
+        }
+        len += encode_len(self.challenges.len(), SHARED_KEY_BITS, &mut e)?;
+        for challenge in self.challenges.iter() {
+            e.write_all(challenge)?;
+            len += challenge.len();
+        }
+        len += encode_len(self.responses.len(), 2 * SHARED_KEY_BITS, &mut e)?;
+        for (secp, ed) in self.responses.iter() {
+            e.write_all(secp)?;
+            e.write_all(ed)?;
+            len += secp.len() + ed.len();
+        }
+        Ok(len)
     }
 }
 
 impl StrictDecode for RingProof {
-    fn strict_decode<D: std::io::Read>(mut _d: D) -> Result<Self, strict_encoding::Error> {
-        Ok(Self)
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let len = decode_len(&mut d, SHARED_KEY_BITS)?;
+        let mut commitments = Vec::with_capacity(len);
+        for _ in 0..len {
+            let mut secp = [0u8; 33];
+            let mut ed = [0u8; 32];
+            d.read_exact(&mut secp)?;
+            d.read_exact(&mut ed)?;
+            commitments.push((secp, ed));
+        }
+        let len = decode_len(&mut d, SHARED_KEY_BITS)?;
+        let mut challenges = Vec::with_capacity(len);
+        for _ in 0..len {
+            let mut challenge = [0u8; 32];
+            d.read_exact(&mut challenge)?;
+            challenges.push(challenge);
+        }
+        let len = decode_len(&mut d, 2 * SHARED_KEY_BITS)?;
+        let mut responses = Vec::with_capacity(len);
+        for _ in 0..len {
+            let mut secp = [0u8; 32];
+            let mut ed = [0u8; 32];
+            d.read_exact(&mut secp)?;
+            d.read_exact(&mut ed)?;
+            responses.push((secp, ed));
+        }
+        Ok(Self {
+            commitments,
+            challenges,
+            responses,
+        })
     }
 }
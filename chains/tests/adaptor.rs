@@ -0,0 +1,187 @@
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{OutPoint, TxIn};
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::{Message, Secp256k1, Signature};
+use bitcoin::util::key::PublicKey;
+use bitcoin::Address;
+
+use farcaster_chains::bitcoin::transaction::{Buy, Error, Lock, Tx};
+use farcaster_chains::bitcoin::{
+    recover_adaptor_secret_from_tx, witness_signatures, Amount, Bitcoin, ECDSAAdaptorSig,
+};
+use farcaster_chains::pairs::btcxmr::BtcXmr;
+
+use farcaster_core::blockchain::FeePolitic;
+use farcaster_core::bundle::SignedAdaptorRefund;
+use farcaster_core::crypto::{self, SignatureType, Signatures};
+use farcaster_core::datum;
+use farcaster_core::role::{Alice, SwapRole};
+use farcaster_core::transaction::{
+    AdaptorSignable, Broadcastable, Buyable, Error as TxError, Lockable, Signable, TxId,
+    Witnessable,
+};
+
+use std::str::FromStr;
+
+mod common;
+use common::{funding, privkey, swap_lock};
+
+fn signature(r: &[u8], s: &SecretKey) -> Signature {
+    let mut compact = r.to_vec();
+    compact.extend_from_slice(&s[..]);
+    Signature::from_compact(&compact).unwrap()
+}
+
+#[test]
+fn recover_adaptor_secret_from_buy() {
+    let secp = Secp256k1::new();
+    let alice_privkey = privkey(1);
+    let alice = PublicKey::from_private_key(&secp, &alice_privkey);
+    let bob = PublicKey::from_private_key(&secp, &privkey(2));
+    let adaptor_secret = privkey(3);
+    let adaptor = PublicKey::from_private_key(&secp, &adaptor_secret);
+
+    let funding = funding(bob);
+    let datalock = swap_lock(alice, bob);
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let destination =
+        Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk").expect("Parsable address");
+    let mut buy =
+        Tx::<Buy>::initialize(&lock, datalock.clone(), destination.clone().into()).unwrap();
+    assert!(buy
+        .verify_template(datalock.clone(), destination.into())
+        .is_ok());
+    let other_destination =
+        Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq").expect("Parsable address");
+    assert!(matches!(
+        buy.verify_template(datalock, other_destination.into()),
+        Err(TxError::WrongTemplate)
+    ));
+
+    // Bob's adaptor signature, adapted by Alice with the adaptor secret
+    let adaptor_sig = buy.generate_adaptor_witness(&privkey(2), &adaptor).unwrap();
+    assert!(buy
+        .verify_adaptor_witness(&bob, &adaptor, adaptor_sig.clone())
+        .is_ok());
+    assert!(buy
+        .verify_adaptor_witness(&alice, &adaptor, adaptor_sig.clone())
+        .is_err());
+    let adapted_sig = Bitcoin::adapt(&adaptor_secret, adaptor_sig.clone()).unwrap();
+    assert!(buy.verify_witness(&bob, adapted_sig).is_ok());
+
+    // spend the swaplock consensus path with both signatures
+    let alice_sig = buy.generate_witness(&alice_privkey).unwrap();
+    buy.add_witness(alice, alice_sig).unwrap();
+    buy.add_witness(bob, adapted_sig).unwrap();
+    let tx = buy.finalize_and_extract().unwrap();
+
+    let secret = recover_adaptor_secret_from_tx(&tx, &adaptor_sig).unwrap();
+    assert_eq!(secret.key, adaptor_secret.key);
+
+    // a transaction without the adapted signature
+    let mut other = tx.clone();
+    other.input[0].witness.remove(2);
+    assert!(matches!(
+        recover_adaptor_secret_from_tx(&other, &adaptor_sig),
+        Err(Error::SecretNotRecoverable)
+    ));
+}
+
+#[test]
+fn recover_secret_from_refund_signature() {
+    let secp = Secp256k1::new();
+    let adaptor_secret = privkey(5);
+    let adaptor = PublicKey::from_private_key(&secp, &adaptor_secret);
+
+    let msg = Message::from_slice(&[0x55; 32]).unwrap();
+    let refund_adaptor_sig = ECDSAAdaptorSig::sign(&privkey(4), &adaptor, &msg).unwrap();
+    let final_sig = Bitcoin::adapt(&adaptor_secret, refund_adaptor_sig.clone()).unwrap();
+    let compact = final_sig.serialize_compact();
+    let (r, s) = (
+        &compact[..32],
+        SecretKey::from_slice(&compact[32..]).unwrap(),
+    );
+
+    let secret = Bitcoin::recover_secret(&refund_adaptor_sig, &final_sig).unwrap();
+    assert_eq!(secret.key, adaptor_secret.key);
+
+    // the final signature with S negated
+    let mut high_s = s;
+    high_s.negate_assign();
+    let secret = Bitcoin::recover_secret(&refund_adaptor_sig, &signature(r, &high_s)).unwrap();
+    assert_eq!(secret.key, adaptor_secret.key);
+
+    let other_sig = signature(r, &SecretKey::from_slice(&[0x77; 32]).unwrap());
+    assert!(Bitcoin::recover_secret(&refund_adaptor_sig, &other_sig).is_err());
+
+    // through the swap driver with the signatures of the refund witness
+    let refund = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: 0,
+            witness: vec![
+                vec![],
+                [&other_sig.serialize_der()[..], &[0x01u8][..]].concat(),
+                [&final_sig.serialize_der()[..], &[0x01u8][..]].concat(),
+                vec![1],
+            ],
+        }],
+        output: vec![],
+    };
+    let signed_adaptor_refund = SignedAdaptorRefund {
+        refund_adaptor_sig: datum::Signature::new(
+            TxId::Refund,
+            SwapRole::Alice,
+            SignatureType::Adaptor(refund_adaptor_sig),
+        ),
+    };
+    let alice: Alice<BtcXmr> = Alice::new(
+        Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+            .expect("Parsable address")
+            .into(),
+        FeePolitic::Aggressive,
+    );
+    let secret = alice
+        .recover_refund_adaptor_secret(&witness_signatures(&refund), &signed_adaptor_refund)
+        .unwrap();
+    assert_eq!(secret.key, adaptor_secret.key);
+    assert!(alice
+        .recover_refund_adaptor_secret(&[other_sig], &signed_adaptor_refund)
+        .is_err());
+}
+
+#[test]
+fn adapt_then_recover_secret() {
+    let secp = Secp256k1::new();
+    let adaptor_secret = privkey(6);
+    let adaptor = PublicKey::from_private_key(&secp, &adaptor_secret);
+
+    let signer = privkey(8);
+    let pubkey = PublicKey::from_private_key(&secp, &signer);
+    let msg = Message::from_slice(&[0x42; 32]).unwrap();
+    let adaptor_sig = ECDSAAdaptorSig::sign(&signer, &adaptor, &msg).unwrap();
+    assert!(adaptor_sig.verify(&pubkey, &msg).is_ok());
+    assert!(matches!(
+        adaptor_sig.verify(&adaptor, &msg),
+        Err(crypto::Error::InvalidAdaptorSignature)
+    ));
+    // the pre-signature alone is not a valid signature
+    assert!(secp.verify(&msg, &adaptor_sig.sig, &pubkey.key).is_err());
+
+    // the adapted signature is a valid low-S signature of the message
+    let adapted_sig = Bitcoin::adapt(&adaptor_secret, adaptor_sig.clone()).unwrap();
+    assert!(secp.verify(&msg, &adapted_sig, &pubkey.key).is_ok());
+    assert!(Bitcoin::validate_signature(&adapted_sig).is_ok());
+
+    let secret = Bitcoin::recover_secret(&adaptor_sig, &adapted_sig).unwrap();
+    assert_eq!(secret.key, adaptor_secret.key);
+
+    // only the adaptor secret decrypts the signature
+    assert!(matches!(
+        Bitcoin::adapt(&privkey(7), adaptor_sig),
+        Err(crypto::Error::InvalidKey)
+    ));
+}
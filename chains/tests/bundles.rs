@@ -1,23 +1,38 @@
-use farcaster_chains::pairs::btcxmr::BtcXmr;
+use farcaster_chains::monero::{private_spend_from_seed, AmountCommitment};
+use farcaster_chains::pairs::btcxmr::{BtcXmr, RingProof};
 
 use farcaster_core::blockchain::FeePolitic;
+use farcaster_core::bundle::AccordantLock;
 use farcaster_core::consensus::deserialize;
+use farcaster_core::crypto::{self, DleqProof, Secret};
+use farcaster_core::datum::KeyPurpose;
 use farcaster_core::negotiation::PublicOffer;
 use farcaster_core::protocol_message::{
-    CommitAliceParameters, CommitBobParameters, RevealAliceParameters, RevealBobParameters,
+    verify_exchange, CommitAliceParameters, CommitBobParameters, CoreArbitratingSetup,
+    RevealAliceParameters, RevealBobParameters,
 };
-use farcaster_core::role::{Alice, Bob};
+use farcaster_core::role::{Alice, Bob, SwapRole};
+use farcaster_core::transaction::TxId;
+use farcaster_core::Error;
 
+use bitcoin::blockdata::transaction::Transaction;
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::util::key::{PrivateKey, PublicKey};
+use bitcoin::util::psbt::PartiallySignedTransaction;
 use bitcoin::Address;
 
+use strict_encoding::{strict_deserialize, strict_serialize};
+
 use std::str::FromStr;
 
 #[test]
 fn create_alice_parameters() {
     let hex = "46435357415001000200000080800000800800a0860100000000000800c80000000000000004000\
-               a00000004000a00000001080014000000000000000203b31a0a70343bb46f3db3768296ac5027f9\
-               873921b37f852860c690063ff9e4c90000000000000000000000000000000000000000000000000\
-               000000000000000000000260700";
+               a00000004000a000000010800140000000000000002030000000300000001000000020000000100\
+               0000010000000a00000003b31a0a70343bb46f3db3768296ac5027f9873921b37f852860c690063\
+               ff9e4c9000000000000000000000000000000000000000000000000000000000000000000000026\
+               0700";
 
     let destination_address = Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
         .expect("Parsable address")
@@ -53,9 +68,10 @@ fn create_alice_parameters() {
 #[test]
 fn create_bob_parameters() {
     let hex = "46435357415001000200000080800000800800a0860100000000000800c80000000000000004000\
-               a00000004000a00000001080014000000000000000203b31a0a70343bb46f3db3768296ac5027f9\
-               873921b37f852860c690063ff9e4c90000000000000000000000000000000000000000000000000\
-               000000000000000000000260700";
+               a00000004000a000000010800140000000000000002030000000300000001000000020000000100\
+               0000010000000a00000003b31a0a70343bb46f3db3768296ac5027f9873921b37f852860c690063\
+               ff9e4c9000000000000000000000000000000000000000000000000000000000000000000000026\
+               0700";
 
     let refund_address = Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
         .expect("Parsable address")
@@ -85,3 +101,317 @@ fn create_bob_parameters() {
 
     assert!(dbg!(commit_bob_params.verify_then_bundle(&reveal_bob_params)).is_ok());
 }
+
+fn dummy_arbitrating_key(byte: u8) -> PublicKey {
+    let secp = Secp256k1::new();
+    let privkey = PrivateKey {
+        compressed: true,
+        network: bitcoin::Network::Bitcoin,
+        key: SecretKey::from_slice(&[byte; 32]).unwrap(),
+    };
+    PublicKey::from_private_key(&secp, &privkey)
+}
+
+#[test]
+fn btcxmr_commit_reveal_smoke_test() {
+    let key = dummy_arbitrating_key;
+    let other_key = dummy_arbitrating_key(9);
+    let address =
+        Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk").expect("Parsable address");
+    let view = private_spend_from_seed(&[1u8; 32]).unwrap();
+    let (alice_spend, alice_adaptor, alice_proof) = RingProof::generate(&[2u8; 32]).unwrap();
+    let (bob_spend, bob_adaptor, bob_proof) = RingProof::generate(&[3u8; 32]).unwrap();
+
+    // Alice
+    let reveal_alice_params = RevealAliceParameters::<BtcXmr> {
+        buy: key(1),
+        cancel: key(2),
+        refund: key(3),
+        punish: key(4),
+        adaptor: alice_adaptor,
+        address: address.clone().into(),
+        spend: alice_spend,
+        view: Secret::new(view.into()),
+        proof: alice_proof,
+    };
+    let debug = format!("{:?}", reveal_alice_params);
+    assert!(debug.contains("<redacted private key>"));
+    assert!(!debug.contains(&format!("{:?}", view)));
+
+    let commit_alice_params =
+        CommitAliceParameters::from_bundle(&reveal_alice_params.into_bundle());
+    assert!(commit_alice_params.verify(&reveal_alice_params).is_ok());
+
+    let mut tampered_alice_params = reveal_alice_params.clone();
+    tampered_alice_params.punish = other_key;
+    assert!(commit_alice_params.verify(&tampered_alice_params).is_err());
+
+    // reusing a key across roles is rejected even when committed to
+    let mut duplicated_alice_params = reveal_alice_params.clone();
+    duplicated_alice_params.refund = duplicated_alice_params.buy;
+    let commit_duplicated =
+        CommitAliceParameters::from_bundle(&duplicated_alice_params.into_bundle());
+    assert!(matches!(
+        commit_duplicated.verify(&duplicated_alice_params),
+        Err(Error::DuplicateKey)
+    ));
+
+    // Bob
+    let reveal_bob_params = RevealBobParameters::<BtcXmr> {
+        buy: key(6),
+        cancel: key(7),
+        refund: key(8),
+        adaptor: bob_adaptor,
+        address: address.into(),
+        spend: bob_spend,
+        view: Secret::new(view.into()),
+        proof: bob_proof,
+    };
+    let commit_bob_params = CommitBobParameters::from_bundle(&reveal_bob_params.into_bundle());
+    assert!(commit_bob_params.verify(&reveal_bob_params).is_ok());
+
+    let mut tampered_bob_params = reveal_bob_params.clone();
+    tampered_bob_params.adaptor = other_key;
+    assert!(commit_bob_params.verify(&tampered_bob_params).is_err());
+
+    let mut duplicated_bob_params = reveal_bob_params.clone();
+    duplicated_bob_params.adaptor = duplicated_bob_params.cancel;
+    let commit_duplicated = CommitBobParameters::from_bundle(&duplicated_bob_params.into_bundle());
+    assert!(matches!(
+        commit_duplicated.verify(&duplicated_bob_params),
+        Err(Error::DuplicateKey)
+    ));
+
+    // Full exchange
+    for role in [SwapRole::Alice, SwapRole::Bob].iter() {
+        assert!(verify_exchange(
+            *role,
+            &commit_alice_params,
+            &reveal_alice_params,
+            &commit_bob_params,
+            &reveal_bob_params,
+        )
+        .is_ok());
+        assert!(verify_exchange(
+            *role,
+            &commit_alice_params,
+            &reveal_alice_params,
+            &commit_bob_params,
+            &tampered_bob_params,
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn reject_identity_and_zero_keys() {
+    let address =
+        Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk").expect("Parsable address");
+    let view = private_spend_from_seed(&[1u8; 32]).unwrap();
+    let (spend, adaptor, proof) = RingProof::generate(&[2u8; 32]).unwrap();
+    let mut identity = [0u8; 32];
+    identity[0] = 0x01;
+    let identity = monero::PublicKey::from_slice(&identity).unwrap();
+    let zero = monero::PrivateKey::from_slice(&[0u8; 32]).unwrap();
+
+    let reveal = RevealBobParameters::<BtcXmr> {
+        buy: dummy_arbitrating_key(1),
+        cancel: dummy_arbitrating_key(2),
+        refund: dummy_arbitrating_key(3),
+        adaptor,
+        address: address.into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof,
+    };
+    let commit = CommitBobParameters::from_bundle(&reveal.into_bundle());
+    assert!(commit.verify(&reveal).is_ok());
+
+    let mut identity_spend = reveal.clone();
+    identity_spend.spend = identity;
+    let commit = CommitBobParameters::from_bundle(&identity_spend.into_bundle());
+    assert!(matches!(
+        commit.verify(&identity_spend),
+        Err(Error::Crypto(crypto::Error::InvalidKey))
+    ));
+
+    let mut zero_view = reveal;
+    zero_view.view = Secret::new(zero.into());
+    let commit = CommitBobParameters::from_bundle(&zero_view.into_bundle());
+    assert!(matches!(
+        commit.verify(&zero_view),
+        Err(Error::Crypto(crypto::Error::InvalidKey))
+    ));
+}
+
+#[test]
+fn reject_view_key_of_spend_point() {
+    let address =
+        Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk").expect("Parsable address");
+    let spend_key = private_spend_from_seed(&[2u8; 32]).unwrap();
+    let (spend, adaptor, proof) = RingProof::generate(&[2u8; 32]).unwrap();
+
+    // the view key shared is the private key of the spend point
+    let reveal = RevealAliceParameters::<BtcXmr> {
+        buy: dummy_arbitrating_key(1),
+        cancel: dummy_arbitrating_key(2),
+        refund: dummy_arbitrating_key(3),
+        punish: dummy_arbitrating_key(4),
+        adaptor,
+        address: address.clone().into(),
+        spend,
+        view: Secret::new(spend_key.into()),
+        proof: proof.clone(),
+    };
+    let commit = CommitAliceParameters::from_bundle(&reveal.into_bundle());
+    assert!(matches!(
+        commit.verify(&reveal),
+        Err(Error::InconsistentAccordantKeys)
+    ));
+
+    let reveal = RevealBobParameters::<BtcXmr> {
+        buy: dummy_arbitrating_key(6),
+        cancel: dummy_arbitrating_key(7),
+        refund: dummy_arbitrating_key(8),
+        adaptor,
+        address: address.into(),
+        spend,
+        view: Secret::new(spend_key.into()),
+        proof,
+    };
+    let commit = CommitBobParameters::from_bundle(&reveal.into_bundle());
+    assert!(matches!(
+        commit.verify(&reveal),
+        Err(Error::InconsistentAccordantKeys)
+    ));
+
+    // an independent view key is accepted
+    let mut independent = reveal;
+    independent.view = Secret::new(private_spend_from_seed(&[1u8; 32]).unwrap().into());
+    let commit = CommitBobParameters::from_bundle(&independent.into_bundle());
+    assert!(commit.verify(&independent).is_ok());
+}
+
+#[test]
+fn verify_accordant_amount_with_view_key() {
+    let view = private_spend_from_seed(&[1u8; 32]).unwrap();
+    let tx_key = private_spend_from_seed(&[3u8; 32]).unwrap();
+    let public_view = monero::PublicKey::from_private_key(&view);
+
+    let amount = AmountCommitment::new(&tx_key, &public_view, 1, 10_000_000).unwrap();
+    assert_eq!(amount.open(&view).unwrap(), 10_000_000);
+
+    let lock = AccordantLock::<BtcXmr> { amount };
+    let bytes = strict_serialize(&lock).unwrap();
+    let lock: AccordantLock<BtcXmr> = strict_deserialize(&bytes).unwrap();
+    assert!(lock.verify_accordant_amount(&view, 10_000_000).is_ok());
+    assert!(lock.verify_accordant_amount(&view, 9_999_999).is_err());
+
+    // another view key decrypts a different amount that does not match the commitment
+    assert!(lock.verify_accordant_amount(&tx_key, 10_000_000).is_err());
+
+    // a different output index derives a different shared secret
+    let mut tampered = lock.clone();
+    tampered.amount.index = 0;
+    assert!(tampered.verify_accordant_amount(&view, 10_000_000).is_err());
+}
+
+#[test]
+fn refund_procedure_signatures_validate_core_first() {
+    let hex = "46435357415001000200000080800000800800a0860100000000000800c80000000000000004000\
+               a00000004000a000000010800140000000000000002030000000300000001000000020000000100\
+               0000010000000a00000003b31a0a70343bb46f3db3768296ac5027f9873921b37f852860c690063\
+               ff9e4c9000000000000000000000000000000000000000000000000000000000000000000000026\
+               0700";
+    let pub_offer: PublicOffer<BtcXmr> =
+        deserialize(&hex::decode(hex).unwrap()[..]).expect("Parsable public offer");
+    let address =
+        Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk").expect("Parsable address");
+
+    let alice: Alice<BtcXmr> = Alice::new(address.clone().into(), FeePolitic::Aggressive);
+    let bob: Bob<BtcXmr> = Bob::new(address.into(), FeePolitic::Aggressive);
+    let alice_ar_seed = [1u8; 32];
+    let alice_params = alice
+        .generate_parameters(&alice_ar_seed, &[2u8; 32], &pub_offer)
+        .unwrap();
+    let bob_params = bob
+        .generate_parameters(&[3u8; 32], &[4u8; 32], &pub_offer)
+        .unwrap();
+
+    // the core arbitrating transactions are validated before anything is signed
+    let psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: Vec::new(),
+        output: Vec::new(),
+    })
+    .unwrap();
+    let secp = Secp256k1::new();
+    let sig = secp.sign(
+        &Message::from_slice(&[0x42; 32]).unwrap(),
+        &SecretKey::from_slice(&[0x01; 32]).unwrap(),
+    );
+    let core = CoreArbitratingSetup::<BtcXmr> {
+        lock: psbt.clone(),
+        cancel: psbt.clone(),
+        refund: psbt,
+        cancel_sig: sig,
+    };
+    assert!(matches!(
+        alice.produce_refund_procedure_signatures(
+            &alice_ar_seed,
+            &alice_params,
+            &bob_params,
+            &core,
+            &pub_offer,
+        ),
+        Err(Error::InvalidCoreTransaction(TxId::Lock, _))
+    ));
+}
+
+#[test]
+fn iterate_over_bundle_keys() {
+    let key = dummy_arbitrating_key;
+    let address =
+        Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk").expect("Parsable address");
+    let view = private_spend_from_seed(&[1u8; 32]).unwrap();
+    let spend = monero::PublicKey::from_private_key(&private_spend_from_seed(&[2u8; 32]).unwrap());
+
+    let alice_params = RevealAliceParameters::<BtcXmr> {
+        buy: key(1),
+        cancel: key(2),
+        refund: key(3),
+        punish: key(4),
+        adaptor: key(5),
+        address: address.clone().into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof: RingProof::default(),
+    }
+    .into_bundle();
+    let bob_params = RevealBobParameters::<BtcXmr> {
+        buy: key(6),
+        cancel: key(7),
+        refund: key(8),
+        adaptor: key(9),
+        address: address.into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof: RingProof::default(),
+    }
+    .into_bundle();
+
+    assert_eq!(alice_params.keys().count(), 7);
+    assert_eq!(bob_params.keys().count(), 6);
+
+    // the purpose matches the identifier of each key
+    for (purpose, key) in alice_params.keys().chain(bob_params.keys()) {
+        assert_eq!(purpose, key.key_id().purpose());
+    }
+    assert!(alice_params
+        .keys()
+        .any(|(purpose, _)| purpose == KeyPurpose::Punish));
+    assert!(!bob_params
+        .keys()
+        .any(|(purpose, _)| purpose == KeyPurpose::Punish));
+}
@@ -0,0 +1,94 @@
+use bitcoin::hashes::{sha256, sha256d, Hash};
+use bitcoin::secp256k1::key::{PublicKey, SecretKey};
+use bitcoin::secp256k1::Secp256k1;
+
+use farcaster_chains::bitcoin::Sha256dEngine;
+use farcaster_chains::pairs::btcxmr::{BtcXmr, KeccakEngine};
+
+use farcaster_core::crypto::{Commitment, CommitmentEngine};
+
+#[test]
+fn default_engine_matches_keccak_commitment() {
+    let value = b"farcaster";
+    let commitment = BtcXmr::commit_to(value);
+    assert_eq!(commitment, monero::cryptonote::hash::Hash::hash(value));
+    assert_eq!(commitment, KeccakEngine::commit(&[], value));
+    assert!(BtcXmr::validate(value, commitment).is_ok());
+    assert!(BtcXmr::validate(b"other", commitment).is_err());
+    assert_ne!(KeccakEngine::commit(b"tag", value), commitment);
+}
+
+#[test]
+fn sha256d_engine_commits_to_tagged_value() {
+    let value = b"farcaster";
+    let commitment = Sha256dEngine::commit(&[], value);
+    assert_eq!(commitment, sha256d::Hash::hash(value));
+    assert!(Sha256dEngine::verify(value, &commitment));
+    assert!(!Sha256dEngine::verify(b"other", &commitment));
+    assert_eq!(
+        Sha256dEngine::commit(b"tag", value),
+        sha256d::Hash::hash(b"tagfarcaster")
+    );
+}
+
+/// Hash-based commitments with double SHA-256.
+struct HashCommitment;
+
+impl Commitment for HashCommitment {
+    type Commitment = sha256d::Hash;
+
+    type Engine = Sha256dEngine;
+}
+
+/// Toy Pedersen commitments on secp256k1, `C = m*G + r*H` with `m` the hashed tagged value and
+/// the blinding factor `r` derived from it, thus not hiding.
+struct PedersenEngine;
+
+impl CommitmentEngine for PedersenEngine {
+    type Commitment = Vec<u8>;
+
+    fn commit(tag: &[u8], value: &[u8]) -> Vec<u8> {
+        let secp = Secp256k1::new();
+        let scalar = |bytes: &[u8]| SecretKey::from_slice(&sha256::Hash::hash(bytes)[..]).unwrap();
+        let m = scalar(&[tag, value].concat());
+        let r = scalar(&[&b"blinding"[..], tag, value].concat());
+        let mut blinding = PublicKey::from_secret_key(&secp, &scalar(b"H"));
+        blinding.mul_assign(&secp, &r[..]).unwrap();
+        PublicKey::from_secret_key(&secp, &m)
+            .combine(&blinding)
+            .unwrap()
+            .serialize()
+            .to_vec()
+    }
+}
+
+struct PedersenCommitment;
+
+impl Commitment for PedersenCommitment {
+    type Commitment = Vec<u8>;
+
+    type Engine = PedersenEngine;
+}
+
+fn commit_and_open<C: Commitment>() -> C::Commitment {
+    let value = b"farcaster";
+    let commitment = C::commit(value);
+    assert!(C::open(value, &commitment));
+    assert!(!C::open(b"other", &commitment));
+    assert_eq!(C::commit_to(value), commitment);
+    assert!(C::validate(value, commitment.clone()).is_ok());
+    assert!(C::validate(b"other", commitment.clone()).is_err());
+    commitment
+}
+
+#[test]
+fn plug_commitment_schemes() {
+    assert_eq!(
+        commit_and_open::<HashCommitment>(),
+        sha256d::Hash::hash(b"farcaster")
+    );
+    // Pedersen commitments are curve points
+    let commitment = commit_and_open::<PedersenCommitment>();
+    assert!(PublicKey::from_slice(&commitment).is_ok());
+    assert_ne!(commitment, PedersenEngine::commit(b"tag", b"farcaster"));
+}
@@ -0,0 +1,70 @@
+//! Key factories and swap setups shared by the integration tests.
+
+#![allow(dead_code)]
+
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{OutPoint, TxIn, TxOut};
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::util::key::{PrivateKey, PublicKey};
+
+use farcaster_chains::bitcoin::transaction::Funding;
+use farcaster_chains::bitcoin::{Bitcoin, CSVTimelock};
+
+use farcaster_core::blockchain::Network;
+use farcaster_core::script::{DataLock, DataPunishableLock, DoubleKeys};
+use farcaster_core::transaction::Fundable;
+
+/// Returns the private key made of 32 times `byte`.
+pub fn privkey(byte: u8) -> PrivateKey {
+    PrivateKey {
+        compressed: true,
+        network: bitcoin::Network::Bitcoin,
+        key: SecretKey::from_slice(&[byte; 32]).unwrap(),
+    }
+}
+
+/// Returns the public key of [`privkey`].
+pub fn pubkey(byte: u8) -> PublicKey {
+    PublicKey::from_private_key(Bitcoin::secp(), &privkey(byte))
+}
+
+/// Returns a funding of 100000 satoshis seen on-chain and spendable by `pubkey`.
+pub fn funding(pubkey: PublicKey) -> Funding {
+    let mut funding = Funding::initialize(pubkey, Network::Local).unwrap();
+    funding
+        .update(bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 100000,
+                script_pubkey: Script::default(),
+            }],
+        })
+        .unwrap();
+    funding
+}
+
+/// Returns the swap lock between `alice` and `bob` with a cancel timelock of 10 blocks.
+pub fn swap_lock(alice: PublicKey, bob: PublicKey) -> DataLock<Bitcoin> {
+    DataLock {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(alice, bob),
+        failure: DoubleKeys::new(alice, bob),
+    }
+}
+
+/// Returns the punishable lock between `alice` and `bob` with a punish timelock of 10 blocks,
+/// Alice punishes with `pubkey(3)`.
+pub fn punishable_lock(alice: PublicKey, bob: PublicKey) -> DataPunishableLock<Bitcoin> {
+    DataPunishableLock {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(alice, bob),
+        failure: pubkey(3),
+    }
+}
@@ -0,0 +1,99 @@
+use bitcoin::consensus::encode::serialize;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::util::key::PublicKey;
+use bitcoin::Address;
+
+use farcaster_chains::bitcoin::fee::{fee_vsize, SatPerVByte};
+use farcaster_chains::bitcoin::transaction::{CooperativeClose, Lock, Tx};
+use farcaster_chains::bitcoin::{Amount, Bitcoin};
+use farcaster_chains::pairs::btcxmr::BtcXmr;
+
+use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy};
+use farcaster_core::protocol_message;
+use farcaster_core::transaction::{Broadcastable, Lockable, Signable, Transaction, Witnessable};
+
+use strict_encoding::{strict_deserialize, strict_serialize};
+
+use std::str::FromStr;
+
+mod common;
+use common::{funding, privkey, swap_lock};
+
+#[test]
+fn finalize_cooperative_close() {
+    let secp = Secp256k1::new();
+    let alice_privkey = privkey(1);
+    let bob_privkey = privkey(2);
+    let alice = PublicKey::from_private_key(&secp, &alice_privkey);
+    let bob = PublicKey::from_private_key(&secp, &bob_privkey);
+
+    let funding = funding(bob);
+
+    let datalock = swap_lock(alice, bob);
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+
+    let refund_address = Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+        .expect("Parsable address")
+        .into();
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(2));
+    let mut close = Tx::<CooperativeClose>::initialize(
+        &lock,
+        datalock,
+        refund_address,
+        &strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    // the output is reduced by the fee
+    let fee = 2 * fee_vsize(close.partial());
+    assert_eq!(
+        close.partial().global.unsigned_tx.output[0].value,
+        99000 - fee
+    );
+    assert!(Bitcoin::validate_fee(close.partial(), &strategy).unwrap());
+
+    // both participants sign the close sighash
+    let alice_sig = close.generate_witness(&alice_privkey).unwrap();
+    let bob_sig = close.generate_witness(&bob_privkey).unwrap();
+    close.verify_witness(&alice, alice_sig).unwrap();
+    close.verify_witness(&bob, bob_sig).unwrap();
+    assert!(close.verify_witness(&alice, bob_sig).is_err());
+    let sighash = Message::from_slice(&close.witness_sighash().unwrap()).unwrap();
+    secp.verify(&sighash, &alice_sig, &alice.key).unwrap();
+
+    let message = protocol_message::CooperativeClose::<BtcXmr> {
+        close: close.partial().clone(),
+        alice_sig,
+        bob_sig,
+    };
+    let bytes = strict_serialize(&message).unwrap();
+    let message_2: protocol_message::CooperativeClose<BtcXmr> = strict_deserialize(&bytes).unwrap();
+    assert_eq!(message, message_2);
+
+    // a single signature is not enough
+    close.add_witness(alice, alice_sig).unwrap();
+    assert!(close.finalize_and_extract().is_err());
+
+    close.add_witness(bob, bob_sig).unwrap();
+    let tx = close.finalize_and_extract().unwrap();
+
+    let witness = &tx.input[0].witness;
+    assert_eq!(witness.len(), 5);
+    assert_eq!(witness[0], Vec::<u8>::new());
+    assert_eq!(
+        witness[1][..witness[1].len() - 1],
+        alice_sig.serialize_der()[..]
+    );
+    assert_eq!(
+        witness[2][..witness[2].len() - 1],
+        bob_sig.serialize_der()[..]
+    );
+    assert_eq!(witness[3], vec![1]);
+
+    // the close spends the lock output under the consensus rules
+    let spent = &lock.partial().global.unsigned_tx.output[0];
+    spent
+        .script_pubkey
+        .verify(0, spent.value, &serialize(&tx))
+        .unwrap();
+}
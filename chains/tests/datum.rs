@@ -1,16 +1,27 @@
 use bitcoin::blockdata::transaction::{TxIn, TxOut};
-use bitcoin::secp256k1::Secp256k1;
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::{Message, Secp256k1, Signature};
 use bitcoin::util::key::{PrivateKey, PublicKey};
 
 use farcaster_chains::bitcoin::transaction::{Funding, Lock, Tx};
-use farcaster_chains::bitcoin::{Amount, Bitcoin, CSVTimelock};
+use farcaster_chains::bitcoin::{
+    signature_from_der, Address, Amount, Bitcoin, CSVTimelock, ECDSAAdaptorSig,
+};
 use farcaster_chains::pairs::btcxmr::BtcXmr;
 
 use farcaster_core::blockchain::Network;
-use farcaster_core::consensus::{deserialize, serialize, serialize_hex};
+use farcaster_core::consensus::{self, deserialize, serialize, serialize_hex};
+use farcaster_core::crypto::{SignatureType, Signatures};
 use farcaster_core::datum::{self, Key};
+use farcaster_core::role::SwapRole;
 use farcaster_core::script::{DataLock, DoubleKeys};
-use farcaster_core::transaction::{Fundable, Lockable, Transaction};
+use farcaster_core::transaction::{
+    ConfirmationBounds, Error as TxError, Fundable, Lockable, Transaction, TxId,
+};
+
+use strict_encoding::{strict_deserialize, strict_serialize};
+
+use std::str::FromStr;
 
 #[test]
 fn create_key_datum() {
@@ -96,3 +107,166 @@ fn create_transaction_datum() {
 
     //assert!(false);
 }
+
+#[test]
+fn confirmation_bounds_roundtrip() {
+    let bounds = ConfirmationBounds::default();
+    assert_eq!(bounds.required_confirmations(TxId::Funding), 3);
+    assert_eq!(bounds.required_confirmations(TxId::Buy), 1);
+    assert_eq!(bounds.required_accordant_lock_confirmations(), 10);
+
+    let bounds = ConfirmationBounds {
+        funding: 6,
+        lock: 4,
+        buy: 1,
+        cancel: 2,
+        refund: 1,
+        punish: 1,
+        accordant_lock: 12,
+    };
+    assert_eq!(bounds.required_confirmations(TxId::Lock), 4);
+    assert_eq!(bounds.required_accordant_lock_confirmations(), 12);
+    let bytes = serialize(&bounds);
+    assert_eq!(bytes.len(), 7 * 4);
+    assert_eq!(&bytes[..4], &[0x06, 0, 0, 0]);
+    assert_eq!(&bytes[6 * 4..], &[0x0c, 0, 0, 0]);
+    assert_eq!(deserialize::<ConfirmationBounds>(&bytes).unwrap(), bounds);
+    let strict: ConfirmationBounds =
+        strict_deserialize(&strict_serialize(&bounds).unwrap()).unwrap();
+    assert_eq!(strict, bounds);
+}
+
+#[test]
+fn transaction_dependency_graph() {
+    let order = TxId::protocol_order();
+    assert_eq!(order.len(), 6);
+    assert_eq!(order[0], TxId::Funding);
+    assert!(TxId::Funding.dependencies().is_empty());
+    assert_eq!(TxId::Lock.dependencies(), &[TxId::Funding]);
+    assert_eq!(TxId::Buy.dependencies(), &[TxId::Lock]);
+    assert_eq!(TxId::Cancel.dependencies(), &[TxId::Lock]);
+    assert_eq!(TxId::Refund.dependencies(), &[TxId::Cancel]);
+    assert_eq!(TxId::Punish.dependencies(), &[TxId::Cancel]);
+
+    // every transaction comes after the transactions it spends
+    for (index, txid) in order.iter().enumerate() {
+        for dependency in txid.dependencies() {
+            assert!(order[..index].contains(dependency));
+        }
+    }
+}
+
+#[test]
+fn confirmation_bounds_require_funding_confirmations() {
+    assert!(ConfirmationBounds::default().validate().is_ok());
+
+    // the funding transaction must be confirmed
+    let unconfirmed = ConfirmationBounds {
+        funding: 0,
+        ..Default::default()
+    };
+    assert!(matches!(
+        unconfirmed.validate(),
+        Err(TxError::InvalidConfirmationBounds)
+    ));
+    let bytes = serialize(&unconfirmed);
+    assert!(deserialize::<ConfirmationBounds>(&bytes).is_err());
+    assert!(strict_deserialize::<ConfirmationBounds>(&bytes).is_err());
+}
+
+#[test]
+fn parse_ecdsa_addresses() {
+    let mainnet = Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+    assert_eq!(mainnet.0.network, bitcoin::Network::Bitcoin);
+    let testnet = Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+    assert_eq!(testnet.0.network, bitcoin::Network::Testnet);
+    let decoded: Address = deserialize(&serialize(&mainnet)).unwrap();
+    assert_eq!(decoded, mainnet);
+
+    // segwit version 1+ outputs cannot be spent with ECDSA signatures
+    for address in [
+        "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+        "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx",
+    ]
+    .iter()
+    {
+        assert!(Address::from_str(address).is_err());
+    }
+}
+
+#[test]
+fn reject_high_s_signatures() {
+    let secp = Secp256k1::new();
+    let privkey = SecretKey::from_slice(&[0x01; 32]).unwrap();
+    let sig = secp.sign(&Message::from_slice(&[0x42; 32]).unwrap(), &privkey);
+    assert!(Bitcoin::validate_signature(&sig).is_ok());
+
+    // the malleated signature with `s` negated
+    let compact = sig.serialize_compact();
+    let mut s = SecretKey::from_slice(&compact[32..]).unwrap();
+    s.negate_assign();
+    let high_s = Signature::from_compact(&[&compact[..32], &s[..]].concat()).unwrap();
+    assert!(matches!(
+        Bitcoin::validate_signature(&high_s),
+        Err(consensus::Error::InvalidSignatureEncoding)
+    ));
+
+    // decoding the datum off the wire validates the signature
+    let wrap = |sig| datum::Signature::<Bitcoin>::new(TxId::Cancel, SwapRole::Bob, sig);
+    let bytes = serialize(&wrap(SignatureType::Regular(sig)));
+    let decoded: datum::Signature<Bitcoin> = deserialize(&bytes).unwrap();
+    assert_eq!(decoded.signature().try_into_regular().unwrap(), sig);
+    let bytes = serialize(&wrap(SignatureType::Regular(high_s)));
+    assert!(matches!(
+        deserialize::<datum::Signature<Bitcoin>>(&bytes),
+        Err(consensus::Error::InvalidSignatureEncoding)
+    ));
+    // the pre-signature of an adaptor signature is not normalized
+    let signer = PrivateKey {
+        compressed: true,
+        network: bitcoin::Network::Bitcoin,
+        key: privkey,
+    };
+    let point = PublicKey::from_private_key(
+        &secp,
+        &PrivateKey {
+            key: SecretKey::from_slice(&[0x02; 32]).unwrap(),
+            ..signer
+        },
+    );
+    let adaptor_sig = (1u8..)
+        .map(|byte| {
+            let msg = Message::from_slice(&[byte; 32]).unwrap();
+            ECDSAAdaptorSig::sign(&signer, &point, &msg).unwrap()
+        })
+        .find(|adaptor_sig| Bitcoin::validate_signature(&adaptor_sig.sig).is_err())
+        .unwrap();
+    let bytes = serialize(&wrap(SignatureType::Adaptor(adaptor_sig.clone())));
+    let decoded: datum::Signature<Bitcoin> = deserialize(&bytes).unwrap();
+    assert_eq!(decoded.signature().try_into_adaptor().unwrap(), adaptor_sig);
+    assert_eq!(
+        SignatureType::<Bitcoin>::Adaptor(adaptor_sig.clone())
+            .try_into_adaptor()
+            .unwrap(),
+        adaptor_sig
+    );
+    // but the signature adapted from it must be low-S
+    let bytes = serialize(&wrap(SignatureType::Adapted(high_s)));
+    assert!(matches!(
+        deserialize::<datum::Signature<Bitcoin>>(&bytes),
+        Err(consensus::Error::InvalidSignatureEncoding)
+    ));
+
+    // DER signatures must be canonical and low-S
+    let der = sig.serialize_der();
+    assert_eq!(signature_from_der(&der[..]).unwrap(), sig);
+    assert!(signature_from_der(&high_s.serialize_der()[..]).is_err());
+    // a zero padded `r` is valid BER but not canonical DER
+    let mut padded = vec![0x30, der[1] + 1, 0x02, der[3] + 1, 0x00];
+    padded.extend_from_slice(&der[4..]);
+    assert!(matches!(
+        signature_from_der(&padded),
+        Err(consensus::Error::InvalidSignatureEncoding)
+    ));
+}
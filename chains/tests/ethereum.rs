@@ -0,0 +1,183 @@
+#![cfg(feature = "ethereum")]
+
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::util::key::{PrivateKey, PublicKey};
+
+use farcaster_chains::ethereum::{
+    deposit_calldata, selector, AdaptorSignature, Address, Amount, BlockTimelock, Cancel,
+    ContractCall, Ethereum, Funding, GasPrice, Lock, Signature, SignedCall, Tx, MAX_CALL_DATA_LEN,
+    MAX_CALL_SIGNATURES, SWAP_CONTRACT,
+};
+
+use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy, Network};
+use farcaster_core::crypto::{self, Signatures};
+use farcaster_core::script::{DataLock, DataPunishableLock, DoubleKeys};
+use farcaster_core::transaction::{
+    Broadcastable, Cancelable, Chainable, Error, Forkable, Fundable, Linkable, Lockable, Signable,
+    Transaction, Witnessable,
+};
+
+use strict_encoding::{strict_deserialize, strict_serialize};
+
+use std::str::FromStr;
+
+fn privkey(byte: u8) -> PrivateKey {
+    let mut secret = [0u8; 32];
+    secret[31] = byte;
+    PrivateKey {
+        compressed: true,
+        network: bitcoin::Network::Bitcoin,
+        key: SecretKey::from_slice(&secret).unwrap(),
+    }
+}
+
+fn pubkey(byte: u8) -> PublicKey {
+    PublicKey::from_private_key(&Secp256k1::new(), &privkey(byte))
+}
+
+#[test]
+fn ethereum_addresses_and_selectors() {
+    // well known account of the private key 1
+    let address = Address::from_pubkey(&pubkey(1));
+    assert_eq!(
+        address,
+        Address::from_str("0x7e5f4552091a69125d5dfcb7b8c2659029395bdf").unwrap()
+    );
+    assert_eq!(
+        address.to_string(),
+        "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+    );
+    assert_eq!(
+        selector("transfer(address,uint256)"),
+        [0xa9, 0x05, 0x9c, 0xbb]
+    );
+}
+
+#[test]
+fn contract_calls_follow_the_swap() {
+    let (alice, bob) = (pubkey(1), pubkey(2));
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    let address = funding.get_address().unwrap();
+
+    // only a deposit credited to the funding address is accepted
+    let deposit = SignedCall {
+        to: SWAP_CONTRACT,
+        value: Amount::from_gwei(1_000_000),
+        gas_limit: 50_000,
+        gas_price: GasPrice::from_gwei(10),
+        data: deposit_calldata(&address),
+    };
+    let other = SignedCall {
+        data: deposit_calldata(&Address::from_pubkey(&alice)),
+        ..deposit.clone()
+    };
+    assert!(matches!(funding.update(other), Err(Error::WrongTemplate)));
+    funding.update(deposit).unwrap();
+
+    let datalock = DataLock {
+        timelock: BlockTimelock::new(100),
+        success: DoubleKeys::new(alice, bob),
+        failure: DoubleKeys::new(alice, bob),
+    };
+    let too_much = Amount::from_gwei(1_000_001);
+    assert!(matches!(
+        Tx::<Lock>::initialize(&funding, datalock.clone(), too_much),
+        Err(Error::NotEnoughAssets)
+    ));
+    let mut lock =
+        Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_gwei(1_000_000)).unwrap();
+    lock.verify_template(datalock.clone()).unwrap();
+    assert_eq!(lock.based_on(), funding.get_consumable_output().unwrap());
+
+    // the gas price follows the fee strategy
+    let strategy = FeeStrategy::Fixed(GasPrice::from_gwei(20));
+    let fee = Ethereum::set_fee(lock.partial_mut(), &strategy, FeePolitic::Normal).unwrap();
+    assert_eq!(fee.as_gwei(), 20 * lock.partial().gas_limit);
+    assert!(Ethereum::validate_fee(lock.partial(), &strategy).unwrap());
+
+    // the lock is authorized by the funding key only
+    let sig = lock.generate_witness(&privkey(1)).unwrap();
+    assert!(matches!(
+        lock.add_witness(bob, sig),
+        Err(Error::UnexpectedSignature)
+    ));
+    let sig = lock.generate_witness(&privkey(2)).unwrap();
+    lock.verify_witness(&bob, sig).unwrap();
+    lock.add_witness(bob, sig).unwrap();
+    let lock_tx = lock.finalize_and_extract().unwrap();
+    assert_eq!(lock_tx.data.len(), lock.partial().data.len() + 3 * 32);
+
+    let punish_lock = DataPunishableLock {
+        timelock: BlockTimelock::new(50),
+        success: DoubleKeys::new(alice, bob),
+        failure: alice,
+    };
+    let mut cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock.clone(),
+        punish_lock.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    cancel.verify_template(datalock, punish_lock).unwrap();
+    cancel.is_build_on_top_of(&lock).unwrap();
+    assert_eq!(cancel.output_amount(), Amount::from_gwei(1_000_000));
+
+    // both participants sign the failure path
+    let alice_sig = cancel.generate_failure_witness(&privkey(1)).unwrap();
+    cancel.verify_failure_witness(&alice, alice_sig).unwrap();
+    cancel.add_witness(alice, alice_sig).unwrap();
+    assert!(matches!(
+        cancel.finalize_and_extract(),
+        Err(Error::MissingSignature)
+    ));
+    let bob_sig = cancel.generate_failure_witness(&privkey(2)).unwrap();
+    cancel.add_witness(bob, bob_sig).unwrap();
+    let cancel_tx = cancel.finalize_and_extract().unwrap();
+    assert_eq!(cancel_tx.data.len(), cancel.partial().data.len() + 6 * 32);
+
+    // partial and finalized calls round-trip their strict encoding
+    let partial = cancel.to_partial();
+    let decoded: ContractCall = strict_deserialize(&strict_serialize(&partial).unwrap()).unwrap();
+    assert_eq!(decoded, partial);
+    assert_eq!(
+        Tx::<Cancel>::from_partial(decoded)
+            .to_unsigned_partial()
+            .signatures,
+        vec![]
+    );
+    // the call data and the signatures are bounded when decoding
+    let mut too_many = partial.clone();
+    too_many.signatures.push(alice_sig);
+    assert_eq!(too_many.signatures.len(), MAX_CALL_SIGNATURES + 1);
+    assert!(strict_deserialize::<ContractCall>(&strict_serialize(&too_many).unwrap()).is_err());
+    let too_long = ContractCall {
+        data: vec![0x00; MAX_CALL_DATA_LEN + 1],
+        ..partial.clone()
+    };
+    assert!(strict_deserialize::<ContractCall>(&strict_serialize(&too_long).unwrap()).is_err());
+    let decoded: SignedCall = strict_deserialize(&strict_serialize(&cancel_tx).unwrap()).unwrap();
+    assert_eq!(decoded, cancel_tx);
+    assert_eq!(decoded.id(), cancel_tx.id());
+}
+
+#[test]
+fn adaptor_signatures_are_unsupported() {
+    let msg = Message::from_slice(&[0x42; 32]).unwrap();
+    let sig = Signature(Secp256k1::new().sign_recoverable(&msg, &privkey(1).key));
+    let adaptor_sig = AdaptorSignature {
+        sig,
+        point: pubkey(2),
+    };
+
+    assert!(matches!(
+        Ethereum::adapt(&privkey(2), adaptor_sig.clone()),
+        Err(crypto::Error::UnsupportedOperation)
+    ));
+    assert!(matches!(
+        Ethereum::recover_secret(&adaptor_sig, &sig),
+        Err(crypto::Error::UnsupportedOperation)
+    ));
+}
@@ -0,0 +1,806 @@
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{OutPoint, SigHashType, TxIn, TxOut};
+use bitcoin::secp256k1::{Secp256k1, Signature};
+use bitcoin::util::key::PrivateKey;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+
+use farcaster_chains::bitcoin::fee::{
+    dust_limit, fee_vsize, set_fee_with_change, PathCost, SatPerVByte, SwapCostEstimate, DUST_LIMIT,
+};
+use farcaster_chains::bitcoin::transaction::{
+    self, Buy, Cancel, Funding, Lock, MetadataOutput, Refund, Tx,
+};
+use farcaster_chains::bitcoin::{Amount, Bitcoin};
+use farcaster_chains::pairs::btcxmr::BtcXmr;
+
+use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy, FeeStrategyError, Network};
+use farcaster_core::bundle;
+use farcaster_core::consensus::{self, deserialize, serialize};
+use farcaster_core::crypto::SignatureType;
+use farcaster_core::datum;
+use farcaster_core::protocol_message::CoreArbitratingSetup;
+use farcaster_core::role::SwapRole;
+use farcaster_core::transaction::{
+    Buyable, Cancelable, Error as TxError, Finalizable, Forkable, Fundable, Lockable, Refundable,
+    Transaction, TxId, Witnessable,
+};
+use farcaster_core::Error;
+
+use std::str::FromStr;
+
+mod common;
+use common::{funding, privkey, pubkey, punishable_lock, swap_lock};
+
+fn psbt(input_value: u64) -> PartiallySignedTransaction {
+    let tx = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: Script::default(),
+        }],
+    };
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+    psbt.inputs[0].witness_utxo = Some(TxOut {
+        value: input_value,
+        script_pubkey: Script::default(),
+    });
+    psbt
+}
+
+#[test]
+fn interpolated_fee_politic() {
+    let strategy = FeeStrategy::Range(SatPerVByte::from_sat(2)..SatPerVByte::from_sat(10));
+
+    for (fraction, rate) in [(0.0, 2), (0.5, 6), (1.0, 10), (1.5, 10), (-1.0, 2)].iter() {
+        let mut psbt = psbt(100_000);
+        let vsize = fee_vsize(&psbt);
+        let fee =
+            Bitcoin::set_fee(&mut psbt, &strategy, FeePolitic::Interpolated(*fraction)).unwrap();
+        assert_eq!(fee.as_sat(), rate * vsize);
+        assert_eq!(
+            psbt.global.unsigned_tx.output[0].value,
+            100_000 - rate * vsize
+        );
+    }
+}
+
+#[test]
+fn fee_absorbed_by_change_output() {
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(2));
+    let payment = TxOut {
+        value: 60_000,
+        script_pubkey: Script::default(),
+    };
+
+    let mut single = psbt(100_000);
+    Bitcoin::set_fee(&mut single, &strategy, FeePolitic::Aggressive).unwrap();
+    let mut explicit = psbt(100_000);
+    set_fee_with_change(&mut explicit, &strategy, FeePolitic::Aggressive, Some(0)).unwrap();
+    assert_eq!(single, explicit);
+
+    let mut with_change = psbt(100_000);
+    with_change
+        .global
+        .unsigned_tx
+        .output
+        .insert(0, payment.clone());
+    with_change.outputs.push(Default::default());
+    // without a change index multiple outputs are rejected
+    let mut multi = with_change.clone();
+    assert!(Bitcoin::set_fee(&mut multi, &strategy, FeePolitic::Aggressive).is_err());
+    assert!(set_fee_with_change(
+        &mut with_change.clone(),
+        &strategy,
+        FeePolitic::Aggressive,
+        Some(2)
+    )
+    .is_err());
+
+    let vsize = fee_vsize(&with_change);
+    let fee =
+        set_fee_with_change(&mut with_change, &strategy, FeePolitic::Aggressive, Some(1)).unwrap();
+    assert_eq!(fee.as_sat(), 2 * vsize);
+    assert_eq!(with_change.global.unsigned_tx.output[0], payment);
+    assert_eq!(
+        with_change.global.unsigned_tx.output[1].value,
+        40_000 - 2 * vsize
+    );
+
+    // change right at the dust limit is accepted, below is rejected
+    let mut at_dust = with_change.clone();
+    let input = 60_000 + DUST_LIMIT + fee.as_sat();
+    at_dust.inputs[0].witness_utxo.as_mut().unwrap().value = input;
+    set_fee_with_change(&mut at_dust, &strategy, FeePolitic::Aggressive, Some(1)).unwrap();
+    assert_eq!(at_dust.global.unsigned_tx.output[1].value, DUST_LIMIT);
+    at_dust.inputs[0].witness_utxo.as_mut().unwrap().value = input - 1;
+    assert!(matches!(
+        set_fee_with_change(&mut at_dust, &strategy, FeePolitic::Aggressive, Some(1)),
+        Err(FeeStrategyError::Other(_))
+    ));
+}
+
+#[test]
+fn reject_dust_output() {
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(2));
+    let pubkey = pubkey(1);
+    let p2wpkh = bitcoin::Address::p2wpkh(&pubkey, bitcoin::Network::Bitcoin)
+        .unwrap()
+        .script_pubkey();
+    let p2wsh = bitcoin::Address::p2wsh(&p2wpkh, bitcoin::Network::Bitcoin).script_pubkey();
+    let p2sh = bitcoin::Address::p2sh(&p2wpkh, bitcoin::Network::Bitcoin).script_pubkey();
+    assert_eq!(dust_limit(&p2wpkh), 294);
+    assert_eq!(dust_limit(&p2wsh), 330);
+    assert_eq!(dust_limit(&p2sh), 540);
+    assert_eq!(dust_limit(&Script::default()), DUST_LIMIT);
+
+    for script in [p2wpkh, p2wsh].iter() {
+        let limit = dust_limit(script);
+        let mut tx = psbt(0);
+        tx.global.unsigned_tx.output[0].script_pubkey = script.clone();
+        let fee = 2 * fee_vsize(&tx);
+
+        // an output right at the dust limit is accepted
+        tx.inputs[0].witness_utxo.as_mut().unwrap().value = limit + fee;
+        Bitcoin::set_fee(&mut tx, &strategy, FeePolitic::Aggressive).unwrap();
+        assert_eq!(tx.global.unsigned_tx.output[0].value, limit);
+
+        // one satoshi below is rejected and the output is left untouched
+        tx.inputs[0].witness_utxo.as_mut().unwrap().value = limit + fee - 1;
+        let err = Bitcoin::set_fee(&mut tx, &strategy, FeePolitic::Aggressive)
+            .unwrap_err()
+            .into_inner()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<transaction::Error>(),
+            Some(transaction::Error::ChangeBelowDust(value)) if *value == limit - 1
+        ));
+        assert_eq!(tx.global.unsigned_tx.output[0].value, limit);
+    }
+}
+
+#[test]
+fn fee_too_high_reports_max_rate() {
+    let mut tx = psbt(100_000);
+    let vsize = fee_vsize(&tx);
+
+    // the fee overflows for this size, the highest rate that fits is reported
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(u64::MAX));
+    let err = Bitcoin::set_fee(&mut tx, &strategy, FeePolitic::Aggressive).unwrap_err();
+    assert!(matches!(
+        err,
+        FeeStrategyError::AmountOfFeeTooHigh { max_sat_per_vbyte }
+            if max_sat_per_vbyte == u64::MAX / vsize
+    ));
+    assert!(matches!(
+        Error::from(err),
+        Error::FeeStrategy(FeeStrategyError::AmountOfFeeTooHigh { .. })
+    ));
+
+    // a fee above the strategy bound reports the bound
+    tx.global.unsigned_tx.output[0].value = 100_000 - 10 * vsize;
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(2));
+    assert!(matches!(
+        Bitcoin::validate_fee(&tx, &strategy),
+        Err(FeeStrategyError::AmountOfFeeTooHigh {
+            max_sat_per_vbyte: 2
+        })
+    ));
+}
+
+#[test]
+fn fee_politic_encoding() {
+    for politic in [
+        FeePolitic::Aggressive,
+        FeePolitic::Conservative,
+        FeePolitic::Normal,
+        FeePolitic::Interpolated(0.6),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            &deserialize::<FeePolitic>(&serialize(politic)).unwrap(),
+            politic
+        );
+    }
+    assert_eq!(serialize(&FeePolitic::Aggressive), vec![0x01]);
+    assert_eq!(serialize(&FeePolitic::Interpolated(0.0))[0], 0x03);
+    assert_eq!(serialize(&FeePolitic::Normal), vec![0x04]);
+}
+
+#[test]
+fn amount_wire_format() {
+    // satoshis as an unsigned 64-bit integer in little-endian byte order
+    let vectors: [(u64, [u8; 8]); 4] = [
+        (0, [0x00; 8]),
+        (1, [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+        (100000, [0xa0, 0x86, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]),
+        (u64::MAX, [0xff; 8]),
+    ];
+    for (sats, bytes) in vectors.iter() {
+        assert_eq!(serialize(&Amount::from_sat(*sats)), bytes.to_vec());
+        assert_eq!(
+            deserialize::<Amount>(&bytes[..]).unwrap(),
+            Amount::from_sat(*sats)
+        );
+        // fee rates share the amount layout
+        assert_eq!(serialize(&SatPerVByte::from_sat(*sats)), bytes.to_vec());
+    }
+
+    // short buffers are rejected
+    for len in 0..8 {
+        assert!(matches!(
+            deserialize::<Amount>(&[0xff; 8][..len]),
+            Err(consensus::Error::ParseFailed(_))
+        ));
+    }
+    assert!(deserialize::<SatPerVByte>(&[0x01; 7]).is_err());
+    // trailing bytes are rejected
+    assert!(deserialize::<Amount>(&[0x01; 9]).is_err());
+}
+
+#[test]
+fn normal_fee_politic() {
+    let vsize = fee_vsize(&psbt(100_000));
+
+    for (start, end, rate) in [(2, 10, 6), (3, 10, 6), (5, 5, 5)].iter() {
+        let strategy =
+            FeeStrategy::Range(SatPerVByte::from_sat(*start)..SatPerVByte::from_sat(*end));
+        let fee = Bitcoin::set_fee(&mut psbt(100_000), &strategy, FeePolitic::Normal).unwrap();
+        assert_eq!(fee.as_sat(), rate * vsize);
+    }
+
+    // fixed strategies are not affected by the politic
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(4));
+    let fee = Bitcoin::set_fee(&mut psbt(100_000), &strategy, FeePolitic::Normal).unwrap();
+    assert_eq!(fee.as_sat(), 4 * vsize);
+
+    // averaging the largest rates does not overflow
+    let max = SatPerVByte::from_sat(u64::MAX);
+    assert_eq!(SatPerVByte::midpoint(&max, &max), max);
+    assert_eq!(
+        SatPerVByte::midpoint(&SatPerVByte::from_sat(u64::MAX - 1), &max).as_sat(),
+        u64::MAX - 1
+    );
+}
+
+#[test]
+fn fee_strategy_string_roundtrip() {
+    let fixed = FeeStrategy::<SatPerVByte>::from_str("5").unwrap();
+    assert_eq!(fixed, FeeStrategy::Fixed(SatPerVByte::from_sat(5)));
+    assert_eq!(fixed.to_string(), "5");
+
+    let range = FeeStrategy::<SatPerVByte>::from_str("3-10").unwrap();
+    assert_eq!(
+        range,
+        FeeStrategy::Range(SatPerVByte::from_sat(3)..SatPerVByte::from_sat(10))
+    );
+    assert_eq!(range.to_string(), "3-10");
+    assert_eq!(FeeStrategy::from_str(&range.to_string()).unwrap(), range);
+    assert_eq!(FeeStrategy::from_str("3..10").unwrap(), range);
+    assert_eq!(SatPerVByte::from_sat(5).to_string(), "5");
+
+    assert!(FeeStrategy::<SatPerVByte>::from_str("10..3").is_err());
+    assert!(FeeStrategy::<SatPerVByte>::from_str("3..").is_err());
+    assert!(FeeStrategy::<SatPerVByte>::from_str("..10").is_err());
+    assert!(FeeStrategy::<SatPerVByte>::from_str("3..5..10").is_err());
+    assert!(FeeStrategy::<SatPerVByte>::from_str("10-3").is_err());
+    assert!(FeeStrategy::<SatPerVByte>::from_str("3-").is_err());
+    assert!(FeeStrategy::<SatPerVByte>::from_str("3-5-10").is_err());
+    assert!(FeeStrategy::<SatPerVByte>::from_str("three").is_err());
+}
+
+#[test]
+fn fee_strategy_containment() {
+    let fixed = |rate| FeeStrategy::Fixed(SatPerVByte::from_sat(rate));
+    let range =
+        |start, end| FeeStrategy::Range(SatPerVByte::from_sat(start)..SatPerVByte::from_sat(end));
+    let none = FeeStrategy::<SatPerVByte>::None;
+
+    // (offered, acceptable, is within)
+    let table = [
+        (fixed(5), fixed(5), true),
+        (fixed(5), fixed(6), false),
+        (fixed(5), range(2, 10), true),
+        (fixed(2), range(2, 10), true),
+        (fixed(10), range(2, 10), true),
+        (fixed(1), range(2, 10), false),
+        (fixed(11), range(2, 10), false),
+        (fixed(5), none.clone(), true),
+        (range(5, 5), fixed(5), true),
+        (range(4, 5), fixed(5), false),
+        (range(3, 8), range(2, 10), true),
+        (range(2, 10), range(2, 10), true),
+        (range(1, 8), range(2, 10), false),
+        (range(3, 11), range(2, 10), false),
+        (range(1, 11), range(2, 10), false),
+        (range(8, 3), range(2, 10), false),
+        (range(3, 8), range(10, 2), false),
+        (range(3, 8), none.clone(), true),
+        (none.clone(), fixed(5), false),
+        (none.clone(), range(2, 10), false),
+        (none.clone(), none.clone(), true),
+    ];
+    for (offered, acceptable, within) in table.iter() {
+        let msg = format!("{:?} within {:?}", offered, acceptable);
+        assert_eq!(offered.is_within(acceptable), *within, "{}", msg);
+    }
+}
+
+fn core_transactions(
+    lock_fee_rate: u64,
+    strategy: &FeeStrategy<SatPerVByte>,
+) -> bundle::CoreArbitratingTransactions<Bitcoin> {
+    let (alice, bob) = (pubkey(1), pubkey(2));
+
+    let funding = funding(bob);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+
+    // the lock pays the difference between the funding output and its target amount
+    let vsize = fee_vsize(
+        Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99_000))
+            .unwrap()
+            .partial(),
+    );
+    let target = Amount::from_sat(100_000 - lock_fee_rate * vsize);
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), target).unwrap();
+
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock.clone(),
+        strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let refund_address = bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+        .unwrap()
+        .into();
+    let refund = Tx::<Refund>::initialize(
+        &cancel,
+        punish_lock,
+        refund_address,
+        strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    bundle::CoreArbitratingTransactions {
+        lock: datum::Transaction::new_lock(lock.to_partial()),
+        cancel: datum::Transaction::new_cancel(cancel.to_partial()),
+        refund: datum::Transaction::new_refund(refund.to_partial()),
+    }
+}
+
+#[test]
+fn initialize_templates_with_fee() {
+    let (alice, bob) = (pubkey(1), pubkey(2));
+
+    let funding = funding(bob);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let address: farcaster_chains::bitcoin::Address =
+        bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+            .unwrap()
+            .into();
+    let lock =
+        Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(100_000)).unwrap();
+    let strategy = FeeStrategy::Range(SatPerVByte::from_sat(2)..SatPerVByte::from_sat(10));
+    let politic = FeePolitic::Aggressive;
+
+    // the output of the template is reduced by the fee at the rate selected by the politic
+    let cancel =
+        Tx::<Cancel>::initialize(&lock, datalock, punish_lock.clone(), &strategy, politic).unwrap();
+    let unsigned_tx = &cancel.partial().global.unsigned_tx;
+    let fee = 2 * fee_vsize(cancel.partial());
+    assert_eq!(unsigned_tx.output[0].value, 100_000 - fee);
+    assert!(Bitcoin::validate_fee(cancel.partial(), &strategy).unwrap());
+
+    let refund =
+        Tx::<Refund>::initialize(&cancel, punish_lock, address, &strategy, politic).unwrap();
+    let unsigned_tx = &refund.partial().global.unsigned_tx;
+    let refund_fee = 2 * fee_vsize(refund.partial());
+    assert_eq!(unsigned_tx.output[0].value, 100_000 - fee - refund_fee);
+    assert!(Bitcoin::validate_fee(refund.partial(), &strategy).unwrap());
+
+    // a template that cannot pay the fee is not created
+    let expensive = FeeStrategy::Fixed(SatPerVByte::from_sat(1_000));
+    assert!(matches!(
+        Tx::<Refund>::initialize(
+            &cancel,
+            punishable_lock(alice, bob),
+            bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+                .unwrap()
+                .into(),
+            &expensive,
+            politic,
+        ),
+        Err(Error::FeeStrategy(FeeStrategyError::NotEnoughAssets))
+    ));
+}
+
+#[test]
+fn core_arbitrating_setup_follows_fee_strategy() {
+    let strategy = FeeStrategy::Range(SatPerVByte::from_sat(1)..SatPerVByte::from_sat(10));
+    let sig = Signature::from_compact(&[0x01; 64]).unwrap();
+    let cancel_sig = bundle::CosignedArbitratingCancel {
+        cancel_sig: datum::Signature::new(TxId::Cancel, SwapRole::Bob, SignatureType::Regular(sig)),
+    };
+
+    let txs = core_transactions(2, &strategy);
+    assert!(CoreArbitratingSetup::<BtcXmr>::from_bundles(&txs, &cancel_sig, &strategy).is_ok());
+
+    // the lock pays more than the highest fee rate agreed upon
+    let txs = core_transactions(50, &strategy);
+    assert!(matches!(
+        CoreArbitratingSetup::<BtcXmr>::from_bundles(&txs, &cancel_sig, &strategy),
+        Err(Error::FeeStrategy(
+            FeeStrategyError::AmountOfFeeTooHigh { .. }
+        ))
+    ));
+}
+
+#[test]
+fn externally_funded_fee_strategy() {
+    let strategy = FeeStrategy::None;
+    let txs = core_transactions(0, &strategy);
+
+    // the full input value is kept on the output of each transaction
+    for tx in [txs.lock, txs.cancel, txs.refund].iter() {
+        let psbt = tx.tx().try_into_partial_transaction().unwrap();
+        assert_eq!(psbt.global.unsigned_tx.output[0].value, 100_000);
+        assert!(Bitcoin::validate_fee(&psbt, &strategy).unwrap());
+    }
+    let mut psbt = psbt(100_000);
+    let fee = Bitcoin::set_fee(&mut psbt, &strategy, FeePolitic::Conservative).unwrap();
+    assert_eq!(fee.as_sat(), 0);
+    assert_eq!(psbt.global.unsigned_tx.output[0].value, 100_000);
+
+    // any fee is accepted, while a fixed zero rate requires a zero fee
+    psbt.global.unsigned_tx.output[0].value = 90_000;
+    assert!(Bitcoin::validate_fee(&psbt, &strategy).unwrap());
+    let zero = FeeStrategy::Fixed(SatPerVByte::from_sat(0));
+    assert!(matches!(
+        Bitcoin::validate_fee(&psbt, &zero),
+        Err(FeeStrategyError::AmountOfFeeTooHigh { .. })
+    ));
+
+    // a distinct discriminant and string representation
+    assert_eq!(serialize(&strategy), vec![0x03]);
+    assert_eq!(
+        deserialize::<FeeStrategy<SatPerVByte>>(&[0x03]).unwrap(),
+        strategy
+    );
+    assert_eq!(strategy.to_string(), "none");
+    assert_eq!(
+        FeeStrategy::<SatPerVByte>::from_str("none").unwrap(),
+        strategy
+    );
+}
+
+#[test]
+fn swap_cost_estimate() {
+    let (alice, bob) = (pubkey(1), pubkey(2));
+
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    let funding_tx = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: 0,
+            witness: vec![vec![0x01; 72], vec![0x02; 33]],
+        }],
+        output: vec![TxOut {
+            value: 100_000,
+            script_pubkey: funding.get_address().unwrap().0.script_pubkey(),
+        }],
+    };
+    funding.update(funding_tx.clone()).unwrap();
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let address: farcaster_chains::bitcoin::Address =
+        bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+            .unwrap()
+            .into();
+    let lock =
+        Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99_000)).unwrap();
+    let buy = Tx::<Buy>::initialize(&lock, datalock.clone(), address.clone()).unwrap();
+    let mut cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let refund = Tx::<Refund>::initialize(
+        &cancel,
+        punish_lock,
+        address,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    // a single P2WPKH input and a P2WSH output, with the largest signature
+    assert_eq!(lock.estimated_vsize(), 122);
+    let unsigned = cancel.partial().global.unsigned_tx.get_weight() as u64 / 4;
+    let estimated = cancel.estimated_vsize();
+    assert!(estimated > unsigned);
+    // the finalized witness is measured and is never larger than the estimate
+    for (byte, key) in [(1, alice), (2, bob)].iter() {
+        let sig = cancel.generate_failure_witness(&privkey(*byte)).unwrap();
+        cancel.add_witness(*key, sig).unwrap();
+    }
+    cancel.finalize().unwrap();
+    assert!(cancel.estimated_vsize() <= estimated);
+    assert!(cancel.estimated_vsize() + 2 >= estimated);
+
+    let strategy = FeeStrategy::Range(SatPerVByte::from_sat(2)..SatPerVByte::from_sat(10));
+    let estimate = SwapCostEstimate::new(
+        &strategy,
+        FeePolitic::Conservative,
+        &funding_tx,
+        &lock,
+        &buy,
+        &cancel,
+        &refund,
+    )
+    .unwrap();
+    let ids = |path: &PathCost| {
+        path.transactions
+            .iter()
+            .map(|cost| cost.tx)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        ids(&estimate.success),
+        vec![TxId::Funding, TxId::Lock, TxId::Buy]
+    );
+    assert_eq!(
+        ids(&estimate.failure),
+        vec![TxId::Funding, TxId::Lock, TxId::Cancel, TxId::Refund]
+    );
+    for path in [&estimate.success, &estimate.failure].iter() {
+        for cost in path.transactions.iter() {
+            assert_eq!(cost.fee.as_sat(), 10 * cost.vsize);
+        }
+        let vsize: u64 = path.transactions.iter().map(|cost| cost.vsize).sum();
+        assert_eq!(path.vsize, vsize);
+        assert_eq!(path.fee.as_sat(), 10 * vsize);
+    }
+    assert_eq!(
+        estimate.success.transactions[1].vsize,
+        lock.estimated_vsize()
+    );
+    assert!(estimate.failure.vsize > estimate.success.vsize);
+
+    // the estimated fees are the fees set on the templates
+    let set_fee = |mut psbt: PartiallySignedTransaction| {
+        Bitcoin::set_fee(&mut psbt, &strategy, FeePolitic::Conservative).unwrap()
+    };
+    assert_eq!(
+        estimate.success.transactions[2].fee,
+        set_fee(buy.to_partial())
+    );
+    assert_eq!(
+        estimate.failure.transactions[2].fee,
+        set_fee(cancel.to_partial())
+    );
+    assert_eq!(
+        estimate.failure.transactions[3].fee,
+        set_fee(refund.to_partial())
+    );
+}
+
+#[test]
+fn refund_fee_bump_anchor() {
+    let (alice, bob) = (pubkey(1), pubkey(2));
+
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    let funding_address = funding.get_address().unwrap();
+    funding
+        .update(bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey: funding_address.0.script_pubkey(),
+            }],
+        })
+        .unwrap();
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let address: farcaster_chains::bitcoin::Address =
+        bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+            .unwrap()
+            .into();
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(100_000));
+    let cancel = Tx::<Cancel>::initialize(
+        &lock.unwrap(),
+        datalock,
+        punish_lock.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    // the anchor is opt-in
+    let refund = Tx::<Refund>::initialize(
+        &cancel,
+        punish_lock.clone(),
+        address.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    assert!(refund.get_anchor_output().is_none());
+
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(2));
+    let refund = Tx::<Refund>::initialize_with_anchor(
+        &cancel,
+        punish_lock,
+        address,
+        &strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let anchor = refund.get_anchor_output().unwrap();
+    assert_eq!(anchor.out_point.vout, 1);
+    assert!(anchor.tx_out.value >= dust_limit(&anchor.tx_out.script_pubkey));
+    assert!(transaction::is_anchor_output(refund.partial(), 1));
+    assert!(!transaction::is_anchor_output(refund.partial(), 0));
+
+    // the fee is deducted from the refund output only
+    let fee = 2 * fee_vsize(refund.partial());
+    let outputs = &refund.partial().global.unsigned_tx.output;
+    assert_eq!(outputs[0].value + fee, 100_000 - anchor.tx_out.value);
+    assert_eq!(outputs[1].value, anchor.tx_out.value);
+    assert!(Bitcoin::validate_fee(refund.partial(), &strategy).unwrap());
+
+    // either participant can spend the anchor in a child transaction
+    let anchor = refund.get_anchor_output().unwrap();
+    assert_anchor_spendable(&anchor, &privkey(1), &privkey(2), &privkey(3));
+}
+
+/// Spends the anchor output in a child transaction signed with `privkey`.
+fn spend_anchor(anchor: &MetadataOutput, privkey: &PrivateKey) -> bitcoin::Transaction {
+    let secp = Secp256k1::new();
+    let script = anchor.script_pubkey.clone().unwrap();
+    let mut child = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: anchor.out_point,
+            script_sig: Script::default(),
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: Script::default(),
+        }],
+    };
+    let sig = transaction::sign_input(
+        &secp,
+        transaction::TxInRef::new(&child, 0),
+        &script,
+        anchor.tx_out.value,
+        SigHashType::All,
+        &privkey.key,
+    )
+    .unwrap();
+    let mut full_sig = sig.serialize_der().to_vec();
+    full_sig.push(SigHashType::All.as_u32() as u8);
+    child.input[0].witness = vec![vec![], full_sig, script.to_bytes()];
+    child
+}
+
+/// Checks with the consensus verifier that the anchor is spendable with either participant key
+/// and not with an unrelated key.
+fn assert_anchor_spendable(
+    anchor: &MetadataOutput,
+    first: &PrivateKey,
+    second: &PrivateKey,
+    other: &PrivateKey,
+) {
+    let verify = |privkey| {
+        anchor.tx_out.script_pubkey.verify(
+            0,
+            anchor.tx_out.value,
+            &bitcoin::consensus::encode::serialize(&spend_anchor(anchor, privkey)),
+        )
+    };
+    assert!(verify(first).is_ok());
+    assert!(verify(second).is_ok());
+    assert!(verify(other).is_err());
+}
+
+#[test]
+fn buy_fee_bump_anchor() {
+    let (alice, bob) = (pubkey(1), pubkey(2));
+
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    let funding_address = funding.get_address().unwrap();
+    funding
+        .update(bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey: funding_address.0.script_pubkey(),
+            }],
+        })
+        .unwrap();
+    let datalock = swap_lock(alice, bob);
+    let address: farcaster_chains::bitcoin::Address =
+        bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+            .unwrap()
+            .into();
+    let lock =
+        Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(100_000)).unwrap();
+
+    // the anchor is opt-in and both shapes match the template
+    let buy = Tx::<Buy>::initialize(&lock, datalock.clone(), address.clone()).unwrap();
+    assert!(buy.get_anchor_output().is_none());
+    buy.verify_template(datalock.clone(), address.clone())
+        .unwrap();
+
+    let mut buy =
+        Tx::<Buy>::initialize_with_anchor(&lock, datalock.clone(), address.clone()).unwrap();
+    buy.verify_template(datalock.clone(), address.clone())
+        .unwrap();
+    let anchor = buy.get_anchor_output().unwrap();
+    assert_eq!(anchor.out_point.vout, 1);
+    assert_eq!(
+        anchor.tx_out.value,
+        dust_limit(&anchor.tx_out.script_pubkey)
+    );
+    assert!(transaction::is_anchor_output(buy.partial(), 1));
+
+    // the fee is deducted from the destination output only
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(2));
+    let fee = Bitcoin::set_fee(buy.partial_mut(), &strategy, FeePolitic::Aggressive).unwrap();
+    let outputs = &buy.partial().global.unsigned_tx.output;
+    assert_eq!(
+        outputs[0].value + fee.as_sat(),
+        100_000 - anchor.tx_out.value
+    );
+    assert_eq!(outputs[1].value, anchor.tx_out.value);
+    assert!(Bitcoin::validate_fee(buy.partial(), &strategy).unwrap());
+    buy.verify_template(datalock.clone(), address.clone())
+        .unwrap();
+
+    // an anchor holding more than its dust limit is rejected
+    let mut inflated = buy.clone();
+    inflated.partial_mut().global.unsigned_tx.output[1].value += 1;
+    assert!(matches!(
+        inflated.verify_template(datalock, address),
+        Err(TxError::WrongTemplate)
+    ));
+
+    // either participant can spend the anchor in a child transaction
+    let anchor = buy.get_anchor_output().unwrap();
+    assert_anchor_spendable(&anchor, &privkey(1), &privkey(2), &privkey(3));
+}
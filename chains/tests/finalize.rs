@@ -0,0 +1,1242 @@
+use bitcoin::blockdata::script::{Builder, Instruction, Script};
+use bitcoin::blockdata::transaction::{OutPoint, TxIn, TxOut};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::{Message, Secp256k1, Signature};
+use bitcoin::util::key::PublicKey;
+use bitcoin::Address;
+
+use farcaster_chains::bitcoin::fee::{fee_vsize, SatPerVByte};
+use farcaster_chains::bitcoin::transaction::cancel::finalize_punish_lock;
+use farcaster_chains::bitcoin::transaction::funding::AddressType;
+use farcaster_chains::bitcoin::transaction::lock::{
+    finalize_swaplock, parse_swaplock, parse_swaplock_keys, swaplock_script_with,
+};
+use farcaster_chains::bitcoin::transaction::{
+    Buy, Cancel, Error as BtcError, Funding, KeyOrdering, Lock, MetadataOutput, Refund, Tx,
+};
+use farcaster_chains::bitcoin::{Amount, Bitcoin, CSVTimelock};
+
+use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy, FeeStrategyError, Network};
+use farcaster_core::consensus;
+use farcaster_core::script::{DataLock, DataPunishableLock, DoubleKeys, ScriptPath};
+use farcaster_core::transaction::{
+    Buyable, Cancelable, Error, Finalizable, Forkable, Fundable, Linkable, Lockable, Refundable,
+    Signable, Transaction, Witnessable,
+};
+
+use strict_encoding::{strict_deserialize, strict_serialize};
+
+use std::convert::TryFrom;
+
+use std::str::FromStr;
+
+mod common;
+use common::{funding, privkey, pubkey, punishable_lock, swap_lock};
+
+#[test]
+fn metadata_output_strict_roundtrip() {
+    let bob = pubkey(2);
+    let datalock = swap_lock(pubkey(1), bob);
+    let lock = Tx::<Lock>::initialize(&funding(bob), datalock, Amount::from_sat(99000)).unwrap();
+    let output = lock.get_consumable_output().unwrap();
+    assert!(output.script_pubkey.is_some());
+
+    let bytes = strict_serialize(&output).unwrap();
+    // txid, vout, value, script pubkey, witness script, no redeem script
+    let script = output.script_pubkey.as_ref().unwrap().len();
+    assert_eq!(bytes.len(), 32 + 4 + 8 + 1 + 34 + 1 + 1 + script + 1);
+    assert_eq!(bytes[bytes.len() - 1], 0x00);
+    let decoded: MetadataOutput = strict_deserialize(&bytes).unwrap();
+    assert_eq!(decoded, output);
+
+    // a P2SH-wrapped output keeps its redeem script
+    let wrapped = MetadataOutput {
+        script_pubkey: None,
+        redeem_script: Some(Script::from(vec![0x00, 0x14])),
+        ..output
+    };
+    let bytes = strict_serialize(&wrapped).unwrap();
+    assert_eq!(
+        strict_deserialize::<MetadataOutput>(&bytes).unwrap(),
+        wrapped
+    );
+
+    // unknown presence flags and truncated data are rejected
+    let mut invalid = bytes.clone();
+    invalid[32 + 4 + 8 + 1 + 34] = 0x02;
+    assert!(strict_deserialize::<MetadataOutput>(&invalid).is_err());
+    assert!(strict_deserialize::<MetadataOutput>(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn finalize_punishable_lock_through_both_paths() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let punisher = pubkey(3);
+
+    let funding = funding(bob);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = DataPunishableLock {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(alice, bob),
+        failure: punisher,
+    };
+
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let refund_address = Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+        .expect("Parsable address")
+        .into();
+    let refund = Tx::<Refund>::initialize(
+        &cancel,
+        punish_lock,
+        refund_address,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    let mut psbt = refund.to_partial();
+    psbt.inputs[0].partial_sigs.insert(alice, vec![0xaa, 0x01]);
+    psbt.inputs[0].partial_sigs.insert(bob, vec![0xbb, 0x01]);
+    psbt.inputs[0]
+        .partial_sigs
+        .insert(punisher, vec![0xcc, 0x01]);
+    let script = psbt.inputs[0].witness_script.clone().unwrap().into_bytes();
+
+    let mut consensus = psbt.clone();
+    finalize_punish_lock(&mut consensus, ScriptPath::Success).unwrap();
+    assert_eq!(
+        consensus.inputs[0].final_script_witness,
+        Some(vec![
+            vec![],
+            vec![0xaa, 0x01],
+            vec![0xbb, 0x01],
+            vec![1],
+            script.clone()
+        ])
+    );
+
+    let mut timelock = psbt.clone();
+    finalize_punish_lock(&mut timelock, ScriptPath::Failure).unwrap();
+    assert_eq!(
+        timelock.inputs[0].final_script_witness,
+        Some(vec![vec![0xcc, 0x01], vec![], script])
+    );
+
+    // missing signatures
+    psbt.inputs[0].partial_sigs.remove(&bob);
+    assert!(finalize_punish_lock(&mut psbt.clone(), ScriptPath::Success).is_err());
+    psbt.inputs[0].partial_sigs.remove(&punisher);
+    assert!(finalize_punish_lock(&mut psbt, ScriptPath::Failure).is_err());
+}
+
+#[test]
+fn finalize_swaplock_resolves_signatures_by_role() {
+    // keys in both orders, the first key is always Alice's
+    for (alice, bob) in [(pubkey(1), pubkey(2)), (pubkey(2), pubkey(1))].iter() {
+        let datalock = swap_lock(*alice, *bob);
+        let lock =
+            Tx::<Lock>::initialize(&funding(*bob), datalock.clone(), Amount::from_sat(99000))
+                .unwrap();
+        let punish_lock = punishable_lock(*alice, *bob);
+        let mut cancel = Tx::<Cancel>::initialize(
+            &lock,
+            datalock.clone(),
+            punish_lock,
+            &FeeStrategy::None,
+            FeePolitic::Aggressive,
+        )
+        .unwrap();
+
+        let mut psbt = cancel.partial().clone();
+        psbt.inputs[0].partial_sigs.insert(*bob, vec![0xbb, 0x01]);
+        psbt.inputs[0].partial_sigs.insert(*alice, vec![0xaa, 0x01]);
+        let script = psbt.inputs[0].witness_script.clone().unwrap().into_bytes();
+        let expected = Some(vec![
+            vec![],
+            vec![0xaa, 0x01],
+            vec![0xbb, 0x01],
+            vec![],
+            script,
+        ]);
+
+        let mut finalized = psbt.clone();
+        finalize_swaplock(&mut finalized, &datalock, ScriptPath::Failure).unwrap();
+        assert_eq!(finalized.inputs[0].final_script_witness, expected);
+
+        *cancel.partial_mut() = psbt.clone();
+        cancel.finalize().unwrap();
+        assert_eq!(cancel.partial().inputs[0].final_script_witness, expected);
+
+        // roles swapped do not match the witness script
+        let swapped = swap_lock(*bob, *alice);
+        assert!(matches!(
+            finalize_swaplock(&mut psbt, &swapped, ScriptPath::Failure),
+            Err(Error::WrongTemplate)
+        ));
+    }
+}
+
+#[test]
+fn lexicographic_key_ordering() {
+    for (alice, bob) in [(pubkey(1), pubkey(2)), (pubkey(2), pubkey(1))].iter() {
+        let datalock = swap_lock(*alice, *bob);
+        let punish_lock = punishable_lock(*alice, *bob);
+        let (first, second) = match alice.to_bytes() < bob.to_bytes() {
+            true => (*alice, *bob),
+            false => (*bob, *alice),
+        };
+
+        // role-based remains the default and is not recorded
+        let role_based =
+            Tx::<Lock>::initialize(&funding(*bob), datalock.clone(), Amount::from_sat(99000))
+                .unwrap();
+        assert!(role_based.partial().global.unknown.is_empty());
+
+        let lock = Tx::<Lock>::initialize_with_ordering(
+            &funding(*bob),
+            datalock.clone(),
+            Amount::from_sat(99000),
+            KeyOrdering::Lexicographic,
+        )
+        .unwrap();
+        assert_eq!(
+            KeyOrdering::recorded(lock.partial()).unwrap(),
+            KeyOrdering::Lexicographic
+        );
+        lock.verify_template(datalock.clone()).unwrap();
+        role_based.verify_template(datalock.clone()).unwrap();
+        let script = lock.partial().outputs[0].witness_script.clone().unwrap();
+        assert_eq!(parse_swaplock_keys(&script).unwrap(), (first, second));
+
+        // the cancel follows the ordering recorded on the lock
+        let mut cancel = Tx::<Cancel>::initialize(
+            &lock,
+            datalock.clone(),
+            punish_lock,
+            &FeeStrategy::None,
+            FeePolitic::Aggressive,
+        )
+        .unwrap();
+        assert_eq!(
+            KeyOrdering::recorded(cancel.partial()).unwrap(),
+            KeyOrdering::Lexicographic
+        );
+        let punish_script = cancel.partial().outputs[0].witness_script.clone().unwrap();
+        let pushed: Vec<_> = punish_script
+            .instructions()
+            .skip(2)
+            .take(2)
+            .map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(bytes)) => bytes.to_vec(),
+                _ => vec![],
+            })
+            .collect();
+        assert_eq!(pushed, vec![first.to_bytes(), second.to_bytes()]);
+
+        // signatures are placed in the order of the keys in the script
+        let mut psbt = cancel.partial().clone();
+        psbt.inputs[0].partial_sigs.insert(*alice, vec![0xaa, 0x01]);
+        psbt.inputs[0].partial_sigs.insert(*bob, vec![0xbb, 0x01]);
+        let sig = |key: &PublicKey| psbt.inputs[0].partial_sigs[key].clone();
+        let expected = Some(vec![
+            vec![],
+            sig(&first),
+            sig(&second),
+            vec![],
+            script.into_bytes(),
+        ]);
+        let mut finalized = psbt.clone();
+        finalize_swaplock(&mut finalized, &datalock, ScriptPath::Failure).unwrap();
+        assert_eq!(finalized.inputs[0].final_script_witness, expected);
+        *cancel.partial_mut() = psbt;
+        cancel.finalize().unwrap();
+        assert_eq!(cancel.partial().inputs[0].final_script_witness, expected);
+    }
+}
+
+#[test]
+fn parse_swaplock_recreates_the_lock() {
+    // timelocks pushed as an empty push, a small integer opcode, and a script number
+    for timelock in [0, 16, 1000].iter() {
+        let datalock = DataLock {
+            timelock: CSVTimelock::new(*timelock),
+            success: DoubleKeys::new(pubkey(2), pubkey(1)),
+            failure: DoubleKeys::new(pubkey(4), pubkey(3)),
+        };
+        let script = swaplock_script_with(&datalock, KeyOrdering::RoleBased);
+        let parsed = parse_swaplock(&script).unwrap();
+        assert_eq!(parsed.timelock, datalock.timelock);
+        assert_eq!(
+            (parsed.success.alice, parsed.success.bob),
+            (pubkey(2), pubkey(1))
+        );
+        assert_eq!(
+            (parsed.failure.alice, parsed.failure.bob),
+            (pubkey(4), pubkey(3))
+        );
+
+        // sorted keys are returned as pushed and sort to the same script
+        let script = swaplock_script_with(&datalock, KeyOrdering::Lexicographic);
+        let parsed = parse_swaplock(&script).unwrap();
+        assert_eq!(
+            swaplock_script_with(&parsed, KeyOrdering::Lexicographic),
+            script
+        );
+        assert_eq!(parsed.timelock, datalock.timelock);
+    }
+}
+
+#[test]
+fn finalize_buy_resolves_signatures_by_role() {
+    let (alice, bob) = (pubkey(2), pubkey(1));
+    let datalock = swap_lock(alice, bob);
+    let destination = Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk").unwrap();
+    for ordering in [KeyOrdering::RoleBased, KeyOrdering::Lexicographic].iter() {
+        let lock = Tx::<Lock>::initialize_with_ordering(
+            &funding(bob),
+            datalock.clone(),
+            Amount::from_sat(99000),
+            *ordering,
+        )
+        .unwrap();
+        let mut buy =
+            Tx::<Buy>::initialize(&lock, datalock.clone(), destination.clone().into()).unwrap();
+        buy.partial_mut().inputs[0]
+            .partial_sigs
+            .insert(alice, vec![0xaa, 0x01]);
+        buy.partial_mut().inputs[0]
+            .partial_sigs
+            .insert(bob, vec![0xbb, 0x01]);
+
+        let mut expected = buy.partial().clone();
+        finalize_swaplock(&mut expected, &datalock, ScriptPath::Success).unwrap();
+        buy.finalize().unwrap();
+        assert_eq!(
+            buy.partial().inputs[0].final_script_witness,
+            expected.inputs[0].final_script_witness
+        );
+    }
+}
+
+#[test]
+fn rollback_failed_finalization() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let mut cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(alice, vec![0xaa, 0x01]);
+
+    let snapshot = cancel.clone();
+    assert!(cancel == snapshot);
+    assert!(cancel.finalize().is_err());
+    cancel = snapshot.clone();
+    assert!(cancel == snapshot);
+
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(bob, vec![0xbb, 0x01]);
+    assert!(cancel != snapshot);
+    cancel.finalize().unwrap();
+}
+
+#[test]
+fn clone_and_compare_templates() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+
+    // building twice the same template yields equal transactions
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock.clone(),
+        punish_lock.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let rebuilt = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    assert!(cancel == rebuilt);
+
+    // mutating a clone leaves the original untouched
+    let mut copy = cancel.clone();
+    assert!(copy == cancel);
+    copy.partial_mut().global.unsigned_tx.output[0].value -= 1;
+    assert!(copy != cancel);
+    assert_eq!(rebuilt.partial(), cancel.partial());
+}
+
+#[test]
+fn reject_mismatched_sighash_type() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let mut cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(alice, vec![0xaa, 0x01]);
+    // signed with SIGHASH_SINGLE while the input expects SIGHASH_ALL
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(bob, vec![0xbb, 0x03]);
+
+    let err = cancel.finalize().unwrap_err().into_inner().unwrap();
+    assert!(matches!(
+        err.downcast_ref::<BtcError>(),
+        Some(BtcError::SigHashTypeMismatch {
+            expected: 0x01,
+            found: 0x03
+        })
+    ));
+    assert_eq!(cancel.partial().inputs[0].final_script_witness, None);
+
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(bob, vec![0xbb, 0x01]);
+    cancel.finalize().unwrap();
+}
+
+#[test]
+fn finalize_cancel_with_exact_signatures() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let template = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    // missing signature
+    let mut cancel = template.clone();
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(alice, vec![0xaa, 0x01]);
+    assert!(matches!(cancel.finalize(), Err(Error::MissingSignature)));
+
+    // stray signature from a key absent of the script
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(bob, vec![0xbb, 0x01]);
+    let mut extra = cancel.clone();
+    extra.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(pubkey(3), vec![0xcc, 0x01]);
+    assert!(matches!(extra.finalize(), Err(Error::UnexpectedSignature)));
+    assert_eq!(extra.partial().inputs[0].final_script_witness, None);
+
+    // exactly the two multisig signatures
+    cancel.finalize().unwrap();
+    let script = template.partial().inputs[0]
+        .witness_script
+        .clone()
+        .unwrap()
+        .into_bytes();
+    assert_eq!(
+        cancel.partial().inputs[0].final_script_witness,
+        Some(vec![
+            vec![],
+            vec![0xaa, 0x01],
+            vec![0xbb, 0x01],
+            vec![],
+            script
+        ])
+    );
+}
+
+#[test]
+fn finalize_cancel_twice() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let mut cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    for (byte, key) in [(1, alice), (2, bob)].iter() {
+        let sig = cancel.generate_failure_witness(&privkey(*byte)).unwrap();
+        cancel.add_witness(*key, sig).unwrap();
+    }
+
+    // a retried finalization keeps the witness and succeeds
+    cancel.finalize().unwrap();
+    let finalized = cancel.clone();
+    cancel.finalize().unwrap();
+    assert!(cancel == finalized);
+
+    // an invalid witness already set is not silently accepted nor rebuilt
+    let mut witness = cancel.partial().inputs[0]
+        .final_script_witness
+        .clone()
+        .unwrap();
+    witness.swap(1, 2);
+    cancel.partial_mut().inputs[0].final_script_witness = Some(witness.clone());
+    assert!(cancel.finalize().is_err());
+    assert_eq!(
+        cancel.partial().inputs[0].final_script_witness,
+        Some(witness)
+    );
+}
+
+#[test]
+fn query_missing_signatures_of_half_signed_cancel() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = DataLock {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(pubkey(4), pubkey(5)),
+        failure: DoubleKeys::new(alice, bob),
+    };
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let mut cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    // only the failure path keys are required
+    assert_eq!(cancel.missing_signatures(), vec![alice, bob]);
+
+    let sig = cancel.generate_failure_witness(&privkey(1)).unwrap();
+    cancel.add_witness(alice, sig).unwrap();
+    assert!(cancel.has_signature(&alice));
+    assert!(!cancel.has_signature(&bob));
+    assert_eq!(cancel.missing_signatures(), vec![bob]);
+
+    let sig = cancel.generate_failure_witness(&privkey(2)).unwrap();
+    cancel.add_witness(bob, sig).unwrap();
+    assert!(cancel.missing_signatures().is_empty());
+    cancel.finalize().unwrap();
+}
+
+#[test]
+fn describe_half_signed_cancel() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = DataLock {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(pubkey(4), pubkey(5)),
+        failure: DoubleKeys::new(alice, bob),
+    };
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let mut cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let sig = cancel.generate_failure_witness(&privkey(1)).unwrap();
+    cancel.add_witness(alice, sig).unwrap();
+
+    let description = cancel.describe();
+    let lines: Vec<&str> = description.lines().collect();
+    let txid = cancel.partial().global.unsigned_tx.txid();
+    let outpoint = lock.get_consumable_output().unwrap().out_point;
+    assert_eq!(lines[0], format!("Cancel {}", txid));
+    assert_eq!(lines[1], "  version 2, lock time 0");
+    assert_eq!(
+        lines[2],
+        format!(
+            "  input 0: {} sequence 0x0000000a value 99000 sat",
+            outpoint
+        )
+    );
+    assert_eq!(lines[3], "  output 0: 99000 sat p2wsh");
+    assert_eq!(lines[4], format!("  signatures present: [{}]", alice));
+    assert_eq!(lines[5], format!("  signatures missing: [{}]", bob));
+    // the snapshot is deterministic
+    assert_eq!(description, cancel.clone().describe());
+
+    let sig = cancel.generate_failure_witness(&privkey(2)).unwrap();
+    cancel.add_witness(bob, sig).unwrap();
+    cancel.finalize().unwrap();
+    assert!(cancel.describe().ends_with("  signatures: finalized\n"));
+}
+
+#[test]
+fn verify_only_canonical_signatures() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    let sig = cancel.generate_failure_witness(&privkey(2)).unwrap();
+    cancel.verify_failure_witness(&bob, sig).unwrap();
+    let err = cancel
+        .verify_failure_witness(&alice, sig)
+        .unwrap_err()
+        .into_inner()
+        .unwrap();
+    assert!(matches!(
+        err.downcast_ref::<BtcError>(),
+        Some(BtcError::Secp256k1(_))
+    ));
+
+    // the malleated signature with `s` negated is valid but not relayed
+    let compact = sig.serialize_compact();
+    let mut s = SecretKey::from_slice(&compact[32..]).unwrap();
+    s.negate_assign();
+    let high_s = Signature::from_compact(&[&compact[..32], &s[..]].concat()).unwrap();
+    let err = cancel
+        .verify_failure_witness(&bob, high_s)
+        .unwrap_err()
+        .into_inner()
+        .unwrap();
+    assert!(matches!(
+        err.downcast_ref::<consensus::Error>(),
+        Some(consensus::Error::InvalidSignatureEncoding)
+    ));
+}
+
+#[test]
+fn strip_signatures_for_resharing() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let mut cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let template = cancel.partial().clone();
+
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(alice, vec![0xaa, 0x01]);
+    cancel.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(bob, vec![0xbb, 0x01]);
+    cancel.finalize().unwrap();
+    assert!(cancel.partial().inputs[0].final_script_witness.is_some());
+
+    let stripped = cancel.to_unsigned_partial();
+    assert!(stripped.inputs[0].partial_sigs.is_empty());
+    assert_eq!(stripped.inputs[0].final_script_witness, None);
+    assert!(stripped.inputs[0].witness_utxo.is_some());
+    assert!(stripped.inputs[0].witness_script.is_some());
+    assert_eq!(stripped, template);
+}
+
+#[test]
+fn cancel_links_to_its_lock() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let other_lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(98000)).unwrap();
+
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    cancel.check_links(&lock).unwrap();
+    assert!(matches!(
+        cancel.check_links(&other_lock),
+        Err(Error::InvalidLink)
+    ));
+
+    // the input amount must match the lock output amount
+    let mut tampered = cancel.clone();
+    tampered.partial_mut().inputs[0]
+        .witness_utxo
+        .as_mut()
+        .unwrap()
+        .value = 98000;
+    assert!(matches!(
+        tampered.check_links(&lock),
+        Err(Error::InvalidLink)
+    ));
+}
+
+#[test]
+fn transactions_consume_their_parent_output() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let funding = funding(bob);
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let other_lock =
+        Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(98000)).unwrap();
+    let funding_output = funding.get_consumable_output().unwrap();
+    assert!(lock.consumes(&funding_output));
+    assert!(!funding.consumes(&funding_output));
+
+    // a cancel built from the wrong lock does not consume the lock output
+    let lock_output = lock.get_consumable_output().unwrap();
+    let cancel = Tx::<Cancel>::initialize(
+        &other_lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    assert!(!cancel.consumes(&lock_output));
+    assert!(cancel.consumes(&other_lock.get_consumable_output().unwrap()));
+}
+
+#[test]
+fn p2sh_wrapped_funding() {
+    let bob = pubkey(2);
+    let native = Funding::initialize(bob, Network::Local).unwrap();
+    assert_eq!(native.address_type(), AddressType::P2wpkh);
+    assert_eq!(
+        native.get_address().unwrap().0,
+        Address::p2wpkh(&bob, bitcoin::Network::Regtest).unwrap()
+    );
+
+    let mut wrapped =
+        Funding::initialize_with_type(bob, Network::Local, AddressType::P2shwpkh).unwrap();
+    let address = wrapped.get_address().unwrap().0;
+    assert_eq!(
+        address,
+        Address::p2shwpkh(&bob, bitcoin::Network::Regtest).unwrap()
+    );
+
+    let funding_tx = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 100000,
+            script_pubkey: address.script_pubkey(),
+        }],
+    };
+    wrapped.update(funding_tx.clone()).unwrap();
+
+    // the type is detected from the output paid on-chain
+    let mut detected = Funding::initialize(bob, Network::Local).unwrap();
+    detected.update(funding_tx.clone()).unwrap();
+    assert_eq!(detected.address_type(), AddressType::P2shwpkh);
+    assert_eq!(
+        Funding::raw(funding_tx).unwrap().address_type(),
+        AddressType::P2shwpkh
+    );
+
+    let datalock = swap_lock(pubkey(1), bob);
+    let mut lock = Tx::<Lock>::initialize(&wrapped, datalock, Amount::from_sat(99000)).unwrap();
+    let witness_program = Address::p2wpkh(&bob, bitcoin::Network::Regtest)
+        .unwrap()
+        .script_pubkey();
+    assert_eq!(
+        lock.partial().inputs[0].redeem_script,
+        Some(witness_program.clone())
+    );
+
+    lock.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(bob, vec![0xaa, 0x01]);
+    lock.finalize().unwrap();
+    let input = &lock.partial().inputs[0];
+    assert_eq!(
+        input.final_script_sig,
+        Some(
+            Builder::new()
+                .push_slice(witness_program.as_bytes())
+                .into_script()
+        )
+    );
+    assert_eq!(
+        input.final_script_witness,
+        Some(vec![vec![0xaa, 0x01], bob.to_bytes()])
+    );
+
+    // native segwit funding leaves the input script empty
+    let datalock = swap_lock(pubkey(1), bob);
+    let mut lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock, Amount::from_sat(99000)).unwrap();
+    lock.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(bob, vec![0xaa, 0x01]);
+    lock.finalize().unwrap();
+    assert_eq!(lock.partial().inputs[0].final_script_sig, None);
+}
+
+#[test]
+fn funding_from_transaction() {
+    let bob = pubkey(2);
+    let txout = |value, address: Address| TxOut {
+        value,
+        script_pubkey: address.script_pubkey(),
+    };
+    let tx = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![
+            txout(
+                50000,
+                Address::p2wpkh(&pubkey(4), bitcoin::Network::Regtest).unwrap(),
+            ),
+            txout(
+                100000,
+                Address::p2wpkh(&bob, bitcoin::Network::Regtest).unwrap(),
+            ),
+            txout(
+                25000,
+                Address::p2shwpkh(&pubkey(5), bitcoin::Network::Regtest).unwrap(),
+            ),
+        ],
+    };
+
+    let funding = Funding::try_from((tx.clone(), bob, Network::Local)).unwrap();
+    assert_eq!(funding.address_type(), AddressType::P2wpkh);
+    let output = funding.get_consumable_output().unwrap();
+    assert_eq!(output.out_point, OutPoint::new(tx.txid(), 1));
+    assert_eq!(output.tx_out.value, 100000);
+
+    // the funding state can be used to build the lock
+    let datalock = swap_lock(pubkey(1), bob);
+    let lock = Tx::<Lock>::initialize(&funding, datalock, Amount::from_sat(99000)).unwrap();
+    assert_eq!(
+        lock.partial().global.unsigned_tx.input[0].previous_output,
+        output.out_point
+    );
+
+    // no output pays the funding address of the key
+    assert!(matches!(
+        Funding::try_from((tx, pubkey(3), Network::Local)),
+        Err(BtcError::NoMatchingOutput)
+    ));
+}
+
+#[test]
+fn lock_aggregates_funding_utxos() {
+    let bob = pubkey(2);
+    let funding_tx = |value| bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: value as u32,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value,
+            script_pubkey: Address::p2wpkh(&bob, bitcoin::Network::Regtest)
+                .unwrap()
+                .script_pubkey(),
+        }],
+    };
+    let (tx1, tx2) = (funding_tx(60000), funding_tx(50000));
+
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    funding.update(tx1.clone()).unwrap();
+    funding.add_utxo(tx2.clone()).unwrap();
+    // aggregating the same output twice has no effect
+    funding.add_utxo(tx2.clone()).unwrap();
+    let outputs = funding.get_consumable_outputs().unwrap();
+    assert_eq!(outputs.len(), 2);
+    assert_eq!(outputs[0].out_point, OutPoint::new(tx1.txid(), 0));
+    assert_eq!(outputs[1].out_point, OutPoint::new(tx2.txid(), 0));
+
+    // an output not paying the funding address cannot be aggregated
+    let mut other = funding_tx(10000);
+    other.output[0].script_pubkey = Script::default();
+    let err = funding.add_utxo(other).unwrap_err().into_inner().unwrap();
+    assert!(matches!(
+        err.downcast_ref::<BtcError>(),
+        Some(BtcError::NoMatchingOutput)
+    ));
+
+    // the lock spends all the aggregated outputs
+    let datalock = swap_lock(pubkey(1), bob);
+    let mut lock =
+        Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(109000)).unwrap();
+    lock.verify_template(datalock.clone()).unwrap();
+    let inputs = &lock.partial().global.unsigned_tx.input;
+    assert_eq!(inputs.len(), 2);
+    assert_eq!(inputs[1].previous_output, outputs[1].out_point);
+    assert_eq!(
+        lock.partial().inputs[1].witness_utxo,
+        Some(outputs[1].tx_out.clone())
+    );
+
+    // all inputs must be signed to finalize, by the key controlling their funding output
+    lock.partial_mut().inputs[0]
+        .partial_sigs
+        .insert(bob, vec![0xaa, 0x01]);
+    assert!(matches!(lock.finalize(), Err(Error::MissingSignature)));
+    lock.partial_mut().inputs[1]
+        .partial_sigs
+        .insert(pubkey(1), vec![0xcc, 0x01]);
+    assert!(matches!(lock.finalize(), Err(Error::MissingSignature)));
+    lock.partial_mut().inputs[1]
+        .partial_sigs
+        .insert(bob, vec![0xbb, 0x01]);
+    lock.finalize().unwrap();
+    assert_eq!(
+        lock.partial().inputs[1].final_script_witness,
+        Some(vec![vec![0xbb, 0x01], bob.to_bytes()])
+    );
+
+    // a single aggregated output does not fund the swap
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    funding.update(tx1).unwrap();
+    assert!(matches!(
+        Tx::<Lock>::initialize(&funding, datalock, Amount::from_sat(109000)),
+        Err(Error::NotEnoughAssets)
+    ));
+}
+
+#[test]
+fn lock_pays_surplus_to_change() {
+    let bob = pubkey(2);
+    let funding_tx = |value| bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: value as u32,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value,
+            script_pubkey: Address::p2wpkh(&bob, bitcoin::Network::Regtest)
+                .unwrap()
+                .script_pubkey(),
+        }],
+    };
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    funding.update(funding_tx(60000)).unwrap();
+    funding.add_utxo(funding_tx(50000)).unwrap();
+    let datalock = swap_lock(pubkey(1), bob);
+    let change = Address::p2wpkh(&bob, bitcoin::Network::Bitcoin).unwrap();
+    let strategy = FeeStrategy::Fixed(SatPerVByte::from_sat(1));
+
+    let lock = Tx::<Lock>::initialize_with_change(
+        &funding,
+        datalock.clone(),
+        Amount::from_sat(100000),
+        KeyOrdering::RoleBased,
+        change.clone().into(),
+        &strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let tx = &lock.partial().global.unsigned_tx;
+    let fee = fee_vsize(lock.partial());
+    assert_eq!(tx.output[0].value, 100000);
+    assert_eq!(tx.output[1].script_pubkey, change.script_pubkey());
+    assert_eq!(tx.output[1].value, 110000 - 100000 - fee);
+    lock.verify_template(datalock.clone()).unwrap();
+    lock.verify_target_amount(Amount::from_sat(100000)).unwrap();
+    assert!(Bitcoin::validate_fee(lock.partial(), &strategy).unwrap());
+    // the next transaction consumes the swaplock output
+    let output = lock.get_consumable_output().unwrap();
+    assert_eq!(output.out_point, OutPoint::new(tx.txid(), 0));
+
+    // the surplus must be worth a change output
+    let err = Tx::<Lock>::initialize_with_change(
+        &funding,
+        datalock,
+        Amount::from_sat(110000 - fee - 100),
+        KeyOrdering::RoleBased,
+        change.into(),
+        &strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        farcaster_core::Error::FeeStrategy(FeeStrategyError::Other(_))
+    ));
+}
+
+#[test]
+fn sign_owned_inputs_of_cofunded_lock() {
+    let (bob, carol) = (pubkey(2), pubkey(3));
+    let funding_tx = |value| bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: value as u32,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value,
+            script_pubkey: Address::p2wpkh(&bob, bitcoin::Network::Regtest)
+                .unwrap()
+                .script_pubkey(),
+        }],
+    };
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    funding.update(funding_tx(60000)).unwrap();
+    funding.add_utxo(funding_tx(50000)).unwrap();
+    let datalock = swap_lock(pubkey(1), bob);
+    let mut lock = Tx::<Lock>::initialize(&funding, datalock, Amount::from_sat(109000)).unwrap();
+    // the second input is contributed by a co-funder
+    lock.partial_mut().inputs[1].witness_script =
+        Some(Address::p2pkh(&carol, bitcoin::Network::Regtest).script_pubkey());
+
+    // only the inputs controlled by the key are signed
+    let owned = lock.generate_owned_witnesses(&privkey(2)).unwrap();
+    assert_eq!(owned.len(), 1);
+    let (index, bob_sig) = owned[0];
+    assert_eq!(index, 0);
+    assert_eq!(bob_sig, lock.generate_witness(&privkey(2)).unwrap());
+    assert!(lock
+        .generate_owned_witnesses(&privkey(4))
+        .unwrap()
+        .is_empty());
+
+    let carol_sig = lock.generate_input_witness(1, &privkey(3)).unwrap();
+    lock.verify_input_witness(1, &carol, carol_sig).unwrap();
+    assert!(lock.verify_input_witness(0, &carol, carol_sig).is_err());
+    lock.verify_witness(&bob, bob_sig).unwrap();
+
+    // each input is finalized with its own signature
+    lock.add_input_witness(0, bob, bob_sig).unwrap();
+    assert!(matches!(lock.finalize(), Err(Error::MissingSignature)));
+    lock.add_input_witness(1, carol, carol_sig).unwrap();
+    lock.finalize().unwrap();
+    let witness = lock.partial().inputs[1]
+        .final_script_witness
+        .clone()
+        .unwrap();
+    assert_eq!(witness[1], carol.to_bytes());
+
+    // out of range inputs are rejected
+    let err = lock.generate_input_witness(2, &privkey(2)).unwrap_err();
+    assert!(matches!(
+        err.into_inner().unwrap().downcast_ref::<BtcError>(),
+        Some(BtcError::MissingInput(2))
+    ));
+}
+
+#[test]
+fn funding_reorg_invalidates_seen_transaction() {
+    let bob = pubkey(2);
+    let tx = |value| bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value,
+            script_pubkey: Address::p2wpkh(&bob, bitcoin::Network::Regtest)
+                .unwrap()
+                .script_pubkey(),
+        }],
+    };
+    let (seen, reseen) = (tx(100000), tx(110000));
+
+    let mut funding = Funding::initialize(bob, Network::Local).unwrap();
+    funding.update(seen.clone()).unwrap();
+    assert!(funding.get_consumable_output().is_ok());
+
+    // another transaction is reorged out, the funding is untouched
+    assert!(!funding.invalidate(&reseen.txid()));
+    assert!(funding.get_consumable_output().is_ok());
+
+    // the funding transaction is reorged out
+    assert!(funding.invalidate(&seen.txid()));
+    assert!(matches!(
+        funding.get_consumable_output(),
+        Err(Error::MissingOnchainTransaction)
+    ));
+    assert!(!funding.invalidate(&seen.txid()));
+
+    // a new funding transaction is seen
+    funding.update(reseen.clone()).unwrap();
+    let output = funding.get_consumable_output().unwrap();
+    assert_eq!(output.out_point, OutPoint::new(reseen.txid(), 0));
+    assert_eq!(output.tx_out.value, 110000);
+}
+
+#[test]
+fn sighash_matches_generated_witnesses() {
+    let secp = Secp256k1::new();
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    let sighash = lock.witness_sighash().unwrap();
+    assert_eq!(sighash, lock.input_sighash().unwrap()[..].to_vec());
+    let msg = Message::from_slice(&sighash).unwrap();
+    let sig = lock.generate_witness(&privkey(2)).unwrap();
+    secp.verify(&msg, &sig, &bob.key).unwrap();
+
+    let sighash = cancel.failure_witness_sighash().unwrap();
+    assert_ne!(sighash, lock.witness_sighash().unwrap());
+    let msg = Message::from_slice(&sighash).unwrap();
+    let sig = cancel.generate_failure_witness(&privkey(1)).unwrap();
+    secp.verify(&msg, &sig, &alice.key).unwrap();
+}
+
+#[test]
+fn deterministic_signatures() {
+    // RFC 6979 vector with the secret key 1 and the message "Satoshi Nakamoto"
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let msg = Message::from_slice(&sha256::Hash::hash(b"Satoshi Nakamoto")[..]).unwrap();
+    let sig = Bitcoin::secp().sign(&msg, &SecretKey::from_slice(&one).unwrap());
+    assert_eq!(
+        hex::encode(sig.serialize_compact().to_vec()),
+        "934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d8\
+         2442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5"
+    );
+
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = punishable_lock(alice, bob);
+    let lock =
+        Tx::<Lock>::initialize(&funding(bob), datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    // the same input signed twice gives byte-identical signatures, with the RFC 6979 nonce
+    let sig = cancel.generate_failure_witness(&privkey(1)).unwrap();
+    let again = cancel
+        .clone()
+        .generate_failure_witness(&privkey(1))
+        .unwrap();
+    assert_eq!(sig.serialize_der().to_vec(), again.serialize_der().to_vec());
+    let msg = Message::from_slice(&cancel.failure_witness_sighash().unwrap()).unwrap();
+    assert_eq!(sig, Bitcoin::secp().sign(&msg, &privkey(1).key));
+}
+
+#[test]
+fn transaction_ids_are_set_by_each_trait() {
+    use farcaster_chains::bitcoin::transaction::{Buy, Punish};
+    use farcaster_core::transaction::{Buyable, Punishable, TxId};
+
+    type Out = MetadataOutput;
+    assert_eq!(<Funding as Fundable<Bitcoin, Out>>::TX_ID, TxId::Funding);
+    assert_eq!(<Tx<Lock> as Lockable<Bitcoin, Out>>::TX_ID, TxId::Lock);
+    assert_eq!(<Tx<Buy> as Buyable<Bitcoin, Out>>::TX_ID, TxId::Buy);
+    assert_eq!(
+        <Tx<Cancel> as Cancelable<Bitcoin, Out>>::TX_ID,
+        TxId::Cancel
+    );
+    assert_eq!(
+        <Tx<Refund> as Refundable<Bitcoin, Out>>::TX_ID,
+        TxId::Refund
+    );
+    assert_eq!(
+        <Tx<Punish> as Punishable<Bitcoin, Out>>::TX_ID,
+        TxId::Punish
+    );
+}
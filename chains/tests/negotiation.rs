@@ -1,12 +1,15 @@
 use farcaster_chains::bitcoin::fee::SatPerVByte;
-use farcaster_chains::bitcoin::{Amount, Bitcoin, CSVTimelock};
+use farcaster_chains::bitcoin::{Amount, Bitcoin, CSVTimelock, Sha256dEngine};
 use farcaster_chains::monero::Monero;
 use farcaster_chains::pairs::btcxmr::BtcXmr;
 
 use farcaster_core::blockchain::{Asset, FeeStrategy, Network};
-use farcaster_core::consensus::{self, deserialize, serialize_hex};
-use farcaster_core::negotiation::{Buy, Offer, PublicOffer, Sell};
-use farcaster_core::role::SwapRole;
+use farcaster_core::consensus::{self, deserialize, serialize, serialize_hex};
+use farcaster_core::crypto::CommitmentEngine;
+use farcaster_core::negotiation::{self, Buy, Offer, PublicOffer, Sell, SwapAmounts};
+use farcaster_core::role::{NegotiationRole, SwapRole};
+use farcaster_core::swap::SWAP_ID_TAG;
+use farcaster_core::transaction::ConfirmationBounds;
 
 use internet2::{RemoteNodeAddr, RemoteSocketAddr};
 
@@ -15,7 +18,7 @@ use std::str::FromStr;
 #[test]
 fn create_offer() {
     let hex = "02000000808000008008000500000000000000080006000000000000000400070000000400080000000\
-               10800090000000000000002";
+               108000900000000000000020300000006000000010000000200000001000000010000000a000000";
     let offer: Offer<BtcXmr> = Offer {
         network: Network::Testnet,
         arbitrating_blockchain: Bitcoin::new(),
@@ -26,11 +29,94 @@ fn create_offer() {
         punish_timelock: CSVTimelock::new(8),
         fee_strategy: FeeStrategy::Fixed(SatPerVByte::from_sat(9)),
         maker_role: SwapRole::Bob,
+        confirmation_bounds: ConfirmationBounds {
+            lock: 6,
+            ..Default::default()
+        },
     };
 
     assert_eq!(hex, serialize_hex(&offer));
 }
 
+#[test]
+fn swap_amounts() {
+    let amounts: SwapAmounts<BtcXmr> = SwapAmounts::new(Amount::from_sat(5), 6).unwrap();
+    assert_eq!(amounts.rate(), 1.2);
+
+    let encoded = "0800050000000000000008000600000000000000";
+    assert_eq!(encoded, serialize_hex(&amounts));
+    let decoded: SwapAmounts<BtcXmr> = deserialize(&hex::decode(encoded).unwrap()).unwrap();
+    assert_eq!(decoded, amounts);
+
+    // zero amounts are rejected when created and decoded
+    assert!(matches!(
+        SwapAmounts::<BtcXmr>::new(Amount::from_sat(0), 6),
+        Err(negotiation::Error::ZeroAmount)
+    ));
+    assert!(matches!(
+        SwapAmounts::<BtcXmr>::new(Amount::from_sat(5), 0),
+        Err(negotiation::Error::ZeroAmount)
+    ));
+    let zero = hex::decode("0800000000000000000008000600000000000000").unwrap();
+    assert!(deserialize::<SwapAmounts<BtcXmr>>(&zero).is_err());
+
+    // the funds must cover the locked amount and the fee
+    amounts
+        .validate_funding(Amount::from_sat(7), Amount::from_sat(2))
+        .unwrap();
+    assert!(matches!(
+        amounts.validate_funding(Amount::from_sat(6), Amount::from_sat(2)),
+        Err(negotiation::Error::NotEnoughFunds)
+    ));
+}
+
+#[test]
+fn swap_role_from_negotiation_role() {
+    let offer = |maker_role| Offer::<BtcXmr> {
+        network: Network::Testnet,
+        arbitrating_blockchain: Bitcoin::new(),
+        accordant_blockchain: Monero::new(),
+        arbitrating_amount: Amount::from_sat(5),
+        accordant_amount: 6,
+        cancel_timelock: CSVTimelock::new(7),
+        punish_timelock: CSVTimelock::new(8),
+        fee_strategy: FeeStrategy::Fixed(SatPerVByte::from_sat(9)),
+        maker_role,
+        confirmation_bounds: ConfirmationBounds::default(),
+    };
+    let cases = [
+        (SwapRole::Alice, NegotiationRole::Maker, SwapRole::Alice),
+        (SwapRole::Alice, NegotiationRole::Taker, SwapRole::Bob),
+        (SwapRole::Bob, NegotiationRole::Maker, SwapRole::Bob),
+        (SwapRole::Bob, NegotiationRole::Taker, SwapRole::Alice),
+    ];
+    for (maker_role, nego_role, swap_role) in cases.iter() {
+        assert_eq!(offer(*maker_role).swap_role(nego_role), *swap_role);
+    }
+
+    assert_eq!(serialize_hex(&NegotiationRole::Maker), "01");
+    assert_eq!(serialize_hex(&NegotiationRole::Taker), "02");
+    let taker: NegotiationRole = deserialize(&[0x02]).unwrap();
+    assert_eq!(taker, NegotiationRole::Taker);
+    assert!(deserialize::<NegotiationRole>(&[0x03]).is_err());
+}
+
+#[test]
+fn swap_role_helpers() {
+    assert_eq!(SwapRole::Alice.opposite(), SwapRole::Bob);
+    assert_eq!(SwapRole::Bob.opposite(), SwapRole::Alice);
+    assert!(SwapRole::Alice.is_alice() && !SwapRole::Alice.is_bob());
+    assert!(SwapRole::Bob.is_bob() && !SwapRole::Bob.is_alice());
+
+    for role in [SwapRole::Alice, SwapRole::Bob].iter() {
+        assert_eq!(role.opposite().opposite(), *role);
+        assert_eq!(SwapRole::from_str(&role.to_string()).unwrap(), *role);
+    }
+    assert_eq!(format!("{}", SwapRole::Bob), "Bob");
+    assert_eq!(SwapRole::from_str("alice").unwrap(), SwapRole::Alice);
+    assert!(SwapRole::from_str("Carol").is_err());
+}
+
 #[test]
 fn maker_buy_arbitrating_assets_offer() {
     let offer: Option<Offer<BtcXmr>> = Buy::some(Bitcoin::new(), Amount::from_sat(100000))
@@ -39,8 +125,11 @@ fn maker_buy_arbitrating_assets_offer() {
         .with_fee(FeeStrategy::Fixed(SatPerVByte::from_sat(20)))
         .on(Network::Testnet)
         .to_offer();
-    assert!(offer.is_some());
-    assert_eq!(offer.expect("an offer").maker_role, SwapRole::Alice);
+    let offer = offer.expect("an offer");
+    assert_eq!(offer.maker_role, SwapRole::Alice);
+    let amounts = offer.amounts().unwrap();
+    assert_eq!(amounts.arbitrating, Amount::from_sat(100000));
+    assert_eq!(amounts.accordant, 200);
 }
 
 #[test]
@@ -58,9 +147,10 @@ fn maker_sell_arbitrating_assets_offer() {
 #[test]
 fn serialize_public_offer() {
     let hex = "46435357415001000200000080800000800800a0860100000000000800c80000000000000004000\
-               a00000004000a00000001080014000000000000000203b31a0a70343bb46f3db3768296ac5027f9\
-               873921b37f852860c690063ff9e4c90000000000000000000000000000000000000000000000000\
-               000000000000000000000260700";
+               a00000004000a000000010800140000000000000002030000000300000001000000020000000100\
+               0000010000000a00000003b31a0a70343bb46f3db3768296ac5027f9873921b37f852860c690063\
+               ff9e4c9000000000000000000000000000000000000000000000000000000000000000000000026\
+               0700";
     let offer: Offer<BtcXmr> = Sell::some(Bitcoin::new(), Amount::from_sat(100000))
         .for_some(Monero::new(), 200)
         .with_timelocks(CSVTimelock::new(10), CSVTimelock::new(10))
@@ -87,12 +177,67 @@ fn serialize_public_offer() {
     assert_eq!(hex, serialize_hex(&public_offer));
 }
 
+#[test]
+fn offer_carries_confirmation_bounds() {
+    let bounds = ConfirmationBounds {
+        lock: 6,
+        accordant_lock: 12,
+        ..Default::default()
+    };
+    let builder = || {
+        Buy::some(Bitcoin::new(), Amount::from_sat(100000))
+            .with(Monero::new(), 200)
+            .with_timelocks(CSVTimelock::new(10), CSVTimelock::new(10))
+            .with_fee(FeeStrategy::Fixed(SatPerVByte::from_sat(20)))
+            .on(Network::Testnet)
+    };
+    let offer: Offer<BtcXmr> = builder().to_offer().unwrap();
+    assert_eq!(offer.confirmation_bounds, ConfirmationBounds::default());
+
+    let mut offer: Offer<BtcXmr> = builder()
+        .with_confirmation_bounds(bounds)
+        .to_offer()
+        .unwrap();
+    assert_eq!(offer.confirmation_bounds, bounds);
+    let decoded: Offer<BtcXmr> = deserialize(&serialize(&offer)).unwrap();
+    assert_eq!(decoded.confirmation_bounds, bounds);
+
+    // an offer not requiring funding confirmations is rejected
+    offer.confirmation_bounds.funding = 0;
+    assert!(deserialize::<Offer<BtcXmr>>(&serialize(&offer)).is_err());
+}
+
+#[test]
+fn swap_id_with_configurable_hasher() {
+    let hex = "46435357415001000200000080800000800800a0860100000000000800c80000000000000004000\
+               a00000004000a000000010800140000000000000002030000000300000001000000020000000100\
+               0000010000000a00000003b31a0a70343bb46f3db3768296ac5027f9873921b37f852860c690063\
+               ff9e4c9000000000000000000000000000000000000000000000000000000000000000000000026\
+               0700";
+    let public_offer: PublicOffer<BtcXmr> = FromStr::from_str(hex).unwrap();
+
+    // the default identifier uses the commitment engine of the context
+    let id = public_offer.swap_id();
+    assert_eq!(id, public_offer.clone().swap_id());
+    assert_eq!(id.as_bytes().len(), 32);
+
+    // the same offer identified with double SHA-256
+    let sha256d_id = public_offer.swap_id_with::<Sha256dEngine>();
+    assert_eq!(
+        sha256d_id.0,
+        Sha256dEngine::commit(SWAP_ID_TAG, &serialize(&public_offer))
+    );
+    assert_ne!(sha256d_id.as_bytes(), id.as_bytes());
+    assert_eq!(sha256d_id.to_string().len(), 64);
+}
+
 #[test]
 fn check_public_offer_magic_bytes() {
     let valid = "46435357415001000200000080800000800800a0860100000000000800c80000000000000004000\
-                 a00000004000a00000001080014000000000000000203b31a0a70343bb46f3db3768296ac5027f9\
-                 873921b37f852860c690063ff9e4c90000000000000000000000000000000000000000000000000\
-                 000000000000000000000260700";
+                 a00000004000a000000010800140000000000000002030000000300000001000000020000000100\
+                 0000010000000a00000003b31a0a70343bb46f3db3768296ac5027f9873921b37f852860c690063\
+                 ff9e4c9000000000000000000000000000000000000000000000000000000000000000000000026\
+                 0700";
     let pub_offer: Result<PublicOffer<BtcXmr>, consensus::Error> =
         deserialize(&hex::decode(valid).unwrap()[..]);
     assert!(pub_offer.is_ok());
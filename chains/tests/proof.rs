@@ -0,0 +1,231 @@
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::{self, Secp256k1};
+
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+
+use farcaster_chains::bitcoin::Bitcoin;
+use farcaster_chains::monero::{private_spend_from_seed, Monero, SHARED_KEY_BITS};
+use farcaster_chains::pairs::btcxmr::{BtcXmr, RingProof};
+
+use farcaster_core::consensus::{deserialize, serialize};
+use farcaster_core::crypto::{self, DleqProof, Keys};
+use farcaster_core::datum;
+
+use rand_core::{impls, CryptoRng, Error, RngCore};
+
+use strict_encoding::{strict_deserialize, strict_serialize};
+
+/// A deterministic generator, only suitable to reproduce test vectors
+struct CounterRng(u64);
+
+impl RngCore for CounterRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(1);
+        self.0
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for CounterRng {}
+
+#[test]
+fn ring_proof_encoding_is_stable() {
+    let proof = RingProof {
+        commitments: vec![([0x02; 33], [0x01; 32]); SHARED_KEY_BITS],
+        challenges: vec![[0x03; 32]; SHARED_KEY_BITS],
+        responses: vec![([0x04; 32], [0x05; 32]); SHARED_KEY_BITS],
+    };
+
+    let bytes = strict_serialize(&proof).unwrap();
+    assert_eq!(bytes.len(), 3 * 2 + SHARED_KEY_BITS * (65 + 32 + 64));
+
+    let proof_2: RingProof = strict_deserialize(&bytes).unwrap();
+    assert_eq!(proof, proof_2);
+    assert_eq!(bytes, strict_serialize(&proof_2).unwrap());
+
+    let empty: RingProof =
+        strict_deserialize(&strict_serialize(&RingProof::default()).unwrap()).unwrap();
+    assert_eq!(empty, RingProof::default());
+}
+
+#[test]
+fn compare_proofs_structurally() {
+    let proof = RingProof {
+        commitments: vec![([0x02; 33], [0x01; 32]); 2],
+        challenges: vec![[0x03; 32]; 2],
+        responses: vec![([0x04; 32], [0x05; 32]); 2],
+    };
+    let mut other = proof.clone();
+    other.responses[1].1[0] = 0x06;
+    assert_ne!(proof, other);
+
+    // a proof received again matches the proof verified earlier
+    let datum = datum::Proof::<BtcXmr>::new_cross_group_dleq(proof.clone());
+    let received: datum::Proof<BtcXmr> = deserialize(&serialize(&datum)).unwrap();
+    assert_eq!(datum, received);
+    assert_eq!(received.proof(), &proof);
+    assert_ne!(datum, datum::Proof::new_cross_group_dleq(other));
+}
+
+#[test]
+fn ring_proof_decoding_is_bounded() {
+    let too_long = RingProof {
+        challenges: vec![[0x03; 32]; SHARED_KEY_BITS + 1],
+        ..Default::default()
+    };
+    assert!(strict_serialize(&too_long).is_err());
+
+    // length prefix of 0xffff commitments followed by nothing
+    let bytes = vec![0xff, 0xff];
+    assert!(strict_deserialize::<RingProof>(&bytes).is_err());
+}
+
+#[test]
+fn derive_cross_group_points_from_same_secret() {
+    let secp = Secp256k1::new();
+    // secp256k1 secret keys are the big-endian encoding of the scalar
+    let secp_point = |secret: &[u8; 32]| {
+        let mut bytes = *secret;
+        bytes.reverse();
+        secp256k1::PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&bytes).unwrap())
+    };
+    let mut one = [0u8; 32];
+    one[0] = 1;
+    let mut two = [0u8; 32];
+    two[0] = 2;
+
+    // the unit scalar derives the generator of each group
+    let generator = secp_point(&one);
+    assert_eq!(
+        Bitcoin::from_cross_group_secret(&one).unwrap().key,
+        generator
+    );
+    assert_eq!(
+        Monero::from_cross_group_secret(&one).unwrap().as_bytes(),
+        ED25519_BASEPOINT_POINT.compress().as_bytes()
+    );
+
+    // adding the scalars adds the points in both groups
+    assert_eq!(
+        Bitcoin::from_cross_group_secret(&two).unwrap().key,
+        generator.combine(&generator).unwrap()
+    );
+    assert_eq!(
+        Monero::from_cross_group_secret(&two).unwrap().as_bytes(),
+        (ED25519_BASEPOINT_POINT + ED25519_BASEPOINT_POINT)
+            .compress()
+            .as_bytes()
+    );
+
+    // the accordant spend secret derives both the spend point and the adaptor point
+    let spend = private_spend_from_seed(&[7u8; 32]).unwrap();
+    let mut secret = [0u8; 32];
+    secret.copy_from_slice(spend.as_bytes());
+    assert_eq!(
+        Monero::from_cross_group_secret(&secret).unwrap(),
+        monero::PublicKey::from_private_key(&spend)
+    );
+    assert_eq!(
+        Bitcoin::from_cross_group_secret(&secret).unwrap().key,
+        secp_point(&secret)
+    );
+    let (_, adaptor, _) = RingProof::generate(&[7u8; 32]).unwrap();
+    assert_eq!(Bitcoin::from_cross_group_secret(&secret).unwrap(), adaptor);
+
+    // secrets must be valid in both groups
+    let mut too_large = [0u8; 32];
+    too_large[31] = 0x10;
+    for secret in [[0u8; 32], too_large].iter() {
+        assert!(matches!(
+            Bitcoin::from_cross_group_secret(secret),
+            Err(crypto::Error::InvalidKey)
+        ));
+        assert!(matches!(
+            Monero::from_cross_group_secret(secret),
+            Err(crypto::Error::InvalidKey)
+        ));
+    }
+}
+
+#[test]
+fn generate_proof_with_seeded_rng() {
+    let seed = [7u8; 32];
+    let first = RingProof::generate_with_rng(&seed, &mut CounterRng(0)).unwrap();
+    let second = RingProof::generate_with_rng(&seed, &mut CounterRng(0)).unwrap();
+    assert_eq!(first, second);
+
+    // one ring signature per bit of the shared key
+    let proof = &first.2;
+    assert_eq!(proof.commitments.len(), SHARED_KEY_BITS);
+    assert_eq!(proof.challenges.len(), SHARED_KEY_BITS);
+    assert_eq!(proof.responses.len(), 2 * SHARED_KEY_BITS);
+    let bytes = strict_serialize(proof).unwrap();
+    assert_eq!(&strict_deserialize::<RingProof>(&bytes).unwrap(), proof);
+
+    // another generator seed gives another transcript for the same keys
+    let other = RingProof::generate_with_rng(&seed, &mut CounterRng(1 << 32)).unwrap();
+    assert_eq!((other.0, other.1), (first.0, first.1));
+    assert_ne!(other.2, first.2);
+
+    // the keys only depend on the seed, not on the generator
+    let (spend, adaptor, proof) = RingProof::generate(&seed).unwrap();
+    assert_eq!((spend, adaptor), (first.0, first.1));
+    assert_ne!(proof, first.2);
+}
+
+#[test]
+fn verify_ring_proof() {
+    let (spend, adaptor, proof) = RingProof::generate(&[7u8; 32]).unwrap();
+    assert!(RingProof::verify(&spend, &adaptor, proof.clone()).is_ok());
+
+    // the proof is bound to both keys
+    let (other_spend, other_adaptor, other_proof) = RingProof::generate(&[8u8; 32]).unwrap();
+    for (spend, adaptor, proof) in [
+        (&other_spend, &adaptor, proof.clone()),
+        (&spend, &other_adaptor, proof.clone()),
+        (&spend, &adaptor, other_proof),
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            RingProof::verify(spend, adaptor, proof.clone()),
+            Err(crypto::Error::InvalidProof)
+        ));
+    }
+
+    // any tampered or missing element is rejected
+    let mut challenge = proof.clone();
+    challenge.challenges[17][0] ^= 0x01;
+    let mut response = proof.clone();
+    response.responses[42].1[0] ^= 0x01;
+    let mut commitment = proof.clone();
+    commitment.commitments.swap(0, 1);
+    let mut truncated = proof.clone();
+    truncated.responses.pop();
+    for proof in [
+        challenge,
+        response,
+        commitment,
+        truncated,
+        RingProof::default(),
+    ]
+    .iter()
+    {
+        assert!(matches!(
+            RingProof::verify(&spend, &adaptor, proof.clone()),
+            Err(crypto::Error::InvalidProof)
+        ));
+    }
+}
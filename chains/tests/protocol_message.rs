@@ -1,13 +1,46 @@
-use bitcoin::blockdata::transaction::Transaction;
-use bitcoin::secp256k1::Secp256k1;
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{OutPoint, SigHashType, Transaction, TxIn, TxOut};
 use bitcoin::secp256k1::Signature;
+use bitcoin::secp256k1::{Message, Secp256k1};
 use bitcoin::util::key::{PrivateKey, PublicKey};
 use bitcoin::util::psbt::PartiallySignedTransaction;
 
-use farcaster_core::protocol_message::{Abort, BuyProcedureSignature};
+use farcaster_core::blockchain::{Fee, FeePolitic, FeeStrategy, Network};
+use farcaster_core::bundle::{CosignedArbitratingCancel, SignedAdaptorRefund};
+use farcaster_core::consensus::{self, deserialize};
+use farcaster_core::crypto::{self, Commitment, DleqProof, Secret, SignatureType, Signatures};
+use farcaster_core::datum;
+use farcaster_core::negotiation::PublicOffer;
+use farcaster_core::observer::{SwapObserver, SwapPhase};
+use farcaster_core::protocol_message::{
+    frame, read_frame, Abort, AccordantFundingConfirmed, BuyProcedureSignature,
+    CommitAliceParameters, CommitBobParameters, CooperativeClose, CoreArbitratingSetup, MessageId,
+    MuSigNonceCommitment, MuSigNonceReveal, ProtocolMessage, RefundProcedureSignatures,
+    RevealAliceParameters, RevealBobParameters, SwapMessage, SwapState, FRAME_MAGIC_BYTES,
+    MAX_ABORT_BODY_LEN, MAX_FRAME_LEN, MAX_PARTIAL_TRANSACTION_LEN, MAX_TXID_LEN, PROTOCOL_VERSION,
+    SIGHASH_ALL,
+};
+use farcaster_core::role::{Bob, SwapRole};
+use farcaster_core::script::{DataLock, DataPunishableLock, DoubleKeys};
+use farcaster_core::transaction::{
+    AdaptorSignable, Broadcastable, Buyable, Cancelable, Error as TxError, Forkable, Fundable,
+    Lockable, Refundable, Signable, Transaction as _, TxId, Witnessable,
+};
+use farcaster_core::Error;
 
-use farcaster_chains::bitcoin::{ECDSAAdaptorSig, PDLEQ};
-use farcaster_chains::pairs::btcxmr::BtcXmr;
+use farcaster_chains::bitcoin::fee::fee_vsize;
+use farcaster_chains::bitcoin::transaction::{Buy, Cancel, Funding, Lock, Refund, Tx};
+use farcaster_chains::bitcoin::{recover_adaptor_secret_from_tx, Bitcoin, ECDSAAdaptorSig};
+use farcaster_chains::monero::private_spend_from_seed;
+use farcaster_chains::pairs::btcxmr::{BtcXmr, RingProof};
+
+use strict_encoding::{strict_deserialize, strict_serialize};
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+mod common;
+use common::{privkey, pubkey};
 
 #[test]
 fn create_abort_message() {
@@ -16,12 +49,151 @@ fn create_abort_message() {
     };
 }
 
+#[test]
+fn frame_protocol_messages() {
+    let empty = Abort { error_body: None };
+    let abort = Abort {
+        error_body: Some(String::from("An error occured ;)")),
+    };
+
+    let empty_frame = frame(&empty).unwrap();
+    assert_eq!(&empty_frame[..4], b"FCSM");
+    assert_eq!(
+        &empty_frame[4..],
+        &[0, 1, 0, 0, 0, 1, 0xd2, 0x02, 0xef, 0x8d, 0x00]
+    );
+
+    let mut corrupted = frame(&abort).unwrap();
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 0x01;
+
+    let mut stream = Vec::new();
+    stream.extend_from_slice(&frame(&abort).unwrap());
+    stream.extend_from_slice(&corrupted);
+    stream.extend_from_slice(&empty_frame);
+
+    let mut reader = &stream[..];
+    let decoded: Abort = strict_deserialize(&read_frame(&mut reader).unwrap()).unwrap();
+    assert_eq!(decoded, abort);
+    assert!(matches!(
+        read_frame(&mut reader),
+        Err(consensus::Error::CorruptFrame)
+    ));
+    // the corrupted frame is skipped entirely, the next frame is still readable
+    let decoded: Abort = strict_deserialize(&read_frame(&mut reader).unwrap()).unwrap();
+    assert_eq!(decoded, empty);
+    assert!(reader.is_empty());
+
+    // length is checked before reading the payload
+    let mut oversized = FRAME_MAGIC_BYTES.to_vec();
+    oversized.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    oversized.extend_from_slice(&(MAX_FRAME_LEN + 1).to_be_bytes());
+    oversized.extend_from_slice(&[0u8; 4]);
+    assert!(matches!(
+        read_frame(&mut &oversized[..]),
+        Err(consensus::Error::CorruptFrame)
+    ));
+
+    // truncated payload
+    assert!(read_frame(&mut &frame(&abort).unwrap()[..18]).is_err());
+}
+
+#[test]
+fn reject_frames_of_other_protocols() {
+    let abort = Abort {
+        error_body: Some(String::from("An error occured ;)")),
+    };
+    let bytes = frame(&abort).unwrap();
+    let msg = SwapMessage::<BtcXmr>::Abort(abort);
+
+    // a frame from a future protocol version
+    let mut future = bytes.clone();
+    future[4..6].copy_from_slice(&(PROTOCOL_VERSION + 1).to_be_bytes());
+    assert!(matches!(
+        read_frame(&mut &future[..]),
+        Err(consensus::Error::IncompatibleVersion(v)) if v == PROTOCOL_VERSION + 1
+    ));
+    let framed = frame(&msg).unwrap();
+    let mut future = framed.clone();
+    future[4..6].copy_from_slice(&(PROTOCOL_VERSION + 1).to_be_bytes());
+    assert!(matches!(
+        SwapMessage::<BtcXmr>::decode_frame(&future),
+        Err(consensus::Error::IncompatibleVersion(_))
+    ));
+    assert!(matches!(
+        SwapMessage::<BtcXmr>::read_framed(&mut &future[..]),
+        Err(consensus::Error::IncompatibleVersion(_))
+    ));
+
+    // a wrong magic is rejected before the version
+    let mut wrong = future;
+    wrong[..4].copy_from_slice(b"FCSW");
+    assert!(matches!(
+        read_frame(&mut &wrong[..]),
+        Err(consensus::Error::IncorrectMagicBytes)
+    ));
+    assert!(matches!(
+        SwapMessage::<BtcXmr>::decode_frame(&wrong),
+        Err(consensus::Error::IncorrectMagicBytes)
+    ));
+    assert!(matches!(
+        SwapMessage::<BtcXmr>::read_framed(&mut &wrong[..]),
+        Err(consensus::Error::IncorrectMagicBytes)
+    ));
+
+    // the current version is accepted
+    assert_eq!(
+        SwapMessage::<BtcXmr>::read_framed(&mut &framed[..]).unwrap(),
+        msg
+    );
+}
+
+#[test]
+fn abort_message_body_is_bounded() {
+    let abort = Abort {
+        error_body: Some(String::from("An error occured ;)")),
+    };
+    let bytes = strict_serialize(&abort).unwrap();
+    let abort_2: Abort = strict_deserialize(&bytes).unwrap();
+    assert_eq!(abort, abort_2);
+
+    let abort = Abort {
+        error_body: Some("a".repeat(MAX_ABORT_BODY_LEN + 1)),
+    };
+    let bytes = strict_serialize(&abort).unwrap();
+    assert!(strict_deserialize::<Abort>(&bytes).is_err());
+
+    // some body with the biggest length prefix and no data fails before reading
+    assert!(strict_deserialize::<Abort>(&[0x01, 0xff, 0xff]).is_err());
+}
+
+#[test]
+fn accordant_lock_txid_is_bounded() {
+    let confirmed = AccordantFundingConfirmed {
+        lock_txid: vec![0xab; MAX_TXID_LEN],
+        block_height: 2_400_000,
+    };
+    let bytes = strict_serialize(&confirmed).unwrap();
+    assert_eq!(
+        strict_deserialize::<AccordantFundingConfirmed>(&bytes).unwrap(),
+        confirmed
+    );
+
+    let confirmed = AccordantFundingConfirmed {
+        lock_txid: vec![0xab; MAX_TXID_LEN + 1],
+        ..confirmed
+    };
+    let bytes = strict_serialize(&confirmed).unwrap();
+    assert!(strict_deserialize::<AccordantFundingConfirmed>(&bytes).is_err());
+
+    // the biggest length prefix and no data fails before reading
+    assert!(strict_deserialize::<AccordantFundingConfirmed>(&[0xff, 0xff]).is_err());
+}
+
 #[test]
 fn create_buy_procedure_signature_message() {
     let secp = Secp256k1::new();
 
-    let ecdsa_sig = "3045022100b75f569de3e57f4f445bcf9e42be9e5b5128f317ab86e451fdfe7be5ffd6a7da0220776b30307b5d761512635dc0394573be7fe17b5300b160340dae370b641bc4ca";
-
     let tx = Transaction {
         version: 2,
         lock_time: 0,
@@ -29,21 +201,1241 @@ fn create_buy_procedure_signature_message() {
         output: Vec::new(),
     };
 
-    let sig = Signature::from_der(&hex::decode(ecdsa_sig).expect("HEX decode should work here"))
-        .expect("Parse DER should work here");
-
     let privkey: PrivateKey =
         PrivateKey::from_wif("L1HKVVLHXiUhecWnwFYF6L3shkf1E12HUmuZTESvBXUdx3yqVP1D").unwrap();
     let point = PublicKey::from_private_key(&secp, &privkey);
+    let msg = Message::from_slice(&[0x42; 32]).expect("Valid message");
 
-    let pdleq = PDLEQ;
-
-    let _ = BuyProcedureSignature::<BtcXmr> {
+    let buy_proc_sig = BuyProcedureSignature::<BtcXmr> {
         buy: (PartiallySignedTransaction::from_unsigned_tx(tx).expect("PSBT should work here")),
-        buy_adaptor_sig: ECDSAAdaptorSig {
-            sig,
-            point,
-            dleq: pdleq,
+        buy_adaptor_sig: ECDSAAdaptorSig::sign(&privkey, &point, &msg)
+            .expect("Adaptor signature should work here"),
+    };
+
+    let bytes = strict_serialize(&buy_proc_sig).unwrap();
+    let buy_proc_sig_2: BuyProcedureSignature<BtcXmr> = strict_deserialize(&bytes).unwrap();
+    assert_eq!(buy_proc_sig, buy_proc_sig_2);
+}
+
+#[test]
+fn commit_message_roundtrip() {
+    let commit = CommitAliceParameters::<BtcXmr> {
+        buy: BtcXmr::commit_to(b"buy"),
+        cancel: BtcXmr::commit_to(b"cancel"),
+        refund: BtcXmr::commit_to(b"refund"),
+        punish: BtcXmr::commit_to(b"punish"),
+        adaptor: BtcXmr::commit_to(b"adaptor"),
+        spend: BtcXmr::commit_to(b"spend"),
+        view: BtcXmr::commit_to(b"view"),
+    };
+
+    let bytes = strict_serialize(&commit).unwrap();
+    let commit_2: CommitAliceParameters<BtcXmr> = strict_deserialize(&bytes).unwrap();
+    assert_eq!(commit, commit_2);
+
+    let mut inbox = HashSet::new();
+    assert!(inbox.insert(commit));
+    assert!(!inbox.insert(commit_2));
+}
+
+fn swap_messages() -> Vec<(SwapMessage<BtcXmr>, SwapRole)> {
+    let secp = Secp256k1::new();
+    let privkey: PrivateKey =
+        PrivateKey::from_wif("L1HKVVLHXiUhecWnwFYF6L3shkf1E12HUmuZTESvBXUdx3yqVP1D").unwrap();
+    let key = PublicKey::from_private_key(&secp, &privkey);
+    let address = bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+        .expect("Parsable address");
+    let view = private_spend_from_seed(&[1u8; 32]).unwrap();
+    let spend = monero::PublicKey::from_private_key(&private_spend_from_seed(&[2u8; 32]).unwrap());
+
+    let ecdsa_sig = "3045022100b75f569de3e57f4f445bcf9e42be9e5b5128f317ab86e451fdfe7be5ffd6a7da0220776b30307b5d761512635dc0394573be7fe17b5300b160340dae370b641bc4ca";
+    let sig = Signature::from_der(&hex::decode(ecdsa_sig).unwrap()).unwrap();
+    let adaptor_sig =
+        ECDSAAdaptorSig::sign(&privkey, &key, &Message::from_slice(&[0x42; 32]).unwrap()).unwrap();
+    let psbt = PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time: 0,
+        input: Vec::new(),
+        output: Vec::new(),
+    })
+    .unwrap();
+
+    let reveal_alice = RevealAliceParameters::<BtcXmr> {
+        buy: key,
+        cancel: key,
+        refund: key,
+        punish: key,
+        adaptor: key,
+        address: address.clone().into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof: RingProof::default(),
+    };
+    let reveal_bob = RevealBobParameters::<BtcXmr> {
+        buy: key,
+        cancel: key,
+        refund: key,
+        adaptor: key,
+        address: address.into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof: RingProof::default(),
+    };
+
+    vec![
+        (
+            SwapMessage::CommitAliceParameters(CommitAliceParameters::from_bundle(
+                &reveal_alice.into_bundle(),
+            )),
+            SwapRole::Alice,
+        ),
+        (
+            SwapMessage::CommitBobParameters(CommitBobParameters::from_bundle(
+                &reveal_bob.into_bundle(),
+            )),
+            SwapRole::Bob,
+        ),
+        (
+            SwapMessage::RevealAliceParameters(reveal_alice),
+            SwapRole::Alice,
+        ),
+        (SwapMessage::RevealBobParameters(reveal_bob), SwapRole::Bob),
+        (
+            SwapMessage::CoreArbitratingSetup(CoreArbitratingSetup {
+                lock: psbt.clone(),
+                cancel: psbt.clone(),
+                refund: psbt.clone(),
+                cancel_sig: sig,
+            }),
+            SwapRole::Bob,
+        ),
+        (
+            SwapMessage::RefundProcedureSignatures(RefundProcedureSignatures {
+                cancel_sig: sig,
+                refund_adaptor_sig: adaptor_sig.clone(),
+                refund_sighash_type: SIGHASH_ALL,
+            }),
+            SwapRole::Alice,
+        ),
+        (
+            SwapMessage::AccordantFundingConfirmed(AccordantFundingConfirmed {
+                lock_txid: vec![0xab; 32],
+                block_height: 2_400_000,
+            }),
+            SwapRole::Alice,
+        ),
+        (
+            SwapMessage::BuyProcedureSignature(BuyProcedureSignature {
+                buy: psbt,
+                buy_adaptor_sig: adaptor_sig,
+            }),
+            SwapRole::Bob,
+        ),
+    ]
+}
+
+#[test]
+fn swap_state_happy_path() {
+    let messages = swap_messages();
+
+    // Alice's messages first, then Bob's messages first during commit and reveal
+    let mut bob_first = messages.clone();
+    bob_first.swap(0, 1);
+    bob_first.swap(2, 3);
+
+    for sequence in [messages, bob_first].iter() {
+        let mut state = SwapState::new();
+        for (msg, role) in sequence.iter() {
+            state.apply(msg, *role).unwrap();
+        }
+        assert_eq!(state, SwapState::Done);
+    }
+}
+
+#[test]
+fn identify_protocol_messages() {
+    let ids: Vec<MessageId> = swap_messages()
+        .iter()
+        .map(|(msg, _)| msg.message_id())
+        .collect();
+    assert_eq!(
+        ids,
+        vec![
+            MessageId::CommitAliceParameters,
+            MessageId::CommitBobParameters,
+            MessageId::RevealAliceParameters,
+            MessageId::RevealBobParameters,
+            MessageId::CoreArbitratingSetup,
+            MessageId::RefundProcedureSignatures,
+            MessageId::AccordantFundingConfirmed,
+            MessageId::BuyProcedureSignature,
+        ]
+    );
+
+    // wrapped messages share the identifier of the inner message
+    let abort = Abort { error_body: None };
+    assert_eq!(abort.message_id(), MessageId::Abort);
+    assert_eq!(
+        SwapMessage::<BtcXmr>::Abort(abort).message_id(),
+        MessageId::Abort
+    );
+
+    assert_eq!(
+        MessageId::CoreArbitratingSetup.to_string(),
+        "core_arbitrating_setup"
+    );
+    assert_eq!(MessageId::Abort.to_string(), "abort");
+}
+
+#[test]
+fn musig_nonce_exchange() {
+    let reveal = MuSigNonceReveal::<BtcXmr> {
+        first_nonce: pubkey(1),
+        second_nonce: pubkey(2),
+    };
+    let commitment = MuSigNonceCommitment::from_reveal(&reveal);
+    assert_eq!(commitment.message_id(), MessageId::MuSigNonceCommitment);
+    assert_eq!(reveal.message_id(), MessageId::MuSigNonceReveal);
+    assert_eq!(
+        MessageId::MuSigNonceReveal.to_string(),
+        "musig_nonce_reveal"
+    );
+
+    let bytes = strict_serialize(&commitment).unwrap();
+    let commitment_2: MuSigNonceCommitment<BtcXmr> = strict_deserialize(&bytes).unwrap();
+    assert_eq!(commitment, commitment_2);
+    let bytes = strict_serialize(&reveal).unwrap();
+    assert_eq!(bytes.len(), 2 * 33);
+    let reveal_2: MuSigNonceReveal<BtcXmr> = strict_deserialize(&bytes).unwrap();
+    assert_eq!(reveal, reveal_2);
+    exact_len(&commitment);
+    exact_len(&reveal);
+
+    commitment_2.verify(&reveal_2).unwrap();
+
+    // the nonces are committed in order
+    let swapped = MuSigNonceReveal::<BtcXmr> {
+        first_nonce: pubkey(2),
+        second_nonce: pubkey(1),
+    };
+    assert!(matches!(
+        commitment.verify(&swapped),
+        Err(Error::Crypto(crypto::Error::InvalidCommitment))
+    ));
+    let other = MuSigNonceReveal::<BtcXmr> {
+        second_nonce: pubkey(3),
+        ..reveal
+    };
+    assert!(commitment.verify(&other).is_err());
+}
+
+#[test]
+fn swap_state_rejects_unexpected_messages() {
+    let messages = swap_messages();
+    let (commit_alice, _) = &messages[0];
+    let (commit_bob, _) = &messages[1];
+    let (reveal_alice, _) = &messages[2];
+
+    // reveal before the matching commit
+    let mut state = SwapState::new();
+    assert!(state.apply(reveal_alice, SwapRole::Alice).is_err());
+    assert_eq!(state, SwapState::new());
+
+    // duplicate commit and commit from the wrong role
+    state.apply(commit_alice, SwapRole::Alice).unwrap();
+    assert!(state.apply(commit_alice, SwapRole::Alice).is_err());
+    assert!(state.apply(commit_bob, SwapRole::Alice).is_err());
+    // reveal before both commitments
+    assert!(state.apply(reveal_alice, SwapRole::Alice).is_err());
+
+    // the buy procedure signature waits for the accordant lock confirmation
+    let mut state = SwapState::new();
+    for (msg, role) in messages[..6].iter() {
+        state.apply(msg, *role).unwrap();
+    }
+    assert_eq!(state, SwapState::AccordantFundingConfirmed);
+    let (buy_proc_sig, _) = &messages[7];
+    assert!(state.apply(buy_proc_sig, SwapRole::Bob).is_err());
+    let (confirmed, _) = &messages[6];
+    assert!(state.apply(confirmed, SwapRole::Bob).is_err());
+    state.apply(confirmed, SwapRole::Alice).unwrap();
+    assert_eq!(state, SwapState::BuyProcedureSignature);
+
+    // abort ends the exchange
+    let abort = SwapMessage::Abort(Abort { error_body: None });
+    state.apply(&abort, SwapRole::Bob).unwrap();
+    assert_eq!(state, SwapState::Aborted);
+    assert!(state.apply(commit_bob, SwapRole::Bob).is_err());
+}
+
+#[test]
+fn cooperative_close_before_accordant_lock() {
+    let messages = swap_messages();
+    let (close, sig) = match &messages[4].0 {
+        SwapMessage::CoreArbitratingSetup(setup) => (setup.lock.clone(), setup.cancel_sig),
+        _ => unreachable!(),
+    };
+    let close = SwapMessage::CooperativeClose(CooperativeClose {
+        close,
+        alice_sig: sig,
+        bob_sig: sig,
+    });
+    assert_eq!(close.message_id(), MessageId::CooperativeClose);
+    let bytes = strict_serialize(&close).unwrap();
+    assert_eq!(
+        strict_deserialize::<SwapMessage<BtcXmr>>(&bytes).unwrap(),
+        close
+    );
+
+    // no lock to close before the core arbitrating setup
+    let mut state = SwapState::new();
+    for (msg, role) in messages[..4].iter() {
+        state.apply(msg, *role).unwrap();
+    }
+    assert!(state.apply(&close, SwapRole::Bob).is_err());
+    assert_eq!(state, SwapState::CoreArbitratingSetup);
+
+    // closed by either participant until Alice sends her refund signatures
+    for role in [SwapRole::Bob, SwapRole::Alice].iter() {
+        let mut state = SwapState::new();
+        for (msg, role) in messages[..5].iter() {
+            state.apply(msg, *role).unwrap();
+        }
+        state.apply(&close, *role).unwrap();
+        assert_eq!(state, SwapState::Closed);
+        assert!(state.apply(&messages[5].0, messages[5].1).is_err());
+    }
+
+    // closing would strand the accordant assets Alice may lock once she sent her refund
+    // signatures
+    for (end, expected) in [
+        (6, SwapState::AccordantFundingConfirmed),
+        (7, SwapState::BuyProcedureSignature),
+    ]
+    .iter()
+    {
+        let mut state = SwapState::new();
+        for (msg, role) in messages[..*end].iter() {
+            state.apply(msg, *role).unwrap();
+        }
+        assert_eq!(state, *expected);
+        assert!(state.apply(&close, SwapRole::Bob).is_err());
+        assert!(state.apply(&close, SwapRole::Alice).is_err());
+        assert_eq!(state, *expected);
+    }
+}
+
+fn exact_len<T: ProtocolMessage>(msg: &T) {
+    let len = msg.strict_encoded_len().unwrap();
+    let bytes = strict_serialize(msg).unwrap();
+    assert_eq!(len, bytes.len());
+    // the framed payload is the serialized message
+    let framed = frame(msg).unwrap();
+    assert_eq!(framed[framed.len() - len..], bytes[..]);
+}
+
+#[test]
+fn strict_encoded_len_is_exact() {
+    for (msg, _) in swap_messages().iter() {
+        match msg {
+            SwapMessage::CommitAliceParameters(msg) => exact_len(msg),
+            SwapMessage::CommitBobParameters(msg) => exact_len(msg),
+            SwapMessage::RevealAliceParameters(msg) => exact_len(msg),
+            SwapMessage::RevealBobParameters(msg) => exact_len(msg),
+            SwapMessage::CoreArbitratingSetup(msg) => exact_len(msg),
+            SwapMessage::RefundProcedureSignatures(msg) => exact_len(msg),
+            SwapMessage::BuyProcedureSignature(msg) => exact_len(msg),
+            SwapMessage::Abort(msg) => exact_len(msg),
+            SwapMessage::AccordantFundingConfirmed(msg) => exact_len(msg),
+            SwapMessage::CooperativeClose(msg) => exact_len(msg),
+        }
+    }
+    exact_len(&Abort {
+        error_body: Some(String::from("An error occured ;)")),
+    });
+}
+
+#[test]
+fn decode_swap_messages_from_stream() {
+    let messages: Vec<SwapMessage<BtcXmr>> =
+        swap_messages().into_iter().map(|(msg, _)| msg).collect();
+
+    // concatenated frames are decoded one after the other
+    let mut stream = Vec::new();
+    for msg in messages.iter() {
+        stream.extend_from_slice(&frame(msg).unwrap());
+    }
+    let mut buf = &stream[..];
+    for msg in messages.iter() {
+        let (decoded, consumed) = SwapMessage::<BtcXmr>::decode_frame(buf).unwrap().unwrap();
+        assert_eq!(&decoded, msg);
+        buf = &buf[consumed..];
+    }
+    assert!(buf.is_empty());
+
+    // partial buffers ask for more bytes, at any cut in the header or the payload
+    let bytes = frame(&messages[4]).unwrap();
+    for cut in 0..bytes.len() {
+        assert!(SwapMessage::<BtcXmr>::decode_frame(&bytes[..cut])
+            .unwrap()
+            .is_none());
+    }
+
+    // corrupt frames are hard errors
+    let mut corrupted = bytes.clone();
+    corrupted[16] ^= 0x01;
+    assert!(matches!(
+        SwapMessage::<BtcXmr>::decode_frame(&corrupted),
+        Err(consensus::Error::CorruptFrame)
+    ));
+    let unknown: Result<SwapMessage<BtcXmr>, _> = strict_deserialize(&[0xff, 0x00]);
+    assert!(unknown.is_err());
+}
+
+#[test]
+fn read_framed_messages_while_decoding() {
+    let messages: Vec<SwapMessage<BtcXmr>> =
+        swap_messages().into_iter().map(|(msg, _)| msg).collect();
+
+    let mut stream = Vec::new();
+    for msg in messages.iter() {
+        stream.extend_from_slice(&frame(msg).unwrap());
+    }
+    let mut reader = &stream[..];
+    for msg in messages.iter() {
+        assert_eq!(
+            &SwapMessage::<BtcXmr>::read_framed(&mut reader).unwrap(),
+            msg
+        );
+    }
+    assert!(reader.is_empty());
+
+    // corrupt frames are hard errors
+    let bytes = frame(&messages[4]).unwrap();
+    let mut corrupted = bytes.clone();
+    corrupted[9] ^= 0x01;
+    assert!(SwapMessage::<BtcXmr>::read_framed(&mut &corrupted[..]).is_err());
+    let mut corrupted = bytes.clone();
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 0x01;
+    assert!(matches!(
+        SwapMessage::<BtcXmr>::read_framed(&mut &corrupted[..]),
+        Err(consensus::Error::CorruptFrame)
+    ));
+
+    // a malformed cancel transaction fails before the refund transaction is read
+    let setup = match &messages[4] {
+        SwapMessage::CoreArbitratingSetup(setup) => setup,
+        _ => panic!("Expected the core arbitrating setup"),
+    };
+    let tx_bytes = strict_serialize(&setup.cancel).unwrap();
+    let magic = tx_bytes.windows(4).position(|w| w == b"psbt").unwrap();
+    let mut corrupted = bytes;
+    // frame header, message type and lock transaction precede the cancel transaction
+    corrupted[14 + 2 + tx_bytes.len() + magic] ^= 0x01;
+    let mut reader = &corrupted[..];
+    assert!(SwapMessage::<BtcXmr>::read_framed(&mut reader).is_err());
+    assert!(reader.len() > tx_bytes.len());
+}
+
+#[test]
+fn refund_adaptor_signature_commits_to_whole_transaction() {
+    let msg = swap_messages()
+        .into_iter()
+        .find_map(|(msg, _)| match msg {
+            SwapMessage::RefundProcedureSignatures(msg) => Some(msg),
+            _ => None,
+        })
+        .unwrap();
+    assert!(msg.into_adaptor_refund().is_ok());
+
+    // SIGHASH_SINGLE does not commit to all the outputs of the refund
+    let mut single = msg;
+    single.refund_sighash_type = 0x03;
+    let decoded: RefundProcedureSignatures<BtcXmr> =
+        strict_deserialize(&strict_serialize(&single).unwrap()).unwrap();
+    assert_eq!(decoded, single);
+    assert!(matches!(
+        decoded.verify_sighash_type(),
+        Err(Error::InvalidSigHashType(0x03))
+    ));
+    assert!(matches!(
+        decoded.into_adaptor_refund(),
+        Err(Error::InvalidSigHashType(0x03))
+    ));
+}
+
+#[test]
+fn refund_sighash_type_follows_refund_input() {
+    let (_, _, _, setup) = core_arbitrating_setup();
+    let refund = Tx::<Refund>::from_partial(setup.refund.clone());
+    let cancel_sig = CosignedArbitratingCancel {
+        cancel_sig: datum::Signature::new(
+            TxId::Cancel,
+            SwapRole::Alice,
+            SignatureType::Regular(setup.cancel_sig),
+        ),
+    };
+    let adaptor_sig = SignedAdaptorRefund {
+        refund_adaptor_sig: datum::Signature::new(
+            TxId::Refund,
+            SwapRole::Alice,
+            SignatureType::Adaptor(
+                refund
+                    .generate_adaptor_witness(&privkey(3), &adaptor_key(9))
+                    .unwrap(),
+            ),
+        ),
+    };
+
+    // the type is read from the refund input
+    let msg =
+        RefundProcedureSignatures::<BtcXmr>::from_bundles(&cancel_sig, &adaptor_sig, &setup.refund)
+            .unwrap();
+    assert_eq!(msg.refund_sighash_type, SIGHASH_ALL);
+    msg.verify_refund_sighash_type(&setup.refund).unwrap();
+
+    // the message type must match the refund input, even when it is SIGHASH_ALL
+    let mut single = setup.refund.clone();
+    single.inputs[0].sighash_type = Some(SigHashType::Single);
+    assert!(matches!(
+        msg.verify_refund_sighash_type(&single),
+        Err(Error::InvalidSigHashType(0x03))
+    ));
+    let msg = RefundProcedureSignatures::<BtcXmr>::from_bundles(&cancel_sig, &adaptor_sig, &single)
+        .unwrap();
+    assert_eq!(msg.refund_sighash_type, 0x03);
+    assert!(matches!(
+        msg.verify_refund_sighash_type(&single),
+        Err(Error::InvalidSigHashType(0x03))
+    ));
+
+    // a refund without signature hash type is not a valid template
+    let mut unset = setup.refund;
+    unset.inputs[0].sighash_type = None;
+    assert!(matches!(
+        RefundProcedureSignatures::<BtcXmr>::from_bundles(&cancel_sig, &adaptor_sig, &unset),
+        Err(Error::InvalidCoreTransaction(
+            TxId::Refund,
+            TxError::WrongTemplate
+        ))
+    ));
+}
+
+/// Returns the adaptor secret linked to the accordant spend key derived from the seed `[byte; 32]`.
+fn adaptor_privkey(byte: u8) -> PrivateKey {
+    RingProof::project_over(&[byte; 32]).unwrap()
+}
+
+fn adaptor_key(byte: u8) -> PublicKey {
+    let secp = Secp256k1::new();
+    PublicKey::from_private_key(&secp, &adaptor_privkey(byte))
+}
+
+/// Returns a public offer, both participants' reveals, and Bob's valid core arbitrating setup.
+fn core_arbitrating_setup() -> (
+    PublicOffer<BtcXmr>,
+    RevealAliceParameters<BtcXmr>,
+    RevealBobParameters<BtcXmr>,
+    CoreArbitratingSetup<BtcXmr>,
+) {
+    let hex = "46435357415001000200000080800000800800a0860100000000000800c80000000000000004000\
+               a00000004000a000000010800140000000000000002030000000300000001000000020000000100\
+               0000010000000a00000003b31a0a70343bb46f3db3768296ac5027f9873921b37f852860c690063\
+               ff9e4c9000000000000000000000000000000000000000000000000000000000000000000000026\
+               0700";
+    let pub_offer: PublicOffer<BtcXmr> = deserialize(&hex::decode(hex).unwrap()[..]).unwrap();
+    let offer = &pub_offer.offer;
+
+    let address = bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+        .expect("Parsable address");
+    let view = private_spend_from_seed(&[1u8; 32]).unwrap();
+    let (alice_spend, _, alice_proof) = RingProof::generate(&[5u8; 32]).unwrap();
+    let (bob_spend, _, bob_proof) = RingProof::generate(&[9u8; 32]).unwrap();
+    let reveal_alice = RevealAliceParameters::<BtcXmr> {
+        buy: pubkey(1),
+        cancel: pubkey(2),
+        refund: pubkey(3),
+        punish: pubkey(4),
+        adaptor: adaptor_key(5),
+        address: address.clone().into(),
+        spend: alice_spend,
+        view: Secret::new(view.into()),
+        proof: alice_proof,
+    };
+    let reveal_bob = RevealBobParameters::<BtcXmr> {
+        buy: pubkey(6),
+        cancel: pubkey(7),
+        refund: pubkey(8),
+        adaptor: adaptor_key(9),
+        address: address.clone().into(),
+        spend: bob_spend,
+        view: Secret::new(view.into()),
+        proof: bob_proof,
+    };
+
+    // Bob's core arbitrating transactions
+    let data_lock = DataLock {
+        timelock: offer.cancel_timelock,
+        success: DoubleKeys::new(pubkey(1), pubkey(6)),
+        failure: DoubleKeys::new(pubkey(2), pubkey(7)),
+    };
+    let punish_lock = DataPunishableLock {
+        timelock: offer.punish_timelock,
+        success: DoubleKeys::new(pubkey(3), pubkey(8)),
+        failure: pubkey(4),
+    };
+    let mut funding = Funding::initialize(pubkey(10), Network::Mainnet).unwrap();
+    let funding_script = funding.get_address().unwrap().0.script_pubkey();
+    funding
+        .update(Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: offer.arbitrating_amount.as_sat(),
+                script_pubkey: funding_script,
+            }],
+        })
+        .unwrap();
+    let mut lock =
+        Tx::<Lock>::initialize(&funding, data_lock.clone(), offer.arbitrating_amount).unwrap();
+    // fund the lock with the exact fee required by the fixed strategy
+    let fee = match &offer.fee_strategy {
+        FeeStrategy::Fixed(rate) => rate
+            .as_native_unit()
+            .checked_mul(fee_vsize(lock.partial()))
+            .unwrap(),
+        _ => unreachable!(),
+    };
+    lock.partial_mut().inputs[0]
+        .witness_utxo
+        .as_mut()
+        .unwrap()
+        .value += fee.as_sat();
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        data_lock,
+        punish_lock.clone(),
+        &offer.fee_strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let refund = Tx::<Refund>::initialize(
+        &cancel,
+        punish_lock,
+        address.into(),
+        &offer.fee_strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    let ecdsa_sig = "3045022100b75f569de3e57f4f445bcf9e42be9e5b5128f317ab86e451fdfe7be5ffd6a7da0220776b30307b5d761512635dc0394573be7fe17b5300b160340dae370b641bc4ca";
+    let setup = CoreArbitratingSetup::<BtcXmr> {
+        lock: lock.to_partial(),
+        cancel: cancel.to_partial(),
+        refund: refund.to_partial(),
+        cancel_sig: Signature::from_der(&hex::decode(ecdsa_sig).unwrap()).unwrap(),
+    };
+    (pub_offer, reveal_alice, reveal_bob, setup)
+}
+
+#[test]
+fn verify_core_arbitrating_setup() {
+    let (pub_offer, reveal_alice, reveal_bob, setup) = core_arbitrating_setup();
+    let alice_params = reveal_alice.into_bundle();
+    let bob_params = reveal_bob.clone().into_bundle();
+    assert!(setup.verify(&alice_params, &bob_params, &pub_offer).is_ok());
+    assert!(setup.verify_chain().is_ok());
+
+    // the cancel must spend the lock
+    let mut unlinked = setup.clone();
+    unlinked.cancel = unlinked.refund.clone();
+    assert!(matches!(
+        unlinked.verify(&alice_params, &bob_params, &pub_offer),
+        Err(Error::InvalidCoreTransaction(TxId::Cancel, _))
+    ));
+    assert!(matches!(
+        unlinked.verify_chain(),
+        Err(Error::InvalidCoreTransaction(
+            TxId::Cancel,
+            TxError::InvalidLink
+        ))
+    ));
+
+    // the refund must spend the cancel
+    let mut unlinked = setup.clone();
+    unlinked.refund = unlinked.cancel.clone();
+    assert!(matches!(
+        unlinked.verify(&alice_params, &bob_params, &pub_offer),
+        Err(Error::InvalidCoreTransaction(TxId::Refund, _))
+    ));
+    assert!(matches!(
+        unlinked.verify_chain(),
+        Err(Error::InvalidCoreTransaction(
+            TxId::Refund,
+            TxError::InvalidLink
+        ))
+    ));
+
+    // the refund must spend the amount of the cancel output
+    let mut unlinked = setup.clone();
+    unlinked.refund.inputs[0]
+        .witness_utxo
+        .as_mut()
+        .unwrap()
+        .value += 1;
+    assert!(matches!(
+        unlinked.verify_chain(),
+        Err(Error::InvalidCoreTransaction(
+            TxId::Refund,
+            TxError::InvalidTransactionChain
+        ))
+    ));
+
+    // the refund cannot pay out more than it spends
+    let mut unlinked = setup.clone();
+    unlinked.refund.global.unsigned_tx.output[0].value =
+        setup.cancel.global.unsigned_tx.output[0].value + 1;
+    assert!(matches!(
+        unlinked.verify_chain(),
+        Err(Error::InvalidCoreTransaction(
+            TxId::Refund,
+            TxError::NotEnoughAssets
+        ))
+    ));
+
+    // the lock must use the keys from the parameters
+    let other_bob = RevealBobParameters::<BtcXmr> {
+        buy: pubkey(11),
+        ..reveal_bob.clone()
+    }
+    .into_bundle();
+    assert!(matches!(
+        setup.verify(&alice_params, &other_bob, &pub_offer),
+        Err(Error::InvalidCoreTransaction(TxId::Lock, _))
+    ));
+
+    // the cancel must lock the funds in the punishable lock of the parameters
+    let other_alice = RevealAliceParameters::<BtcXmr> {
+        punish: pubkey(12),
+        ..reveal_alice.clone()
+    }
+    .into_bundle();
+    assert!(matches!(
+        setup.verify(&other_alice, &bob_params, &pub_offer),
+        Err(Error::InvalidCoreTransaction(
+            TxId::Cancel,
+            TxError::WrongTemplate
+        ))
+    ));
+
+    // the cancel must spend the swaplock through its timelocked path
+    let mut wrong_sequence = setup.clone();
+    wrong_sequence.cancel.global.unsigned_tx.input[0].sequence = 0;
+    assert!(matches!(
+        wrong_sequence.verify(&alice_params, &bob_params, &pub_offer),
+        Err(Error::InvalidCoreTransaction(TxId::Cancel, _))
+    ));
+
+    // the refund must pay Bob's refund address
+    let other_address = bitcoin::Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+        .expect("Parsable address");
+    let other_bob = RevealBobParameters::<BtcXmr> {
+        address: other_address.into(),
+        ..reveal_bob
+    }
+    .into_bundle();
+    assert!(matches!(
+        setup.verify(&alice_params, &other_bob, &pub_offer),
+        Err(Error::InvalidCoreTransaction(
+            TxId::Refund,
+            TxError::WrongTemplate
+        ))
+    ));
+}
+
+#[test]
+fn observe_swap_from_public_messages() {
+    let (pub_offer, reveal_alice, reveal_bob, setup) = core_arbitrating_setup();
+    let commit_alice = CommitAliceParameters::from_bundle(&reveal_alice.clone().into_bundle());
+    let commit_bob = CommitBobParameters::from_bundle(&reveal_bob.clone().into_bundle());
+    // Alice's cancel signature and refund signature encrypted with Bob's adaptor key
+    let cancel_sig = Tx::<Cancel>::from_partial(setup.cancel.clone())
+        .generate_failure_witness(&privkey(2))
+        .unwrap();
+    let refund = Tx::<Refund>::from_partial(setup.refund.clone());
+    let refund_adaptor_sig = refund
+        .generate_adaptor_witness(&privkey(3), &adaptor_key(9))
+        .unwrap();
+    // Bob's buy paying Alice, signed and encrypted with Alice's adaptor key
+    let data_lock = DataLock {
+        timelock: pub_offer.offer.cancel_timelock,
+        success: DoubleKeys::new(pubkey(1), pubkey(6)),
+        failure: DoubleKeys::new(pubkey(2), pubkey(7)),
+    };
+    let lock = Tx::<Lock>::from_partial(setup.lock.clone());
+    let mut buy = Tx::<Buy>::initialize(&lock, data_lock, reveal_alice.address.clone()).unwrap();
+    Bitcoin::set_fee(
+        buy.partial_mut(),
+        &pub_offer.offer.fee_strategy,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let buy_adaptor_sig = buy
+        .generate_adaptor_witness(&privkey(6), &adaptor_key(5))
+        .unwrap();
+
+    let mut observer = SwapObserver::new(pub_offer);
+    assert_eq!(observer.current_phase(), SwapPhase::Commit);
+    assert!(observer.watched_addresses().is_empty());
+
+    // a reveal must follow its commitment
+    let early = SwapMessage::RevealAliceParameters(reveal_alice.clone());
+    assert!(observer.ingest(&early, SwapRole::Alice).is_err());
+
+    let msg = SwapMessage::CommitAliceParameters(commit_alice);
+    observer.ingest(&msg, SwapRole::Alice).unwrap();
+    let msg = SwapMessage::CommitBobParameters(commit_bob);
+    observer.ingest(&msg, SwapRole::Bob).unwrap();
+    assert_eq!(observer.current_phase(), SwapPhase::Reveal);
+
+    // revealed parameters must match the commitment
+    let other_alice = RevealAliceParameters::<BtcXmr> {
+        buy: pubkey(11),
+        ..reveal_alice.clone()
+    };
+    let msg = SwapMessage::RevealAliceParameters(other_alice);
+    assert!(observer.ingest(&msg, SwapRole::Alice).is_err());
+    let state = SwapState::Reveal {
+        alice: false,
+        bob: false,
+    };
+    assert_eq!(observer.state(), state);
+
+    let msg = SwapMessage::RevealAliceParameters(reveal_alice.clone());
+    observer.ingest(&msg, SwapRole::Alice).unwrap();
+    let msg = SwapMessage::RevealBobParameters(reveal_bob);
+    observer.ingest(&msg, SwapRole::Bob).unwrap();
+    assert_eq!(observer.current_phase(), SwapPhase::CoreSetup);
+    assert!(observer.alice_parameters().is_some() && observer.bob_parameters().is_some());
+    assert_eq!(observer.watched_addresses(), vec![reveal_alice.address]);
+
+    // the core arbitrating transactions must form a chain
+    let mut unlinked = setup.clone();
+    unlinked.refund = unlinked.cancel.clone();
+    let msg = SwapMessage::CoreArbitratingSetup(unlinked);
+    assert!(matches!(
+        observer.ingest(&msg, SwapRole::Bob),
+        Err(Error::InvalidCoreTransaction(TxId::Refund, _))
+    ));
+    assert!(observer.core_arbitrating_setup().is_none());
+
+    // the refund must pay Bob's refund address
+    let mut wrong_refund = setup.clone();
+    let other_address = bitcoin::Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+        .expect("Parsable address");
+    wrong_refund.refund.global.unsigned_tx.output[0].script_pubkey = other_address.script_pubkey();
+    let msg = SwapMessage::CoreArbitratingSetup(wrong_refund);
+    assert!(matches!(
+        observer.ingest(&msg, SwapRole::Bob),
+        Err(Error::InvalidCoreTransaction(
+            TxId::Refund,
+            TxError::WrongTemplate
+        ))
+    ));
+    assert!(observer.core_arbitrating_setup().is_none());
+    assert_eq!(observer.state(), SwapState::CoreArbitratingSetup);
+
+    let msg = SwapMessage::CoreArbitratingSetup(setup.clone());
+    observer.ingest(&msg, SwapRole::Bob).unwrap();
+    assert_eq!(observer.core_arbitrating_setup(), Some(&setup));
+    assert_eq!(observer.state(), SwapState::RefundProcedureSignatures);
+
+    let mut sigs = RefundProcedureSignatures::<BtcXmr> {
+        cancel_sig,
+        refund_adaptor_sig: refund_adaptor_sig.clone(),
+        refund_sighash_type: SIGHASH_ALL,
+    };
+    let rejected = |sigs: &RefundProcedureSignatures<BtcXmr>, observer: &mut SwapObserver<_>| {
+        let msg = SwapMessage::RefundProcedureSignatures(sigs.clone());
+        observer.ingest(&msg, SwapRole::Alice).is_err()
+            && observer.state() == SwapState::RefundProcedureSignatures
+    };
+    // the refund adaptor signature must commit to the whole transaction
+    sigs.refund_sighash_type = 0x83;
+    assert!(rejected(&sigs, &mut observer));
+    sigs.refund_sighash_type = SIGHASH_ALL;
+    // the cancel must be signed by Alice, not Bob
+    let bob_cancel = RefundProcedureSignatures {
+        cancel_sig: setup.cancel_sig,
+        ..sigs.clone()
+    };
+    assert!(rejected(&bob_cancel, &mut observer));
+    // the refund must be encrypted with Bob's adaptor key
+    let other_adaptor = RefundProcedureSignatures {
+        refund_adaptor_sig: refund
+            .generate_adaptor_witness(&privkey(3), &adaptor_key(5))
+            .unwrap(),
+        ..sigs.clone()
+    };
+    assert!(rejected(&other_adaptor, &mut observer));
+
+    observer
+        .ingest(
+            &SwapMessage::RefundProcedureSignatures(sigs),
+            SwapRole::Alice,
+        )
+        .unwrap();
+    assert_eq!(observer.current_phase(), SwapPhase::CoreSetup);
+
+    let msg = SwapMessage::AccordantFundingConfirmed(AccordantFundingConfirmed {
+        lock_txid: vec![0xab; 32],
+        block_height: 2_400_000,
+    });
+    observer.ingest(&msg, SwapRole::Alice).unwrap();
+    assert_eq!(observer.current_phase(), SwapPhase::Locked);
+
+    // an aborted observer stops at its last phase
+    let mut aborted = observer.clone();
+    let abort = SwapMessage::Abort(Abort { error_body: None });
+    aborted.ingest(&abort, SwapRole::Bob).unwrap();
+    assert_eq!(aborted.current_phase(), SwapPhase::Aborted);
+    assert!(aborted.ingest(&abort, SwapRole::Alice).is_err());
+
+    // the buy must spend the lock and be signed by Bob, encrypted with Alice's adaptor key
+    let rejected = [
+        BuyProcedureSignature {
+            buy: setup.lock.clone(),
+            buy_adaptor_sig: buy_adaptor_sig.clone(),
+        },
+        BuyProcedureSignature {
+            buy: buy.to_partial(),
+            buy_adaptor_sig: refund_adaptor_sig,
         },
+        BuyProcedureSignature {
+            buy: buy.to_partial(),
+            buy_adaptor_sig: buy
+                .generate_adaptor_witness(&privkey(1), &adaptor_key(5))
+                .unwrap(),
+        },
+    ];
+    for msg in rejected.iter() {
+        let msg = SwapMessage::BuyProcedureSignature(msg.clone());
+        assert!(observer.ingest(&msg, SwapRole::Bob).is_err());
+        assert_eq!(observer.current_phase(), SwapPhase::Locked);
+    }
+
+    let msg = SwapMessage::BuyProcedureSignature(BuyProcedureSignature {
+        buy: buy.to_partial(),
+        buy_adaptor_sig,
+    });
+    observer.ingest(&msg, SwapRole::Bob).unwrap();
+    assert_eq!(observer.current_phase(), SwapPhase::Completed);
+}
+
+#[test]
+fn refund_adaptor_signature_from_alice_to_bob() {
+    let (_, reveal_alice, reveal_bob, setup) = core_arbitrating_setup();
+    let alice_params = reveal_alice.into_bundle();
+    let bob_params = reveal_bob.clone().into_bundle();
+    let core = setup.into_core_transactions();
+    let alice_refund = pubkey(3);
+    let bob_adaptor = adaptor_key(9);
+
+    // Alice signs the refund with her refund key, encrypted with Bob's adaptor key
+    let mut refund = Tx::<Refund>::from_partial(setup.refund.clone());
+    let adaptor_sig = refund
+        .generate_adaptor_witness(&privkey(3), &bob_adaptor)
+        .unwrap();
+    let signed = |sig| SignedAdaptorRefund {
+        refund_adaptor_sig: datum::Signature::new(
+            TxId::Refund,
+            SwapRole::Alice,
+            SignatureType::Adaptor(sig),
+        ),
+    };
+
+    // Bob verifies it against the revealed parameters
+    let bob: Bob<BtcXmr> = Bob::new(reveal_bob.address, FeePolitic::Aggressive);
+    assert!(bob
+        .validate_adaptor_refund(
+            &alice_params,
+            &bob_params,
+            &core,
+            &signed(adaptor_sig.clone())
+        )
+        .is_ok());
+
+    // signed by another key
+    let other = refund
+        .generate_adaptor_witness(&privkey(4), &bob_adaptor)
+        .unwrap();
+    assert!(bob
+        .validate_adaptor_refund(&alice_params, &bob_params, &core, &signed(other))
+        .is_err());
+    // encrypted with another adaptor key
+    let other = refund
+        .generate_adaptor_witness(&privkey(3), &adaptor_key(5))
+        .unwrap();
+    assert!(refund
+        .verify_adaptor_witness(&alice_refund, &bob_adaptor, other)
+        .is_err());
+    // with a forged proof
+    let mut forged = adaptor_sig.clone();
+    forged.dleq.challenge = forged.dleq.response;
+    assert!(matches!(
+        forged.verify(
+            &alice_refund,
+            &Message::from_slice(&refund.witness_sighash().unwrap()).unwrap()
+        ),
+        Err(crypto::Error::InvalidProof)
+    ));
+    assert!(refund
+        .verify_adaptor_witness(&alice_refund, &bob_adaptor, forged)
+        .is_err());
+    // over another transaction
+    let mut tampered = setup.refund.clone();
+    tampered.global.unsigned_tx.output[0].value -= 1;
+    assert!(Tx::<Refund>::from_partial(tampered)
+        .verify_adaptor_witness(&alice_refund, &bob_adaptor, adaptor_sig.clone())
+        .is_err());
+
+    // Bob decrypts Alice's signature with his adaptor secret and broadcasts the refund
+    let adapted = Bitcoin::adapt(&adaptor_privkey(9), adaptor_sig.clone()).unwrap();
+    assert!(refund.verify_witness(&alice_refund, adapted).is_ok());
+    refund.add_witness(alice_refund, adapted).unwrap();
+    let bob_sig = refund.generate_witness(&privkey(8)).unwrap();
+    refund.add_witness(pubkey(8), bob_sig).unwrap();
+    let tx = refund.finalize_and_extract().unwrap();
+
+    // Alice recovers Bob's adaptor secret from the refund seen on-chain
+    let secret = recover_adaptor_secret_from_tx(&tx, &adaptor_sig).unwrap();
+    assert_eq!(secret.key, adaptor_privkey(9).key);
+}
+
+#[test]
+fn decode_errors_name_the_failing_field() {
+    let setup = swap_messages()
+        .into_iter()
+        .find_map(|(msg, _)| match msg {
+            SwapMessage::CoreArbitratingSetup(setup) => Some(setup),
+            _ => None,
+        })
+        .unwrap();
+    let bytes = strict_serialize(&setup).unwrap();
+    let decoded: CoreArbitratingSetup<BtcXmr> = deserialize(&bytes).unwrap();
+    assert_eq!(decoded, setup);
+
+    // truncate the message right after the lock transaction
+    let lock_len = strict_serialize(&setup.lock).unwrap().len();
+    let truncated = &bytes[..lock_len + 1];
+    let err = deserialize::<CoreArbitratingSetup<BtcXmr>>(truncated).unwrap_err();
+    assert!(matches!(
+        err,
+        consensus::Error::Field { name: "cancel", .. }
+    ));
+    assert_eq!(err.field_path(), vec!["cancel"]);
+
+    // the field is kept in the strict encoding error message
+    let res: Result<CoreArbitratingSetup<BtcXmr>, _> = strict_deserialize(&truncated);
+    assert!(res.unwrap_err().to_string().contains("cancel"));
+}
+
+#[test]
+fn partial_transactions_are_bounded() {
+    let buy = swap_messages()
+        .into_iter()
+        .find_map(|(msg, _)| match msg {
+            SwapMessage::BuyProcedureSignature(buy) => Some(buy),
+            _ => None,
+        })
+        .unwrap();
+    let bytes = strict_serialize(&buy).unwrap();
+    let decoded: BuyProcedureSignature<BtcXmr> = deserialize(&bytes).unwrap();
+    assert_eq!(decoded, buy);
+
+    // a transaction longer than the cap fails on its field
+    let buy_len = strict_serialize(&buy.buy).unwrap().len();
+    assert!(buy_len <= MAX_PARTIAL_TRANSACTION_LEN);
+    let err = consensus::strict_decode_bounded_field::<PartiallySignedTransaction, _>(
+        "buy",
+        &mut &bytes[..],
+        buy_len - 1,
+    )
+    .unwrap_err();
+    assert_eq!(err.field_path(), vec!["buy"]);
+}
+
+fn unsigned_psbt(lock_time: u32) -> PartiallySignedTransaction {
+    PartiallySignedTransaction::from_unsigned_tx(Transaction {
+        version: 2,
+        lock_time,
+        input: Vec::new(),
+        output: Vec::new(),
+    })
+    .unwrap()
+}
+
+fn concat_fields(fields: &[Vec<u8>]) -> Vec<u8> {
+    fields.iter().flatten().cloned().collect()
+}
+
+#[test]
+fn core_arbitrating_setup_golden_encoding() {
+    let setup = CoreArbitratingSetup::<BtcXmr> {
+        lock: unsigned_psbt(0),
+        cancel: unsigned_psbt(1),
+        refund: unsigned_psbt(2),
+        cancel_sig: Signature::from_compact(&[0x01; 64]).unwrap(),
+    };
+
+    // lock, cancel and refund as consensus serialized PSBTs, then the compact cancel signature
+    let golden = "70736274ff01000a020000000000000000000070736274ff01000a02000000000001000000007073\
+                  6274ff01000a02000000000002000000000101010101010101010101010101010101010101010101\
+                  01010101010101010101010101010101010101010101010101010101010101010101010101010101\
+                  01";
+    let bytes = strict_serialize(&setup).unwrap();
+    assert_eq!(hex::encode(&bytes), golden);
+    assert_eq!(
+        strict_deserialize::<CoreArbitratingSetup<BtcXmr>>(&bytes).unwrap(),
+        setup
+    );
+}
+
+#[test]
+fn reveal_parameters_encoding_follows_field_order() {
+    let address = bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+        .expect("Parsable address");
+    let view = private_spend_from_seed(&[1u8; 32]).unwrap();
+    let spend = monero::PublicKey::from_private_key(&private_spend_from_seed(&[2u8; 32]).unwrap());
+    let proof = RingProof {
+        challenges: vec![[0x03; 32]],
+        ..Default::default()
+    };
+
+    // every field holds a distinct value, swapping two fields changes the encoding
+    let reveal_alice = RevealAliceParameters::<BtcXmr> {
+        buy: pubkey(1),
+        cancel: pubkey(2),
+        refund: pubkey(3),
+        punish: pubkey(4),
+        adaptor: pubkey(5),
+        address: address.clone().into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof: proof.clone(),
+    };
+    let fields = concat_fields(&[
+        strict_serialize(&reveal_alice.buy).unwrap(),
+        strict_serialize(&reveal_alice.cancel).unwrap(),
+        strict_serialize(&reveal_alice.refund).unwrap(),
+        strict_serialize(&reveal_alice.punish).unwrap(),
+        strict_serialize(&reveal_alice.adaptor).unwrap(),
+        strict_serialize(&reveal_alice.address).unwrap(),
+        strict_serialize(&reveal_alice.spend).unwrap(),
+        strict_serialize(&reveal_alice.view).unwrap(),
+        strict_serialize(&reveal_alice.proof).unwrap(),
+    ]);
+    assert_eq!(strict_serialize(&reveal_alice).unwrap(), fields);
+    // the arbitrating keys are compressed points and lead the message
+    assert_eq!(&fields[..33], &pubkey(1).key.serialize()[..]);
+    assert_eq!(&fields[33 * 4..33 * 5], &pubkey(5).key.serialize()[..]);
+
+    let reveal_bob = RevealBobParameters::<BtcXmr> {
+        buy: pubkey(6),
+        cancel: pubkey(7),
+        refund: pubkey(8),
+        adaptor: pubkey(9),
+        address: address.into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof,
+    };
+    let fields = concat_fields(&[
+        strict_serialize(&reveal_bob.buy).unwrap(),
+        strict_serialize(&reveal_bob.cancel).unwrap(),
+        strict_serialize(&reveal_bob.refund).unwrap(),
+        strict_serialize(&reveal_bob.adaptor).unwrap(),
+        strict_serialize(&reveal_bob.address).unwrap(),
+        strict_serialize(&reveal_bob.spend).unwrap(),
+        strict_serialize(&reveal_bob.view).unwrap(),
+        strict_serialize(&reveal_bob.proof).unwrap(),
+    ]);
+    assert_eq!(strict_serialize(&reveal_bob).unwrap(), fields);
+    assert_eq!(&fields[..33], &pubkey(6).key.serialize()[..]);
+    // the message ends with the proof
+    let proof = strict_serialize(&reveal_bob.proof).unwrap();
+    assert_eq!(&fields[fields.len() - proof.len()..], &proof[..]);
+}
+
+#[test]
+fn reveal_parameters_golden_encoding() {
+    let address = bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+        .expect("Parsable address");
+    let view = monero::PrivateKey::from_slice(&[0x01; 32]).unwrap();
+    let spend =
+        monero::PublicKey::from_private_key(&monero::PrivateKey::from_slice(&[0x02; 32]).unwrap());
+    let proof = RingProof {
+        challenges: vec![[0x03; 32]],
+        ..Default::default()
+    };
+
+    let reveal_alice = RevealAliceParameters::<BtcXmr> {
+        buy: pubkey(1),
+        cancel: pubkey(2),
+        refund: pubkey(3),
+        punish: pubkey(4),
+        adaptor: pubkey(5),
+        address: address.clone().into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof: proof.clone(),
+    };
+    // buy, cancel, refund, punish and adaptor compressed keys, the u16 length prefixed address
+    // string, the spend public key, the view private key, then the proof with its u16 counts
+    let golden = "031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f024d4b6cd13610\
+                  32ca9bd2aeb9d900aa4d45d9ead80ac9423374c451a7254d076602531fe6068134503d2723133227\
+                  c867ac8fa6c83c537e9a44c3c5bdbdcb1fe33703462779ad4aad39514614751a71085f2f10e1c7a5\
+                  93e4e030efb5b8721ce55b0b0362c0a046dacce86ddd0343c6d3c7c79c2208ba0d9c9cf24a6d046d\
+                  21d21f90f72a0062633171657367767479783979366c6178307833346e617063326d3774357a6471\
+                  36733778787770766b179a12fc164f7a2f37dbcb70fb392caba621c9043f1e481c832447dc4f171a\
+                  4f010101010101010101010101010101010101010101010101010101010101010100000100030303\
+                  03030303030303030303030303030303030303030303030303030303030000";
+    let bytes = strict_serialize(&reveal_alice).unwrap();
+    assert_eq!(hex::encode(&bytes), golden);
+    assert_eq!(
+        strict_deserialize::<RevealAliceParameters<BtcXmr>>(&hex::decode(golden).unwrap()).unwrap(),
+        reveal_alice
+    );
+
+    let reveal_bob = RevealBobParameters::<BtcXmr> {
+        buy: pubkey(6),
+        cancel: pubkey(7),
+        refund: pubkey(8),
+        adaptor: pubkey(9),
+        address: address.into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof,
     };
+    // same layout as Alice's reveal without the punish key
+    let golden = "03f006a18d5653c4edf5391ff23a61f03ff83d237e880ee61187fa9f379a028e0a02989c0b76cb56\
+                  3971fdc9bef31ec06c3560f3249d6ee9e5d83c57625596e05f6f03f991f944d1e1954a7fc8b9bf62\
+                  e0d78f015f4c07762d505e20e6c45260a3661b0256b328b30c8bf5839e24058747879408bdb36241\
+                  dc9c2e7c619faa12b29209672a0062633171657367767479783979366c6178307833346e61706332\
+                  6d3774357a647136733778787770766b179a12fc164f7a2f37dbcb70fb392caba621c9043f1e481c\
+                  832447dc4f171a4f0101010101010101010101010101010101010101010101010101010101010101\
+                  0000010003030303030303030303030303030303030303030303030303030303030303030000";
+    let bytes = strict_serialize(&reveal_bob).unwrap();
+    assert_eq!(hex::encode(&bytes), golden);
+    assert_eq!(
+        strict_deserialize::<RevealBobParameters<BtcXmr>>(&hex::decode(golden).unwrap()).unwrap(),
+        reveal_bob
+    );
 }
@@ -0,0 +1,85 @@
+use bitcoin::consensus::encode::serialize;
+use bitcoin::util::key::PrivateKey;
+
+use farcaster_chains::bitcoin::fee::{fee_vsize, SatPerVByte};
+use farcaster_chains::bitcoin::transaction::{Cancel, Lock, Punish, Tx};
+use farcaster_chains::bitcoin::{Address, Amount, CSVTimelock};
+
+use farcaster_core::blockchain::{FeePolitic, FeeStrategy};
+use farcaster_core::script::{DataPunishableLock, DoubleKeys};
+use farcaster_core::transaction::{
+    Broadcastable, Cancelable, Finalizable, Forkable, Lockable, Punishable, Transaction,
+    Witnessable,
+};
+
+mod common;
+use common::{funding, pubkey, swap_lock};
+
+/// Signs the punish transaction with the punisher key, finalizes it, and checks the spend of the
+/// cancel output against the consensus rules.
+fn verify_punish(
+    mut punish: Tx<Punish>,
+    punisher: &PrivateKey,
+    cancel: &Tx<Cancel>,
+) -> Result<(), bitcoin::blockdata::script::Error> {
+    let sig = punish.generate_failure_witness(punisher).unwrap();
+    punish.add_witness(pubkey(3), sig).unwrap();
+    punish.finalize().unwrap();
+
+    let spent = &cancel.partial().global.unsigned_tx.output[0];
+    spent
+        .script_pubkey
+        .verify(0, spent.value, &serialize(&punish.extract()))
+}
+
+#[test]
+fn punish_spends_after_timelock() {
+    let alice = pubkey(1);
+    let bob = pubkey(2);
+
+    let funding = funding(bob);
+
+    let datalock = swap_lock(alice, bob);
+    let punish_lock = DataPunishableLock {
+        timelock: CSVTimelock::new(20),
+        success: DoubleKeys::new(alice, bob),
+        failure: pubkey(3),
+    };
+
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    let destination =
+        Address(bitcoin::Address::p2wpkh(&pubkey(4), bitcoin::Network::Regtest).unwrap());
+    let punish = Tx::<Punish>::initialize_with_fee(
+        &cancel,
+        punish_lock,
+        destination,
+        &FeeStrategy::Fixed(SatPerVByte::from_sat(2)),
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let punish_tx = &punish.partial().global.unsigned_tx;
+    assert_eq!(punish_tx.input[0].sequence, 20);
+    // the fee is paid from the cancel output
+    let fee = 2 * fee_vsize(punish.partial());
+    assert_eq!(
+        punish_tx.output[0].value,
+        cancel.partial().global.unsigned_tx.output[0].value - fee
+    );
+
+    // the timelock is reached, the CSV failure path is valid
+    verify_punish(punish.clone(), &privkey(3), &cancel).unwrap();
+
+    // the timelock is not reached yet, the spend is rejected
+    let mut early = punish;
+    early.partial_mut().global.unsigned_tx.input[0].sequence = 19;
+    assert!(verify_punish(early, &privkey(3), &cancel).is_err());
+}
@@ -0,0 +1,393 @@
+use bitcoin::blockdata::opcodes;
+use bitcoin::blockdata::script::{Builder, Script};
+use bitcoin::blockdata::transaction::{OutPoint, TxIn, TxOut};
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::key::{PrivateKey, PublicKey};
+
+use farcaster_chains::bitcoin::descriptor;
+use farcaster_chains::bitcoin::transaction::lock::{parse_swaplock_keys, swaplock_script};
+use farcaster_chains::bitcoin::transaction::{Cancel, Error, Funding, Lock, Tx};
+use farcaster_chains::bitcoin::{
+    Amount, Bitcoin, CSVTimelock, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_TYPE_FLAG,
+};
+
+use farcaster_core::blockchain::{FeePolitic, FeeStrategy, Network};
+use farcaster_core::script::{DataLock, DataPunishableLock, DoubleKeys};
+use farcaster_core::transaction::{Cancelable, Fundable, Lockable, Transaction};
+
+fn pubkey() -> PublicKey {
+    let secp = Secp256k1::new();
+    let privkey: PrivateKey =
+        PrivateKey::from_wif("L1HKVVLHXiUhecWnwFYF6L3shkf1E12HUmuZTESvBXUdx3yqVP1D").unwrap();
+    PublicKey::from_private_key(&secp, &privkey)
+}
+
+#[test]
+fn block_based_timelocks_expiry() {
+    let pubkey = pubkey();
+
+    let lock = DataLock::<Bitcoin> {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: DoubleKeys::new(pubkey, pubkey),
+    };
+    assert_eq!(lock.cancel_available_at(680_000), 680_010);
+
+    let punish_lock = DataPunishableLock::<Bitcoin> {
+        timelock: CSVTimelock::new(144),
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: pubkey,
+    };
+    assert_eq!(punish_lock.punish_available_at(680_010), 680_154);
+    assert_eq!(punish_lock.punish_available_at(u32::MAX), u32::MAX);
+}
+
+#[test]
+fn time_based_timelocks_expiry() {
+    let pubkey = pubkey();
+
+    // ten units of 512 seconds after the median time past of the lock block
+    let lock = DataLock::<Bitcoin> {
+        timelock: CSVTimelock::new(SEQUENCE_LOCKTIME_TYPE_FLAG | 10),
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: DoubleKeys::new(pubkey, pubkey),
+    };
+    assert_eq!(lock.cancel_available_at(1_600_000_000), 1_600_005_120);
+
+    let punish_lock = DataPunishableLock::<Bitcoin> {
+        timelock: CSVTimelock::new(SEQUENCE_LOCKTIME_TYPE_FLAG | 0xffff),
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: pubkey,
+    };
+    assert_eq!(
+        punish_lock.punish_available_at(1_600_000_000),
+        1_600_000_000 + 0xffff * 512
+    );
+    assert_eq!(punish_lock.punish_available_at(u32::MAX - 1), u32::MAX);
+}
+
+#[test]
+fn descriptors_match_transaction_outputs() {
+    let pubkey = pubkey();
+
+    assert_eq!(descriptor::checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+    assert_eq!(
+        descriptor::checksum("addr(bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk)").unwrap(),
+        "29serv37"
+    );
+    assert!(descriptor::checksum("raw(é)").is_none());
+
+    let mut funding = Funding::initialize(pubkey, Network::Local).unwrap();
+    let funding_tx_seen = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::default(),
+            sequence: 0,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 100000,
+            script_pubkey: Script::default(),
+        }],
+    };
+    funding.update(funding_tx_seen).unwrap();
+
+    let datalock = DataLock {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: DoubleKeys::new(pubkey, pubkey),
+    };
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+
+    let script_pubkey = &lock.partial().global.unsigned_tx.output[0].script_pubkey;
+    let desc = descriptor::lock_descriptor(&datalock);
+    assert_eq!(desc, descriptor::raw_descriptor(script_pubkey));
+    assert!(desc.starts_with(&format!("raw({})#", hex::encode(script_pubkey.as_bytes()))));
+    assert_eq!(desc.len(), "raw()#".len() + 2 * 34 + 8);
+}
+
+#[test]
+fn timelocks_as_sequence() {
+    let pubkey = pubkey();
+
+    // block-based
+    let blocks = CSVTimelock::new(144);
+    assert_eq!(blocks.as_sequence().unwrap(), 144);
+
+    // time-based
+    let time = CSVTimelock::new(SEQUENCE_LOCKTIME_TYPE_FLAG | 10);
+    assert_eq!(
+        time.as_sequence().unwrap(),
+        SEQUENCE_LOCKTIME_TYPE_FLAG | 10
+    );
+    assert!(time.is_time_based());
+
+    // disabled or unknown flags
+    assert!(CSVTimelock::new(SEQUENCE_LOCKTIME_DISABLE_FLAG | 10)
+        .as_sequence()
+        .is_err());
+    assert!(CSVTimelock::new(1 << 16).as_sequence().is_err());
+
+    // validated construction
+    assert_eq!(CSVTimelock::from_u32(144).unwrap(), blocks);
+    assert_eq!(CSVTimelock::from_u32(time.as_u32()).unwrap(), time);
+    assert!(matches!(
+        CSVTimelock::from_u32(u32::MAX),
+        Err(Error::InvalidRelativeTimelock(u32::MAX))
+    ));
+    assert!(CSVTimelock::from_u32(SEQUENCE_LOCKTIME_DISABLE_FLAG).is_err());
+
+    // chained expiry without wraparound
+    assert_eq!(blocks.checked_add(blocks), Some(CSVTimelock::new(288)));
+    assert_eq!(
+        time.checked_add(time),
+        Some(CSVTimelock::new(SEQUENCE_LOCKTIME_TYPE_FLAG | 20))
+    );
+    assert_eq!(blocks.checked_add(time), None);
+    let max = CSVTimelock::from_u32(u16::MAX as u32).unwrap();
+    assert_eq!(max.checked_add(CSVTimelock::new(1)), None);
+    assert_eq!(max.checked_add(CSVTimelock::new(0)), Some(max));
+
+    let mut funding = Funding::initialize(pubkey, Network::Local).unwrap();
+    funding
+        .update(bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 100000,
+                script_pubkey: Script::default(),
+            }],
+        })
+        .unwrap();
+
+    for timelock in [blocks, time].iter() {
+        let datalock = DataLock {
+            timelock: *timelock,
+            success: DoubleKeys::new(pubkey, pubkey),
+            failure: DoubleKeys::new(pubkey, pubkey),
+        };
+        let punish_lock = DataPunishableLock {
+            timelock: CSVTimelock::new(10),
+            success: DoubleKeys::new(pubkey, pubkey),
+            failure: pubkey,
+        };
+        let lock =
+            Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+        let cancel = Tx::<Cancel>::initialize(
+            &lock,
+            datalock,
+            punish_lock,
+            &FeeStrategy::None,
+            FeePolitic::Aggressive,
+        )
+        .unwrap();
+        assert_eq!(
+            cancel.partial().global.unsigned_tx.input[0].sequence,
+            timelock.as_u32()
+        );
+    }
+
+    // the sequence cannot diverge from the consumed swaplock
+    let datalock = DataLock {
+        timelock: blocks,
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: DoubleKeys::new(pubkey, pubkey),
+    };
+    let punish_lock = DataPunishableLock {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: pubkey,
+    };
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let other = DataLock {
+        timelock: CSVTimelock::new(145),
+        ..datalock.clone()
+    };
+    assert!(Tx::<Cancel>::initialize(
+        &lock,
+        other,
+        punish_lock.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive
+    )
+    .is_err());
+    let disabled = DataLock {
+        timelock: CSVTimelock::new(SEQUENCE_LOCKTIME_DISABLE_FLAG | 144),
+        ..datalock
+    };
+    assert!(Tx::<Cancel>::initialize(
+        &lock,
+        disabled,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive
+    )
+    .is_err());
+}
+
+#[test]
+fn csv_values_fit_relative_timelock() {
+    let pubkey = pubkey();
+
+    // the relative value is limited to 16 bits, the type flag is kept
+    assert_eq!(CSVTimelock::new(0xffff).to_csv_value().unwrap(), 0xffff);
+    let time = CSVTimelock::new(SEQUENCE_LOCKTIME_TYPE_FLAG | 0xffff);
+    assert_eq!(time.to_csv_value().unwrap(), time.as_u32() as i64);
+    assert!(matches!(
+        CSVTimelock::new(0x10000).to_csv_value(),
+        Err(Error::InvalidRelativeTimelock(0x10000))
+    ));
+
+    let mut funding = Funding::initialize(pubkey, Network::Local).unwrap();
+    funding
+        .update(bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 100000,
+                script_pubkey: Script::default(),
+            }],
+        })
+        .unwrap();
+    let datalock = DataLock {
+        timelock: CSVTimelock::new(0xffff),
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: DoubleKeys::new(pubkey, pubkey),
+    };
+    let punish_lock = DataPunishableLock {
+        timelock: CSVTimelock::new(0xffff),
+        success: DoubleKeys::new(pubkey, pubkey),
+        failure: pubkey,
+    };
+
+    // no unspendable timelocked path is ever created
+    let out_of_range = DataLock {
+        timelock: CSVTimelock::new(0x10000),
+        ..datalock.clone()
+    };
+    assert!(Tx::<Lock>::initialize(&funding, out_of_range, Amount::from_sat(99000)).is_err());
+    let lock = Tx::<Lock>::initialize(&funding, datalock.clone(), Amount::from_sat(99000)).unwrap();
+    let out_of_range = DataPunishableLock {
+        timelock: CSVTimelock::new(0x10000),
+        ..punish_lock.clone()
+    };
+    assert!(Tx::<Cancel>::initialize(
+        &lock,
+        datalock.clone(),
+        out_of_range,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive
+    )
+    .is_err());
+    assert!(Tx::<Cancel>::initialize(
+        &lock,
+        datalock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive
+    )
+    .is_ok());
+}
+
+#[test]
+fn parse_swaplock_script_keys() {
+    let alice = pubkey();
+    let secp = Secp256k1::new();
+    let bob = PublicKey::from_private_key(
+        &secp,
+        &PrivateKey {
+            compressed: true,
+            network: bitcoin::Network::Bitcoin,
+            key: SecretKey::from_slice(&[2u8; 32]).unwrap(),
+        },
+    );
+    let lock = DataLock::<Bitcoin> {
+        timelock: CSVTimelock::new(10),
+        success: DoubleKeys::new(alice, bob),
+        failure: DoubleKeys::new(bob, alice),
+    };
+    assert_eq!(
+        parse_swaplock_keys(&swaplock_script(&lock)).unwrap(),
+        (bob, alice)
+    );
+
+    // truncated before the final OP_ENDIF
+    let truncated = Builder::new()
+        .push_opcode(opcodes::all::OP_IF)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_key(&alice)
+        .push_key(&bob)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::all::OP_ELSE)
+        .push_int(144)
+        .push_opcode(opcodes::all::OP_CSV)
+        .push_opcode(opcodes::all::OP_DROP)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_key(&alice)
+        .push_key(&bob)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .into_script();
+    assert!(matches!(
+        parse_swaplock_keys(&truncated),
+        Err(Error::InvalidSwaplockScript {
+            index: 15,
+            expected: "OP_ENDIF"
+        })
+    ));
+
+    // OP_CSV and OP_DROP swapped
+    let swapped = Builder::new()
+        .push_opcode(opcodes::all::OP_IF)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_key(&alice)
+        .push_key(&bob)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::all::OP_ELSE)
+        .push_int(144)
+        .push_opcode(opcodes::all::OP_DROP)
+        .push_opcode(opcodes::all::OP_CSV)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_key(&alice)
+        .push_key(&bob)
+        .push_opcode(opcodes::all::OP_PUSHNUM_2)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::all::OP_ENDIF)
+        .into_script();
+    assert!(matches!(
+        parse_swaplock_keys(&swapped),
+        Err(Error::InvalidSwaplockScript {
+            index: 8,
+            expected: "OP_CSV"
+        })
+    ));
+
+    // trailing data and empty script
+    let mut trailing = swaplock_script(&lock).into_bytes();
+    trailing.push(opcodes::all::OP_DROP.into_u8());
+    assert!(matches!(
+        parse_swaplock_keys(&Script::from(trailing)),
+        Err(Error::InvalidSwaplockScript { index: 16, .. })
+    ));
+    assert!(matches!(
+        parse_swaplock_keys(&Script::default()),
+        Err(Error::InvalidSwaplockScript { index: 0, .. })
+    ));
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "session")]
+
+use farcaster_core::session::{EncryptedSession, NONCE_LEN};
+use farcaster_core::Error;
+
+#[test]
+fn seal_and_open_session() {
+    let session = vec![0x42u8; 64];
+    let encrypted = EncryptedSession::<Vec<u8>>::new(&[0x01; 32]);
+    let sealed = encrypted.seal(&session, [0x02; NONCE_LEN]).unwrap();
+    assert_eq!(&sealed[..NONCE_LEN], &[0x02; NONCE_LEN]);
+    // the key material does not appear in the sealed session
+    assert!(!sealed.windows(8).any(|w| w == &[0x42; 8][..]));
+    assert_eq!(encrypted.open(&sealed).unwrap(), session);
+
+    // tampered ciphertext, tag, or nonce fail the authentication
+    for i in [0, NONCE_LEN, sealed.len() - 1].iter() {
+        let mut tampered = sealed.clone();
+        tampered[*i] ^= 0x01;
+        assert!(matches!(
+            encrypted.open(&tampered),
+            Err(Error::DecryptionFailed)
+        ));
+    }
+    assert!(matches!(
+        encrypted.open(&sealed[..4]),
+        Err(Error::DecryptionFailed)
+    ));
+
+    // a different master secret cannot open the session
+    let other = EncryptedSession::<Vec<u8>>::new(&[0x03; 32]);
+    assert!(matches!(other.open(&sealed), Err(Error::DecryptionFailed)));
+}
@@ -54,20 +54,21 @@ macro_rules! setup_txs {
         };
 
         let mut cancel =
-            Tx::<Cancel>::initialize(&lock, datalock, datapunishablelock.clone()).unwrap();
-
-        // Set the fees according to the given strategy
-        Bitcoin::set_fee(cancel.partial_mut(), &fee, politic).unwrap();
+            Tx::<Cancel>::initialize(&lock, datalock, datapunishablelock.clone(), &fee, politic)
+                .unwrap();
 
         //
         // Create refund tx
         //
         let (new_address, _, _) = new_address!();
-        let mut refund =
-            Tx::<Refund>::initialize(&cancel, datapunishablelock, new_address.into()).unwrap();
-
-        // Set the fees according to the given strategy
-        Bitcoin::set_fee(refund.partial_mut(), &fee, politic).unwrap();
+        let refund = Tx::<Refund>::initialize(
+            &cancel,
+            datapunishablelock,
+            new_address.into(),
+            &fee,
+            politic,
+        )
+        .unwrap();
 
         //
         // Co-Sign cancel
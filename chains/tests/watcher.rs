@@ -0,0 +1,191 @@
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use bitcoin::secp256k1::key::SecretKey;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::key::{PrivateKey, PublicKey};
+
+use farcaster_chains::bitcoin::transaction::{Cancel, Funding, KeyOrdering, Lock, Refund, Tx};
+use farcaster_chains::bitcoin::watcher::SwapWatcher;
+use farcaster_chains::monero::private_spend_from_seed;
+use farcaster_chains::pairs::btcxmr::{BtcXmr, RingProof};
+
+use farcaster_core::blockchain::{FeePolitic, FeeStrategy, Network};
+use farcaster_core::consensus::deserialize;
+use farcaster_core::crypto::Secret;
+use farcaster_core::negotiation::PublicOffer;
+use farcaster_core::protocol_message::{RevealAliceParameters, RevealBobParameters};
+use farcaster_core::script::{DataLock, DataPunishableLock, DoubleKeys};
+use farcaster_core::transaction::{
+    Cancelable, Error as TxError, Fundable, Lockable, Refundable, Transaction as _,
+};
+
+use std::str::FromStr;
+
+fn key(byte: u8) -> PublicKey {
+    let secp = Secp256k1::new();
+    let privkey = PrivateKey {
+        compressed: true,
+        network: bitcoin::Network::Bitcoin,
+        key: SecretKey::from_slice(&[byte; 32]).unwrap(),
+    };
+    PublicKey::from_private_key(&secp, &privkey)
+}
+
+#[test]
+fn reconstruct_swap_scripts_from_reveals() {
+    let hex = "46435357415001000200000080800000800800a0860100000000000800c80000000000000004000\
+               a00000004000a000000010800140000000000000002030000000300000001000000020000000100\
+               0000010000000a00000003b31a0a70343bb46f3db3768296ac5027f9873921b37f852860c690063\
+               ff9e4c9000000000000000000000000000000000000000000000000000000000000000000000026\
+               0700";
+    let pub_offer: PublicOffer<BtcXmr> = deserialize(&hex::decode(hex).unwrap()[..]).unwrap();
+    let offer = &pub_offer.offer;
+
+    let address = bitcoin::Address::from_str("bc1qesgvtyx9y6lax0x34napc2m7t5zdq6s7xxwpvk")
+        .expect("Parsable address");
+    let view = private_spend_from_seed(&[1u8; 32]).unwrap();
+    let spend = monero::PublicKey::from_private_key(&private_spend_from_seed(&[2u8; 32]).unwrap());
+    let alice = RevealAliceParameters::<BtcXmr> {
+        buy: key(1),
+        cancel: key(2),
+        refund: key(3),
+        punish: key(4),
+        adaptor: key(5),
+        address: address.clone().into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof: RingProof::default(),
+    };
+    let bob = RevealBobParameters::<BtcXmr> {
+        buy: key(6),
+        cancel: key(7),
+        refund: key(8),
+        adaptor: key(9),
+        address: address.clone().into(),
+        spend,
+        view: Secret::new(view.into()),
+        proof: RingProof::default(),
+    };
+    let watcher = SwapWatcher::from_reveals(offer, &alice, &bob);
+
+    // the transactions built by Bob with the same parameters
+    let data_lock = DataLock {
+        timelock: offer.cancel_timelock,
+        success: DoubleKeys::new(key(1), key(6)),
+        failure: DoubleKeys::new(key(2), key(7)),
+    };
+    let punish_lock = DataPunishableLock {
+        timelock: offer.punish_timelock,
+        success: DoubleKeys::new(key(3), key(8)),
+        failure: key(4),
+    };
+    let mut funding = Funding::initialize(key(10), Network::Mainnet).unwrap();
+    let funding_script = funding.get_address().unwrap().0.script_pubkey();
+    funding
+        .update(Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::default(),
+                sequence: 0,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 110000,
+                script_pubkey: funding_script,
+            }],
+        })
+        .unwrap();
+    let lock =
+        Tx::<Lock>::initialize(&funding, data_lock.clone(), offer.arbitrating_amount).unwrap();
+    let cancel = Tx::<Cancel>::initialize(
+        &lock,
+        data_lock.clone(),
+        punish_lock.clone(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    let refund = Tx::<Refund>::initialize(
+        &cancel,
+        punish_lock.clone(),
+        address.into(),
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+
+    let lock_tx = &lock.partial().global.unsigned_tx;
+    assert_eq!(
+        watcher.lock_script_pubkey(),
+        lock_tx.output[0].script_pubkey
+    );
+    assert_eq!(
+        lock.partial().outputs[0].witness_script,
+        Some(watcher.lock_script())
+    );
+    assert!(watcher.verify_lock(&lock).is_ok());
+    assert!(watcher.verify_cancel(&cancel).is_ok());
+    assert!(watcher.verify_refund(&refund).is_ok());
+
+    // the refund must pay Bob's refund address
+    let other_address = bitcoin::Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+        .expect("Parsable address");
+    let other_bob = RevealBobParameters::<BtcXmr> {
+        address: other_address.into(),
+        ..bob.clone()
+    };
+    let other_watcher = SwapWatcher::from_reveals(offer, &alice, &other_bob);
+    assert!(other_watcher.verify_cancel(&cancel).is_ok());
+    assert!(matches!(
+        other_watcher.verify_refund(&refund),
+        Err(TxError::WrongTemplate)
+    ));
+
+    // the cancel must create the punishable contract with Alice's punish key
+    let other_alice = RevealAliceParameters::<BtcXmr> {
+        punish: key(12),
+        ..alice.clone()
+    };
+    let other_watcher = SwapWatcher::from_reveals(offer, &other_alice, &bob);
+    assert!(other_watcher.verify_lock(&lock).is_ok());
+    assert!(matches!(
+        other_watcher.verify_cancel(&cancel),
+        Err(TxError::WrongTemplate)
+    ));
+
+    // the transactions must follow the watched key ordering
+    let sorted = watcher.clone().with_ordering(KeyOrdering::Lexicographic);
+    assert!(matches!(
+        sorted.verify_lock(&lock),
+        Err(TxError::WrongTemplate)
+    ));
+    let sorted_lock = Tx::<Lock>::initialize_with_ordering(
+        &funding,
+        data_lock.clone(),
+        offer.arbitrating_amount,
+        KeyOrdering::Lexicographic,
+    )
+    .unwrap();
+    let sorted_cancel = Tx::<Cancel>::initialize(
+        &sorted_lock,
+        data_lock,
+        punish_lock,
+        &FeeStrategy::None,
+        FeePolitic::Aggressive,
+    )
+    .unwrap();
+    assert!(sorted.verify_lock(&sorted_lock).is_ok());
+    assert!(sorted.verify_cancel(&sorted_cancel).is_ok());
+    assert!(watcher.verify_cancel(&sorted_cancel).is_err());
+
+    // the watchtower detects the cancel transaction
+    let cancel_tx = &cancel.partial().global.unsigned_tx;
+    assert_eq!(
+        watcher.punish_lock_script_pubkey(),
+        cancel_tx.output[0].script_pubkey
+    );
+    assert!(watcher.is_cancel(cancel_tx));
+    assert!(!watcher.is_cancel(lock_tx));
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "zeroize")]
+
+use farcaster_chains::monero::{private_spend_from_seed, PrivateViewKey};
+
+use farcaster_core::crypto::Secret;
+
+use zeroize::Zeroize;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Counts the calls to `zeroize` on the wrapped value.
+#[derive(Clone)]
+struct ZeroizeProbe(Rc<Cell<usize>>);
+
+impl Zeroize for ZeroizeProbe {
+    fn zeroize(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[test]
+fn secret_zeroizes_inner_key_once() {
+    let calls = Rc::new(Cell::new(0));
+    let secret = Secret::new(ZeroizeProbe(calls.clone()));
+    let copy = secret.clone();
+    assert_eq!(Rc::strong_count(&calls), 3);
+    assert_eq!(calls.get(), 0);
+
+    // the key is zeroized then dropped with its wrapper
+    drop(secret);
+    assert_eq!(calls.get(), 1);
+    assert_eq!(Rc::strong_count(&calls), 2);
+    drop(copy);
+    assert_eq!(calls.get(), 2);
+    assert_eq!(Rc::strong_count(&calls), 1);
+
+    // the scalar of the monero view key is wiped
+    let mut view = PrivateViewKey::from(private_spend_from_seed(&[1u8; 32]).unwrap());
+    assert_ne!(view.0.as_bytes(), &[0u8; 32][..]);
+    view.zeroize();
+    assert_eq!(view.0.as_bytes(), &[0u8; 32][..]);
+}
@@ -4,7 +4,7 @@
 //! asset, e.g. for Etherum blockchain assets can be eth or dai.
 
 use std::error;
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display};
 use std::io;
 use std::ops::Range;
 use std::str::FromStr;
@@ -20,7 +20,14 @@ use crate::transaction::{Buyable, Cancelable, Fundable, Lockable, Punishable, Re
 /// Defines the type for a blockchain address, this type is used when manipulating transactions.
 pub trait Address {
     /// Defines the address format for the arbitrating blockchain.
-    type Address: Clone + Debug + Encodable + Decodable + StrictEncode + StrictDecode;
+    type Address: Clone
+        + PartialEq
+        + Eq
+        + Debug
+        + Encodable
+        + Decodable
+        + StrictEncode
+        + StrictDecode;
 }
 
 /// Defines the type for a blockchain timelock, this type is used when manipulating transactions
@@ -28,13 +35,19 @@ pub trait Address {
 pub trait Timelock {
     /// Defines the type of timelock used for the arbitrating transactions.
     type Timelock: Copy + Debug + Encodable + Decodable + PartialEq + Eq;
+
+    /// Returns the height at which an output locked with the relative `timelock` becomes
+    /// spendable, given the `height` at which the output has been mined. If the timelock is
+    /// expressed in time instead of blocks, `height` and the returned value are timestamps.
+    fn available_at(timelock: &Self::Timelock, height: u32) -> u32;
 }
 
 /// Defines the asset identifier for a blockchain and its associated asset unit type, it is carried
 /// in the [Offer](crate::negotiation::Offer) to fix exchanged amounts.
 pub trait Asset: Copy + Debug {
-    /// Type for the traded asset unit for a blockchain.
-    type AssetUnit: Copy + Eq + Debug + Encodable + Decodable;
+    /// Type for the traded asset unit for a blockchain, convertible into its smallest
+    /// denomination.
+    type AssetUnit: Copy + Eq + Debug + Encodable + Decodable + Into<u64>;
 
     /// Create a new blockchain.
     fn new() -> Self;
@@ -53,10 +66,21 @@ pub trait Asset: Copy + Debug {
 pub trait Onchain {
     /// Defines the transaction format used to transfer partial transaction between participant for
     /// the arbitrating blockchain
-    type PartialTransaction: Clone + Debug + StrictEncode + StrictDecode;
+    type PartialTransaction: Clone + PartialEq + Debug + StrictEncode + StrictDecode;
 
     /// Defines the finalized transaction format for the arbitrating blockchain
     type Transaction: Clone + Debug + StrictEncode + StrictDecode;
+
+    /// Defines the identifier of a finalized transaction on the arbitrating blockchain
+    type TransactionId: Clone + Eq + Debug;
+
+    /// Removes all signatures and finalized witnesses from the partial transaction, keeping the
+    /// metadata needed to sign it again.
+    fn strip_signatures(partial: &mut Self::PartialTransaction);
+
+    /// Returns the signature hash type the first input of the partial transaction must be signed
+    /// with, `None` if the partial transaction does not set one.
+    fn sighash_type(partial: &Self::PartialTransaction) -> Option<u8>;
 }
 
 /// Fix the types for all arbitrating transactions needed for the swap: [Fundable], [Lockable],
@@ -94,17 +118,45 @@ where
 {
     type Err = consensus::Error;
 
+    /// Parses a fixed strategy from a single value, e.g. `5`, or a range strategy from two values
+    /// separated by `-`, e.g. `3-10`, as rendered by [`Display`]. Ranges separated by `..`, e.g.
+    /// `3..10`, are also accepted. The externally funded strategy is parsed from `none`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // range parsing not implemented
-        match s.parse::<T>() {
-            Ok(x) => Ok(Self::Fixed(x)),
-            Err(_) => Err(consensus::Error::ParseFailed("Failed parsing FeeStrategy")),
+        let parse = |x: &str| {
+            x.trim()
+                .parse::<T>()
+                .map_err(|_| consensus::Error::ParseFailed("Failed parsing FeeStrategy"))
+        };
+        if s.trim() == "none" {
+            return Ok(Self::None);
+        }
+        match (s.find(".."), s.find('-')) {
+            (Some(i), _) => Self::new_range(parse(&s[..i])?, parse(&s[i + 2..])?),
+            (None, Some(i)) => Self::new_range(parse(&s[..i])?, parse(&s[i + 1..])?),
+            (None, None) => Ok(Self::Fixed(parse(s)?)),
+        }
+    }
+}
+
+/// Renders a fixed strategy as its value, e.g. `5`, and a range strategy as its inclusive bounds
+/// separated by `-`, e.g. `3-10`, and the externally funded strategy as `none`. The output is
+/// parsed back with [`FromStr`].
+impl<T> Display for FeeStrategy<T>
+where
+    T: Clone + PartialOrd + PartialEq + Encodable + Decodable + Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeStrategy::Fixed(t) => write!(f, "{}", t),
+            FeeStrategy::Range(Range { start, end }) => write!(f, "{}-{}", start, end),
+            FeeStrategy::None => write!(f, "none"),
         }
     }
 }
 
 /// A fee strategy to be applied on an arbitrating transaction. As described in the specifications
-/// a fee strategy can be: fixed or range.
+/// a fee strategy can be: fixed or range. Transactions can also be left without fee to be bumped
+/// externally, see [`FeeStrategy::None`].
 ///
 /// A fee strategy is included in an offer, so Alice and Bob can verify that transactions are valid
 /// upon reception by the other participant.
@@ -117,6 +169,51 @@ where
     Fixed(T),
     /// A range with a minimum and maximum (inclusive) possible fees
     Range(Range<T>),
+    /// No fee is set, the transactions are broadcast at zero fee and bumped with a
+    /// child-pays-for-parent transaction managed outside the crate. Unlike `Fixed` with a zero
+    /// rate, any fee paid is accepted during validation.
+    None,
+}
+
+impl<T> FeeStrategy<T>
+where
+    T: Clone + PartialOrd + PartialEq + Encodable + Decodable,
+{
+    /// Creates a range strategy, fails if the minimum is greater than the maximum.
+    pub fn new_range(start: T, end: T) -> Result<Self, consensus::Error> {
+        match start <= end {
+            true => Ok(Self::Range(Range { start, end })),
+            false => Err(consensus::Error::ParseFailed(
+                "FeeStrategy range bounds are reversed",
+            )),
+        }
+    }
+
+    /// Returns `true` if every fee the strategy can select is accepted by the `acceptable`
+    /// strategy, e.g. to check an offered strategy against local bounds before accepting an
+    /// offer. Containment is defined as follows:
+    ///
+    /// * a fixed fee is within a fixed fee if they are equal, and within a range if it lies
+    ///   between the bounds (inclusive)
+    /// * a range is within a fixed fee if both bounds equal it, and within a range if both its
+    ///   bounds lie in the range; a range with reversed bounds is never within another strategy
+    /// * [`FeeStrategy::None`] accepts any fee, so every strategy is within it, but it is only
+    ///   within itself as the fee is then set outside the crate
+    pub fn is_within(&self, acceptable: &Self) -> bool {
+        let contains = |fee: &T| match acceptable {
+            FeeStrategy::Fixed(fixed) => fee == fixed,
+            FeeStrategy::Range(range) => range.start <= *fee && *fee <= range.end,
+            FeeStrategy::None => true,
+        };
+        match (self, acceptable) {
+            (_, FeeStrategy::None) => true,
+            (FeeStrategy::None, _) => false,
+            (FeeStrategy::Fixed(fee), _) => contains(fee),
+            (FeeStrategy::Range(range), _) => {
+                range.start <= range.end && contains(&range.start) && contains(&range.end)
+            }
+        }
+    }
 }
 
 impl<T> Encodable for FeeStrategy<T>
@@ -134,6 +231,7 @@ where
                 let len = wrap_in_vec!(wrap start in writer);
                 Ok(wrap_in_vec!(wrap end in writer) + len + 1)
             }
+            FeeStrategy::None => 0x03u8.consensus_encode(writer),
         }
     }
 }
@@ -150,6 +248,7 @@ where
                 let end = unwrap_from_vec!(d);
                 Ok(FeeStrategy::Range(Range { start, end }))
             }
+            0x03u8 => Ok(FeeStrategy::None),
             _ => Err(consensus::Error::UnknownType),
         }
     }
@@ -165,9 +264,13 @@ pub enum FeeStrategyError {
     /// Fee amount is too low and does not match the fee strategy requirements.
     #[error("Fee amount is too low")]
     AmountOfFeeTooLow,
-    /// Fee amount is too high and does not match the fee strategy requirements.
-    #[error("Fee amount is too high")]
-    AmountOfFeeTooHigh,
+    /// Fee amount is too high and does not match the fee strategy requirements, carries the
+    /// highest fee rate acceptable for the transaction.
+    #[error("Fee amount is too high, the fee rate must not exceed {max_sat_per_vbyte}")]
+    AmountOfFeeTooHigh {
+        /// The maximum fee rate, in the fee unit of the blockchain, usable for the transaction.
+        max_sat_per_vbyte: u64,
+    },
     /// Not enough assets to cover the fees.
     #[error("Not enough assets to cover the fees")]
     NotEnoughAssets,
@@ -201,12 +304,60 @@ impl FeeStrategyError {
 }
 
 /// Defines how to set the fee when a strategy allows multiple possibilities.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FeePolitic {
     /// Set the fee at the minimum allowed by the strategy
     Aggressive,
     /// Set the fee at the maximum allowed by the strategy
     Conservative,
+    /// Set the fee at the midpoint between the minimum and the maximum allowed by the strategy
+    Normal,
+    /// Set the fee at the given fraction between the minimum (0.0) and the maximum (1.0) allowed
+    /// by the strategy
+    Interpolated(f64),
+}
+
+impl FeePolitic {
+    /// Returns the fraction between the minimum and the maximum allowed by the strategy targeted
+    /// by the politic, clamped to `[0, 1]`.
+    pub fn fraction(&self) -> f64 {
+        match self {
+            FeePolitic::Aggressive => 0.0,
+            FeePolitic::Conservative => 1.0,
+            FeePolitic::Normal => 0.5,
+            // NaN is mapped to 0.0 by max
+            FeePolitic::Interpolated(fraction) => fraction.max(0.0).min(1.0),
+        }
+    }
+}
+
+impl Encodable for FeePolitic {
+    fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        match self {
+            FeePolitic::Aggressive => 0x01u8.consensus_encode(writer),
+            FeePolitic::Conservative => 0x02u8.consensus_encode(writer),
+            FeePolitic::Interpolated(fraction) => {
+                0x03u8.consensus_encode(writer)?;
+                Ok(fraction.to_bits().consensus_encode(writer)? + 1)
+            }
+            FeePolitic::Normal => 0x04u8.consensus_encode(writer),
+        }
+    }
+}
+
+impl Decodable for FeePolitic {
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        match Decodable::consensus_decode(d)? {
+            0x01u8 => Ok(FeePolitic::Aggressive),
+            0x02u8 => Ok(FeePolitic::Conservative),
+            0x03u8 => {
+                let bits: u64 = Decodable::consensus_decode(d)?;
+                Ok(FeePolitic::Interpolated(f64::from_bits(bits)))
+            }
+            0x04u8 => Ok(FeePolitic::Normal),
+            _ => Err(consensus::Error::UnknownType),
+        }
+    }
 }
 
 /// Enable fee management for an arbitrating blockchain. This trait require implementing the
@@ -226,7 +377,9 @@ pub trait Fee: Onchain + Asset {
         politic: FeePolitic,
     ) -> Result<Self::AssetUnit, FeeStrategyError>;
 
-    /// Validates that the fee for the given transaction are set accordingly to the strategy.
+    /// Validates that the fee for the given transaction are set accordingly to the strategy, fails
+    /// with [`FeeStrategyError::AmountOfFeeTooLow`] or [`FeeStrategyError::AmountOfFeeTooHigh`]
+    /// if the fee paid is out of the strategy bounds.
     fn validate_fee(
         tx: &Self::PartialTransaction,
         strategy: &FeeStrategy<Self::FeeUnit>,
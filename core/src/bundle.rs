@@ -3,9 +3,10 @@
 //! Datum are succinct and are used to convey atomic chunk of data (datum) between clients and
 //! daemons. Bundles are used during the different steps of the swap by both Alice and Bob.
 
-use crate::blockchain::Onchain;
-use crate::crypto::Signatures;
-use crate::datum;
+use crate::blockchain::{Asset, Onchain};
+use crate::crypto::{self, AmountCommitments, SharedPrivateKeys, Signatures};
+use crate::datum::{self, KeyPurpose};
+use crate::role::Acc;
 use crate::swap::Swap;
 use strict_encoding::{StrictDecode, StrictEncode};
 
@@ -46,6 +47,68 @@ pub struct BobParameters<Ctx: Swap> {
     pub fee_strategy: Option<datum::Parameter<Ctx::Ar>>,
 }
 
+impl<Ctx> AliceParameters<Ctx>
+where
+    Ctx: Swap,
+{
+    /// Iterates over all the keys of the bundle with their purpose, i.e. the buy, cancel, refund,
+    /// punish and adaptor arbitrating keys, the accordant spend key and the private view key.
+    pub fn keys(&self) -> impl Iterator<Item = (KeyPurpose, &datum::Key<Ctx>)> {
+        vec![
+            (KeyPurpose::Buy, &self.buy),
+            (KeyPurpose::Cancel, &self.cancel),
+            (KeyPurpose::Refund, &self.refund),
+            (KeyPurpose::Punish, &self.punish),
+            (KeyPurpose::Adaptor, &self.adaptor),
+            (KeyPurpose::Spend, &self.spend),
+            (KeyPurpose::PrivateView, &self.view),
+        ]
+        .into_iter()
+    }
+}
+
+impl<Ctx> BobParameters<Ctx>
+where
+    Ctx: Swap,
+{
+    /// Iterates over all the keys of the bundle with their purpose, i.e. the buy, cancel, refund
+    /// and adaptor arbitrating keys, the accordant spend key and the private view key.
+    pub fn keys(&self) -> impl Iterator<Item = (KeyPurpose, &datum::Key<Ctx>)> {
+        vec![
+            (KeyPurpose::Buy, &self.buy),
+            (KeyPurpose::Cancel, &self.cancel),
+            (KeyPurpose::Refund, &self.refund),
+            (KeyPurpose::Adaptor, &self.adaptor),
+            (KeyPurpose::Spend, &self.spend),
+            (KeyPurpose::PrivateView, &self.view),
+        ]
+        .into_iter()
+    }
+}
+
+/// Provides the (counter-party) daemon with the commitment to the amount locked on the accordant
+/// blockchain, the amount is hidden and verified with the accordant private view key.
+#[derive(Debug, Clone, StrictEncode, StrictDecode)]
+pub struct AccordantLock<Ctx: Swap> {
+    pub amount: <Ctx::Ac as AmountCommitments<Acc>>::AmountCommitment,
+}
+
+impl<Ctx> AccordantLock<Ctx>
+where
+    Ctx: Swap,
+{
+    /// Verifies with the private view key that the accordant lock hides the `expected` amount.
+    pub fn verify_accordant_amount(
+        &self,
+        view_key: &<Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey,
+        expected: <Ctx::Ac as Asset>::AssetUnit,
+    ) -> Result<(), crypto::Error> {
+        Ctx::Ac::verify_amount(&self.amount, view_key, expected)
+    }
+}
+
+impl<Ctx> Bundle for AccordantLock<Ctx> where Ctx: Swap {}
+
 /// Provides daemon with a signature on the unsigned cancel (d) transaction.
 #[derive(Debug, Clone, StrictEncode, StrictDecode)]
 pub struct CosignedArbitratingCancel<S>
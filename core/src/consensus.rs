@@ -33,6 +33,60 @@ pub enum Error {
     /// Strict encoding error.
     #[error("Strict encoding error: {0}")]
     StrictEncoding(#[from] strict_encoding::Error),
+    /// The frame length is out of bounds or its checksum does not match the payload.
+    #[error("Corrupted frame")]
+    CorruptFrame,
+    /// The frame was created with a protocol version not supported by this implementation.
+    #[error("Incompatible protocol version {0}")]
+    IncompatibleVersion(u16),
+    /// The signature is not canonically encoded, e.g. a high-S signature, and would be rejected
+    /// by the blockchain.
+    #[error("Invalid signature encoding")]
+    InvalidSignatureEncoding,
+    /// Decoding a field of a composite type failed, carries the name of the field and the error
+    /// raised while decoding it. Nested fields are reported as nested errors, see
+    /// [`Error::field_path`].
+    #[error("Failed to decode field `{name}`: {source}")]
+    Field {
+        /// The name of the field in the composite type
+        name: &'static str,
+        /// The error raised while decoding the field
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Wraps the error into an [`Error::Field`] error with the name of the field being decoded.
+    pub fn in_field(self, name: &'static str) -> Self {
+        Self::Field {
+            name,
+            source: Box::new(self),
+        }
+    }
+
+    /// Returns the names of the nested fields that failed to decode, from the outermost type to
+    /// the innermost field, empty if the error does not carry any field context.
+    pub fn field_path(&self) -> Vec<&'static str> {
+        let mut path = vec![];
+        let mut error = self;
+        while let Self::Field { name, source } = error {
+            path.push(*name);
+            error = source;
+        }
+        path
+    }
+}
+
+/// Converts the error into a strict encoding data integrity error, the message keeps the field
+/// context of the error.
+impl From<Error> for strict_encoding::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::StrictEncoding(e) => e,
+            e => strict_encoding::Error::DataIntegrityError(e.to_string()),
+        }
+    }
 }
 
 /// Encode an object into a vector
@@ -73,6 +127,60 @@ pub fn deserialize_partial<T: Decodable>(data: &[u8]) -> Result<(T, usize), Erro
     Ok((rv, consumed))
 }
 
+/// Decodes a field of a composite type with the `decode` function, a failure is wrapped into an
+/// [`Error::Field`] error with the name of the field.
+pub fn decode_field<T, F>(name: &'static str, decode: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    decode().map_err(|e| e.in_field(name))
+}
+
+/// Decodes a strict encoded field of a composite type, see [`decode_field`].
+pub fn strict_decode_field<T, D>(name: &'static str, d: &mut D) -> Result<T, Error>
+where
+    T: strict_encoding::StrictDecode,
+    D: io::Read,
+{
+    decode_field(name, || Ok(T::strict_decode(d)?))
+}
+
+/// Decodes a strict encoded field of at most `max_len` bytes, see [`strict_decode_field`]. The
+/// field is read from the stream limited to `max_len` bytes, a longer field fails with an
+/// unexpected end of data instead of consuming the rest of the message.
+pub fn strict_decode_bounded_field<T, D>(
+    name: &'static str,
+    d: &mut D,
+    max_len: usize,
+) -> Result<T, Error>
+where
+    T: strict_encoding::StrictDecode,
+    D: io::Read,
+{
+    strict_decode_field(name, &mut d.by_ref().take(max_len as u64))
+}
+
+/// Decode a length prefixed vector of bytes of at most `max_len` bytes. The length prefix is
+/// checked before allocating and reading the data, a peer cannot force a large allocation by
+/// sending a big length prefix.
+pub fn decode_bounded_vec<D: io::Read>(d: &mut D, max_len: usize) -> Result<Vec<u8>, Error> {
+    let len = u16::consensus_decode(d)? as usize;
+    if len > max_len {
+        return Err(Error::ParseFailed(
+            "Length prefix exceeds the maximum length",
+        ));
+    }
+    let mut ret = vec![0u8; len];
+    d.read_exact(&mut ret)?;
+    Ok(ret)
+}
+
+/// Decode a length prefixed UTF-8 string of at most `max_len` bytes, see [`decode_bounded_vec`].
+pub fn decode_bounded_string<D: io::Read>(d: &mut D, max_len: usize) -> Result<String, Error> {
+    let bytes = decode_bounded_vec(d, max_len)?;
+    String::from_utf8(bytes).map_err(|_| Error::ParseFailed("Invalid UTF-8 string"))
+}
+
 /// Data which can be encoded in a consensus-consistent way
 ///
 /// **When implemented on foreign blockchain specific types such as `Amount` from the bitcoin
@@ -251,4 +359,48 @@ mod tests {
         let vec = vec![0x41; u16::MAX.into()];
         assert_eq!(deserialize::<Vec<u8>>(&serialize(&vec)[..]).unwrap(), vec);
     }
+
+    #[test]
+    fn bounded_vec() {
+        let vec = vec![0xde, 0xad, 0xbe, 0xef];
+        let bytes = serialize(&vec);
+        assert_eq!(decode_bounded_vec(&mut &bytes[..], 4).unwrap(), vec);
+        assert!(matches!(
+            decode_bounded_vec(&mut &bytes[..], 3),
+            Err(Error::ParseFailed(_))
+        ));
+
+        // the biggest length prefix possible is rejected without reading the (missing) data
+        let bytes = [0xff, 0xff];
+        assert!(matches!(
+            decode_bounded_vec(&mut &bytes[..], 1024),
+            Err(Error::ParseFailed(_))
+        ));
+        assert!(matches!(
+            decode_bounded_string(&mut &bytes[..], 1024),
+            Err(Error::ParseFailed(_))
+        ));
+
+        let bytes = serialize(&vec![0xff, 0xfe]);
+        assert!(matches!(
+            decode_bounded_string(&mut &bytes[..], 1024),
+            Err(Error::ParseFailed(_))
+        ));
+    }
+
+    #[test]
+    fn bounded_field() {
+        let vec = vec![0xde, 0xad, 0xbe, 0xef];
+        let bytes = strict_encoding::strict_serialize(&vec).unwrap();
+        let decoded: Vec<u8> =
+            strict_decode_bounded_field("vec", &mut &bytes[..], bytes.len()).unwrap();
+        assert_eq!(decoded, vec);
+
+        // the field does not fit in the cap and fails without reading past it
+        let mut reader = &bytes[..];
+        let err = strict_decode_bounded_field::<Vec<u8>, _>("vec", &mut reader, bytes.len() - 1)
+            .unwrap_err();
+        assert_eq!(err.field_path(), vec!["vec"]);
+        assert!(!reader.is_empty());
+    }
 }
@@ -1,11 +1,18 @@
 //! Cryptographic type definitions and primitives supported in Farcaster
 
 use std::error;
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::Hash;
+use std::io;
 
+use rand_core::{CryptoRng, OsRng, RngCore};
 use strict_encoding::{StrictDecode, StrictEncode};
+use subtle::ConstantTimeEq;
 use thiserror::Error;
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
 
+use crate::blockchain::Asset;
 use crate::consensus::{self};
 use crate::role::{Acc, Accordant, Arbitrating, Blockchain};
 use crate::swap::Swap;
@@ -26,6 +33,16 @@ pub enum Error {
     /// The commitment does not match the given value.
     #[error("The commitment does not match the given value")]
     InvalidCommitment,
+    /// The key is not usable, e.g. a public key at the identity point or a zero private key.
+    #[error("The key is invalid")]
+    InvalidKey,
+    /// The amount commitment cannot be opened or does not hide the expected amount.
+    #[error("The amount commitment does not hide the expected amount")]
+    InvalidAmountCommitment,
+    /// The operation is not supported by the blockchain, e.g. adaptor signatures on a chain
+    /// without an adaptor signature scheme.
+    #[error("The operation is not supported")]
+    UnsupportedOperation,
     /// Any cryptographic error not part of this list.
     #[error("Cryptographic error: {0}")]
     Other(Box<dyn error::Error + Send + Sync>),
@@ -55,6 +72,120 @@ impl Error {
     }
 }
 
+/// Private key material that can be wrapped in a [`Secret`]. With the `zeroize` feature enabled
+/// the key must implement [`Zeroize`] to be wiped when dropped, otherwise any type is accepted.
+#[cfg(feature = "zeroize")]
+pub trait KeyMaterial: Zeroize {}
+
+#[cfg(feature = "zeroize")]
+impl<T> KeyMaterial for T where T: Zeroize {}
+
+/// Private key material that can be wrapped in a [`Secret`]. With the `zeroize` feature enabled
+/// the key must implement `Zeroize` to be wiped when dropped, otherwise any type is accepted.
+#[cfg(not(feature = "zeroize"))]
+pub trait KeyMaterial {}
+
+#[cfg(not(feature = "zeroize"))]
+impl<T> KeyMaterial for T {}
+
+/// Wrapper around private key material. The [`Debug`] and [`Display`] implementations do not
+/// print the inner value to prevent leaking secrets in logs, the key must be accessed explicitly
+/// with [`Secret::expose_secret`].
+///
+/// With the `zeroize` feature enabled the key is overwritten through its `Zeroize` implementation
+/// when the wrapper is dropped.
+pub struct Secret<T: KeyMaterial>(T);
+
+impl<T> Secret<T>
+where
+    T: KeyMaterial,
+{
+    /// Wrap the private key material.
+    pub fn new(secret: T) -> Self {
+        Self(secret)
+    }
+
+    /// Returns a reference to the private key material.
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> Drop for Secret<T>
+where
+    T: KeyMaterial,
+{
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T> Clone for Secret<T>
+where
+    T: KeyMaterial + Clone,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.expose_secret().clone())
+    }
+}
+
+impl<T> PartialEq for Secret<T>
+where
+    T: KeyMaterial + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.expose_secret() == other.expose_secret()
+    }
+}
+
+impl<T> Eq for Secret<T> where T: KeyMaterial + Eq {}
+
+impl<T> From<T> for Secret<T>
+where
+    T: KeyMaterial,
+{
+    fn from(secret: T) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl<T> Debug for Secret<T>
+where
+    T: KeyMaterial,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted private key>")
+    }
+}
+
+impl<T> Display for Secret<T>
+where
+    T: KeyMaterial,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted private key>")
+    }
+}
+
+impl<T> StrictEncode for Secret<T>
+where
+    T: KeyMaterial + StrictEncode,
+{
+    fn strict_encode<E: io::Write>(&self, e: E) -> Result<usize, strict_encoding::Error> {
+        self.expose_secret().strict_encode(e)
+    }
+}
+
+impl<T> StrictDecode for Secret<T>
+where
+    T: KeyMaterial + StrictDecode,
+{
+    fn strict_decode<D: io::Read>(d: D) -> Result<Self, strict_encoding::Error> {
+        Ok(Self::new(T::strict_decode(d)?))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, StrictDecode, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub enum KeyType<Ctx>
@@ -63,7 +194,7 @@ where
 {
     PublicArbitrating(<Ctx::Ar as Keys>::PublicKey),
     PublicAccordant(<Ctx::Ac as Keys>::PublicKey),
-    SharedPrivate(<Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey),
+    SharedPrivate(Secret<<Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey>),
 }
 
 impl<Ctx> KeyType<Ctx>
@@ -92,7 +223,7 @@ where
         &self,
     ) -> Result<<Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey, consensus::Error> {
         match self {
-            KeyType::SharedPrivate(key) => Ok(key.clone()),
+            KeyType::SharedPrivate(key) => Ok(key.expose_secret().clone()),
             _ => Err(consensus::Error::TypeMismatch),
         }
     }
@@ -101,7 +232,9 @@ where
         match self {
             KeyType::PublicArbitrating(key) => <Ctx::Ar as Keys>::as_bytes(&key),
             KeyType::PublicAccordant(key) => <Ctx::Ac as Keys>::as_bytes(&key),
-            KeyType::SharedPrivate(key) => <Ctx::Ac as SharedPrivateKeys<Acc>>::as_bytes(&key),
+            KeyType::SharedPrivate(key) => {
+                <Ctx::Ac as SharedPrivateKeys<Acc>>::as_bytes(key.expose_secret())
+            }
         }
     }
 }
@@ -122,23 +255,42 @@ impl<S> SignatureType<S>
 where
     S: Signatures,
 {
+    /// Validates the canonical encoding of the signature, fails with
+    /// [`consensus::Error::InvalidSignatureEncoding`] if the signature would be rejected by the
+    /// blockchain.
+    pub fn validate(&self) -> Result<(), consensus::Error> {
+        match self {
+            SignatureType::Adaptor(sig) => S::validate_adaptor_signature(sig),
+            SignatureType::Adapted(sig) | SignatureType::Regular(sig) => S::validate_signature(sig),
+        }
+    }
+
     pub fn try_into_adaptor(&self) -> Result<S::AdaptorSignature, consensus::Error> {
         match self {
-            SignatureType::Adaptor(sig) => Ok(sig.clone()),
+            SignatureType::Adaptor(sig) => {
+                S::validate_adaptor_signature(sig)?;
+                Ok(sig.clone())
+            }
             _ => Err(consensus::Error::TypeMismatch),
         }
     }
 
     pub fn try_into_adapted(&self) -> Result<S::Signature, consensus::Error> {
         match self {
-            SignatureType::Adapted(sig) => Ok(sig.clone()),
+            SignatureType::Adapted(sig) => {
+                S::validate_signature(sig)?;
+                Ok(sig.clone())
+            }
             _ => Err(consensus::Error::TypeMismatch),
         }
     }
 
     pub fn try_into_regular(&self) -> Result<S::Signature, consensus::Error> {
         match self {
-            SignatureType::Regular(sig) => Ok(sig.clone()),
+            SignatureType::Regular(sig) => {
+                S::validate_signature(sig)?;
+                Ok(sig.clone())
+            }
             _ => Err(consensus::Error::TypeMismatch),
         }
     }
@@ -170,10 +322,38 @@ pub trait Keys {
     type PrivateKey;
 
     /// Public key type given the blockchain and the crypto engine.
-    type PublicKey: Clone + PartialEq + Debug + StrictEncode + StrictDecode;
+    type PublicKey: Clone + PartialEq + Eq + Debug + StrictEncode + StrictDecode;
 
     /// Get the bytes from the public key.
     fn as_bytes(pubkey: &Self::PublicKey) -> Vec<u8>;
+
+    /// Validates a public key received from the counter-party, fails with [`Error::InvalidKey`]
+    /// if the key is the identity point or otherwise unusable. The default implementation accepts
+    /// all keys and must only be kept when the public key type cannot hold such values.
+    fn validate_pubkey(_pubkey: &Self::PublicKey) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Derives the public key of a secret shared across the arbitrating and the accordant groups,
+    /// e.g. the adaptor point `Ta` or `Tb` and the accordant spend point of the same participant.
+    /// The secret is the little-endian encoding of the scalar, it must pass
+    /// [`check_cross_group_secret`] so both groups derive their point from the same discrete log.
+    fn from_cross_group_secret(secret: &[u8; 32]) -> Result<Self::PublicKey, Error>;
+}
+
+/// Maximum bit length of a secret shared across groups, below the order of both the secp256k1
+/// and the ed25519 groups.
+pub const CROSS_GROUP_SECRET_BITS: usize = 252;
+
+/// Checks that the little-endian encoded scalar is a valid secret in both groups, i.e. it is not
+/// zero and fits in [`CROSS_GROUP_SECRET_BITS`], fails with [`Error::InvalidKey`] otherwise.
+pub fn check_cross_group_secret(secret: &[u8; 32]) -> Result<(), Error> {
+    // bits of the last byte above the maximum bit length must be unset
+    let overflow = secret[31] >> (CROSS_GROUP_SECRET_BITS - 248) != 0;
+    match overflow || secret.iter().all(|byte| *byte == 0) {
+        true => Err(Error::InvalidKey),
+        false => Ok(()),
+    }
 }
 
 /// Generate the keys for a blockchain from a master seed.
@@ -195,8 +375,15 @@ pub trait SharedPrivateKeys<T>: FromSeed<T>
 where
     T: Blockchain,
 {
-    /// A shareable private key type used to parse non-transparent blockchain
-    type SharedPrivateKey: Clone + PartialEq + Debug + StrictEncode + StrictDecode;
+    /// A shareable private key type used to parse non-transparent blockchain, zeroized when
+    /// dropped inside a [`Secret`] with the `zeroize` feature enabled
+    type SharedPrivateKey: Clone
+        + PartialEq
+        + Eq
+        + Debug
+        + StrictEncode
+        + StrictDecode
+        + KeyMaterial;
 
     fn get_shared_privkey(
         seed: &Self::Seed,
@@ -205,21 +392,104 @@ where
 
     /// Get the bytes from the shared private key.
     fn as_bytes(privkey: &Self::SharedPrivateKey) -> Vec<u8>;
+
+    /// Validates a shared private key received from the counter-party, fails with
+    /// [`Error::InvalidKey`] if the key is zero or not a valid scalar.
+    fn validate_shared_privkey(_privkey: &Self::SharedPrivateKey) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Checks that the shared private key can be revealed along the public key of the same
+    /// participant, e.g. on Monero the private view key must not be the private key of the spend
+    /// point as sharing it would give away the spend key. The default implementation accepts all
+    /// pairs.
+    fn is_consistent_with(_privkey: &Self::SharedPrivateKey, _pubkey: &Self::PublicKey) -> bool {
+        true
+    }
+}
+
+/// This trait is required for blockchains hiding the transferred amounts, such as Monero. The
+/// amount locked on-chain is only known through a commitment that the counter-party opens with
+/// the shared private view key to verify the amount it hides.
+pub trait AmountCommitments<T>: Asset + SharedPrivateKeys<T>
+where
+    T: Blockchain,
+{
+    /// Commitment to an amount locked on the blockchain, carrying the data needed to open it.
+    type AmountCommitment: Clone + Debug + StrictEncode + StrictDecode;
+
+    /// Opens the commitment with the private view key and verifies that it hides the `expected`
+    /// amount, fails with [`Error::InvalidAmountCommitment`] otherwise.
+    fn verify_amount(
+        commitment: &Self::AmountCommitment,
+        view_key: &Self::SharedPrivateKey,
+        expected: Self::AssetUnit,
+    ) -> Result<(), Error>;
+}
+
+/// Commitment primitive used in the commit/reveal scheme. An engine computes commitments over a
+/// tagged value and verifies a revealed value against a commitment, allowing swap contexts to
+/// plug different commitment schemes without changing the protocol messages.
+pub trait CommitmentEngine {
+    /// Commitment type produced by the engine.
+    type Commitment: Clone
+        + PartialEq
+        + Eq
+        + Hash
+        + Debug
+        + AsRef<[u8]>
+        + StrictEncode
+        + StrictDecode;
+
+    /// Commit to the value prefixed by the tag, an empty tag commits to the value alone.
+    fn commit(tag: &[u8], value: &[u8]) -> Self::Commitment;
+
+    /// Verify that the value, without tag, commits to the given commitment. The default
+    /// implementation compares in constant time as the value is revealed by the counter-party.
+    fn verify(value: &[u8], commitment: &Self::Commitment) -> bool {
+        let computed = Self::commit(&[], value);
+        bool::from(computed.as_ref().ct_eq(commitment.as_ref()))
+    }
 }
 
 /// This trait is required for blockchains for fixing the commitment types of the keys and
 /// parameters that must go through the commit/reveal scheme at the beginning of the protocol.
 pub trait Commitment {
     /// Commitment type used in the commit/reveal scheme during swap parameters setup.
-    type Commitment: Clone + PartialEq + Eq + Debug + StrictEncode + StrictDecode;
+    type Commitment: Clone
+        + PartialEq
+        + Eq
+        + Hash
+        + Debug
+        + AsRef<[u8]>
+        + StrictEncode
+        + StrictDecode;
+
+    /// Engine computing and verifying the commitments.
+    type Engine: CommitmentEngine<Commitment = Self::Commitment>;
+
+    /// Commit to the value, the protocol messages commit to the swap parameters through this
+    /// method. The default implementation uses the context's engine, a context can override it to
+    /// use another scheme, e.g. Pedersen commitments.
+    fn commit(value: &[u8]) -> Self::Commitment {
+        Self::Engine::commit(&[], value)
+    }
+
+    /// Open the commitment with the revealed value, return true if the value commits to the
+    /// commitment. Must be overridden along with [`Commitment::commit`].
+    fn open(value: &[u8], commitment: &Self::Commitment) -> bool {
+        Self::Engine::verify(value, commitment)
+    }
 
     /// Provides a generic method to commit to any value referencable as stream of bytes.
-    fn commit_to<T: AsRef<[u8]>>(value: T) -> Self::Commitment;
+    fn commit_to<T: AsRef<[u8]>>(value: T) -> Self::Commitment {
+        Self::commit(value.as_ref())
+    }
 
     /// Validate the equality between a value and a commitment, return ok if the value commits to
     /// the same commitment's value.
     fn validate<T: AsRef<[u8]>>(value: T, commitment: Self::Commitment) -> Result<(), Error> {
-        if Self::commit_to(value) == commitment {
+        if Self::open(value.as_ref(), &commitment) {
             Ok(())
         } else {
             Err(Error::InvalidCommitment)
@@ -231,32 +501,61 @@ pub trait Commitment {
 /// adaptor signatures.
 pub trait Signatures: Keys {
     /// Defines the signature format for the arbitrating blockchain
-    type Signature: Clone + Debug + StrictEncode + StrictDecode;
+    type Signature: Clone + PartialEq + Eq + Debug + StrictEncode + StrictDecode;
 
     /// Defines the adaptor signature format for the arbitrating blockchain. Adaptor signature may
     /// have a different format from the signature depending on the cryptographic primitives used.
-    type AdaptorSignature: Clone + Debug + StrictEncode + StrictDecode;
+    type AdaptorSignature: Clone + PartialEq + Eq + Debug + StrictEncode + StrictDecode;
 
     /// Finalize an adaptor signature into an adapted signature following the regular signature
     /// format.
     fn adapt(key: &Self::PrivateKey, sig: Self::AdaptorSignature)
         -> Result<Self::Signature, Error>;
 
-    /// Recover the encryption key based on the adaptor signature and the decrypted signature.
-    fn recover_key(sig: Self::Signature, adapted_sig: Self::AdaptorSignature) -> Self::PrivateKey;
+    /// Recover the adaptor secret from an adaptor signature and the final signature adapted from
+    /// it, fails with [`Error::InvalidAdaptorSignature`] if the final signature does not
+    /// correspond to the adaptor signature.
+    fn recover_secret(
+        adaptor_sig: &Self::AdaptorSignature,
+        final_sig: &Self::Signature,
+    ) -> Result<Self::PrivateKey, Error>;
+
+    /// Validates the encoding of a signature received from a peer, fails with
+    /// [`consensus::Error::InvalidSignatureEncoding`] if the signature is not canonical, e.g. a
+    /// malleated signature the blockchain would reject at broadcast.
+    fn validate_signature(sig: &Self::Signature) -> Result<(), consensus::Error>;
+
+    /// Validates the encoding of an adaptor signature received from a peer, fails with
+    /// [`consensus::Error::InvalidSignatureEncoding`] if the signature is not canonical.
+    fn validate_adaptor_signature(sig: &Self::AdaptorSignature) -> Result<(), consensus::Error>;
 }
 
 /// Define a proving system to link two different blockchain cryptographic group parameters.
-pub trait DleqProof<Ar, Ac>: Clone + Debug + StrictEncode + StrictDecode
+///
+/// Proofs are compared structurally, on the points and scalars of their transcript, so a proof
+/// received again can be matched against a proof already verified.
+pub trait DleqProof<Ar, Ac>: Clone + PartialEq + Eq + Debug + StrictEncode + StrictDecode
 where
     Ar: Arbitrating,
     Ac: Accordant,
 {
     fn project_over(ac_seed: &<Ac as FromSeed<Acc>>::Seed) -> Result<Ar::PrivateKey, Error>;
 
-    fn generate(
+    /// Generates the accordant spend key, the arbitrating adaptor key and the proof linking them,
+    /// the proof nonces are drawn from the given random number generator. A seeded generator
+    /// makes the proof reproducible.
+    fn generate_with_rng<R: RngCore + CryptoRng>(
         ac_seed: &<Ac as FromSeed<Acc>>::Seed,
+        rng: &mut R,
     ) -> Result<(Ac::PublicKey, Ar::PublicKey, Self), Error>;
 
+    /// Generates the keys and the proof as [`DleqProof::generate_with_rng`] with the operating
+    /// system's random number generator.
+    fn generate(
+        ac_seed: &<Ac as FromSeed<Acc>>::Seed,
+    ) -> Result<(Ac::PublicKey, Ar::PublicKey, Self), Error> {
+        Self::generate_with_rng(ac_seed, &mut OsRng)
+    }
+
     fn verify(spend: &Ac::PublicKey, adaptor: &Ar::PublicKey, proof: Self) -> Result<(), Error>;
 }
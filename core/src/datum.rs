@@ -214,6 +214,35 @@ impl Decodable for KeyId {
     }
 }
 
+/// The purpose of a key in the swap, regardless of the role holding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyPurpose {
+    Fund,
+    Buy,
+    Cancel,
+    Refund,
+    Punish,
+    Adaptor,
+    Spend,
+    PrivateView,
+}
+
+impl KeyId {
+    /// Returns the purpose of the identified key.
+    pub fn purpose(&self) -> KeyPurpose {
+        match self {
+            KeyId::BobFund => KeyPurpose::Fund,
+            KeyId::AliceBuy | KeyId::BobBuy => KeyPurpose::Buy,
+            KeyId::AliceCancel | KeyId::BobCancel => KeyPurpose::Cancel,
+            KeyId::AliceRefund | KeyId::BobRefund => KeyPurpose::Refund,
+            KeyId::AlicePunish => KeyPurpose::Punish,
+            KeyId::AliceAdaptor | KeyId::BobAdaptor => KeyPurpose::Adaptor,
+            KeyId::AliceSpend | KeyId::BobSpend => KeyPurpose::Spend,
+            KeyId::AlicePrivateView | KeyId::BobPrivateView => KeyPurpose::PrivateView,
+        }
+    }
+}
+
 /// The key datum is used to convey keys between clients and daemons. The key is transmitted within
 /// the key_value field in its serialized format and is identified by the key_id.
 #[derive(Clone, Debug, PartialEq)]
@@ -245,7 +274,7 @@ macro_rules! impl_new_key {
         pub fn $fnname(key_value: <Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey) -> Self {
             Self {
                 key_id: $type,
-                key_value: crypto::KeyType::SharedPrivate(key_value),
+                key_value: crypto::KeyType::SharedPrivate(crypto::Secret::new(key_value)),
             }
         }
     };
@@ -400,7 +429,8 @@ where
         let tx_id = Decodable::consensus_decode(d)?;
         let role = Decodable::consensus_decode(d)?;
         let bytes: Vec<u8> = Decodable::consensus_decode(d)?;
-        let sig_value = strict_deserialize(&bytes)?;
+        let sig_value: crypto::SignatureType<S> = strict_deserialize(&bytes)?;
+        sig_value.validate()?;
         Ok(Self {
             tx_id,
             role,
@@ -431,7 +461,7 @@ where
     }
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum ProofId {
     CrossGroupDleq,
 }
@@ -463,6 +493,16 @@ pub struct Proof<Ctx: Swap> {
     pub proof_value: Ctx::Proof,
 }
 
+impl<Ctx: Swap> Eq for Proof<Ctx> {}
+
+// Implemented manually to not require the swap context to implement the trait. Proofs are public,
+// the comparison is structural and not constant-time.
+impl<Ctx: Swap> PartialEq for Proof<Ctx> {
+    fn eq(&self, other: &Self) -> bool {
+        self.proof_id == other.proof_id && self.proof_value == other.proof_value
+    }
+}
+
 impl<Ctx> Proof<Ctx>
 where
     Ctx: Swap,
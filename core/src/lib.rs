@@ -11,9 +11,12 @@ pub mod crypto;
 pub mod datum;
 pub mod instruction;
 pub mod negotiation;
+pub mod observer;
 pub mod protocol_message;
 pub mod role;
 pub mod script;
+#[cfg(feature = "session")]
+pub mod session;
 pub mod swap;
 pub mod transaction;
 
@@ -38,4 +41,26 @@ pub enum Error {
     /// A negotiation error.
     #[error("Negotiation error: {0}")]
     Negotiation(#[from] negotiation::Error),
+    /// The shared accordant private key revealed by a participant cannot be shared along its
+    /// accordant public key.
+    #[error("The shared accordant private key is inconsistent with the public key")]
+    InconsistentAccordantKeys,
+    /// The same public key is revealed for two different roles by a participant.
+    #[error("The same public key is used for multiple roles")]
+    DuplicateKey,
+    /// A protocol message arrived out of order, twice, or from the wrong participant.
+    #[error("Unexpected protocol message")]
+    UnexpectedMessage,
+    /// A signature is computed over a signature hash that does not commit to the whole
+    /// transaction.
+    #[error("Invalid signature hash type: `{0:#04x}`")]
+    InvalidSigHashType(u8),
+    /// A core arbitrating transaction received from the counterparty is malformed, not linked to
+    /// the previous transaction, or does not match the agreed parameters.
+    #[error("Invalid {0:?} transaction: {1}")]
+    InvalidCoreTransaction(transaction::TxId, transaction::Error),
+    /// An encrypted session failed authentication, the data is corrupted or has been tampered
+    /// with.
+    #[error("Decryption failed")]
+    DecryptionFailed,
 }
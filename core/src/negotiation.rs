@@ -8,8 +8,10 @@ use std::io;
 
 use crate::blockchain::{Asset, Fee, FeeStrategy, Network, Timelock};
 use crate::consensus::{self, Decodable, Encodable};
+use crate::crypto::CommitmentEngine;
 use crate::role::{NegotiationRole, SwapRole};
-use crate::swap::Swap;
+use crate::swap::{Swap, SwapId, SWAP_ID_TAG};
+use crate::transaction::ConfirmationBounds;
 
 /// First six magic bytes of a public offer
 pub const OFFER_MAGIC_BYTES: &[u8; 6] = b"FCSWAP";
@@ -57,6 +59,112 @@ pub enum Error {
     /// The public offer signature does not pass the validation tests.
     #[error("Invalid signature")]
     InvalidSignature,
+    /// One of the exchanged amounts is zero.
+    #[error("Swap amounts must not be zero")]
+    ZeroAmount,
+    /// The funds do not cover the arbitrating amount to lock and the fees.
+    #[error("Not enough funds to lock the arbitrating amount and pay the fees")]
+    NotEnoughFunds,
+}
+
+/// The amounts exchanged during a swap, the arbitrating amount is traded for the accordant amount.
+/// Both amounts are validated to be non-zero when created or decoded.
+#[derive(Debug, Clone)]
+pub struct SwapAmounts<Ctx: Swap> {
+    /// Amount of arbitrating assets locked in the swap
+    pub arbitrating: <Ctx::Ar as Asset>::AssetUnit,
+    /// Amount of accordant assets locked in the swap
+    pub accordant: <Ctx::Ac as Asset>::AssetUnit,
+}
+
+impl<Ctx: Swap> Eq for SwapAmounts<Ctx> {}
+
+impl<Ctx: Swap> PartialEq for SwapAmounts<Ctx> {
+    fn eq(&self, other: &Self) -> bool {
+        self.arbitrating == other.arbitrating && self.accordant == other.accordant
+    }
+}
+
+impl<Ctx: Swap> SwapAmounts<Ctx> {
+    /// Create the swap amounts, fails with [`Error::ZeroAmount`] if one of the amounts is zero.
+    pub fn new(
+        arbitrating: <Ctx::Ar as Asset>::AssetUnit,
+        accordant: <Ctx::Ac as Asset>::AssetUnit,
+    ) -> Result<Self, Error> {
+        let units: (u64, u64) = (arbitrating.into(), accordant.into());
+        if units.0 == 0 || units.1 == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        Ok(Self {
+            arbitrating,
+            accordant,
+        })
+    }
+
+    /// Return the exchange rate as the number of accordant smallest units traded for one
+    /// arbitrating smallest unit.
+    pub fn rate(&self) -> f64 {
+        let arbitrating: u64 = self.arbitrating.into();
+        let accordant: u64 = self.accordant.into();
+        accordant as f64 / arbitrating as f64
+    }
+
+    /// Validates that the `funded` arbitrating amount covers the amount to lock and the `fee`,
+    /// fails with [`Error::NotEnoughFunds`] otherwise.
+    pub fn validate_funding(
+        &self,
+        funded: <Ctx::Ar as Asset>::AssetUnit,
+        fee: <Ctx::Ar as Asset>::AssetUnit,
+    ) -> Result<(), Error> {
+        let (arbitrating, fee, funded): (u64, u64, u64) =
+            (self.arbitrating.into(), fee.into(), funded.into());
+        match arbitrating.checked_add(fee) {
+            Some(required) if funded >= required => Ok(()),
+            _ => Err(Error::NotEnoughFunds),
+        }
+    }
+}
+
+impl<Ctx> Encodable for SwapAmounts<Ctx>
+where
+    Ctx: Swap,
+{
+    fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        let len = wrap_in_vec!(wrap arbitrating for self in writer);
+        Ok(len + wrap_in_vec!(wrap accordant for self in writer))
+    }
+}
+
+impl<Ctx> Decodable for SwapAmounts<Ctx>
+where
+    Ctx: Swap,
+{
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Self::new(unwrap_from_vec!(d), unwrap_from_vec!(d))
+            .map_err(|_| consensus::Error::ParseFailed("Swap amounts must not be zero"))
+    }
+}
+
+impl<Ctx> StrictEncode for SwapAmounts<Ctx>
+where
+    Ctx: Swap,
+{
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        Encodable::consensus_encode(self, &mut e).map_err(strict_encoding::Error::from)
+    }
+}
+
+impl<Ctx> StrictDecode for SwapAmounts<Ctx>
+where
+    Ctx: Swap,
+{
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Decodable::consensus_decode(&mut d).map_err(|_| {
+            strict_encoding::Error::DataIntegrityError(
+                "Failed to decode the swap amounts".to_string(),
+            )
+        })
+    }
 }
 
 /// An offer is created by a Maker before the start of his daemon, it references all the data
@@ -83,6 +191,8 @@ pub struct Offer<Ctx: Swap> {
     pub fee_strategy: FeeStrategy<<Ctx::Ar as Fee>::FeeUnit>,
     /// The future maker swap role
     pub maker_role: SwapRole,
+    /// The confirmations required on each transaction before moving to the next step
+    pub confirmation_bounds: ConfirmationBounds,
 }
 
 impl<Ctx: Swap> Eq for Offer<Ctx> {}
@@ -103,11 +213,16 @@ impl<Ctx: Swap> Offer<Ctx> {
         }
     }
 
+    /// Return the validated amounts exchanged in the offer.
+    pub fn amounts(&self) -> Result<SwapAmounts<Ctx>, Error> {
+        SwapAmounts::new(self.arbitrating_amount, self.accordant_amount)
+    }
+
     /// Return the future swap role for the given negotiation role.
     pub fn swap_role(&self, nego_role: &NegotiationRole) -> SwapRole {
         match nego_role {
             NegotiationRole::Maker => self.maker_role,
-            NegotiationRole::Taker => self.maker_role.other(),
+            NegotiationRole::Taker => self.maker_role.opposite(),
         }
     }
 }
@@ -131,7 +246,8 @@ where
         len += wrap_in_vec!(wrap cancel_timelock for self in writer);
         len += wrap_in_vec!(wrap punish_timelock for self in writer);
         len += self.fee_strategy.consensus_encode(writer)?;
-        Ok(len + self.maker_role.consensus_encode(writer)?)
+        len += self.maker_role.consensus_encode(writer)?;
+        Ok(len + self.confirmation_bounds.consensus_encode(writer)?)
     }
 }
 
@@ -140,18 +256,24 @@ where
     Ctx: Swap,
 {
     fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        use consensus::decode_field as field;
         Ok(Offer {
-            network: Decodable::consensus_decode(d)?,
-            arbitrating_blockchain: Ctx::Ar::from_u32(Decodable::consensus_decode(d)?)
-                .ok_or(consensus::Error::UnknownType)?,
-            accordant_blockchain: Ctx::Ac::from_u32(Decodable::consensus_decode(d)?)
-                .ok_or(consensus::Error::UnknownType)?,
-            arbitrating_amount: unwrap_from_vec!(d),
-            accordant_amount: unwrap_from_vec!(d),
-            cancel_timelock: unwrap_from_vec!(d),
-            punish_timelock: unwrap_from_vec!(d),
-            fee_strategy: Decodable::consensus_decode(d)?,
-            maker_role: Decodable::consensus_decode(d)?,
+            network: field("network", || Decodable::consensus_decode(d))?,
+            arbitrating_blockchain: field("arbitrating_blockchain", || {
+                Ctx::Ar::from_u32(Decodable::consensus_decode(d)?)
+                    .ok_or(consensus::Error::UnknownType)
+            })?,
+            accordant_blockchain: field("accordant_blockchain", || {
+                Ctx::Ac::from_u32(Decodable::consensus_decode(d)?)
+                    .ok_or(consensus::Error::UnknownType)
+            })?,
+            arbitrating_amount: field("arbitrating_amount", || Ok(unwrap_from_vec!(d)))?,
+            accordant_amount: field("accordant_amount", || Ok(unwrap_from_vec!(d)))?,
+            cancel_timelock: field("cancel_timelock", || Ok(unwrap_from_vec!(d)))?,
+            punish_timelock: field("punish_timelock", || Ok(unwrap_from_vec!(d)))?,
+            fee_strategy: field("fee_strategy", || Decodable::consensus_decode(d))?,
+            maker_role: field("maker_role", || Decodable::consensus_decode(d))?,
+            confirmation_bounds: field("confirmation_bounds", || Decodable::consensus_decode(d))?,
         })
     }
 }
@@ -209,6 +331,13 @@ where
         self
     }
 
+    /// Sets the confirmation bounds for the proposed offer, defaults to
+    /// [`ConfirmationBounds::default`] if not set
+    pub fn with_confirmation_bounds(mut self, bounds: ConfirmationBounds) -> Self {
+        self.0.confirmation_bounds = Some(bounds);
+        self
+    }
+
     /// Transform the internal state into an offer if all parameters have been
     /// set properly, otherwise return `None`.
     ///
@@ -226,6 +355,7 @@ where
             punish_timelock: self.0.punish_timelock?,
             fee_strategy: self.0.fee_strategy?,
             maker_role: self.0.maker_role?,
+            confirmation_bounds: self.0.confirmation_bounds.unwrap_or_default(),
         })
     }
 }
@@ -283,6 +413,13 @@ where
         self
     }
 
+    /// Sets the confirmation bounds for the proposed offer, defaults to
+    /// [`ConfirmationBounds::default`] if not set
+    pub fn with_confirmation_bounds(mut self, bounds: ConfirmationBounds) -> Self {
+        self.0.confirmation_bounds = Some(bounds);
+        self
+    }
+
     /// Transform the internal state into an offer if all parameters have been
     /// set properly, otherwise return `None`.
     ///
@@ -300,6 +437,7 @@ where
             punish_timelock: self.0.punish_timelock?,
             fee_strategy: self.0.fee_strategy?,
             maker_role: self.0.maker_role?,
+            confirmation_bounds: self.0.confirmation_bounds.unwrap_or_default(),
         })
     }
 }
@@ -315,6 +453,7 @@ struct BuilderState<Ctx: Swap> {
     punish_timelock: Option<<Ctx::Ar as Timelock>::Timelock>,
     fee_strategy: Option<FeeStrategy<<Ctx::Ar as Fee>::FeeUnit>>,
     maker_role: Option<SwapRole>,
+    confirmation_bounds: Option<ConfirmationBounds>,
 }
 
 impl<Ctx> Default for BuilderState<Ctx>
@@ -332,6 +471,7 @@ where
             punish_timelock: None,
             fee_strategy: None,
             maker_role: None,
+            confirmation_bounds: None,
         }
     }
 }
@@ -355,6 +495,18 @@ impl<Ctx: Swap> PublicOffer<Ctx> {
     pub fn swap_role(&self, nego_role: &NegotiationRole) -> SwapRole {
         self.offer.swap_role(nego_role)
     }
+
+    /// Returns the identifier of the swap executing this offer, hashed with the commitment engine
+    /// of the swap context.
+    pub fn swap_id(&self) -> SwapId<Ctx::Commitment> {
+        self.swap_id_with::<Ctx::Engine>()
+    }
+
+    /// Returns the identifier of the swap executing this offer hashed with the given engine, e.g.
+    /// to derive identifiers matching the hash function of another blockchain.
+    pub fn swap_id_with<E: CommitmentEngine>(&self) -> SwapId<E::Commitment> {
+        SwapId(E::commit(SWAP_ID_TAG, &consensus::serialize(self)))
+    }
 }
 
 impl<Ctx> std::fmt::Display for PublicOffer<Ctx>
@@ -421,10 +573,9 @@ where
             return Err(consensus::Error::IncorrectMagicBytes);
         }
         Ok(PublicOffer {
-            version: Decodable::consensus_decode(d)?,
-            offer: Decodable::consensus_decode(d)?,
-            daemon_service: strict_encoding::StrictDecode::strict_decode(d)
-                .map_err(|_| consensus::Error::ParseFailed("Failed to decode RemoteNodeAddr"))?,
+            version: consensus::decode_field("version", || Decodable::consensus_decode(d))?,
+            offer: consensus::decode_field("offer", || Decodable::consensus_decode(d))?,
+            daemon_service: consensus::strict_decode_field("daemon_service", d)?,
         })
     }
 }
@@ -443,11 +594,7 @@ where
     Ctx: Swap,
 {
     fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
-        Decodable::consensus_decode(&mut d).map_err(|_| {
-            strict_encoding::Error::DataIntegrityError(
-                "Failed to decode the public offer".to_string(),
-            )
-        })
+        Ok(<Self as Decodable>::consensus_decode(&mut d)?)
     }
 }
 
@@ -0,0 +1,255 @@
+//! View-only observation of a swap from its public protocol messages
+
+use crate::blockchain::{Address, Fee, Transactions};
+use crate::bundle;
+use crate::negotiation::PublicOffer;
+use crate::protocol_message::{
+    BuyProcedureSignature, CommitAliceParameters, CommitBobParameters, CoreArbitratingSetup,
+    RefundProcedureSignatures, SwapMessage, SwapState,
+};
+use crate::role::{validate_core_transactions, SwapRole, ValidatedCoreTransactions};
+use crate::swap::Swap;
+use crate::transaction::{AdaptorSignable, Buyable, Chainable, Forkable, Transaction};
+use crate::Error;
+
+/// Phase of a swap as seen by an observer. A swap progresses from [`SwapPhase::Commit`] to
+/// [`SwapPhase::Completed`] and can be aborted at any phase before completion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SwapPhase {
+    /// The participants exchange the commitments to their parameters
+    Commit,
+    /// The participants reveal their committed parameters
+    Reveal,
+    /// The core arbitrating transactions are proposed and signed
+    CoreSetup,
+    /// The assets are locked on both blockchains
+    Locked,
+    /// All the protocol messages have been exchanged
+    Completed,
+    /// One of the participants aborted the swap
+    Aborted,
+    /// The participants closed the swap cooperatively
+    Closed,
+}
+
+impl From<SwapState> for SwapPhase {
+    fn from(state: SwapState) -> Self {
+        match state {
+            SwapState::Commit { .. } => SwapPhase::Commit,
+            SwapState::Reveal { .. } => SwapPhase::Reveal,
+            SwapState::CoreArbitratingSetup
+            | SwapState::RefundProcedureSignatures
+            | SwapState::AccordantFundingConfirmed => SwapPhase::CoreSetup,
+            SwapState::BuyProcedureSignature => SwapPhase::Locked,
+            SwapState::Done => SwapPhase::Completed,
+            SwapState::Aborted => SwapPhase::Aborted,
+            SwapState::Closed => SwapPhase::Closed,
+        }
+    }
+}
+
+/// Follows a swap from the messages exchanged by its participants without holding any private
+/// key. The commitments, the DLEQ proofs, the core arbitrating transactions, and the exchanged
+/// signatures are verified as the participants would, but nothing is ever signed.
+#[derive(Debug, Clone)]
+pub struct SwapObserver<Ctx: Swap> {
+    public_offer: PublicOffer<Ctx>,
+    state: SwapState,
+    alice_commit: Option<CommitAliceParameters<Ctx>>,
+    bob_commit: Option<CommitBobParameters<Ctx>>,
+    alice_parameters: Option<bundle::AliceParameters<Ctx>>,
+    bob_parameters: Option<bundle::BobParameters<Ctx>>,
+    core_arbitrating_setup: Option<CoreArbitratingSetup<Ctx>>,
+}
+
+impl<Ctx> SwapObserver<Ctx>
+where
+    Ctx: Swap,
+{
+    /// Create an observer of the swap executing the public offer, waiting for the commitments.
+    pub fn new(public_offer: PublicOffer<Ctx>) -> Self {
+        Self {
+            public_offer,
+            state: SwapState::new(),
+            alice_commit: None,
+            bob_commit: None,
+            alice_parameters: None,
+            bob_parameters: None,
+            core_arbitrating_setup: None,
+        }
+    }
+
+    /// Ingest a message sent by the participant with the given role. The message must be
+    /// expected in the current state, see [`SwapState::apply`], and is verified against the
+    /// public data already observed: reveals against their commitments and DLEQ proof, the core
+    /// arbitrating setup against the revealed parameters and the public offer, the refund
+    /// procedure signatures against Alice's keys and the `cancel (d)` and `refund (e)`
+    /// transactions, and the buy procedure signature against Bob's buy key and the `buy (c)`
+    /// template. Fails without changing the observed state.
+    pub fn ingest(&mut self, msg: &SwapMessage<Ctx>, role: SwapRole) -> Result<(), Error> {
+        let mut state = self.state;
+        state.apply(msg, role)?;
+
+        match msg {
+            SwapMessage::CommitAliceParameters(commit) => {
+                self.alice_commit = Some(commit.clone());
+            }
+            SwapMessage::CommitBobParameters(commit) => {
+                self.bob_commit = Some(commit.clone());
+            }
+            SwapMessage::RevealAliceParameters(reveal) => {
+                let commit = self.alice_commit.as_ref().ok_or(Error::UnexpectedMessage)?;
+                self.alice_parameters = Some(commit.verify_then_bundle(reveal)?);
+            }
+            SwapMessage::RevealBobParameters(reveal) => {
+                let commit = self.bob_commit.as_ref().ok_or(Error::UnexpectedMessage)?;
+                self.bob_parameters = Some(commit.verify_then_bundle(reveal)?);
+            }
+            SwapMessage::CoreArbitratingSetup(setup) => {
+                match (&self.alice_parameters, &self.bob_parameters) {
+                    (Some(alice), Some(bob)) => setup.verify(alice, bob, &self.public_offer)?,
+                    _ => return Err(Error::UnexpectedMessage),
+                }
+                self.core_arbitrating_setup = Some(setup.clone());
+            }
+            SwapMessage::RefundProcedureSignatures(sigs) => {
+                self.verify_refund_procedure_signatures(sigs)?
+            }
+            SwapMessage::BuyProcedureSignature(sig) => self.verify_buy_procedure_signature(sig)?,
+            SwapMessage::AccordantFundingConfirmed(_)
+            | SwapMessage::Abort(_)
+            | SwapMessage::CooperativeClose(_) => (),
+        }
+
+        self.state = state;
+        Ok(())
+    }
+
+    /// Verify Alice's `cancel (d)` signature and her `refund (e)` adaptor signature, encrypted
+    /// with Bob's adaptor key, against the verified core arbitrating setup.
+    fn verify_refund_procedure_signatures(
+        &self,
+        sigs: &RefundProcedureSignatures<Ctx>,
+    ) -> Result<(), Error> {
+        let (alice, bob, setup) = self.verified_setup()?;
+        sigs.verify_refund_sighash_type(&setup.refund)?;
+
+        let cancel = <<Ctx::Ar as Transactions>::Cancel>::from_partial(setup.cancel.clone());
+        cancel.verify_failure_witness(
+            &alice.cancel.key().try_into_arbitrating_pubkey()?,
+            sigs.cancel_sig.clone(),
+        )?;
+
+        let refund = <<Ctx::Ar as Transactions>::Refund>::from_partial(setup.refund.clone());
+        refund.verify_adaptor_witness(
+            &alice.refund.key().try_into_arbitrating_pubkey()?,
+            &bob.adaptor.key().try_into_arbitrating_pubkey()?,
+            sigs.refund_adaptor_sig.clone(),
+        )?;
+        Ok(())
+    }
+
+    /// Verify that the `buy (c)` transaction spends the verified `lock (b)` to Alice's
+    /// destination address following the fee strategy, and that Bob's adaptor signature,
+    /// encrypted with Alice's adaptor key, is valid.
+    fn verify_buy_procedure_signature(
+        &self,
+        msg: &BuyProcedureSignature<Ctx>,
+    ) -> Result<(), Error> {
+        let (alice, bob, setup) = self.verified_setup()?;
+        let ValidatedCoreTransactions {
+            lock, data_lock, ..
+        } = validate_core_transactions(
+            alice,
+            bob,
+            &setup.into_core_transactions(),
+            &self.public_offer,
+        )?;
+
+        let buy = <<Ctx::Ar as Transactions>::Buy>::from_partial(msg.buy.clone());
+        buy.is_build_on_top_of(&lock)?;
+        buy.verify_template(
+            data_lock,
+            alice.destination_address.param().try_into_address()?,
+        )?;
+        <Ctx::Ar as Fee>::validate_fee(buy.partial(), &self.public_offer.offer.fee_strategy)?;
+        buy.verify_adaptor_witness(
+            &bob.buy.key().try_into_arbitrating_pubkey()?,
+            &alice.adaptor.key().try_into_arbitrating_pubkey()?,
+            msg.buy_adaptor_sig.clone(),
+        )?;
+        Ok(())
+    }
+
+    /// Return both participants' parameters and the core arbitrating setup once verified.
+    #[allow(clippy::type_complexity)]
+    fn verified_setup(
+        &self,
+    ) -> Result<
+        (
+            &bundle::AliceParameters<Ctx>,
+            &bundle::BobParameters<Ctx>,
+            &CoreArbitratingSetup<Ctx>,
+        ),
+        Error,
+    > {
+        match (
+            &self.alice_parameters,
+            &self.bob_parameters,
+            &self.core_arbitrating_setup,
+        ) {
+            (Some(alice), Some(bob), Some(setup)) => Ok((alice, bob, setup)),
+            _ => Err(Error::UnexpectedMessage),
+        }
+    }
+
+    /// Return the current phase of the swap.
+    pub fn current_phase(&self) -> SwapPhase {
+        self.state.into()
+    }
+
+    /// Return the state of the protocol messages exchange.
+    pub fn state(&self) -> SwapState {
+        self.state
+    }
+
+    /// Return the public offer executed by the swap.
+    pub fn public_offer(&self) -> &PublicOffer<Ctx> {
+        &self.public_offer
+    }
+
+    /// Return Alice's parameters once revealed and verified.
+    pub fn alice_parameters(&self) -> Option<&bundle::AliceParameters<Ctx>> {
+        self.alice_parameters.as_ref()
+    }
+
+    /// Return Bob's parameters once revealed and verified.
+    pub fn bob_parameters(&self) -> Option<&bundle::BobParameters<Ctx>> {
+        self.bob_parameters.as_ref()
+    }
+
+    /// Return the `lock (b)`, `cancel (d)`, and `refund (e)` transaction templates once
+    /// verified.
+    pub fn core_arbitrating_setup(&self) -> Option<&CoreArbitratingSetup<Ctx>> {
+        self.core_arbitrating_setup.as_ref()
+    }
+
+    /// Return the arbitrating addresses receiving the funds at the end of the swap, Alice's
+    /// destination address and Bob's refund address, as soon as they are revealed.
+    pub fn watched_addresses(&self) -> Vec<<Ctx::Ar as Address>::Address> {
+        let mut addresses = vec![];
+        if let Some(alice) = &self.alice_parameters {
+            if let Ok(address) = alice.destination_address.param().try_into_address() {
+                addresses.push(address);
+            }
+        }
+        if let Some(bob) = &self.bob_parameters {
+            if let Ok(address) = bob.refund_address.param().try_into_address() {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+        addresses
+    }
+}
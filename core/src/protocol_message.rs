@@ -1,23 +1,189 @@
 //! Protocol messages exchanged between swap daemons
 
 use std::convert::TryInto;
+use std::fmt;
+use std::io;
 use strict_encoding::{StrictDecode, StrictEncode};
 
-use crate::blockchain::{Address, Onchain};
+use crate::blockchain::{Address, Asset, Fee, FeeStrategy, Onchain, Transactions};
 use crate::bundle;
-use crate::crypto::{DleqProof, Keys, SharedPrivateKeys, SignatureType, Signatures};
+use crate::consensus;
+use crate::crypto::{DleqProof, Keys, Secret, SharedPrivateKeys, SignatureType, Signatures};
 use crate::datum;
-use crate::role::{Acc, SwapRole};
+use crate::negotiation::PublicOffer;
+use crate::role::{validate_core_transactions, Acc, SwapRole};
 use crate::swap::Swap;
-use crate::transaction::TxId;
+use crate::transaction::{self, Chainable, Linkable, Transaction, TxId};
 use crate::Error;
 
 /// Trait for defining inter-daemon communication messages.
-pub trait ProtocolMessage: StrictEncode + StrictDecode {}
+pub trait ProtocolMessage: StrictEncode + StrictDecode {
+    /// Returns the identifier of the message type, allowing generic code to log or route a
+    /// message without matching on its concrete type.
+    fn message_id(&self) -> MessageId;
+
+    /// Returns the exact length in bytes of the strict encoded message. The message is encoded
+    /// into a sink to count the bytes, allowing callers to allocate the buffer before encoding.
+    /// Fails if the message cannot be encoded.
+    fn strict_encoded_len(&self) -> Result<usize, consensus::Error> {
+        Ok(self.strict_encode(io::sink())?)
+    }
+}
+
+/// Identifies the type of a [`ProtocolMessage`]. Identifiers are displayed with the message names
+/// used in the specifications, e.g. `core_arbitrating_setup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageId {
+    CommitAliceParameters,
+    CommitBobParameters,
+    RevealAliceParameters,
+    RevealBobParameters,
+    CoreArbitratingSetup,
+    RefundProcedureSignatures,
+    BuyProcedureSignature,
+    CooperativeClose,
+    MuSigNonceCommitment,
+    MuSigNonceReveal,
+    Abort,
+    AccordantFundingConfirmed,
+}
+
+impl fmt::Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MessageId::CommitAliceParameters => "commit_alice_session_params",
+            MessageId::CommitBobParameters => "commit_bob_session_params",
+            MessageId::RevealAliceParameters => "reveal_alice_session_params",
+            MessageId::RevealBobParameters => "reveal_bob_session_params",
+            MessageId::CoreArbitratingSetup => "core_arbitrating_setup",
+            MessageId::RefundProcedureSignatures => "refund_procedure_signatures",
+            MessageId::BuyProcedureSignature => "buy_procedure_signature",
+            MessageId::CooperativeClose => "cooperative_close",
+            MessageId::MuSigNonceCommitment => "musig_nonce_commitment",
+            MessageId::MuSigNonceReveal => "musig_nonce_reveal",
+            MessageId::Abort => "abort",
+            MessageId::AccordantFundingConfirmed => "accordant_funding_confirmed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Fails with [`Error::DuplicateKey`] if any two of the serialized public keys are equal.
+fn ensure_distinct_keys(keys: &[Vec<u8>]) -> Result<(), Error> {
+    for (i, key) in keys.iter().enumerate() {
+        if keys[i + 1..].contains(key) {
+            return Err(Error::DuplicateKey);
+        }
+    }
+    Ok(())
+}
+
+/// Maximum length in bytes of a framed message payload accepted by [`read_frame`].
+pub const MAX_FRAME_LEN: u32 = 1 << 24;
+
+/// Magic bytes starting every frame created with [`frame`].
+pub const FRAME_MAGIC_BYTES: &[u8; 4] = b"FCSM";
+
+/// Version of the protocol messages, written in every frame created with [`frame`]. Frames with
+/// another version are rejected with [`consensus::Error::IncompatibleVersion`].
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Length in bytes of the frame header: magic bytes, protocol version, payload length and
+/// checksum.
+const FRAME_HEADER_LEN: usize = 14;
+
+/// Updates a running CRC-32 (IEEE) register with the given bytes.
+fn crc32_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+    crc
+}
+
+/// Computes the CRC-32 (IEEE) checksum of the given bytes.
+fn crc32(bytes: &[u8]) -> u32 {
+    !crc32_update(0xffff_ffff, bytes)
+}
+
+/// Reader computing the CRC-32 checksum of the bytes read through it.
+struct Crc32Reader<R> {
+    inner: R,
+    crc: u32,
+}
+
+impl<R: io::Read> io::Read for Crc32Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.crc = crc32_update(self.crc, &buf[..len]);
+        Ok(len)
+    }
+}
+
+/// Serializes a protocol message into a self-delimiting frame for stream transports. The strict
+/// encoded message is prefixed with the [`FRAME_MAGIC_BYTES`], the [`PROTOCOL_VERSION`] as a
+/// 2-byte big-endian integer, then its length and its CRC-32 checksum, both as 4-byte big-endian
+/// integers.
+pub fn frame(msg: &impl ProtocolMessage) -> Result<Vec<u8>, consensus::Error> {
+    let mut frame = Vec::with_capacity(msg.strict_encoded_len()? + FRAME_HEADER_LEN);
+    frame.extend_from_slice(&[0u8; FRAME_HEADER_LEN]);
+    let len = msg.strict_encode(&mut frame)?;
+    let len = match len as u64 <= MAX_FRAME_LEN as u64 {
+        true => len as u32,
+        false => return Err(consensus::Error::CorruptFrame),
+    };
+    let checksum = crc32(&frame[FRAME_HEADER_LEN..]);
+    frame[..4].copy_from_slice(FRAME_MAGIC_BYTES);
+    frame[4..6].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    frame[6..10].copy_from_slice(&len.to_be_bytes());
+    frame[10..14].copy_from_slice(&checksum.to_be_bytes());
+    Ok(frame)
+}
+
+/// Parses a frame header and returns the payload length and checksum. Fails with
+/// [`consensus::Error::IncorrectMagicBytes`] if the frame does not start with the magic bytes,
+/// with [`consensus::Error::IncompatibleVersion`] if it was created with another protocol version,
+/// and with [`consensus::Error::CorruptFrame`] if the length exceeds [`MAX_FRAME_LEN`].
+fn parse_frame_header(header: &[u8; FRAME_HEADER_LEN]) -> Result<(u32, u32), consensus::Error> {
+    if header[..4] != FRAME_MAGIC_BYTES[..] {
+        return Err(consensus::Error::IncorrectMagicBytes);
+    }
+    let version = u16::from_be_bytes([header[4], header[5]]);
+    if version != PROTOCOL_VERSION {
+        return Err(consensus::Error::IncompatibleVersion(version));
+    }
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&header[6..10]);
+    let len = u32::from_be_bytes(len);
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&header[10..]);
+    if len > MAX_FRAME_LEN {
+        return Err(consensus::Error::CorruptFrame);
+    }
+    Ok((len, u32::from_be_bytes(checksum)))
+}
+
+/// Reads one frame created with [`frame`] from the reader and returns its payload. The magic
+/// bytes and the protocol version are checked first, then the length is checked against
+/// [`MAX_FRAME_LEN`] before reading the payload and the checksum is validated against it, the
+/// reader is never consumed past the end of the frame.
+pub fn read_frame<R: io::Read>(r: &mut R) -> Result<Vec<u8>, consensus::Error> {
+    let mut header = [0u8; FRAME_HEADER_LEN];
+    r.read_exact(&mut header)?;
+    let (len, checksum) = parse_frame_header(&header)?;
+    let mut payload = vec![0u8; len as usize];
+    r.read_exact(&mut payload)?;
+    match crc32(&payload) == checksum {
+        true => Ok(payload),
+        false => Err(consensus::Error::CorruptFrame),
+    }
+}
 
 /// `commit_alice_session_params` forces Alice to commit to the result of her cryptographic setup
 /// before receiving Bob's setup. This is done to remove adaptive behavior.
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, StrictDecode, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct CommitAliceParameters<Ctx: Swap> {
     /// Commitment to `Ab` curve point
@@ -53,6 +219,21 @@ where
     }
 
     pub fn verify(&self, reveal: &RevealAliceParameters<Ctx>) -> Result<(), Error> {
+        // Check that the revealed keys are valid
+        for key in [
+            &reveal.buy,
+            &reveal.cancel,
+            &reveal.refund,
+            &reveal.punish,
+            &reveal.adaptor,
+        ]
+        .iter()
+        {
+            <Ctx::Ar as Keys>::validate_pubkey(key)?;
+        }
+        <Ctx::Ac as Keys>::validate_pubkey(&reveal.spend)?;
+        <Ctx::Ac as SharedPrivateKeys<Acc>>::validate_shared_privkey(reveal.view.expose_secret())?;
+
         // Check buy commitment
         Ctx::validate(<Ctx::Ar as Keys>::as_bytes(&reveal.buy), self.buy.clone())?;
         // Check cancel commitment
@@ -82,11 +263,27 @@ where
         )?;
         // Check private view commitment
         Ctx::validate(
-            <Ctx::Ac as SharedPrivateKeys<Acc>>::as_bytes(&reveal.view),
+            <Ctx::Ac as SharedPrivateKeys<Acc>>::as_bytes(reveal.view.expose_secret()),
             self.view.clone(),
         )?;
+        // Check that the private view key can be shared along the spend key
+        if !<Ctx::Ac as SharedPrivateKeys<Acc>>::is_consistent_with(
+            reveal.view.expose_secret(),
+            &reveal.spend,
+        ) {
+            return Err(Error::InconsistentAccordantKeys);
+        }
+
+        // Check that no public key is reused across roles
+        ensure_distinct_keys(&[
+            <Ctx::Ar as Keys>::as_bytes(&reveal.buy),
+            <Ctx::Ar as Keys>::as_bytes(&reveal.cancel),
+            <Ctx::Ar as Keys>::as_bytes(&reveal.refund),
+            <Ctx::Ar as Keys>::as_bytes(&reveal.punish),
+            <Ctx::Ar as Keys>::as_bytes(&reveal.adaptor),
+        ])?;
 
-        // Check the Dleq proof
+        // Check the Dleq proof last, it is the most expensive check
         DleqProof::verify(&reveal.spend, &reveal.adaptor, reveal.proof.clone())?;
 
         // All validations passed, return ok
@@ -111,11 +308,18 @@ where
     }
 }
 
-impl<Ctx> ProtocolMessage for CommitAliceParameters<Ctx> where Ctx: Swap {}
+impl<Ctx> ProtocolMessage for CommitAliceParameters<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::CommitAliceParameters
+    }
+}
 
 /// `commit_bob_session_params` forces Bob to commit to the result of his cryptographic setup
 /// before receiving Alice's setup. This is done to remove adaptive behavior.
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, StrictDecode, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct CommitBobParameters<Ctx: Swap> {
     /// Commitment to `Bb` curve point
@@ -148,6 +352,13 @@ where
     }
 
     pub fn verify(&self, reveal: &RevealBobParameters<Ctx>) -> Result<(), Error> {
+        // Check that the revealed keys are valid
+        for key in [&reveal.buy, &reveal.cancel, &reveal.refund, &reveal.adaptor].iter() {
+            <Ctx::Ar as Keys>::validate_pubkey(key)?;
+        }
+        <Ctx::Ac as Keys>::validate_pubkey(&reveal.spend)?;
+        <Ctx::Ac as SharedPrivateKeys<Acc>>::validate_shared_privkey(reveal.view.expose_secret())?;
+
         // Check buy commitment
         Ctx::validate(<Ctx::Ar as Keys>::as_bytes(&reveal.buy), self.buy.clone())?;
         // Check cancel commitment
@@ -172,11 +383,26 @@ where
         )?;
         // Check private view commitment
         Ctx::validate(
-            <Ctx::Ac as SharedPrivateKeys<Acc>>::as_bytes(&reveal.view),
+            <Ctx::Ac as SharedPrivateKeys<Acc>>::as_bytes(reveal.view.expose_secret()),
             self.view.clone(),
         )?;
+        // Check that the private view key can be shared along the spend key
+        if !<Ctx::Ac as SharedPrivateKeys<Acc>>::is_consistent_with(
+            reveal.view.expose_secret(),
+            &reveal.spend,
+        ) {
+            return Err(Error::InconsistentAccordantKeys);
+        }
+
+        // Check that no public key is reused across roles
+        ensure_distinct_keys(&[
+            <Ctx::Ar as Keys>::as_bytes(&reveal.buy),
+            <Ctx::Ar as Keys>::as_bytes(&reveal.cancel),
+            <Ctx::Ar as Keys>::as_bytes(&reveal.refund),
+            <Ctx::Ar as Keys>::as_bytes(&reveal.adaptor),
+        ])?;
 
-        // Check the Dleq proof
+        // Check the Dleq proof last, it is the most expensive check
         DleqProof::verify(&reveal.spend, &reveal.adaptor, reveal.proof.clone())?;
 
         // All validations passed, return ok
@@ -201,11 +427,45 @@ where
     }
 }
 
-impl<Ctx> ProtocolMessage for CommitBobParameters<Ctx> where Ctx: Swap {}
+impl<Ctx> ProtocolMessage for CommitBobParameters<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::CommitBobParameters
+    }
+}
+
+/// Verifies both parties' commit/reveal exchange and returns both bundles. The counter-party
+/// parameters, i.e. Bob's parameters if the local role is Alice and vice versa, are verified
+/// first; the commitments and the DLEQ proof of each party are checked and the first error
+/// encountered is returned.
+pub fn verify_exchange<Ctx>(
+    local_role: SwapRole,
+    alice_commit: &CommitAliceParameters<Ctx>,
+    alice_reveal: &RevealAliceParameters<Ctx>,
+    bob_commit: &CommitBobParameters<Ctx>,
+    bob_reveal: &RevealBobParameters<Ctx>,
+) -> Result<(bundle::AliceParameters<Ctx>, bundle::BobParameters<Ctx>), Error>
+where
+    Ctx: Swap,
+{
+    match local_role {
+        SwapRole::Alice => {
+            bob_commit.verify(bob_reveal)?;
+            alice_commit.verify(alice_reveal)?;
+        }
+        SwapRole::Bob => {
+            alice_commit.verify(alice_reveal)?;
+            bob_commit.verify(bob_reveal)?;
+        }
+    }
+    Ok((alice_reveal.into_bundle(), bob_reveal.into_bundle()))
+}
 
 /// `reveal_alice_session_params` reveals the parameters commited by the
 /// `commit_alice_session_params` message.
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+#[derive(Clone, Debug, PartialEq, Eq, StrictDecode, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct RevealAliceParameters<Ctx: Swap> {
     /// The buy `Ab` public key
@@ -223,7 +483,7 @@ pub struct RevealAliceParameters<Ctx: Swap> {
     /// The `K_v^a` view private key
     pub spend: <Ctx::Ac as Keys>::PublicKey,
     /// The `K_s^a` spend public key
-    pub view: <Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey,
+    pub view: Secret<<Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey>,
     /// The cross-group discrete logarithm zero-knowledge proof
     pub proof: Ctx::Proof,
 }
@@ -241,7 +501,7 @@ where
             adaptor: bundle.adaptor.key().try_into_arbitrating_pubkey()?,
             address: bundle.destination_address.param().try_into_address()?,
             spend: bundle.spend.key().try_into_accordant_pubkey()?,
-            view: bundle.view.key().try_into_shared_private()?,
+            view: Secret::new(bundle.view.key().try_into_shared_private()?),
             proof: bundle.proof.proof().clone(),
         })
     }
@@ -254,7 +514,7 @@ where
             punish: datum::Key::new_alice_punish(self.punish.clone()),
             adaptor: datum::Key::new_alice_adaptor(self.adaptor.clone()),
             destination_address: datum::Parameter::new_destination_address(self.address.clone()),
-            view: datum::Key::new_alice_private_view(self.view.clone()),
+            view: datum::Key::new_alice_private_view(self.view.expose_secret().clone()),
             spend: datum::Key::new_alice_spend(self.spend.clone()),
             proof: datum::Proof::new_cross_group_dleq(self.proof.clone()),
             cancel_timelock: None,
@@ -284,11 +544,18 @@ where
     }
 }
 
-impl<Ctx> ProtocolMessage for RevealAliceParameters<Ctx> where Ctx: Swap {}
+impl<Ctx> ProtocolMessage for RevealAliceParameters<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::RevealAliceParameters
+    }
+}
 
 /// `reveal_bob_session_params` reveals the parameters commited by the `commit_bob_session_params`
 /// message.
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+#[derive(Clone, Debug, PartialEq, Eq, StrictDecode, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct RevealBobParameters<Ctx: Swap> {
     /// The buy `Bb` public key
@@ -304,7 +571,7 @@ pub struct RevealBobParameters<Ctx: Swap> {
     /// The `K_v^b` view private key
     pub spend: <Ctx::Ac as Keys>::PublicKey,
     /// The `K_s^b` spend public key
-    pub view: <Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey,
+    pub view: Secret<<Ctx::Ac as SharedPrivateKeys<Acc>>::SharedPrivateKey>,
     /// The cross-group discrete logarithm zero-knowledge proof
     pub proof: Ctx::Proof,
 }
@@ -321,7 +588,7 @@ where
             adaptor: bundle.adaptor.key().try_into_arbitrating_pubkey()?,
             address: bundle.refund_address.param().try_into_address()?,
             spend: bundle.spend.key().try_into_accordant_pubkey()?,
-            view: bundle.view.key().try_into_shared_private()?,
+            view: Secret::new(bundle.view.key().try_into_shared_private()?),
             proof: bundle.proof.proof().clone(),
         })
     }
@@ -333,7 +600,7 @@ where
             refund: datum::Key::new_bob_refund(self.refund.clone()),
             adaptor: datum::Key::new_bob_adaptor(self.adaptor.clone()),
             refund_address: datum::Parameter::new_refund_address(self.address.clone()),
-            view: datum::Key::new_bob_private_view(self.view.clone()),
+            view: datum::Key::new_bob_private_view(self.view.expose_secret().clone()),
             spend: datum::Key::new_bob_spend(self.spend.clone()),
             proof: datum::Proof::new_cross_group_dleq(self.proof.clone()),
             cancel_timelock: None,
@@ -363,11 +630,22 @@ where
     }
 }
 
-impl<Ctx> ProtocolMessage for RevealBobParameters<Ctx> where Ctx: Swap {}
+impl<Ctx> ProtocolMessage for RevealBobParameters<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::RevealBobParameters
+    }
+}
+
+/// Maximum length in bytes of an encoded partial transaction accepted when decoding a message,
+/// far above the size of the swap transactions.
+pub const MAX_PARTIAL_TRANSACTION_LEN: usize = 1 << 16;
 
 /// `core_arbitrating_setup` sends the `lock (b)`, `cancel (d)` and `refund (e)` arbritrating
 /// transactions from Bob to Alice, as well as Bob's signature for the `cancel (d)` transaction.
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+#[derive(Clone, Debug, PartialEq, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct CoreArbitratingSetup<Ctx: Swap> {
     /// The arbitrating `lock (b)` transaction
@@ -384,18 +662,60 @@ impl<Ctx> CoreArbitratingSetup<Ctx>
 where
     Ctx: Swap,
 {
+    /// Creates the message from Bob's core arbitrating transactions and cancel signature. Each
+    /// transaction must pay a fee allowed by the negotiated `fee_strategy`, otherwise the fee
+    /// strategy error is returned.
     pub fn from_bundles(
         txs: &bundle::CoreArbitratingTransactions<Ctx::Ar>,
         sig: &bundle::CosignedArbitratingCancel<Ctx::Ar>,
+        fee_strategy: &FeeStrategy<<Ctx::Ar as Fee>::FeeUnit>,
     ) -> Result<Self, Error> {
+        let lock = txs.lock.tx().try_into_partial_transaction()?;
+        let cancel = txs.cancel.tx().try_into_partial_transaction()?;
+        let refund = txs.refund.tx().try_into_partial_transaction()?;
+        for partial in [&lock, &cancel, &refund].iter() {
+            <Ctx::Ar as Fee>::validate_fee(partial, fee_strategy)?;
+        }
         Ok(Self {
-            lock: txs.lock.tx().try_into_partial_transaction()?,
-            cancel: txs.cancel.tx().try_into_partial_transaction()?,
-            refund: txs.refund.tx().try_into_partial_transaction()?,
+            lock,
+            cancel,
+            refund,
             cancel_sig: sig.cancel_sig.signature().try_into_regular()?,
         })
     }
 
+    /// Verifies the received `lock (b)`, `cancel (d)`, and `refund (e)` transactions before Alice
+    /// countersigns them: each transaction must follow its template with the keys and timelocks
+    /// agreed in the parameters and the public offer, and must spend the previous transaction.
+    /// Fails with [`Error::InvalidCoreTransaction`] naming the first invalid transaction.
+    pub fn verify(
+        &self,
+        alice_parameters: &bundle::AliceParameters<Ctx>,
+        bob_parameters: &bundle::BobParameters<Ctx>,
+        public_offer: &PublicOffer<Ctx>,
+    ) -> Result<(), Error> {
+        let core = self.into_core_transactions();
+        validate_core_transactions(alice_parameters, bob_parameters, &core, public_offer)?;
+        Ok(())
+    }
+
+    /// Verifies that the `lock (b)`, `cancel (d)`, and `refund (e)` transactions form a chain:
+    /// `cancel (d)` must spend the consumable output of `lock (b)` and `refund (e)` the
+    /// consumable output of `cancel (d)`, with the amount and scripts of the spent output, and
+    /// neither can pay out more than the output it spends. Only the links between the
+    /// transactions are checked, not their templates, see [`CoreArbitratingSetup::verify`].
+    /// Fails with [`Error::InvalidCoreTransaction`] naming the first transaction not chained on
+    /// its parent.
+    pub fn verify_chain(&self) -> Result<(), Error> {
+        let lock = <<Ctx::Ar as Transactions>::Lock>::from_partial(self.lock.clone());
+        let cancel = <<Ctx::Ar as Transactions>::Cancel>::from_partial(self.cancel.clone());
+        let refund = <<Ctx::Ar as Transactions>::Refund>::from_partial(self.refund.clone());
+        verify_link(&lock, &cancel).map_err(|e| Error::InvalidCoreTransaction(TxId::Cancel, e))?;
+        verify_link(&cancel, &refund)
+            .map_err(|e| Error::InvalidCoreTransaction(TxId::Refund, e))?;
+        Ok(())
+    }
+
     pub fn into_core_transactions(&self) -> bundle::CoreArbitratingTransactions<Ctx::Ar> {
         bundle::CoreArbitratingTransactions {
             lock: datum::Transaction::new_lock(self.lock.clone()),
@@ -415,27 +735,96 @@ where
     }
 }
 
-impl<Ctx> ProtocolMessage for CoreArbitratingSetup<Ctx> where Ctx: Swap {}
+/// Checks that `tx` spends the consumable output of `prev` and pays out at most its amount.
+fn verify_link<T, O, P, U>(prev: &P, tx: &U) -> Result<(), transaction::Error>
+where
+    T: Asset + Onchain,
+    O: Eq,
+    P: Transaction<T, O> + Linkable<O>,
+    U: Transaction<T, O> + Linkable<O>,
+{
+    // Checked first, a transaction without input cannot be based on anything
+    if !tx.consumes(&prev.get_consumable_output()?) {
+        return Err(transaction::Error::InvalidLink);
+    }
+    <U as Chainable<T, O>>::is_build_on_top_of(tx, prev)?;
+    let (spent, paid): (u64, u64) = (prev.output_amount().into(), tx.output_amount().into());
+    if paid > spent {
+        return Err(transaction::Error::NotEnoughAssets);
+    }
+    Ok(())
+}
+
+impl<Ctx> ProtocolMessage for CoreArbitratingSetup<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::CoreArbitratingSetup
+    }
+}
+
+/// Decodes the message field after field, a failure is reported as a
+/// [`consensus::Error::Field`] error naming the field that failed, e.g. `cancel`.
+impl<Ctx> consensus::Decodable for CoreArbitratingSetup<Ctx>
+where
+    Ctx: Swap,
+{
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Ok(Self {
+            lock: consensus::strict_decode_bounded_field("lock", d, MAX_PARTIAL_TRANSACTION_LEN)?,
+            cancel: consensus::strict_decode_bounded_field(
+                "cancel",
+                d,
+                MAX_PARTIAL_TRANSACTION_LEN,
+            )?,
+            refund: consensus::strict_decode_bounded_field(
+                "refund",
+                d,
+                MAX_PARTIAL_TRANSACTION_LEN,
+            )?,
+            cancel_sig: consensus::strict_decode_field("cancel_sig", d)?,
+        })
+    }
+}
+
+impl<Ctx> StrictDecode for CoreArbitratingSetup<Ctx>
+where
+    Ctx: Swap,
+{
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Ok(<Self as consensus::Decodable>::consensus_decode(&mut d)?)
+    }
+}
+
+/// Signature hash type committing to all the inputs and outputs of a transaction, the only type
+/// accepted for the `refund (e)` adaptor signature.
+pub const SIGHASH_ALL: u8 = 0x01;
 
 /// `refund_procedure_signatures` is intended to transmit Alice's signature for the `cancel (d)`
 /// transaction and Alice's adaptor signature for the `refund (e)` transaction. Uppon reception Bob
 /// must validate the signatures.
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+#[derive(Clone, Debug, PartialEq, Eq, StrictDecode, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct RefundProcedureSignatures<Ctx: Swap> {
     /// The `Ac` `cancel (d)` signature
     pub cancel_sig: <Ctx::Ar as Signatures>::Signature,
     /// The `Ar(Tb)` `refund (e)` adaptor signature
     pub refund_adaptor_sig: <Ctx::Ar as Signatures>::AdaptorSignature,
+    /// The signature hash type used to compute the `refund (e)` adaptor signature
+    pub refund_sighash_type: u8,
 }
 
 impl<Ctx> RefundProcedureSignatures<Ctx>
 where
     Ctx: Swap,
 {
+    /// Creates the message from the signature bundles, the signature hash type is the one set on
+    /// the input of the `refund (e)` transaction the adaptor signature is computed on.
     pub fn from_bundles(
         sig: &bundle::CosignedArbitratingCancel<Ctx::Ar>,
         adaptor_sig: &bundle::SignedAdaptorRefund<Ctx::Ar>,
+        refund: &<Ctx::Ar as Onchain>::PartialTransaction,
     ) -> Result<Self, Error> {
         Ok(Self {
             cancel_sig: sig.cancel_sig.signature().try_into_regular()?,
@@ -443,9 +832,41 @@ where
                 .refund_adaptor_sig
                 .signature()
                 .try_into_adaptor()?,
+            refund_sighash_type: Self::input_sighash_type(refund)?,
+        })
+    }
+
+    /// Returns the signature hash type set on the input of the `refund (e)` transaction, a
+    /// transaction without one is not a valid refund template.
+    fn input_sighash_type(refund: &<Ctx::Ar as Onchain>::PartialTransaction) -> Result<u8, Error> {
+        <Ctx::Ar as Onchain>::sighash_type(refund).ok_or_else(|| {
+            Error::InvalidCoreTransaction(TxId::Refund, transaction::Error::WrongTemplate)
         })
     }
 
+    /// Validates that the `refund (e)` adaptor signature commits to the whole transaction, fails
+    /// with [`Error::InvalidSigHashType`] if the signature hash type is not [`SIGHASH_ALL`].
+    pub fn verify_sighash_type(&self) -> Result<(), Error> {
+        match self.refund_sighash_type {
+            SIGHASH_ALL => Ok(()),
+            sighash_type => Err(Error::InvalidSigHashType(sighash_type)),
+        }
+    }
+
+    /// Validates the signature hash type as [`Self::verify_sighash_type`] and that it is the one
+    /// set on the input of the `refund (e)` transaction, fails with
+    /// [`Error::InvalidSigHashType`] carrying the input type if they differ.
+    pub fn verify_refund_sighash_type(
+        &self,
+        refund: &<Ctx::Ar as Onchain>::PartialTransaction,
+    ) -> Result<(), Error> {
+        self.verify_sighash_type()?;
+        match Self::input_sighash_type(refund)? {
+            sighash_type if sighash_type == self.refund_sighash_type => Ok(()),
+            sighash_type => Err(Error::InvalidSigHashType(sighash_type)),
+        }
+    }
+
     pub fn into_cosigned_cancel(&self) -> bundle::CosignedArbitratingCancel<Ctx::Ar> {
         bundle::CosignedArbitratingCancel {
             cancel_sig: datum::Signature::new(
@@ -456,23 +877,33 @@ where
         }
     }
 
-    pub fn into_adaptor_refund(&self) -> bundle::SignedAdaptorRefund<Ctx::Ar> {
-        bundle::SignedAdaptorRefund {
+    /// Return the `refund (e)` adaptor signature bundle, the signature is trusted only if it
+    /// commits to the whole transaction, see [`Self::verify_sighash_type`].
+    pub fn into_adaptor_refund(&self) -> Result<bundle::SignedAdaptorRefund<Ctx::Ar>, Error> {
+        self.verify_sighash_type()?;
+        Ok(bundle::SignedAdaptorRefund {
             refund_adaptor_sig: datum::Signature::new(
                 TxId::Refund,
                 SwapRole::Alice,
                 SignatureType::Adaptor(self.refund_adaptor_sig.clone()),
             ),
-        }
+        })
     }
 }
 
-impl<Ctx> ProtocolMessage for RefundProcedureSignatures<Ctx> where Ctx: Swap {}
+impl<Ctx> ProtocolMessage for RefundProcedureSignatures<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::RefundProcedureSignatures
+    }
+}
 
 /// `buy_procedure_signature`is intended to transmit Bob's adaptor signature for the `buy (c)`
 /// transaction and the transaction itself. Uppon reception Alice must validate the transaction and
 /// the adaptor signature.
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+#[derive(Clone, Debug, PartialEq, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct BuyProcedureSignature<Ctx: Swap> {
     /// The arbitrating `buy (c)` transaction
@@ -524,15 +955,521 @@ where
     }
 }
 
-impl<Ctx> ProtocolMessage for BuyProcedureSignature<Ctx> where Ctx: Swap {}
+impl<Ctx> ProtocolMessage for BuyProcedureSignature<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::BuyProcedureSignature
+    }
+}
+
+impl<Ctx> consensus::Decodable for BuyProcedureSignature<Ctx>
+where
+    Ctx: Swap,
+{
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Ok(Self {
+            buy: consensus::strict_decode_bounded_field("buy", d, MAX_PARTIAL_TRANSACTION_LEN)?,
+            buy_adaptor_sig: consensus::strict_decode_field("buy_adaptor_sig", d)?,
+        })
+    }
+}
+
+impl<Ctx> StrictDecode for BuyProcedureSignature<Ctx>
+where
+    Ctx: Swap,
+{
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Ok(<Self as consensus::Decodable>::consensus_decode(&mut d)?)
+    }
+}
+
+/// `cooperative_close` carries both participants' signatures on a transaction spending the
+/// consensus path of the `lock (b)` transaction back to Bob, used when both participants agree to
+/// abort the swap without waiting for the `cancel (d)` timelock to expire. It is only accepted
+/// after `core_arbitrating_setup` and before `refund_procedure_signatures`, once Alice sent her
+/// refund signatures she may lock the accordant assets and closing would leave them stranded.
+#[derive(Clone, Debug, PartialEq, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct CooperativeClose<Ctx: Swap> {
+    /// The arbitrating cooperative close transaction
+    pub close: <Ctx::Ar as Onchain>::PartialTransaction,
+    /// The `Ab` cooperative close signature
+    pub alice_sig: <Ctx::Ar as Signatures>::Signature,
+    /// The `Bb` cooperative close signature
+    pub bob_sig: <Ctx::Ar as Signatures>::Signature,
+}
+
+impl<Ctx> ProtocolMessage for CooperativeClose<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::CooperativeClose
+    }
+}
+
+impl<Ctx> consensus::Decodable for CooperativeClose<Ctx>
+where
+    Ctx: Swap,
+{
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Ok(Self {
+            close: consensus::strict_decode_bounded_field("close", d, MAX_PARTIAL_TRANSACTION_LEN)?,
+            alice_sig: consensus::strict_decode_field("alice_sig", d)?,
+            bob_sig: consensus::strict_decode_field("bob_sig", d)?,
+        })
+    }
+}
+
+impl<Ctx> StrictDecode for CooperativeClose<Ctx>
+where
+    Ctx: Swap,
+{
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Ok(<Self as consensus::Decodable>::consensus_decode(&mut d)?)
+    }
+}
+
+/// `musig_nonce_commitment` commits to the public nonces of a participant for a MuSig2 signing
+/// session on a key path spend of the `lock (b)` output. Both participants exchange their
+/// commitment before revealing their nonces with [`MuSigNonceReveal`], removing adaptive choices of
+/// the nonces.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, StrictDecode, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct MuSigNonceCommitment<Ctx: Swap> {
+    /// Commitment to the `R1` and `R2` public nonces
+    pub nonces: Ctx::Commitment,
+}
+
+impl<Ctx> MuSigNonceCommitment<Ctx>
+where
+    Ctx: Swap,
+{
+    pub fn from_reveal(reveal: &MuSigNonceReveal<Ctx>) -> Self {
+        Self {
+            nonces: Ctx::commit_to(reveal.nonces_bytes()),
+        }
+    }
+
+    /// Verifies that the revealed nonces are valid public keys and match the commitment.
+    pub fn verify(&self, reveal: &MuSigNonceReveal<Ctx>) -> Result<(), Error> {
+        <Ctx::Ar as Keys>::validate_pubkey(&reveal.first_nonce)?;
+        <Ctx::Ar as Keys>::validate_pubkey(&reveal.second_nonce)?;
+        Ctx::validate(reveal.nonces_bytes(), self.nonces.clone())?;
+        Ok(())
+    }
+}
+
+impl<Ctx> ProtocolMessage for MuSigNonceCommitment<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::MuSigNonceCommitment
+    }
+}
+
+/// `musig_nonce_reveal` reveals the public nonces committed by the `musig_nonce_commitment`
+/// message, the second round of the MuSig2 nonce exchange.
+#[derive(Clone, Debug, PartialEq, Eq, StrictDecode, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct MuSigNonceReveal<Ctx: Swap> {
+    /// The `R1` public nonce
+    pub first_nonce: <Ctx::Ar as Keys>::PublicKey,
+    /// The `R2` public nonce
+    pub second_nonce: <Ctx::Ar as Keys>::PublicKey,
+}
+
+impl<Ctx> MuSigNonceReveal<Ctx>
+where
+    Ctx: Swap,
+{
+    /// Returns the serialized nonces in order, the value committed to.
+    fn nonces_bytes(&self) -> Vec<u8> {
+        let mut bytes = <Ctx::Ar as Keys>::as_bytes(&self.first_nonce);
+        bytes.extend(<Ctx::Ar as Keys>::as_bytes(&self.second_nonce));
+        bytes
+    }
+}
+
+impl<Ctx> ProtocolMessage for MuSigNonceReveal<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        MessageId::MuSigNonceReveal
+    }
+}
+
+/// Maximum length in bytes of the [`Abort`] message body accepted when decoding.
+pub const MAX_ABORT_BODY_LEN: usize = 1024;
 
 /// `abort` is an `OPTIONAL` courtesy message from either swap partner to inform the counterparty
 /// that they have aborted the swap with an `OPTIONAL` message body to provide the reason.
-#[derive(Clone, Debug, StrictDecode, StrictEncode)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, StrictEncode)]
 #[strict_encoding_crate(strict_encoding)]
 pub struct Abort {
     /// OPTIONAL `body`: error code | string
     pub error_body: Option<String>,
 }
 
-impl ProtocolMessage for Abort {}
+impl StrictDecode for Abort {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let error_body = match u8::strict_decode(&mut d)? {
+            0x00 => None,
+            0x01 => Some(
+                consensus::decode_bounded_string(&mut d, MAX_ABORT_BODY_LEN)
+                    .map_err(|e| strict_encoding::Error::DataIntegrityError(e.to_string()))?,
+            ),
+            _ => {
+                return Err(strict_encoding::Error::DataIntegrityError(
+                    "Failed to decode the abort error body".to_string(),
+                ))
+            }
+        };
+        Ok(Self { error_body })
+    }
+}
+
+impl ProtocolMessage for Abort {
+    fn message_id(&self) -> MessageId {
+        MessageId::Abort
+    }
+}
+
+/// Maximum length in bytes of the transaction identifier in [`AccordantFundingConfirmed`]
+/// accepted when decoding.
+pub const MAX_TXID_LEN: usize = 64;
+
+/// `accordant_funding_confirmed` is sent by Alice once her `lock` transaction on the accordant
+/// blockchain is confirmed, after receiving Bob's `core_arbitrating_setup` and sending her
+/// `refund_procedure_signatures`. It is the synchronization point between both blockchains: Bob
+/// verifies the accordant lock on-chain and only then releases the `buy_procedure_signature`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, StrictEncode)]
+#[strict_encoding_crate(strict_encoding)]
+pub struct AccordantFundingConfirmed {
+    /// The identifier of the accordant `lock` transaction, as serialized by the accordant
+    /// blockchain
+    pub lock_txid: Vec<u8>,
+    /// The height of the block including the accordant `lock` transaction
+    pub block_height: u64,
+}
+
+impl StrictDecode for AccordantFundingConfirmed {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let lock_txid = consensus::decode_bounded_vec(&mut d, MAX_TXID_LEN)
+            .map_err(|e| strict_encoding::Error::DataIntegrityError(e.to_string()))?;
+        Ok(Self {
+            lock_txid,
+            block_height: u64::strict_decode(&mut d)?,
+        })
+    }
+}
+
+impl ProtocolMessage for AccordantFundingConfirmed {
+    fn message_id(&self) -> MessageId {
+        MessageId::AccordantFundingConfirmed
+    }
+}
+
+/// Protocol messages exchanged between the participants during the swap, used to enforce their
+/// ordering with [`SwapState`]. Messages are encoded with a `u16` type prefix followed by the
+/// message itself.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SwapMessage<Ctx: Swap> {
+    CommitAliceParameters(CommitAliceParameters<Ctx>),
+    CommitBobParameters(CommitBobParameters<Ctx>),
+    RevealAliceParameters(RevealAliceParameters<Ctx>),
+    RevealBobParameters(RevealBobParameters<Ctx>),
+    CoreArbitratingSetup(CoreArbitratingSetup<Ctx>),
+    RefundProcedureSignatures(RefundProcedureSignatures<Ctx>),
+    BuyProcedureSignature(BuyProcedureSignature<Ctx>),
+    Abort(Abort),
+    AccordantFundingConfirmed(AccordantFundingConfirmed),
+    CooperativeClose(CooperativeClose<Ctx>),
+}
+
+impl<Ctx> SwapMessage<Ctx>
+where
+    Ctx: Swap,
+{
+    /// Decodes a message from a buffer starting with a frame created with [`frame`]. Returns the
+    /// message and the number of bytes consumed from the buffer, or `None` if the buffer does not
+    /// contain the full frame yet and more bytes must be read from the transport. Fails if the
+    /// frame is corrupted, was created with another protocol version, or its payload is not a
+    /// valid message.
+    pub fn decode_frame(buf: &[u8]) -> Result<Option<(Self, usize)>, consensus::Error> {
+        if buf.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+        let mut header = [0u8; FRAME_HEADER_LEN];
+        header.copy_from_slice(&buf[..FRAME_HEADER_LEN]);
+        let (len, _) = parse_frame_header(&header)?;
+        let end = FRAME_HEADER_LEN + len as usize;
+        if buf.len() < end {
+            return Ok(None);
+        }
+        let mut reader = &buf[..end];
+        let payload = read_frame(&mut reader)?;
+        let msg: Self = strict_encoding::strict_deserialize(&payload)?;
+        Ok(Some((msg, end)))
+    }
+
+    /// Reads one frame created with [`frame`] from the reader and decodes its message while
+    /// reading, without buffering the payload. Large messages such as [`CoreArbitratingSetup`]
+    /// are decoded field after field and a malformed field fails before the following bytes are
+    /// read, the reader is then left inside the frame. The checksum is validated once the payload
+    /// is consumed, the message is returned only if it matches and the payload has no trailing
+    /// bytes.
+    ///
+    /// The reader is accessed through small reads, wrap unbuffered transports in a
+    /// [`std::io::BufReader`].
+    pub fn read_framed<R: io::Read>(r: &mut R) -> Result<Self, consensus::Error> {
+        let mut header = [0u8; FRAME_HEADER_LEN];
+        r.read_exact(&mut header)?;
+        let (len, checksum) = parse_frame_header(&header)?;
+        let mut reader = Crc32Reader {
+            inner: io::Read::take(r, len as u64),
+            crc: 0xffff_ffff,
+        };
+        let msg = Self::strict_decode(&mut reader)?;
+        match reader.inner.limit() == 0 && !reader.crc == checksum {
+            true => Ok(msg),
+            false => Err(consensus::Error::CorruptFrame),
+        }
+    }
+}
+
+impl<Ctx> StrictEncode for SwapMessage<Ctx>
+where
+    Ctx: Swap,
+{
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        let len = match self {
+            SwapMessage::CommitAliceParameters(msg) => {
+                0x01u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::CommitBobParameters(msg) => {
+                0x02u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::RevealAliceParameters(msg) => {
+                0x03u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::RevealBobParameters(msg) => {
+                0x04u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::CoreArbitratingSetup(msg) => {
+                0x05u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::RefundProcedureSignatures(msg) => {
+                0x06u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::BuyProcedureSignature(msg) => {
+                0x07u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::Abort(msg) => {
+                0x08u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::AccordantFundingConfirmed(msg) => {
+                0x09u16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+            SwapMessage::CooperativeClose(msg) => {
+                0x0au16.strict_encode(&mut e)? + msg.strict_encode(&mut e)?
+            }
+        };
+        Ok(len)
+    }
+}
+
+impl<Ctx> StrictDecode for SwapMessage<Ctx>
+where
+    Ctx: Swap,
+{
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        match u16::strict_decode(&mut d)? {
+            0x01u16 => Ok(SwapMessage::CommitAliceParameters(
+                StrictDecode::strict_decode(&mut d)?,
+            )),
+            0x02u16 => Ok(SwapMessage::CommitBobParameters(
+                StrictDecode::strict_decode(&mut d)?,
+            )),
+            0x03u16 => Ok(SwapMessage::RevealAliceParameters(
+                StrictDecode::strict_decode(&mut d)?,
+            )),
+            0x04u16 => Ok(SwapMessage::RevealBobParameters(
+                StrictDecode::strict_decode(&mut d)?,
+            )),
+            0x05u16 => Ok(SwapMessage::CoreArbitratingSetup(
+                StrictDecode::strict_decode(&mut d)?,
+            )),
+            0x06u16 => Ok(SwapMessage::RefundProcedureSignatures(
+                StrictDecode::strict_decode(&mut d)?,
+            )),
+            0x07u16 => Ok(SwapMessage::BuyProcedureSignature(
+                StrictDecode::strict_decode(&mut d)?,
+            )),
+            0x08u16 => Ok(SwapMessage::Abort(StrictDecode::strict_decode(&mut d)?)),
+            0x09u16 => Ok(SwapMessage::AccordantFundingConfirmed(
+                StrictDecode::strict_decode(&mut d)?,
+            )),
+            0x0au16 => Ok(SwapMessage::CooperativeClose(StrictDecode::strict_decode(
+                &mut d,
+            )?)),
+            _ => Err(strict_encoding::Error::DataIntegrityError(
+                "Unknown swap message type".to_string(),
+            )),
+        }
+    }
+}
+
+impl<Ctx> ProtocolMessage for SwapMessage<Ctx>
+where
+    Ctx: Swap,
+{
+    fn message_id(&self) -> MessageId {
+        match self {
+            SwapMessage::CommitAliceParameters(msg) => msg.message_id(),
+            SwapMessage::CommitBobParameters(msg) => msg.message_id(),
+            SwapMessage::RevealAliceParameters(msg) => msg.message_id(),
+            SwapMessage::RevealBobParameters(msg) => msg.message_id(),
+            SwapMessage::CoreArbitratingSetup(msg) => msg.message_id(),
+            SwapMessage::RefundProcedureSignatures(msg) => msg.message_id(),
+            SwapMessage::BuyProcedureSignature(msg) => msg.message_id(),
+            SwapMessage::Abort(msg) => msg.message_id(),
+            SwapMessage::AccordantFundingConfirmed(msg) => msg.message_id(),
+            SwapMessage::CooperativeClose(msg) => msg.message_id(),
+        }
+    }
+}
+
+/// Progress of the protocol messages exchange of a swap. Both participants commit, then reveal
+/// once both commitments are received, then Bob sends the core arbitrating setup, Alice the refund
+/// procedure signatures and the confirmation of her accordant lock, and Bob the buy procedure
+/// signature. Either participant can abort the swap until the exchange is done, and close it
+/// cooperatively once the core arbitrating setup is received until Alice sends the refund
+/// procedure signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapState {
+    /// Waiting for the commitments, flags are set when a participant's commitment is received
+    Commit { alice: bool, bob: bool },
+    /// Waiting for the reveals, flags are set when a participant's reveal is received
+    Reveal { alice: bool, bob: bool },
+    /// Waiting for Bob's core arbitrating setup
+    CoreArbitratingSetup,
+    /// Waiting for Alice's refund procedure signatures
+    RefundProcedureSignatures,
+    /// Waiting for Alice's confirmation of the accordant lock
+    AccordantFundingConfirmed,
+    /// Waiting for Bob's buy procedure signature
+    BuyProcedureSignature,
+    /// All the messages have been exchanged
+    Done,
+    /// One of the participants aborted the swap
+    Aborted,
+    /// The participants closed the swap cooperatively, returning the arbitrating funds to Bob
+    Closed,
+}
+
+impl Default for SwapState {
+    fn default() -> Self {
+        Self::Commit {
+            alice: false,
+            bob: false,
+        }
+    }
+}
+
+impl SwapState {
+    /// Create a new state waiting for the commitments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advance the state with a message sent by the participant with the given role. Fails without
+    /// changing the state if the message is out of order, a duplicate, or not sent by the role
+    /// expected to send it.
+    pub fn apply<Ctx>(&mut self, msg: &SwapMessage<Ctx>, role: SwapRole) -> Result<(), Error>
+    where
+        Ctx: Swap,
+    {
+        let next = match (*self, msg, role) {
+            (SwapState::Done, _, _) | (SwapState::Aborted, _, _) | (SwapState::Closed, _, _) => {
+                None
+            }
+            (_, SwapMessage::Abort(_), _) => Some(SwapState::Aborted),
+            // The lock exists once the setup is received, Alice may lock the accordant assets as
+            // soon as she sent her refund signatures and closing would strand her funds
+            (SwapState::RefundProcedureSignatures, SwapMessage::CooperativeClose(_), _) => {
+                Some(SwapState::Closed)
+            }
+            (
+                SwapState::Commit { alice: false, bob },
+                SwapMessage::CommitAliceParameters(_),
+                SwapRole::Alice,
+            ) => Some(Self::committed(true, bob)),
+            (
+                SwapState::Commit { alice, bob: false },
+                SwapMessage::CommitBobParameters(_),
+                SwapRole::Bob,
+            ) => Some(Self::committed(alice, true)),
+            (
+                SwapState::Reveal { alice: false, bob },
+                SwapMessage::RevealAliceParameters(_),
+                SwapRole::Alice,
+            ) => Some(Self::revealed(true, bob)),
+            (
+                SwapState::Reveal { alice, bob: false },
+                SwapMessage::RevealBobParameters(_),
+                SwapRole::Bob,
+            ) => Some(Self::revealed(alice, true)),
+            (
+                SwapState::CoreArbitratingSetup,
+                SwapMessage::CoreArbitratingSetup(_),
+                SwapRole::Bob,
+            ) => Some(SwapState::RefundProcedureSignatures),
+            (
+                SwapState::RefundProcedureSignatures,
+                SwapMessage::RefundProcedureSignatures(_),
+                SwapRole::Alice,
+            ) => Some(SwapState::AccordantFundingConfirmed),
+            (
+                SwapState::AccordantFundingConfirmed,
+                SwapMessage::AccordantFundingConfirmed(_),
+                SwapRole::Alice,
+            ) => Some(SwapState::BuyProcedureSignature),
+            (
+                SwapState::BuyProcedureSignature,
+                SwapMessage::BuyProcedureSignature(_),
+                SwapRole::Bob,
+            ) => Some(SwapState::Done),
+            _ => None,
+        };
+
+        match next {
+            Some(state) => {
+                *self = state;
+                Ok(())
+            }
+            None => Err(Error::UnexpectedMessage),
+        }
+    }
+
+    fn committed(alice: bool, bob: bool) -> Self {
+        match alice && bob {
+            true => SwapState::Reveal {
+                alice: false,
+                bob: false,
+            },
+            false => SwapState::Commit { alice, bob },
+        }
+    }
+
+    fn revealed(alice: bool, bob: bool) -> Self {
+        match alice && bob {
+            true => SwapState::CoreArbitratingSetup,
+            false => SwapState::Reveal { alice, bob },
+        }
+    }
+}
@@ -1,9 +1,11 @@
 //! Roles during negotiation and swap phases, blockchain roles, and network definitions.
 
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display};
 use std::io;
 use std::str::FromStr;
 
+use rand_core::{CryptoRng, OsRng, RngCore};
+
 use crate::blockchain::{Address, Asset, Fee, FeePolitic, Onchain, Timelock, Transactions};
 use crate::bundle::{
     AliceParameters, BobParameters, CoreArbitratingTransactions, CosignedArbitratingCancel,
@@ -12,11 +14,12 @@ use crate::bundle::{
 };
 use crate::consensus::{self, Decodable, Encodable};
 use crate::crypto::{
-    AccordantKey, ArbitratingKey, DleqProof, FromSeed, Keys, SharedPrivateKey, SharedPrivateKeys,
-    SignatureType, Signatures,
+    self, AccordantKey, AmountCommitments, ArbitratingKey, DleqProof, FromSeed, Keys,
+    SharedPrivateKey, SharedPrivateKeys, SignatureType, Signatures,
 };
 use crate::datum::{self, Key, Parameter, Proof, Signature};
 use crate::negotiation::PublicOffer;
+use crate::protocol_message::{CoreArbitratingSetup, RefundProcedureSignatures};
 use crate::script::{DataLock, DataPunishableLock, DoubleKeys};
 use crate::swap::Swap;
 use crate::transaction::{
@@ -47,6 +50,25 @@ impl NegotiationRole {
     }
 }
 
+impl Encodable for NegotiationRole {
+    fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        match self {
+            NegotiationRole::Maker => 0x01u8.consensus_encode(writer),
+            NegotiationRole::Taker => 0x02u8.consensus_encode(writer),
+        }
+    }
+}
+
+impl Decodable for NegotiationRole {
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        match Decodable::consensus_decode(d)? {
+            0x01u8 => Ok(NegotiationRole::Maker),
+            0x02u8 => Ok(NegotiationRole::Taker),
+            _ => Err(consensus::Error::UnknownType),
+        }
+    }
+}
+
 impl FromStr for NegotiationRole {
     type Err = consensus::Error;
 
@@ -90,11 +112,27 @@ pub enum SwapRole {
 impl SwapRole {
     /// Return the other role possible in the swap phase.
     pub fn other(&self) -> Self {
+        self.opposite()
+    }
+
+    /// Return the counterparty role, e.g. the role that must have produced a signature received
+    /// by the local role.
+    pub fn opposite(self) -> Self {
         match self {
             Self::Alice => Self::Bob,
             Self::Bob => Self::Alice,
         }
     }
+
+    /// Return true if the role is Alice.
+    pub fn is_alice(self) -> bool {
+        self == Self::Alice
+    }
+
+    /// Return true if the role is Bob.
+    pub fn is_bob(self) -> bool {
+        self == Self::Bob
+    }
 }
 
 impl Encodable for SwapRole {
@@ -128,11 +166,11 @@ impl FromStr for SwapRole {
     }
 }
 
-impl ToString for SwapRole {
-    fn to_string(&self) -> String {
+impl Display for SwapRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Alice => "Alice".to_string(),
-            Self::Bob => "Bob".to_string(),
+            Self::Alice => write!(f, "Alice"),
+            Self::Bob => write!(f, "Bob"),
         }
     }
 }
@@ -146,12 +184,12 @@ pub struct Alice<Ctx: Swap> {
     pub fee_politic: FeePolitic,
 }
 
-struct ValidatedCoreTransactions<Ctx: Swap> {
-    lock: <Ctx::Ar as Transactions>::Lock,
-    cancel: <Ctx::Ar as Transactions>::Cancel,
-    refund: <Ctx::Ar as Transactions>::Refund,
-    data_lock: DataLock<Ctx::Ar>,
-    punish_lock: DataPunishableLock<Ctx::Ar>,
+pub(crate) struct ValidatedCoreTransactions<Ctx: Swap> {
+    pub(crate) lock: <Ctx::Ar as Transactions>::Lock,
+    pub(crate) cancel: <Ctx::Ar as Transactions>::Cancel,
+    pub(crate) refund: <Ctx::Ar as Transactions>::Refund,
+    pub(crate) data_lock: DataLock<Ctx::Ar>,
+    pub(crate) punish_lock: DataPunishableLock<Ctx::Ar>,
 }
 
 impl<Ctx> Alice<Ctx>
@@ -190,7 +228,19 @@ where
         ac_seed: &<Ctx::Ac as FromSeed<Acc>>::Seed,
         public_offer: &PublicOffer<Ctx>,
     ) -> Result<AliceParameters<Ctx>, Error> {
-        let (spend, adaptor, proof) = Ctx::Proof::generate(ac_seed)?;
+        self.generate_parameters_with_rng(ar_seed, ac_seed, public_offer, &mut OsRng)
+    }
+
+    /// Generate Alice's parameters as [`Alice::generate_parameters`], the randomness needed by the
+    /// cross-group proof is drawn from the given random number generator.
+    pub fn generate_parameters_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        ar_seed: &<Ctx::Ar as FromSeed<Arb>>::Seed,
+        ac_seed: &<Ctx::Ac as FromSeed<Acc>>::Seed,
+        public_offer: &PublicOffer<Ctx>,
+        rng: &mut R,
+    ) -> Result<AliceParameters<Ctx>, Error> {
+        let (spend, adaptor, proof) = Ctx::Proof::generate_with_rng(ac_seed, rng)?;
         Ok(AliceParameters {
             buy: Key::new_alice_buy(<Ctx::Ar as FromSeed<Arb>>::get_pubkey(
                 ar_seed,
@@ -348,6 +398,69 @@ where
         })
     }
 
+    /// Produces the [`RefundProcedureSignatures`] message in response to Bob's
+    /// [`CoreArbitratingSetup`] message.
+    ///
+    /// # Safety
+    ///
+    /// The same assumptions as [`cosign_arbitrating_cancel`] and [`sign_adaptor_refund`] apply,
+    /// the core arbitrating transactions are validated before signing.
+    ///
+    /// # Execution
+    ///
+    ///  * Co-sign the [`Cancelable`] transaction with [`cosign_arbitrating_cancel`]
+    ///  * Adaptor sign the [`Refundable`] transaction with [`sign_adaptor_refund`]
+    ///  * Package both signatures in the message and verify the adaptor witness with
+    ///  [`verify_adaptor_witness`] as Bob will upon reception
+    ///
+    /// [`cosign_arbitrating_cancel`]: Alice::cosign_arbitrating_cancel
+    /// [`sign_adaptor_refund`]: Alice::sign_adaptor_refund
+    /// [`verify_adaptor_witness`]: AdaptorSignable::verify_adaptor_witness
+    ///
+    pub fn produce_refund_procedure_signatures(
+        &self,
+        ar_seed: &<Ctx::Ar as FromSeed<Arb>>::Seed,
+        alice_parameters: &AliceParameters<Ctx>,
+        bob_parameters: &BobParameters<Ctx>,
+        core: &CoreArbitratingSetup<Ctx>,
+        public_offer: &PublicOffer<Ctx>,
+    ) -> Result<RefundProcedureSignatures<Ctx>, Error> {
+        let core = core.into_core_transactions();
+
+        // Sign the cancel and the refund, both validate the core arbitrating transactions first.
+        let cancel_sig = self.cosign_arbitrating_cancel(
+            ar_seed,
+            alice_parameters,
+            bob_parameters,
+            &core,
+            public_offer,
+        )?;
+        let adaptor_refund = self.sign_adaptor_refund(
+            ar_seed,
+            alice_parameters,
+            bob_parameters,
+            &core,
+            public_offer,
+        )?;
+
+        // Validate the message as Bob does upon reception.
+        let partial_refund = core.refund.tx().try_into_partial_transaction()?;
+        let msg =
+            RefundProcedureSignatures::from_bundles(&cancel_sig, &adaptor_refund, &partial_refund)?;
+        msg.verify_refund_sighash_type(&partial_refund)?;
+        let refund = <<Ctx::Ar as Transactions>::Refund>::from_partial(partial_refund);
+        refund.verify_adaptor_witness(
+            &alice_parameters
+                .refund
+                .key()
+                .try_into_arbitrating_pubkey()?,
+            &bob_parameters.adaptor.key().try_into_arbitrating_pubkey()?,
+            msg.refund_adaptor_sig.clone(),
+        )?;
+
+        Ok(msg)
+    }
+
     /// Validates the adaptor buy witness with [`verify_adaptor_witness`] based on the parameters
     /// and the buy arbitrating transactions.
     ///
@@ -556,15 +669,18 @@ where
 
         let fee_strategy = &public_offer.offer.fee_strategy;
 
-        // Initialize the punish transaction based on the cancel transaction.
-        let mut punish =
-            <<Ctx::Ar as Transactions>::Punish as Punishable<
-                Ctx::Ar,
-                <Ctx::Ar as Transactions>::Metadata,
-            >>::initialize(&cancel, punish_lock, self.destination_address.clone())?;
-
-        // Set the fees according to the strategy in the offer and the local politic.
-        <Ctx::Ar as Fee>::set_fee(punish.partial_mut(), &fee_strategy, self.fee_politic)?;
+        // Initialize the punish transaction based on the cancel transaction, the fees are set
+        // according to the strategy in the offer and the local politic.
+        let punish = <<Ctx::Ar as Transactions>::Punish as Punishable<
+            Ctx::Ar,
+            <Ctx::Ar as Transactions>::Metadata,
+        >>::initialize_with_fee(
+            &cancel,
+            punish_lock,
+            self.destination_address.clone(),
+            fee_strategy,
+            self.fee_politic,
+        )?;
 
         // Derive the punish private key and generate the witness data for the punish transaction.
         let privkey = <Ctx::Ar as FromSeed<Arb>>::get_privkey(ar_seed, ArbitratingKey::Punish)?;
@@ -580,21 +696,48 @@ where
         })
     }
 
+    /// Recovers Bob's adaptor secret once the `refund (e)` transaction is seen on-chain.
+    ///
+    /// # Safety
+    ///
+    /// The signatures are extracted from the broadcasted transaction and are not trusted.
+    ///
+    /// _Trusted data_:
+    ///  * `signed_adaptor_refund`: The refund adaptor signature generated by Alice
+    ///
+    /// _Verified data_:
+    ///  * `refund_sigs`: The signatures found in the `refund (e)` transaction witness
+    ///
+    /// # Execution
+    ///
+    ///  * Pair each signature with the refund adaptor signature and [`recover_secret`]
+    ///
+    /// Returns the first adaptor secret recovered.
+    ///
+    /// [`recover_secret`]: Signatures::recover_secret
+    ///
+    pub fn recover_refund_adaptor_secret(
+        &self,
+        refund_sigs: &[<Ctx::Ar as Signatures>::Signature],
+        signed_adaptor_refund: &SignedAdaptorRefund<Ctx::Ar>,
+    ) -> Result<<Ctx::Ar as Keys>::PrivateKey, Error> {
+        let adaptor_sig = signed_adaptor_refund
+            .refund_adaptor_sig
+            .signature()
+            .try_into_adaptor()?;
+
+        refund_sigs
+            .iter()
+            .find_map(|sig| <Ctx::Ar as Signatures>::recover_secret(&adaptor_sig, sig).ok())
+            .ok_or_else(|| crypto::Error::InvalidAdaptorSignature.into())
+    }
+
     pub fn recover_accordant_assets(&self) -> Result<(), Error> {
         todo!()
     }
 
     // Internal method to parse and validate the core arbitratring transactions received by Alice
-    // from Bob.
-    //
-    // Each transaction is parsed from the bundle and initialized from its partial transaction
-    // format. After initialization validation tests are performed to ensure:
-    //
-    //  * the transaction template is valid (transaction is well formed, contract and keys are used
-    //  correctly)
-    //  * the target amount from the offer is correct (for the lock transaction)
-    //  * the fee strategy validation passes
-    //
+    // from Bob, see `validate_core_transactions`.
     fn validate_core(
         &self,
         alice_parameters: &AliceParameters<Ctx>,
@@ -602,98 +745,127 @@ where
         core: &CoreArbitratingTransactions<Ctx::Ar>,
         public_offer: &PublicOffer<Ctx>,
     ) -> Result<ValidatedCoreTransactions<Ctx>, Error> {
-        // Extract the partial transaction from the core arbitrating bundle, this operation should
-        // not error if the bundle is well formed.
-        let partial_lock = core.lock.tx().try_into_partial_transaction()?;
-
-        // Initialize the lock transaction based on the extracted partial transaction format.
-        let lock = <<Ctx::Ar as Transactions>::Lock>::from_partial(partial_lock);
-
-        // Get the four keys, Alice and Bob for Buy and Cancel. The keys are needed, along with the
-        // timelock for the cancel, to create the cancelable on-chain contract on the arbitrating
-        // blockchain.
-        let alice_buy = alice_parameters.buy.key().try_into_arbitrating_pubkey()?;
-        let bob_buy = bob_parameters.buy.key().try_into_arbitrating_pubkey()?;
-        let alice_cancel = alice_parameters
-            .cancel
-            .key()
-            .try_into_arbitrating_pubkey()?;
-        let bob_cancel = bob_parameters.cancel.key().try_into_arbitrating_pubkey()?;
-
-        // Create the data structure that represents an on-chain cancelable contract for the
-        // arbitrating blockchain.
-        let data_lock = DataLock {
-            timelock: public_offer.offer.cancel_timelock,
-            success: DoubleKeys::new(alice_buy, bob_buy),
-            failure: DoubleKeys::new(alice_cancel, bob_cancel),
-        };
-
-        // Verify the lock transaction template.
-        lock.verify_template(data_lock.clone())?;
-        // The target amount is dictated from the public offer.
-        let target_amount = public_offer.offer.arbitrating_amount;
-        // Verify the target amount
-        lock.verify_target_amount(target_amount)?;
-        // Validate that the transaction follows the strategy.
-        let fee_strategy = &public_offer.offer.fee_strategy;
-        <Ctx::Ar as Fee>::validate_fee(lock.partial(), &fee_strategy)?;
-
-        // Get the three keys, Alice and Bob for refund and Alice's punish key. The keys are
-        // needed, along with the timelock for the punish, to create the punishable on-chain
-        // contract on the arbitrating blockchain.
-        let alice_refund = alice_parameters
-            .refund
-            .key()
-            .try_into_arbitrating_pubkey()?;
-        let bob_refund = bob_parameters.refund.key().try_into_arbitrating_pubkey()?;
-        let alice_punish = alice_parameters
-            .punish
-            .key()
-            .try_into_arbitrating_pubkey()?;
-
-        // Create the data structure that represents an on-chain punishable contract for the
-        // arbitrating blockchain.
-        let punish_lock = DataPunishableLock {
-            timelock: public_offer.offer.punish_timelock,
-            success: DoubleKeys::new(alice_refund, bob_refund),
-            failure: alice_punish,
-        };
-
-        // Extract the partial transaction from the core arbitrating bundle, this operation should
-        // not error if the bundle is well formed.
-        let partial_cancel = core.lock.tx().try_into_partial_transaction()?;
-
-        // Initialize the lock transaction based on the extracted partial transaction format.
-        let cancel = <<Ctx::Ar as Transactions>::Cancel>::from_partial(partial_cancel);
-        // Check that the cancel transaction is build on top of the lock.
-        cancel.is_build_on_top_of(&lock)?;
-        cancel.verify_template(data_lock.clone(), punish_lock.clone())?;
-        // Validate the fee strategy
-        <Ctx::Ar as Fee>::validate_fee(cancel.partial(), &fee_strategy)?;
-
-        // Extract the partial transaction from the core arbitrating bundle, this operation should
-        // not error if the bundle is well formed.
-        let partial_refund = core.refund.tx().try_into_partial_transaction()?;
-
-        // Initialize the refund transaction based on the extracted partial transaction format.
-        let refund = <<Ctx::Ar as Transactions>::Refund>::from_partial(partial_refund);
-        // Check that the refund transaction is build on top of the cancel transaction.
-        refund.is_build_on_top_of(&cancel)?;
-        let refund_address = bob_parameters.refund_address.param().try_into_address()?;
-        refund.verify_template(punish_lock.clone(), refund_address)?;
-        // Validate the fee strategy
-        <Ctx::Ar as Fee>::validate_fee(refund.partial(), &fee_strategy)?;
-
-        Ok(ValidatedCoreTransactions {
-            lock,
-            cancel,
-            refund,
-            data_lock,
-            punish_lock,
-        })
+        validate_core_transactions(alice_parameters, bob_parameters, core, public_offer)
     }
 }
 
+// Parses and validates the core arbitratring transactions received by Alice from Bob.
+//
+// Each transaction is parsed from the bundle and initialized from its partial transaction
+// format. After initialization validation tests are performed to ensure:
+//
+//  * the transaction template is valid (transaction is well formed, contract and keys are used
+//  correctly)
+//  * the target amount from the offer is correct (for the lock transaction)
+//  * the fee strategy validation passes
+//
+pub(crate) fn validate_core_transactions<Ctx: Swap>(
+    alice_parameters: &AliceParameters<Ctx>,
+    bob_parameters: &BobParameters<Ctx>,
+    core: &CoreArbitratingTransactions<Ctx::Ar>,
+    public_offer: &PublicOffer<Ctx>,
+) -> Result<ValidatedCoreTransactions<Ctx>, Error> {
+    // Extract the partial transaction from the core arbitrating bundle, this operation should
+    // not error if the bundle is well formed.
+    let partial_lock = core.lock.tx().try_into_partial_transaction()?;
+
+    // Initialize the lock transaction based on the extracted partial transaction format.
+    let lock = <<Ctx::Ar as Transactions>::Lock>::from_partial(partial_lock);
+
+    // Get the four keys, Alice and Bob for Buy and Cancel. The keys are needed, along with the
+    // timelock for the cancel, to create the cancelable on-chain contract on the arbitrating
+    // blockchain.
+    let alice_buy = alice_parameters.buy.key().try_into_arbitrating_pubkey()?;
+    let bob_buy = bob_parameters.buy.key().try_into_arbitrating_pubkey()?;
+    let alice_cancel = alice_parameters
+        .cancel
+        .key()
+        .try_into_arbitrating_pubkey()?;
+    let bob_cancel = bob_parameters.cancel.key().try_into_arbitrating_pubkey()?;
+
+    // Create the data structure that represents an on-chain cancelable contract for the
+    // arbitrating blockchain.
+    let data_lock = DataLock {
+        timelock: public_offer.offer.cancel_timelock,
+        success: DoubleKeys::new(alice_buy, bob_buy),
+        failure: DoubleKeys::new(alice_cancel, bob_cancel),
+    };
+
+    // Verify the lock transaction template.
+    lock.verify_template(data_lock.clone())
+        .map_err(|e| Error::InvalidCoreTransaction(TxId::Lock, e))?;
+    // The target amount is dictated from the public offer.
+    let target_amount = public_offer.offer.arbitrating_amount;
+    // Verify the target amount
+    lock.verify_target_amount(target_amount)
+        .map_err(|e| Error::InvalidCoreTransaction(TxId::Lock, e))?;
+    // Validate that the transaction follows the strategy.
+    let fee_strategy = &public_offer.offer.fee_strategy;
+    <Ctx::Ar as Fee>::validate_fee(lock.partial(), &fee_strategy)?;
+
+    // Get the three keys, Alice and Bob for refund and Alice's punish key. The keys are
+    // needed, along with the timelock for the punish, to create the punishable on-chain
+    // contract on the arbitrating blockchain.
+    let alice_refund = alice_parameters
+        .refund
+        .key()
+        .try_into_arbitrating_pubkey()?;
+    let bob_refund = bob_parameters.refund.key().try_into_arbitrating_pubkey()?;
+    let alice_punish = alice_parameters
+        .punish
+        .key()
+        .try_into_arbitrating_pubkey()?;
+
+    // Create the data structure that represents an on-chain punishable contract for the
+    // arbitrating blockchain.
+    let punish_lock = DataPunishableLock {
+        timelock: public_offer.offer.punish_timelock,
+        success: DoubleKeys::new(alice_refund, bob_refund),
+        failure: alice_punish,
+    };
+
+    // Extract the partial transaction from the core arbitrating bundle, this operation should
+    // not error if the bundle is well formed.
+    let partial_cancel = core.cancel.tx().try_into_partial_transaction()?;
+
+    // Initialize the cancel transaction based on the extracted partial transaction format.
+    let cancel = <<Ctx::Ar as Transactions>::Cancel>::from_partial(partial_cancel);
+    // Check that the cancel transaction is build on top of the lock.
+    cancel
+        .is_build_on_top_of(&lock)
+        .map_err(|e| Error::InvalidCoreTransaction(TxId::Cancel, e))?;
+    cancel
+        .verify_template(data_lock.clone(), punish_lock.clone())
+        .map_err(|e| Error::InvalidCoreTransaction(TxId::Cancel, e))?;
+    // Validate the fee strategy
+    <Ctx::Ar as Fee>::validate_fee(cancel.partial(), &fee_strategy)?;
+
+    // Extract the partial transaction from the core arbitrating bundle, this operation should
+    // not error if the bundle is well formed.
+    let partial_refund = core.refund.tx().try_into_partial_transaction()?;
+
+    // Initialize the refund transaction based on the extracted partial transaction format.
+    let refund = <<Ctx::Ar as Transactions>::Refund>::from_partial(partial_refund);
+    // Check that the refund transaction is build on top of the cancel transaction.
+    refund
+        .is_build_on_top_of(&cancel)
+        .map_err(|e| Error::InvalidCoreTransaction(TxId::Refund, e))?;
+    let refund_address = bob_parameters.refund_address.param().try_into_address()?;
+    refund
+        .verify_template(punish_lock.clone(), refund_address)
+        .map_err(|e| Error::InvalidCoreTransaction(TxId::Refund, e))?;
+    // Validate the fee strategy
+    <Ctx::Ar as Fee>::validate_fee(refund.partial(), &fee_strategy)?;
+
+    Ok(ValidatedCoreTransactions {
+        lock,
+        cancel,
+        refund,
+        data_lock,
+        punish_lock,
+    })
+}
+
 /// Bob, the swap role, is the role starting with arbitrating blockchain assets and exchange them
 /// for accordant blockchain assets.
 pub struct Bob<Ctx: Swap> {
@@ -734,7 +906,19 @@ impl<Ctx: Swap> Bob<Ctx> {
         ac_seed: &<Ctx::Ac as FromSeed<Acc>>::Seed,
         public_offer: &PublicOffer<Ctx>,
     ) -> Result<BobParameters<Ctx>, Error> {
-        let (spend, adaptor, proof) = Ctx::Proof::generate(ac_seed)?;
+        self.generate_parameters_with_rng(ar_seed, ac_seed, public_offer, &mut OsRng)
+    }
+
+    /// Generate Bob's parameters as [`Bob::generate_parameters`], the randomness needed by the
+    /// cross-group proof is drawn from the given random number generator.
+    pub fn generate_parameters_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        ar_seed: &<Ctx::Ar as FromSeed<Arb>>::Seed,
+        ac_seed: &<Ctx::Ac as FromSeed<Acc>>::Seed,
+        public_offer: &PublicOffer<Ctx>,
+        rng: &mut R,
+    ) -> Result<BobParameters<Ctx>, Error> {
+        let (spend, adaptor, proof) = Ctx::Proof::generate_with_rng(ac_seed, rng)?;
         Ok(BobParameters {
             buy: Key::new_bob_buy(<Ctx::Ar as FromSeed<Arb>>::get_pubkey(
                 ar_seed,
@@ -881,24 +1065,31 @@ impl<Ctx: Swap> Bob<Ctx> {
         };
 
         // Initialize the cancel transaction for the lock transaction, removing the funds from the
-        // buy and moving them into a punisable on-chain contract.
-        let mut cancel = <<Ctx::Ar as Transactions>::Cancel as Cancelable<
+        // buy and moving them into a punisable on-chain contract. The fees are set according to
+        // the strategy in the offer and the local politic.
+        let cancel = <<Ctx::Ar as Transactions>::Cancel as Cancelable<
             Ctx::Ar,
             <Ctx::Ar as Transactions>::Metadata,
-        >>::initialize(&lock, cancel_lock, punish_lock.clone())?;
-
-        // Set the fees according to the strategy in the offer and the local politic.
-        <Ctx::Ar as Fee>::set_fee(cancel.partial_mut(), &fee_strategy, self.fee_politic)?;
+        >>::initialize(
+            &lock,
+            cancel_lock,
+            punish_lock.clone(),
+            fee_strategy,
+            self.fee_politic,
+        )?;
 
         // Initialize the refund transaction for the cancel transaction, moving the funds out of
-        // the punishable lock to Bob's refund address.
-        let mut refund = <<Ctx::Ar as Transactions>::Refund as Refundable<
+        // the punishable lock to Bob's refund address, with the fees set as for the cancel.
+        let refund = <<Ctx::Ar as Transactions>::Refund as Refundable<
             Ctx::Ar,
             <Ctx::Ar as Transactions>::Metadata,
-        >>::initialize(&cancel, punish_lock, self.refund_address.clone())?;
-
-        // Set the fees according to the strategy in the offer and the local politic.
-        <Ctx::Ar as Fee>::set_fee(refund.partial_mut(), &fee_strategy, self.fee_politic)?;
+        >>::initialize(
+            &cancel,
+            punish_lock,
+            self.refund_address.clone(),
+            fee_strategy,
+            self.fee_politic,
+        )?;
 
         Ok(CoreArbitratingTransactions {
             lock: datum::Transaction::new_lock(lock.to_partial()),
@@ -1267,7 +1458,10 @@ pub trait Arbitrating:
 
 /// An accordant is the blockchain which does not need transaction inside the protocol nor
 /// timelocks, it is the blockchain with the less requirements for an atomic swap.
-pub trait Accordant: Asset + Keys + SharedPrivateKeys<Acc> + FromSeed<Acc> + Clone + Eq {}
+pub trait Accordant:
+    Asset + Keys + SharedPrivateKeys<Acc> + AmountCommitments<Acc> + FromSeed<Acc> + Clone + Eq
+{
+}
 
 /// Defines the role of a blockchain. Farcaster uses two blockchain roles (1) [Arbitrating] and (2)
 /// [Accordant].
@@ -42,6 +42,17 @@ where
     pub failure: DoubleKeys<T>,
 }
 
+impl<T> DataLock<T>
+where
+    T: Timelock + Keys,
+{
+    /// Returns the height at which the `cancel (d)` transaction becomes valid, given the height at
+    /// which the `lock (b)` transaction has been mined.
+    pub fn cancel_available_at(&self, lock_height: u32) -> u32 {
+        T::available_at(&self.timelock, lock_height)
+    }
+}
+
 /// The data used to create a lock and remove the double spending problem and create an unilateral
 /// punishment mechanism.
 #[derive(Clone)]
@@ -53,3 +64,14 @@ where
     pub success: DoubleKeys<T>,
     pub failure: T::PublicKey,
 }
+
+impl<T> DataPunishableLock<T>
+where
+    T: Timelock + Keys,
+{
+    /// Returns the height at which the `punish (f)` transaction becomes valid, given the height at
+    /// which the `cancel (d)` transaction has been mined.
+    pub fn punish_available_at(&self, cancel_height: u32) -> u32 {
+        T::available_at(&self.timelock, cancel_height)
+    }
+}
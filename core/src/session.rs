@@ -0,0 +1,78 @@
+//! Encryption at rest of swap sessions persisted by daemons.
+//!
+//! A session holds private key material, [`EncryptedSession`] seals its strict encoding with
+//! ChaCha20-Poly1305 under a key derived from the daemon's master secret before it is written to
+//! disk. The sealed format is the 12 bytes nonce followed by the ciphertext and its 16 bytes
+//! authentication tag.
+
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use sha2::{Digest, Sha256};
+use strict_encoding::{strict_deserialize, strict_serialize, StrictDecode, StrictEncode};
+use zeroize::Zeroize;
+
+use std::marker::PhantomData;
+
+use crate::consensus;
+use crate::Error;
+
+/// Length in bytes of the nonce prepended to a sealed session.
+pub const NONCE_LEN: usize = 12;
+
+/// Domain separation tag used to derive the session encryption key from the master secret.
+const KEY_DERIVATION_TAG: &[u8] = b"farcaster:session-encryption";
+
+/// Seals and opens sessions of type `T` with a key derived from the daemon's master secret.
+///
+/// A nonce must never be reused with the same master secret, e.g. use a counter persisted with
+/// the sessions or random nonces.
+pub struct EncryptedSession<T> {
+    cipher: ChaCha20Poly1305,
+    _t: PhantomData<T>,
+}
+
+impl<T> EncryptedSession<T>
+where
+    T: StrictEncode + StrictDecode,
+{
+    /// Derives the session encryption key from the daemon's master secret.
+    pub fn new(master_secret: &[u8; 32]) -> Self {
+        let mut key = Sha256::new()
+            .chain(KEY_DERIVATION_TAG)
+            .chain(master_secret)
+            .finalize();
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+        key.as_mut_slice().zeroize();
+        Self {
+            cipher,
+            _t: PhantomData,
+        }
+    }
+
+    /// Encrypts and authenticates the strict encoded session with the given nonce.
+    pub fn seal(&self, session: &T, nonce: [u8; NONCE_LEN]) -> Result<Vec<u8>, Error> {
+        let mut plaintext = strict_serialize(session).map_err(consensus::Error::from)?;
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .expect("Session length is bounded");
+        plaintext.zeroize();
+        Ok([&nonce[..], &ciphertext[..]].concat())
+    }
+
+    /// Authenticates and decrypts a sealed session, fails with [`Error::DecryptionFailed`] if the
+    /// bytes are truncated or have been tampered with.
+    pub fn open(&self, bytes: &[u8]) -> Result<T, Error> {
+        if bytes.len() < NONCE_LEN {
+            return Err(Error::DecryptionFailed);
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let mut plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::DecryptionFailed)?;
+        let session = strict_deserialize(&plaintext).map_err(consensus::Error::from);
+        plaintext.zeroize();
+        Ok(session?)
+    }
+}
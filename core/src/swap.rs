@@ -1,6 +1,6 @@
 //! Defines the high level of a swap between a Arbitrating blockchain and an Accordant blockchain.
 
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display};
 
 use crate::crypto::{Commitment, DleqProof};
 use crate::role::{Accordant, Arbitrating};
@@ -17,3 +17,30 @@ pub trait Swap: Debug + Clone + Commitment {
     /// The concrete type to link both blockchain cryptographic groups used in by the signatures.
     type Proof: DleqProof<Self::Ar, Self::Ac>;
 }
+
+/// Tag prefixed to the serialized public offer when computing a [`SwapId`].
+pub const SWAP_ID_TAG: &[u8] = b"farcaster:swap_id";
+
+/// Identifies a swap by the tagged hash of the public offer it executes. The hash function is
+/// provided by a [`CommitmentEngine`](crate::crypto::CommitmentEngine), by default the engine of
+/// the swap context, so both participants derive the same identifier from the same offer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SwapId<C>(pub C);
+
+impl<C> SwapId<C>
+where
+    C: AsRef<[u8]>,
+{
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl<C> Display for SwapId<C>
+where
+    C: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.as_bytes()))
+    }
+}
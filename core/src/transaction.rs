@@ -1,12 +1,20 @@
 //! Arbitrating transaction module
+//!
+//! Each transaction trait of the protocol, e.g. [`Lockable`] or [`Cancelable`], declares a
+//! `TX_ID` associated constant without a default value. Implementations must set it to the
+//! identifier of the transaction they represent, there is no overridable accessor that could
+//! report a different identifier than the constant.
 
 use std::error;
 use std::fmt::Debug;
+use std::future::Future;
 use std::io;
+use std::pin::Pin;
 
+use strict_encoding::{StrictDecode, StrictEncode};
 use thiserror::Error;
 
-use crate::blockchain::{Address, Asset, Fee, Network, Onchain, Timelock};
+use crate::blockchain::{Address, Asset, Fee, FeePolitic, FeeStrategy, Network, Onchain, Timelock};
 use crate::consensus::{self, Decodable, Encodable};
 use crate::crypto::{Keys, Signatures};
 use crate::script::{DataLock, DataPunishableLock};
@@ -41,6 +49,15 @@ pub enum Error {
     /// The transaction chain validation failed
     #[error("The transaction chain validation failed")]
     InvalidTransactionChain,
+    /// The transaction input does not spend the output of the previous transaction
+    #[error("The transaction input does not spend the previous transaction output")]
+    InvalidLink,
+    /// The partial transaction carries a signature from a key not expected by the script.
+    #[error("Unexpected signature in the partial transaction")]
+    UnexpectedSignature,
+    /// The confirmation bounds do not require the funding transaction to be confirmed.
+    #[error("The confirmation bounds must require at least one funding confirmation")]
+    InvalidConfirmationBounds,
     /// Any transaction error not part of this list.
     #[error("Transaction error: {0}")]
     Other(Box<dyn error::Error + Send + Sync>),
@@ -87,6 +104,15 @@ where
     /// partial format is used to exchange unsigned or patially signed transactions.
     fn to_partial(self) -> T::PartialTransaction;
 
+    /// Returns a copy of the partial transaction stripped of all signatures and finalized
+    /// witnesses, keeping the metadata needed to sign it. Used to re-share a clean transaction
+    /// template after it has been partially signed.
+    fn to_unsigned_partial(&self) -> T::PartialTransaction {
+        let mut partial = self.partial().clone();
+        T::strip_signatures(&mut partial);
+        partial
+    }
+
     /// Construct the transaction type from a deserialized partial transaction.
     fn from_partial(partial: T::PartialTransaction) -> Self;
 
@@ -116,6 +142,32 @@ pub enum TxId {
     Punish,
 }
 
+impl TxId {
+    /// Returns all the transactions in protocol order, each transaction comes after the
+    /// transactions it spends.
+    pub fn protocol_order() -> [TxId; 6] {
+        [
+            TxId::Funding,
+            TxId::Lock,
+            TxId::Buy,
+            TxId::Cancel,
+            TxId::Refund,
+            TxId::Punish,
+        ]
+    }
+
+    /// Returns the transactions spent by the transaction, i.e. its parents in the transaction
+    /// graph. The funding transaction is created outside of the swap and has no dependency.
+    pub fn dependencies(&self) -> &'static [TxId] {
+        match self {
+            TxId::Funding => &[],
+            TxId::Lock => &[TxId::Funding],
+            TxId::Buy | TxId::Cancel => &[TxId::Lock],
+            TxId::Refund | TxId::Punish => &[TxId::Cancel],
+        }
+    }
+}
+
 impl Encodable for TxId {
     fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
         match self {
@@ -143,6 +195,117 @@ impl Decodable for TxId {
     }
 }
 
+/// Number of confirmations required on each transaction of the swap before moving to the next
+/// step. The bounds are agreed upon by both participants in the offer, deeper confirmations are
+/// expected on transactions locking funds than on the ones ending the swap. The funding
+/// transaction must always be confirmed, bounds not requiring it are rejected when decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConfirmationBounds {
+    /// Confirmations on the funding transaction before the lock is broadcast
+    pub funding: u32,
+    /// Confirmations on the arbitrating lock before the accordant lock is broadcast
+    pub lock: u32,
+    /// Confirmations on the buy transaction before the swap is considered done
+    pub buy: u32,
+    /// Confirmations on the cancel transaction before the refund or punish is broadcast
+    pub cancel: u32,
+    /// Confirmations on the refund transaction before the swap is considered aborted
+    pub refund: u32,
+    /// Confirmations on the punish transaction before the swap is considered aborted
+    pub punish: u32,
+    /// Confirmations on the accordant lock before the buy procedure signature is released
+    pub accordant_lock: u32,
+}
+
+impl ConfirmationBounds {
+    /// Returns the number of confirmations required on the arbitrating transaction.
+    pub fn required_confirmations(&self, txid: TxId) -> u32 {
+        match txid {
+            TxId::Funding => self.funding,
+            TxId::Lock => self.lock,
+            TxId::Buy => self.buy,
+            TxId::Cancel => self.cancel,
+            TxId::Refund => self.refund,
+            TxId::Punish => self.punish,
+        }
+    }
+
+    /// Returns the number of confirmations required on the accordant lock transaction.
+    pub fn required_accordant_lock_confirmations(&self) -> u32 {
+        self.accordant_lock
+    }
+
+    /// Fails with [`Error::InvalidConfirmationBounds`] if no confirmation is required on the
+    /// funding transaction.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.funding == 0 {
+            return Err(Error::InvalidConfirmationBounds);
+        }
+        Ok(())
+    }
+}
+
+impl Default for ConfirmationBounds {
+    fn default() -> Self {
+        Self {
+            funding: 3,
+            lock: 3,
+            buy: 1,
+            cancel: 2,
+            refund: 1,
+            punish: 1,
+            // Outputs are spendable on Monero after 10 blocks
+            accordant_lock: 10,
+        }
+    }
+}
+
+impl Encodable for ConfirmationBounds {
+    fn consensus_encode<W: io::Write>(&self, writer: &mut W) -> Result<usize, io::Error> {
+        let mut len = self.funding.consensus_encode(writer)?;
+        len += self.lock.consensus_encode(writer)?;
+        len += self.buy.consensus_encode(writer)?;
+        len += self.cancel.consensus_encode(writer)?;
+        len += self.refund.consensus_encode(writer)?;
+        len += self.punish.consensus_encode(writer)?;
+        Ok(len + self.accordant_lock.consensus_encode(writer)?)
+    }
+}
+
+impl Decodable for ConfirmationBounds {
+    fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        let bounds = Self {
+            funding: Decodable::consensus_decode(d)?,
+            lock: Decodable::consensus_decode(d)?,
+            buy: Decodable::consensus_decode(d)?,
+            cancel: Decodable::consensus_decode(d)?,
+            refund: Decodable::consensus_decode(d)?,
+            punish: Decodable::consensus_decode(d)?,
+            accordant_lock: Decodable::consensus_decode(d)?,
+        };
+        bounds
+            .validate()
+            .map_err(|_| consensus::Error::ParseFailed("Funding confirmations cannot be zero"))?;
+        Ok(bounds)
+    }
+}
+
+impl StrictEncode for ConfirmationBounds {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        Encodable::consensus_encode(self, &mut e).map_err(strict_encoding::Error::from)
+    }
+}
+
+impl StrictDecode for ConfirmationBounds {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Decodable::consensus_decode(&mut d).map_err(|_| {
+            strict_encoding::Error::DataIntegrityError(
+                "Failed to decode the confirmation bounds".to_string(),
+            )
+        })
+    }
+}
+
 /// Transaction that requries multiple participants to construct and finalize the transaction.
 pub trait Witnessable<T>
 where
@@ -159,6 +322,26 @@ pub trait Finalizable {
     fn finalize(&mut self) -> Result<(), Error>;
 }
 
+/// Transaction that can be finalized with only one signature from the local participant, without
+/// the cooperation of the counter-party, such as the `punish (f)` transaction. Transactions
+/// spending a path that requires both participants' signatures must not implement this trait.
+pub trait Unilateral<T>: Witnessable<T> + Finalizable
+where
+    T: Keys + Signatures,
+    Self: Sized,
+{
+    /// Add the participant's signature and finalize the transaction without waiting for any
+    /// counter-party signature.
+    fn finalize_with_witness(
+        &mut self,
+        pubkey: T::PublicKey,
+        sig: T::Signature,
+    ) -> Result<(), Error> {
+        self.add_witness(pubkey, sig)?;
+        self.finalize()
+    }
+}
+
 /// Define a transaction broadcastable by the system. Externally managed transaction are not
 /// broadcastable.
 pub trait Broadcastable<T>: Finalizable
@@ -198,6 +381,23 @@ where
     /// partial transaction. This is used to get all data needed to describe this output as an
     /// input in another transaction.
     fn get_consumable_output(&self) -> Result<O, Error>;
+
+    /// Return all the consumable outputs of this transaction, by default only the output returned
+    /// by [`get_consumable_output`](Self::get_consumable_output). Implementations aggregating
+    /// multiple outputs, e.g. a funding spread over multiple UTXOs, return all of them and the
+    /// next transaction must consume them all.
+    fn get_consumable_outputs(&self) -> Result<Vec<O>, Error> {
+        Ok(vec![self.get_consumable_output()?])
+    }
+
+    /// Return `true` if this transaction consumes the given output of its parent transaction,
+    /// i.e. the consumable output returned by the parent's
+    /// [`get_consumable_output`](Self::get_consumable_output). Used to catch a transaction
+    /// template built on top of the wrong parent. By default nothing is consumed, e.g. for a
+    /// funding transaction created outside of the system.
+    fn consumes(&self, _parent_output: &O) -> bool {
+        false
+    }
 }
 
 /// Implemented by transactions based on another transaction. This trait is auto implemented for
@@ -237,6 +437,10 @@ where
     /// Generate the witness to unlock the default path of the locked asset.
     fn generate_witness(&self, privkey: &T::PrivateKey) -> Result<T::Signature, Error>;
 
+    /// Return the message digest signed by [`generate_witness`](Self::generate_witness) to unlock
+    /// the default path, e.g. to display it or to sign it with an external signer.
+    fn witness_sighash(&self) -> Result<Vec<u8>, Error>;
+
     /// Verify that the signature is valid to unlock the default path of the locked asset.
     fn verify_witness(&self, pubkey: &T::PublicKey, sig: T::Signature) -> Result<(), Error>;
 }
@@ -274,12 +478,48 @@ where
     /// path.
     fn generate_failure_witness(&self, privkey: &T::PrivateKey) -> Result<T::Signature, Error>;
 
+    /// Return the message digest signed by
+    /// [`generate_failure_witness`](Self::generate_failure_witness) to unlock the failure path,
+    /// e.g. to display it or to sign it with an external signer.
+    fn failure_witness_sighash(&self) -> Result<Vec<u8>, Error>;
+
     /// Verify that the signature is valid to unlock the second path of of the locked asset, i.e.
     /// the failure path.
     fn verify_failure_witness(&self, pubkey: &T::PublicKey, sig: T::Signature)
         -> Result<(), Error>;
 }
 
+/// Future returned by a [`ChainWatcher`], resolved when the awaited on-chain event happened.
+pub type WatchFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// Watches a blockchain for the on-chain events the swap depends on. A daemon awaits the funding
+/// transaction with this trait and feeds the result into [`Fundable::update`].
+pub trait ChainWatcher<T>
+where
+    T: Address + Asset + Onchain,
+{
+    /// Identifier of a transaction on the blockchain, not to be confused with [`TxId`].
+    type Txid;
+
+    /// A transaction output with the reference needed to spend it.
+    type Output;
+
+    /// Wait until the transaction is included in the chain with at least `confirmations`
+    /// confirmations and return it.
+    fn wait_for_confirmation(
+        &self,
+        txid: Self::Txid,
+        confirmations: u32,
+    ) -> WatchFuture<'_, T::Transaction>;
+
+    /// Wait until an output paying exactly `amount` to the address is seen and return it.
+    fn wait_for_output(
+        &self,
+        address: T::Address,
+        amount: T::AssetUnit,
+    ) -> WatchFuture<'_, Self::Output>;
+}
+
 /// Fundable is NOT a transaction generated by this library but the funds that arrived in the
 /// generated address are controlled by the system. This trait allows to inject assets in the
 /// system.
@@ -302,13 +542,19 @@ where
     /// system by an external wallet, the txid is not known in advance.
     fn update(&mut self, tx: T::Transaction) -> Result<(), Error>;
 
+    /// Invalidate the transaction seen on-chain if its identifier is `txid`, the funding is then
+    /// pending again and no output can be consumed until the next [`update`](Self::update).
+    /// Return `true` if the funding was reset.
+    ///
+    /// The driver must call this function when the funding transaction is removed from the chain
+    /// by a reorganization, otherwise the next transactions are built on a vanished output.
+    fn invalidate(&mut self, txid: &T::TransactionId) -> bool;
+
     /// Create a raw funding structure based only on the transaction seen on-chain.
     fn raw(tx: T::Transaction) -> Result<Self, Error>;
 
-    /// Return the Farcaster transaction identifier.
-    fn get_id(&self) -> TxId {
-        TxId::Funding
-    }
+    /// The Farcaster transaction identifier, must be [`TxId::Funding`].
+    const TX_ID: TxId;
 }
 
 /// Represent a lockable transaction such as the `lock (b)` transaction that consumes the `funding
@@ -351,10 +597,8 @@ where
         }
     }
 
-    /// Return the Farcaster transaction identifier.
-    fn get_id(&self) -> TxId {
-        TxId::Lock
-    }
+    /// The Farcaster transaction identifier, must be [`TxId::Lock`].
+    const TX_ID: TxId;
 }
 
 /// Represent a buyable transaction such as the `buy (c)` transaction that consumes the `lock (b)`
@@ -394,10 +638,8 @@ where
         destination_target: T::Address,
     ) -> Result<(), Error>;
 
-    /// Return the Farcaster transaction identifier.
-    fn get_id(&self) -> TxId {
-        TxId::Buy
-    }
+    /// The Farcaster transaction identifier, must be [`TxId::Buy`].
+    const TX_ID: TxId;
 }
 
 /// Represent a cancelable transaction such as the `cancel (d)` transaction that consumes the `lock
@@ -412,8 +654,9 @@ where
     Self: Sized,
 {
     /// Creates a new `cancel (d)` transaction based on the `lock (b)` transaction and the data
-    /// needed for creating the lock primitive (i.e. the timelock and the keys). Return a new
-    /// `cancel (d)` transaction.
+    /// needed for creating the lock primitive (i.e. the timelock and the keys). The fee is set
+    /// according to the fee strategy and the politic. Return a new `cancel (d)` transaction ready
+    /// to be signed.
     ///
     /// This correspond to the "creator" and initial "updater" roles in BIP 174. Creates a new
     /// transaction and fill the inputs and outputs data.
@@ -421,7 +664,9 @@ where
         prev: &impl Lockable<T, O>,
         lock: DataLock<T>,
         punish_lock: DataPunishableLock<T>,
-    ) -> Result<Self, Error>;
+        fee_strategy: &FeeStrategy<T::FeeUnit>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, crate::Error>;
 
     /// Verifies that the transaction is compliant with the protocol requirements and implements
     /// the correct conditions of the [`DataLock`] and the [`DataPunishableLock`].
@@ -431,10 +676,8 @@ where
         punish_lock: DataPunishableLock<T>,
     ) -> Result<(), Error>;
 
-    /// Return the Farcaster transaction identifier.
-    fn get_id(&self) -> TxId {
-        TxId::Cancel
-    }
+    /// The Farcaster transaction identifier, must be [`TxId::Cancel`].
+    const TX_ID: TxId;
 }
 
 /// Represent a refundable transaction such as the `refund (e)` transaction that consumes the
@@ -454,8 +697,8 @@ where
     Self: Sized,
 {
     /// Creates a new `refund (e)` transaction based on the `cancel (d)` transaction and the data
-    /// needed for refunding the funds (i.e. the refund address). Return a new `refund (e)`
-    /// transaction.
+    /// needed for refunding the funds (i.e. the refund address). The fee is set according to the
+    /// fee strategy and the politic. Return a new `refund (e)` transaction ready to be signed.
     ///
     /// This correspond to the "creator" and initial "updater" roles in BIP 174. Creates a new
     /// transaction and fill the inputs and outputs data.
@@ -463,7 +706,9 @@ where
         prev: &impl Cancelable<T, O>,
         punish_lock: DataPunishableLock<T>,
         refund_target: T::Address,
-    ) -> Result<Self, Error>;
+        fee_strategy: &FeeStrategy<T::FeeUnit>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, crate::Error>;
 
     /// Verifies that the transaction is compliant with the protocol requirements and implements
     /// the correct conditions of the [`DataPunishableLock`] and the refund address.
@@ -473,10 +718,8 @@ where
         refund_target: T::Address,
     ) -> Result<(), Error>;
 
-    /// Return the Farcaster transaction identifier.
-    fn get_id(&self) -> TxId {
-        TxId::Refund
-    }
+    /// The Farcaster transaction identifier, must be [`TxId::Refund`].
+    const TX_ID: TxId;
 }
 
 /// Represent a punishable transaction such as the `punish (f)` transaction that consumes the
@@ -489,8 +732,19 @@ where
 ///
 /// This transaction does not have a `verify_template` function as it is created unilaterally and
 /// thus is fully trusted by the creator.
+///
+/// # Unilateral
+///
+/// The punish path only requires the punisher's signature, this is enforced by requiring the
+/// [`Unilateral`] trait.
 pub trait Punishable<T, O>:
-    Transaction<T, O> + Forkable<T> + Broadcastable<T> + Linkable<O> + Witnessable<T> + Chainable<T, O>
+    Transaction<T, O>
+    + Forkable<T>
+    + Broadcastable<T>
+    + Linkable<O>
+    + Witnessable<T>
+    + Unilateral<T>
+    + Chainable<T, O>
 where
     T: Keys + Address + Timelock + Fee + Signatures,
     O: Eq,
@@ -508,8 +762,21 @@ where
         destination_target: T::Address,
     ) -> Result<Self, Error>;
 
-    /// Return the Farcaster transaction identifier.
-    fn get_id(&self) -> TxId {
-        TxId::Punish
+    /// Creates a new `punish (f)` transaction as [`Punishable::initialize`] and sets its fee
+    /// according to the fee strategy and the politic, the returned template is ready to be
+    /// signed. Fails if the fee cannot be set.
+    fn initialize_with_fee(
+        prev: &impl Cancelable<T, O>,
+        punish_lock: DataPunishableLock<T>,
+        destination_target: T::Address,
+        fee_strategy: &FeeStrategy<T::FeeUnit>,
+        fee_politic: FeePolitic,
+    ) -> Result<Self, crate::Error> {
+        let mut punish = Self::initialize(prev, punish_lock, destination_target)?;
+        T::set_fee(punish.partial_mut(), fee_strategy, fee_politic)?;
+        Ok(punish)
     }
+
+    /// The Farcaster transaction identifier, must be [`TxId::Punish`].
+    const TX_ID: TxId;
 }